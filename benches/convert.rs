@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use apca::api::v2::order::Order as ApcaOrder;
+use apca::api::v2::position::Position;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use irontrade_alpaca::convert::{OpenPosition, Order};
+use std::hint::black_box;
+
+const ORDER_COUNT: usize = 10_000;
+
+const ORDER_JSON: &str = r#"{
+    "id": "61e69015-8549-4bfd-b9c3-01e75843f47d",
+    "client_order_id": "eb9e2aaa-f71a-4f51-b5b4-52a6c565dad4",
+    "created_at": "2024-01-18T15:00:00.000000Z",
+    "updated_at": "2024-01-18T15:00:01.000000Z",
+    "submitted_at": "2024-01-18T15:00:00.000000Z",
+    "filled_at": "2024-01-18T15:00:01.000000Z",
+    "expired_at": null,
+    "canceled_at": null,
+    "failed_at": null,
+    "replaced_at": null,
+    "replaced_by": null,
+    "replaces": null,
+    "asset_id": "276e2673-764b-4ab6-a611-caf665ca6340",
+    "symbol": "BTC/USD",
+    "asset_class": "crypto",
+    "notional": null,
+    "qty": "0.25",
+    "filled_qty": "0.25",
+    "filled_avg_price": "42000.5",
+    "order_class": "",
+    "order_type": "limit",
+    "type": "limit",
+    "side": "buy",
+    "time_in_force": "gtc",
+    "limit_price": "42001",
+    "stop_price": null,
+    "status": "filled",
+    "extended_hours": false,
+    "legs": null,
+    "trail_percent": null,
+    "trail_price": null,
+    "hwm": null
+}"#;
+
+const POSITION_JSON: &str = r#"{
+    "asset_id": "276e2673-764b-4ab6-a611-caf665ca6340",
+    "symbol": "BTCUSD",
+    "exchange": "CRYPTO",
+    "asset_class": "crypto",
+    "asset_marginable": false,
+    "avg_entry_price": "42000.5",
+    "qty": "0.25",
+    "qty_available": "0.25",
+    "side": "long",
+    "market_value": "10625.25",
+    "cost_basis": "10500.125",
+    "unrealized_pl": "125.125",
+    "unrealized_plpc": "0.0119",
+    "unrealized_intraday_pl": "25.5",
+    "unrealized_intraday_plpc": "0.0024",
+    "current_price": "42501",
+    "lastday_price": "42399",
+    "change_today": "0.0024"
+}"#;
+
+fn orders() -> Vec<ApcaOrder> {
+    let order: ApcaOrder = serde_json::from_str(ORDER_JSON).expect("valid order fixture");
+    vec![order; ORDER_COUNT]
+}
+
+fn convert_orders(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convert_orders");
+
+    group.bench_function("clone_then_convert", |b| {
+        b.iter_batched(
+            orders,
+            |orders| {
+                orders
+                    .iter()
+                    .map(|order| Order::from(order.clone()).0)
+                    .collect::<Vec<_>>()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("by_value", |b| {
+        b.iter_batched(
+            orders,
+            |orders| orders.into_iter().map(|order| Order::from(order).0).collect::<Vec<_>>(),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("by_reference", |b| {
+        let orders = orders();
+        b.iter(|| {
+            black_box(&orders)
+                .iter()
+                .map(|order| Order::from(order).0)
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.finish();
+}
+
+fn positions() -> Vec<Position> {
+    let position: Position = serde_json::from_str(POSITION_JSON).expect("valid position fixture");
+    vec![position; ORDER_COUNT]
+}
+
+fn convert_positions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convert_positions");
+
+    group.bench_function("by_value", |b| {
+        b.iter_batched(
+            positions,
+            |positions| {
+                positions
+                    .into_iter()
+                    .map(|position| OpenPosition::from(position).0)
+                    .collect::<Vec<_>>()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("by_reference", |b| {
+        let positions = positions();
+        b.iter(|| {
+            black_box(&positions)
+                .iter()
+                .map(|position| OpenPosition::from(position).0)
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, convert_orders, convert_positions);
+criterion_main!(benches);
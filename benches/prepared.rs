@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use apca::api::v2::order::{Amount, CreateReq, CreateReqInit, Side, TimeInForce, Type};
+use criterion::{Criterion, criterion_group, criterion_main};
+use num_decimal::Num;
+use std::hint::black_box;
+
+fn create_request() -> CreateReq {
+    CreateReqInit {
+        type_: Type::Limit,
+        time_in_force: TimeInForce::UntilCanceled,
+        limit_price: Some(Num::new(4_200_050, 100)),
+        client_order_id: Some("scalper-000001".into()),
+        ..Default::default()
+    }
+    .init(
+        "BTC/USD".to_string(),
+        Side::Buy,
+        Amount::Quantity {
+            quantity: Num::new(1, 4),
+        },
+    )
+}
+
+/// The work left between signal and wire: building and serializing the request per order, as
+/// `place_order` does, against taking the body `prepare_order` serialized ahead of time.
+fn order_body(c: &mut Criterion) {
+    let mut group = c.benchmark_group("order_body");
+
+    group.bench_function("build_and_serialize", |b| {
+        b.iter(|| serde_json::to_string(&black_box(create_request())).expect("serializable request"))
+    });
+
+    group.bench_function("prepared", |b| {
+        let body = serde_json::to_string(&create_request()).expect("serializable request");
+        b.iter(|| black_box(&body).clone())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, order_body);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::Result;
+use apca::api::v2::account::{self, Status};
+use num_decimal::Num;
+use std::time::Duration;
+
+/// How long an account snapshot is reused by default, see [`AlpacaClient::set_account_cache_ttl`].
+pub const DEFAULT_ACCOUNT_CACHE_TTL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AccountStatus {
+    Active,
+    Onboarding,
+    Rejected,
+    /// Any other state, e.g. pending approval, none of which can trade
+    Inactive,
+}
+
+impl From<Status> for AccountStatus {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Active => AccountStatus::Active,
+            Status::Onboarding => AccountStatus::Onboarding,
+            Status::Rejected => AccountStatus::Rejected,
+            _ => AccountStatus::Inactive,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Account {
+    pub status: AccountStatus,
+    pub cash: Num,
+    pub buying_power: Num,
+    /// Portfolio value, cash plus the market value of all positions
+    pub equity: Num,
+    /// Equity at the previous close
+    pub last_equity: Num,
+    pub long_market_value: Num,
+    /// Negative for short positions
+    pub short_market_value: Num,
+    /// 1 for cash accounts, 2 or 4 for margin accounts
+    pub margin_multiplier: Num,
+    /// Day trades in the last five trading days
+    pub daytrade_count: u64,
+    pub pattern_day_trader: bool,
+    pub trading_blocked: bool,
+    pub shorting_enabled: bool,
+}
+
+impl AlpacaClient {
+    /// How long sequential account reads, e.g. `get_cash` followed by `get_buying_power`, reuse
+    /// the last account response. Submitting, replacing or canceling an order and closing
+    /// positions drops the cached response regardless, zero only shares requests in flight.
+    pub fn set_account_cache_ttl(&mut self, account_cache_ttl: Duration) {
+        self.account_cache_ttl = account_cache_ttl;
+    }
+
+    /// The state of the account from a single request. Concurrent calls share the request in
+    /// flight and the response is reused for the account cache TTL.
+    pub async fn get_account(&self) -> Result<Account> {
+        self.account_flight
+            .run(self.account_cache_ttl, || self.fetch_account())
+            .await
+    }
+
+    async fn fetch_account(&self) -> Result<Account> {
+        let account = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<account::Get>(&())
+            })
+            .await?;
+
+        Ok(Account {
+            status: account.status.into(),
+            cash: account.cash,
+            buying_power: account.buying_power,
+            equity: account.equity,
+            last_equity: account.last_equity,
+            long_market_value: account.market_value_long,
+            short_market_value: account.market_value_short,
+            margin_multiplier: account.multiplier,
+            daytrade_count: account.daytrade_count,
+            pattern_day_trader: account.day_trader,
+            trading_blocked: account.trading_blocked,
+            shorting_enabled: account.shorting_enabled,
+        })
+    }
+}
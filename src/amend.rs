@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::replace::OrderChange;
+use anyhow::Result;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AmendOutcome {
+    /// The change and any coalesced after it were sent, `order_id` is the latest replacement
+    Sent { order_id: String },
+    /// A replace is already in flight, the change will be sent together with later ones once it
+    /// is acknowledged
+    Coalesced,
+}
+
+#[derive(Debug)]
+struct QueueState {
+    order_id: String,
+    in_flight: bool,
+    pending: Option<OrderChange>,
+}
+
+/// Serializes replace requests for one order so a pegging strategy can amend as often as it
+/// likes without racing Alpaca. While a replace is in flight, new changes are merged into a
+/// single pending one that is sent against the replacement order once the previous replace is
+/// acknowledged, instead of hitting the old order and getting "order not replaceable" back.
+#[derive(Debug)]
+pub struct AmendmentQueue {
+    state: Mutex<QueueState>,
+}
+
+impl AmendmentQueue {
+    pub fn new(order_id: impl Into<String>) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                order_id: order_id.into(),
+                in_flight: false,
+                pending: None,
+            }),
+        }
+    }
+
+    /// Id of the latest replacement of the order.
+    pub fn order_id(&self) -> String {
+        self.lock().order_id.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Enqueues `change`, the caller that finds the queue idle sends it and keeps sending
+    /// whatever got coalesced in the meantime. Dropping the future mid-send frees the queue and
+    /// discards the coalesced changes, the next amend is sent against the last acknowledged id.
+    pub async fn amend(&self, client: &AlpacaClient, change: OrderChange) -> Result<AmendOutcome> {
+        self.amend_with(change, async |order_id: &str, change| {
+            client.change_order(order_id, change).await
+        })
+        .await
+    }
+
+    async fn amend_with(
+        &self,
+        change: OrderChange,
+        mut send: impl AsyncFnMut(&str, OrderChange) -> Result<String>,
+    ) -> Result<AmendOutcome> {
+        let (mut order_id, mut change) = {
+            let mut state = self.lock();
+            if state.in_flight {
+                state.pending = Some(match state.pending.take() {
+                    Some(pending) => pending.merge(change),
+                    None => change,
+                });
+                return Ok(AmendOutcome::Coalesced);
+            }
+            state.in_flight = true;
+            (state.order_id.clone(), change)
+        };
+        // Declared before any state lock below, so it runs once the lock is released
+        let _in_flight = InFlight(self);
+
+        loop {
+            let new_order_id = send(&order_id, change).await?;
+
+            let mut state = self.lock();
+            state.order_id = new_order_id.clone();
+            order_id = new_order_id;
+
+            match state.pending.take() {
+                Some(pending) => change = pending,
+                None => return Ok(AmendOutcome::Sent { order_id }),
+            }
+        }
+    }
+}
+
+/// Frees the queue however the sending caller stops: done, failed or dropped mid-send. Changes
+/// coalesced by then targeted a replace that may not have happened, so they're discarded.
+struct InFlight<'a>(&'a AmendmentQueue);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.in_flight = false;
+        state.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use futures::FutureExt;
+    use futures::future::pending;
+    use num_decimal::Num;
+
+    fn limit_price(price: i64) -> OrderChange {
+        OrderChange {
+            limit_price: Some(Num::from(price)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_amend_frees_the_queue() {
+        let queue = AmendmentQueue::new("original");
+        let stuck = queue.amend_with(limit_price(100), async |_: &str, _| pending().await);
+        assert!(stuck.now_or_never().is_none());
+
+        let mut sent = Vec::new();
+        let outcome = queue
+            .amend_with(limit_price(101), async |order_id: &str, change| {
+                sent.push((order_id.to_string(), change));
+                Ok("replacement".to_string())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            outcome,
+            AmendOutcome::Sent {
+                order_id: "replacement".into()
+            }
+        );
+        assert_eq!(sent, vec![("original".to_string(), limit_price(101))]);
+        assert_eq!(queue.order_id(), "replacement");
+    }
+
+    #[tokio::test]
+    async fn failed_amend_frees_the_queue() {
+        let queue = AmendmentQueue::new("original");
+        let failed = queue
+            .amend_with(limit_price(100), async |_: &str, _| {
+                Err(anyhow!("order not replaceable"))
+            })
+            .await;
+        assert!(failed.is_err());
+
+        let outcome = queue
+            .amend_with(limit_price(101), async |_: &str, _| Ok("replacement".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            AmendOutcome::Sent {
+                order_id: "replacement".into()
+            }
+        );
+    }
+}
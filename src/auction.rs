@@ -0,0 +1,335 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::data::deserialize_num;
+use crate::order_defaults::{OrderOptions, OrderTimeInForce};
+use crate::positions::AssetClass;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::{Result, anyhow, bail};
+use apca::api::v2::calendar::{self, ListReqInit, OpenClose};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Opening auction orders are accepted until this long before the open.
+const OPEN_CUTOFF: TimeDelta = TimeDelta::minutes(2);
+/// Closing auction orders are accepted until this long before the close.
+const CLOSE_CUTOFF: TimeDelta = TimeDelta::minutes(10);
+/// Opening auction orders for the next session are accepted from 19:00 New York time.
+const OPEN_ACCEPTED_FROM: NaiveTime = NaiveTime::from_hms_opt(19, 0, 0).unwrap();
+/// Conditions of the official opening and closing prints among the auction prints of a day.
+const OFFICIAL_OPEN_CONDITION: &str = "Q";
+const OFFICIAL_CLOSE_CONDITION: &str = "M";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Auction {
+    /// Market or limit on open, time in force OPG
+    Open,
+    /// Market or limit on close, time in force CLS
+    Close,
+}
+
+impl Auction {
+    pub fn time_in_force(&self) -> OrderTimeInForce {
+        match self {
+            Auction::Open => OrderTimeInForce::AtTheOpen,
+            Auction::Close => OrderTimeInForce::AtTheClose,
+        }
+    }
+}
+
+/// A trading day from the market calendar, early closes included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketSession {
+    pub date: NaiveDate,
+    pub open: DateTime<Utc>,
+    pub close: DateTime<Utc>,
+}
+
+pub(crate) fn new_york_time(date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    let local = date.and_time(time);
+    New_York
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|| local.and_utc())
+}
+
+impl From<&OpenClose> for MarketSession {
+    fn from(open_close: &OpenClose) -> Self {
+        Self {
+            date: open_close.date,
+            open: new_york_time(open_close.date, open_close.open),
+            close: new_york_time(open_close.date, open_close.close),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuctionWindowError {
+    pub auction: Auction,
+    /// When orders for the next auction are accepted again
+    pub accepted_from: Option<DateTime<Utc>>,
+}
+
+impl Display for AuctionWindowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let auction = match self.auction {
+            Auction::Open => "opening",
+            Auction::Close => "closing",
+        };
+        match self.accepted_from {
+            Some(accepted_from) => write!(
+                f,
+                "{auction} auction orders are not accepted until {}",
+                accepted_from.to_rfc3339()
+            ),
+            None => write!(f, "no upcoming session to submit {auction} auction orders for"),
+        }
+    }
+}
+
+impl std::error::Error for AuctionWindowError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuctionPrint {
+    pub time: DateTime<Utc>,
+    pub price: Num,
+}
+
+/// The official opening and closing auction prices of a stock for one session, as published by
+/// its primary exchange. Either is `None` when the exchange didn't publish it, e.g. on a halt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuctionPrices {
+    pub symbol: String,
+    pub date: NaiveDate,
+    pub open: Option<AuctionPrint>,
+    pub close: Option<AuctionPrint>,
+}
+
+#[derive(Deserialize)]
+struct RawAuctionPrint {
+    #[serde(rename = "t")]
+    time: DateTime<Utc>,
+    #[serde(rename = "p", deserialize_with = "deserialize_num")]
+    price: Num,
+    #[serde(rename = "c")]
+    condition: String,
+}
+
+#[derive(Deserialize)]
+struct RawDailyAuctions {
+    #[serde(rename = "d")]
+    date: NaiveDate,
+    #[serde(rename = "o", default)]
+    open: Vec<RawAuctionPrint>,
+    #[serde(rename = "c", default)]
+    close: Vec<RawAuctionPrint>,
+}
+
+#[derive(Deserialize)]
+struct AuctionPage {
+    auctions: HashMap<String, Vec<RawDailyAuctions>>,
+    next_page_token: Option<String>,
+}
+
+fn official_print(prints: &[RawAuctionPrint], condition: &str) -> Option<AuctionPrint> {
+    prints
+        .iter()
+        .find(|print| print.condition == condition)
+        .map(|print| AuctionPrint {
+            time: print.time,
+            price: print.price.clone(),
+        })
+}
+
+/// Checks that an order for `auction` submitted at `now` makes the cutoff of the next session
+/// in `sessions`, which have to be sorted by date. Opening auction orders are accepted from
+/// 19:00 New York time after the previous session until two minutes before the open, closing
+/// auction orders on the session day until ten minutes before the close.
+pub fn check_auction_window(
+    auction: Auction,
+    sessions: &[MarketSession],
+    now: DateTime<Utc>,
+) -> Result<(), AuctionWindowError> {
+    let cutoff = |session: &MarketSession| match auction {
+        Auction::Open => session.open - OPEN_CUTOFF,
+        Auction::Close => session.close - CLOSE_CUTOFF,
+    };
+    let Some(index) = sessions.iter().position(|session| now < cutoff(session)) else {
+        return Err(AuctionWindowError {
+            auction,
+            accepted_from: None,
+        });
+    };
+    let session = &sessions[index];
+
+    let accepted_from = match auction {
+        Auction::Open => index
+            .checked_sub(1)
+            .map(|previous| new_york_time(sessions[previous].date, OPEN_ACCEPTED_FROM)),
+        Auction::Close => Some(new_york_time(session.date, NaiveTime::MIN)),
+    };
+
+    match accepted_from {
+        Some(accepted_from) if now < accepted_from => Err(AuctionWindowError {
+            auction,
+            accepted_from: Some(accepted_from),
+        }),
+        _ => Ok(()),
+    }
+}
+
+impl AlpacaClient {
+    /// Market sessions from `start` to `end`, both included.
+    pub async fn get_market_sessions(&self, start: NaiveDate, end: NaiveDate) -> Result<Vec<MarketSession>> {
+        let request = ListReqInit::default().init(start, end);
+        let sessions = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<calendar::List>(&request)
+            })
+            .await?
+            .iter()
+            .map(MarketSession::from)
+            .collect();
+        Ok(sessions)
+    }
+
+    /// The official auction prices of `symbols` for every session from `start` to `end`, both
+    /// included, sorted by symbol then date, e.g. to benchmark market on close fills.
+    pub async fn get_auction_prices(
+        &self,
+        symbols: &[String],
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<AuctionPrices>> {
+        let (alpaca_symbols, renames) = self.symbol_aliases.resolve_all(symbols);
+        let mut query = vec![
+            ("symbols", alpaca_symbols.join(",")),
+            ("start", start.to_string()),
+            ("end", end.to_string()),
+        ];
+
+        let mut prices = Vec::new();
+        loop {
+            let page: AuctionPage = self.get_data("v2/stocks/auctions", &query).await?;
+            for (symbol, days) in page.auctions {
+                let mut symbol = symbol;
+                renames.restore(&mut symbol);
+                prices.extend(days.iter().map(|day| AuctionPrices {
+                    symbol: symbol.clone(),
+                    date: day.date,
+                    open: official_print(&day.open, OFFICIAL_OPEN_CONDITION),
+                    close: official_print(&day.close, OFFICIAL_CLOSE_CONDITION),
+                }));
+            }
+            match page.next_page_token {
+                Some(page_token) => {
+                    query.retain(|(name, _)| *name != "page_token");
+                    query.push(("page_token", page_token));
+                }
+                None => break,
+            }
+        }
+
+        prices.sort_by(|a, b| (&a.symbol, a.date).cmp(&(&b.symbol, b.date)));
+        Ok(prices)
+    }
+
+    /// Places a stock order for the opening or closing auction, a market on open/close order
+    /// without a limit price and a limit on open/close order with one. Orders that would miss
+    /// the auction's submission cutoff are rejected locally with [`AuctionWindowError`].
+    pub async fn place_auction_order(&mut self, req: OrderRequest, auction: Auction) -> Result<String> {
+        let asset_symbol = req.asset_pair.to_string();
+        if AssetClass::of_symbol(&asset_symbol) != AssetClass::Equity {
+            bail!("{asset_symbol} doesn't trade in the auctions, only stocks do");
+        }
+
+        let now = Utc::now();
+        let today = now.with_timezone(&New_York).date_naive();
+        let start = today
+            .checked_sub_signed(TimeDelta::days(7))
+            .ok_or_else(|| anyhow!("calendar date out of range"))?;
+        let end = today
+            .checked_add_signed(TimeDelta::days(7))
+            .ok_or_else(|| anyhow!("calendar date out of range"))?;
+        let sessions = self.get_market_sessions(start, end).await?;
+        check_auction_window(auction, &sessions, now)?;
+
+        let options = OrderOptions {
+            time_in_force: Some(auction.time_in_force()),
+            ..Default::default()
+        };
+        self.place_order_with_options(req, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(day: u32, close_hour: u32) -> MarketSession {
+        let date = NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        MarketSession {
+            date,
+            open: new_york_time(date, NaiveTime::from_hms_opt(9, 30, 0).unwrap()),
+            close: new_york_time(date, NaiveTime::from_hms_opt(close_hour, 0, 0).unwrap()),
+        }
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        let date = NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        new_york_time(date, NaiveTime::from_hms_opt(hour, minute, 0).unwrap())
+    }
+
+    #[test]
+    fn opening_auction_window() {
+        // Friday the 12th, then Tuesday the 16th after the holiday
+        let sessions = [session(12, 16), session(16, 16)];
+
+        assert!(check_auction_window(Auction::Open, &sessions, at(12, 9, 27)).is_ok());
+        assert!(check_auction_window(Auction::Open, &sessions, at(12, 9, 29)).is_err());
+        assert!(check_auction_window(Auction::Open, &sessions, at(12, 18, 59)).is_err());
+        assert!(check_auction_window(Auction::Open, &sessions, at(12, 19, 0)).is_ok());
+        assert!(check_auction_window(Auction::Open, &sessions, at(15, 12, 0)).is_ok());
+        assert!(check_auction_window(Auction::Open, &sessions, at(16, 10, 0)).is_err());
+    }
+
+    #[test]
+    fn closing_auction_window_follows_early_close() {
+        let sessions = [session(12, 13), session(16, 16)];
+
+        assert!(check_auction_window(Auction::Close, &sessions, at(12, 12, 49)).is_ok());
+        assert!(check_auction_window(Auction::Close, &sessions, at(12, 12, 51)).is_err());
+        assert!(check_auction_window(Auction::Close, &sessions, at(16, 15, 49)).is_ok());
+
+        let error = check_auction_window(Auction::Close, &sessions, at(15, 12, 0)).unwrap_err();
+        assert_eq!(error.accepted_from, Some(at(16, 0, 0)));
+    }
+
+    #[test]
+    fn picks_official_auction_prints() {
+        let page: AuctionPage = serde_json::from_str(
+            r#"{"auctions":{"AAPL":[{"d":"2024-01-12",
+                "o":[{"c":"O","p":186.06,"s":1190,"t":"2024-01-12T14:30:00.5Z","x":"Q"},{"c":"Q","p":186.06,"t":"2024-01-12T14:30:00.6Z","x":"Q"}],
+                "c":[{"c":"6","p":185.92,"s":3085812,"t":"2024-01-12T21:00:00.1Z","x":"Q"},{"c":"M","p":185.92,"t":"2024-01-12T21:00:00.2Z","x":"P"}]}]},
+                "next_page_token":null}"#,
+        )
+        .unwrap();
+
+        let day = &page.auctions["AAPL"][0];
+        let open = official_print(&day.open, OFFICIAL_OPEN_CONDITION).unwrap();
+        assert_eq!(open.price, Num::new(186_06, 2));
+        assert_eq!(open.time, "2024-01-12T14:30:00.6Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(
+            official_print(&day.close, OFFICIAL_CLOSE_CONDITION).map(|close| close.price),
+            Some(Num::new(185_92, 2))
+        );
+    }
+}
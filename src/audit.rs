@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use num_decimal::Num;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+/// Hash the first entry of a log chains to.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Who triggered the following trading actions and why, recorded with every audit entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditContext {
+    pub actor: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditAction {
+    Submit {
+        asset_symbol: String,
+        side: String,
+        quantity: Option<Num>,
+        notional: Option<Num>,
+        limit_price: Option<Num>,
+        stop_price: Option<Num>,
+    },
+    Modify {
+        order_id: String,
+        details: String,
+    },
+    Cancel {
+        order_id: String,
+    },
+    ClosePosition {
+        asset_symbol: String,
+    },
+    KillSwitch {
+        details: String,
+    },
+    /// A rejected order altered by a remediation policy before being resubmitted
+    Remediate {
+        asset_symbol: String,
+        rejection: String,
+        details: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// Written before the action is sent, so nothing reaches Alpaca unrecorded
+    Requested,
+    Succeeded { order_id: Option<String> },
+    Failed { error: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub context: AuditContext,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> Result<String> {
+        let unhashed = AuditEntry {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let digest = Sha256::digest(serde_json::to_vec(&unhashed)?);
+        Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+}
+
+/// Append-only JSON lines file where every entry includes the hash of the previous one, so any
+/// edit, removal or reordering of past entries breaks the chain and shows up in [`AuditLog::verify`].
+pub struct AuditLog {
+    file: File,
+    next_sequence: u64,
+    last_hash: String,
+}
+
+impl AuditLog {
+    /// Opens the log at `path`, creating it if needed. An existing log is verified first and new
+    /// entries continue its chain.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let (next_sequence, last_hash) = if path.exists() {
+            let entries = Self::verify(path)?;
+            match entries.last() {
+                Some(entry) => (entry.sequence + 1, entry.hash.clone()),
+                None => (0, GENESIS_HASH.to_string()),
+            }
+        } else {
+            (0, GENESIS_HASH.to_string())
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open audit log {}", path.display()))?;
+
+        Ok(Self {
+            file,
+            next_sequence,
+            last_hash,
+        })
+    }
+
+    pub fn append(&mut self, context: &AuditContext, action: AuditAction, outcome: AuditOutcome) -> Result<()> {
+        let mut entry = AuditEntry {
+            sequence: self.next_sequence,
+            timestamp: Utc::now(),
+            context: context.clone(),
+            action,
+            outcome,
+            previous_hash: self.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+
+        self.next_sequence += 1;
+        self.last_hash = entry.hash;
+
+        Ok(())
+    }
+
+    /// Reads the whole log and checks its hash chain, returning the entries if it's intact.
+    pub fn verify(path: impl AsRef<Path>) -> Result<Vec<AuditEntry>> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("failed to open audit log {}", path.display()))?;
+
+        let mut entries: Vec<AuditEntry> = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let entry: AuditEntry =
+                serde_json::from_str(&line?).with_context(|| format!("malformed audit entry on line {}", index + 1))?;
+
+            let expected_previous = entries.last().map_or(GENESIS_HASH, |previous| previous.hash.as_str());
+            if entry.sequence != index as u64 || entry.previous_hash != expected_previous {
+                bail!("audit log chain broken at entry {index}");
+            }
+            if entry.hash != entry.compute_hash()? {
+                bail!("audit entry {index} was modified");
+            }
+
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+}
+
+impl AlpacaClient {
+    /// Starts recording every trading action to `audit_log`.
+    pub fn set_audit_log(&mut self, audit_log: AuditLog) {
+        self.audit_log = Some(Mutex::new(audit_log));
+    }
+
+    /// Sets the who/why metadata recorded with subsequent trading actions.
+    pub fn set_audit_context(&mut self, context: AuditContext) {
+        self.audit_context = context;
+    }
+
+    pub(crate) fn audit(&self, action: &AuditAction, outcome: AuditOutcome) -> Result<()> {
+        let Some(audit_log) = &self.audit_log else {
+            return Ok(());
+        };
+
+        audit_log
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .append(&self.audit_context, action.clone(), outcome)
+    }
+
+    /// Records the action before running it and its outcome afterward. Failing to record the
+    /// request aborts the action, while the outcome is recorded on a best effort basis since the
+    /// action already happened at that point.
+    pub(crate) async fn audited<T>(
+        &self,
+        action: AuditAction,
+        order_id: impl Fn(&T) -> Option<String>,
+        operation: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        self.audit(&action, AuditOutcome::Requested)?;
+
+        let result = operation.await;
+        let outcome = match &result {
+            Ok(output) => AuditOutcome::Succeeded {
+                order_id: order_id(output),
+            },
+            Err(error) => AuditOutcome::Failed {
+                error: format!("{error:#}"),
+            },
+        };
+        if let Err(error) = self.audit(&action, outcome.clone()) {
+            self.record_unflushed("audit log", format!("{action:?} {outcome:?}"), &error);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::unique_temp_path;
+    use std::fs;
+
+    #[test]
+    fn appends_and_verifies_chain() -> Result<()> {
+        let path = unique_temp_path("audit_chain.jsonl");
+        let context = AuditContext {
+            actor: "tests".into(),
+            reason: "chain".into(),
+        };
+
+        let mut audit_log = AuditLog::open(&path)?;
+        audit_log.append(&context, cancel("a"), AuditOutcome::Requested)?;
+        drop(audit_log);
+
+        // Reopening continues the existing chain
+        let mut audit_log = AuditLog::open(&path)?;
+        audit_log.append(&context, cancel("a"), AuditOutcome::Succeeded { order_id: None })?;
+
+        let entries = AuditLog::verify(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].previous_hash, entries[0].hash);
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_tampering() -> Result<()> {
+        let path = unique_temp_path("audit_tamper.jsonl");
+        let context = AuditContext::default();
+
+        let mut audit_log = AuditLog::open(&path)?;
+        audit_log.append(&context, cancel("a"), AuditOutcome::Requested)?;
+        audit_log.append(&context, cancel("b"), AuditOutcome::Requested)?;
+        drop(audit_log);
+
+        let tampered = fs::read_to_string(&path)?.replace("\"order_id\":\"a\"", "\"order_id\":\"c\"");
+        fs::write(&path, tampered)?;
+
+        let result = AuditLog::verify(&path);
+        fs::remove_file(&path)?;
+
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    fn cancel(order_id: &str) -> AuditAction {
+        AuditAction::Cancel {
+            order_id: order_id.into(),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use anyhow::Result;
+use num_decimal::Num;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Balances {
+    pub cash: Num,
+    pub buying_power: Num,
+    pub equity: Num,
+}
+
+impl AlpacaClient {
+    /// Cash, buying power and equity from the account snapshot of [`AlpacaClient::get_account`],
+    /// shared with `get_cash` and `get_buying_power`.
+    pub async fn get_balances(&self) -> Result<Balances> {
+        let account = self.get_account().await?;
+        Ok(Balances {
+            cash: account.cash,
+            buying_power: account.buying_power,
+            equity: account.equity,
+        })
+    }
+}
@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::order_class::{
+    LinkedOrder, OrderClass, OrderLeg, check_equity, check_exit_prices, multi_leg_time_in_force, stop_loss,
+};
+use crate::order_defaults::{OrderOptions, OrderTimeInForce};
+use anyhow::{Result, bail};
+use apca::api::v2::order::{Class, Order as ApcaOrder, TakeProfit, Type};
+use irontrade::api::common::{Order as IronTradeOrder, OrderSide, OrderType};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+
+/// An entry order with a take-profit and a stop-loss exit attached. Once the entry fills both
+/// exits go live and whichever fills first cancels the other.
+#[derive(Clone, Debug)]
+pub struct BracketRequest {
+    /// A market order, or a limit order with a limit price
+    pub entry: OrderRequest,
+    pub take_profit_limit_price: Num,
+    pub stop_loss_stop_price: Num,
+    /// Makes the stop-loss a stop limit order
+    pub stop_loss_limit_price: Option<Num>,
+    /// Day or good til canceled, applies to all three orders
+    pub time_in_force: Option<OrderTimeInForce>,
+}
+
+impl BracketRequest {
+    fn check_exits(&self) -> Result<()> {
+        let exit_side = match self.entry.side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        check_exit_prices(&exit_side, &self.take_profit_limit_price, &self.stop_loss_stop_price)
+    }
+}
+
+/// A bracket order as Alpaca reports it, the entry with its take-profit and stop-loss legs.
+#[derive(Clone, Debug)]
+pub struct BracketOrder {
+    pub entry: IronTradeOrder,
+    pub take_profit: Option<OrderLeg>,
+    pub stop_loss: Option<OrderLeg>,
+}
+
+impl TryFrom<LinkedOrder> for BracketOrder {
+    type Error = anyhow::Error;
+
+    fn try_from(linked_order: LinkedOrder) -> Result<Self> {
+        if linked_order.class != OrderClass::Bracket {
+            bail!("order {} is not a bracket order", linked_order.order.order_id);
+        }
+
+        let mut take_profit = None;
+        let mut stop_loss = None;
+        for leg in linked_order.legs {
+            match leg.order.type_ {
+                OrderType::Limit => take_profit = Some(leg),
+                _ => stop_loss = Some(leg),
+            }
+        }
+
+        Ok(Self {
+            entry: linked_order.order,
+            take_profit,
+            stop_loss,
+        })
+    }
+}
+
+impl TryFrom<&ApcaOrder> for BracketOrder {
+    type Error = anyhow::Error;
+
+    fn try_from(order: &ApcaOrder) -> Result<Self> {
+        BracketOrder::try_from(LinkedOrder::from(order))
+    }
+}
+
+impl AlpacaClient {
+    /// Submits the entry with its take-profit and stop-loss legs in a single bracket order, so
+    /// there's never a filled entry without exits. Returns the orders as Alpaca created them.
+    pub async fn place_bracket_order(&mut self, req: BracketRequest) -> Result<BracketOrder> {
+        let asset_symbol = self.symbol_aliases.resolve(&req.entry.asset_pair.to_string()).to_string();
+        check_equity(&asset_symbol)?;
+        req.check_exits()?;
+
+        let options = OrderOptions {
+            time_in_force: Some(multi_leg_time_in_force(req.time_in_force)?),
+            extended_hours: Some(false),
+            ..Default::default()
+        };
+        let retry = options.retry;
+        let (_, mut request) = self.create_request(req.entry, options).await?;
+        if !matches!(request.type_, Type::Market | Type::Limit) {
+            bail!("the entry of a bracket order has to be a market or limit order");
+        }
+
+        request.class = Class::Bracket;
+        request.take_profit = Some(TakeProfit::Limit(req.take_profit_limit_price));
+        request.stop_loss = Some(stop_loss(req.stop_loss_stop_price, req.stop_loss_limit_price));
+
+        let order = self.submit_apca_order(&asset_symbol, &request, retry).await?;
+        BracketOrder::try_from(&order)
+    }
+
+    /// Current state of a bracket order and its legs, by the id of its entry.
+    pub async fn get_bracket_order(&self, order_id: &str) -> Result<BracketOrder> {
+        let order = self.fetch_apca_order(order_id).await?;
+        BracketOrder::try_from(&order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use irontrade::api::common::{Amount, AssetPair};
+    use std::str::FromStr;
+
+    fn bracket(side: OrderSide, take_profit: i64, stop_loss: i64) -> BracketRequest {
+        BracketRequest {
+            entry: OrderRequest {
+                asset_pair: AssetPair::from_str("BTC/USD").unwrap(),
+                amount: Amount::Quantity { quantity: Num::from(1) },
+                side,
+                limit_price: None,
+            },
+            take_profit_limit_price: Num::from(take_profit),
+            stop_loss_stop_price: Num::from(stop_loss),
+            stop_loss_limit_price: None,
+            time_in_force: None,
+        }
+    }
+
+    #[test]
+    fn exits_have_to_bracket_the_entry() {
+        assert!(bracket(OrderSide::Buy, 110, 90).check_exits().is_ok());
+        assert!(bracket(OrderSide::Buy, 90, 110).check_exits().is_err());
+        assert!(bracket(OrderSide::Sell, 90, 110).check_exits().is_ok());
+        assert!(bracket(OrderSide::Sell, 110, 90).check_exits().is_err());
+    }
+}
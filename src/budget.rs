@@ -0,0 +1,295 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::order_defaults::OrderOptions;
+use crate::retry::RetryOverride;
+use anyhow::{Result, anyhow, bail};
+use irontrade::api::client::IronTradeClient;
+use irontrade::api::common::{OpenPosition as IronTradeOpenPosition, Order as IronTradeOrder};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+tokio::task_local! {
+    /// Strategy whose budget the client calls of the current task draw from.
+    static STRATEGY: String;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetKind {
+    /// Any API request, orders included
+    Request,
+    /// Order submissions only, on top of their request
+    Order,
+}
+
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    per_second: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn per_minute(per_minute: f64) -> Self {
+        Self {
+            capacity: per_minute,
+            tokens: per_minute,
+            per_second: per_minute / 60.0,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let refill = now.duration_since(self.refilled_at).as_secs_f64() * self.per_second;
+        self.tokens = (self.tokens + refill).min(self.capacity);
+        self.refilled_at = now;
+    }
+
+    /// Share of the capacity currently available, from 0 to 1.
+    pub(crate) fn headroom(&mut self) -> f64 {
+        self.refill();
+        self.tokens / self.capacity
+    }
+
+    /// Takes a token, or returns how long until one is available.
+    pub(crate) fn try_take(&mut self) -> Result<(), Duration> {
+        self.refill();
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / self.per_second))
+    }
+}
+
+#[derive(Debug)]
+struct Allocation {
+    requests: TokenBucket,
+    orders: TokenBucket,
+}
+
+/// Splits the account's API capacity between strategies sharing a client, so one noisy strategy
+/// can't starve the others. Each strategy gets a percentage of the request and order throughput
+/// and waits when it has used up its own share.
+#[derive(Debug)]
+pub struct ApiBudget {
+    requests_per_minute: u32,
+    orders_per_minute: u32,
+    allocated_percent: u32,
+    allocations: Mutex<HashMap<String, Allocation>>,
+}
+
+impl ApiBudget {
+    pub fn new(requests_per_minute: u32, orders_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute,
+            orders_per_minute,
+            allocated_percent: 0,
+            allocations: Mutex::default(),
+        }
+    }
+
+    /// Gives `strategy` `percent` of the total budget. Fails if the shares would add up to more
+    /// than 100%, or if the share comes to less than one request or order a minute, which could
+    /// never be spent.
+    pub fn allocate(&mut self, strategy: impl Into<String>, percent: u32) -> Result<()> {
+        let strategy = strategy.into();
+        let allocations = self.allocations.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if allocations.contains_key(&strategy) {
+            bail!("strategy {strategy} already has a budget");
+        }
+        if percent == 0 || self.allocated_percent + percent > 100 {
+            bail!(
+                "cannot allocate {percent}% to {strategy}, {}% is left",
+                100 - self.allocated_percent
+            );
+        }
+
+        let share = |total: u32| f64::from(total) * f64::from(percent) / 100.0;
+        if share(self.requests_per_minute) < 1.0 || share(self.orders_per_minute) < 1.0 {
+            bail!(
+                "{percent}% of {} requests and {} orders per minute is less than one a minute for {strategy}",
+                self.requests_per_minute,
+                self.orders_per_minute
+            );
+        }
+        allocations.insert(
+            strategy,
+            Allocation {
+                requests: TokenBucket::per_minute(share(self.requests_per_minute)),
+                orders: TokenBucket::per_minute(share(self.orders_per_minute)),
+            },
+        );
+        self.allocated_percent += percent;
+
+        Ok(())
+    }
+
+    /// Waits until `strategy` has budget left for the call and consumes it.
+    pub async fn acquire(&self, strategy: &str, kind: BudgetKind) -> Result<()> {
+        loop {
+            let wait = {
+                let mut allocations = self.allocations.lock().unwrap_or_else(PoisonError::into_inner);
+                let allocation = allocations
+                    .get_mut(strategy)
+                    .ok_or_else(|| anyhow!("no API budget allocated to strategy {strategy}"))?;
+                let bucket = match kind {
+                    BudgetKind::Request => &mut allocation.requests,
+                    BudgetKind::Order => &mut allocation.orders,
+                };
+                match bucket.try_take() {
+                    Ok(()) => return Ok(()),
+                    Err(wait) => wait,
+                }
+            };
+            sleep(wait).await;
+        }
+    }
+}
+
+/// The `[api_budget]` config table, e.g. splitting the budget 60/40 between two strategies:
+///
+/// ```toml
+/// [api_budget]
+/// requests_per_minute = 200
+/// orders_per_minute = 50
+/// allocations = { momentum = 60, mean_reversion = 40 }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(deny_unknown_fields)]
+pub struct ApiBudgetConfig {
+    pub requests_per_minute: u32,
+    pub orders_per_minute: u32,
+    /// Percentage of the budget given to each strategy
+    pub allocations: BTreeMap<String, u32>,
+}
+
+impl ApiBudgetConfig {
+    pub fn build(&self) -> Result<ApiBudget> {
+        let mut api_budget = ApiBudget::new(self.requests_per_minute, self.orders_per_minute);
+        for (strategy, percent) in &self.allocations {
+            api_budget.allocate(strategy.clone(), *percent)?;
+        }
+        Ok(api_budget)
+    }
+}
+
+/// View of the client for one strategy, every API call made through it draws from that
+/// strategy's budget on top of the client's rate limit. Views of several strategies can be used
+/// at the same time.
+#[derive(Clone)]
+pub struct StrategyClient<'a> {
+    client: &'a AlpacaClient,
+    strategy: String,
+}
+
+impl<'a> StrategyClient<'a> {
+    /// Makes any call on the client within the strategy's budget, e.g.
+    /// `strategy.run(|client| client.cancel_order(&order_id))`.
+    pub async fn run<Fut: Future>(&self, call: impl FnOnce(&'a AlpacaClient) -> Fut) -> Fut::Output {
+        STRATEGY.scope(self.strategy.clone(), call(self.client)).await
+    }
+}
+
+impl IronTradeClient for StrategyClient<'_> {
+    async fn place_order(&mut self, req: OrderRequest) -> Result<String> {
+        self.run(|client| async move {
+            let (asset_symbol, request) = client.create_request(req, OrderOptions::default()).await?;
+            client.submit_order(&asset_symbol, &request, RetryOverride::Default).await
+        })
+        .await
+    }
+
+    async fn get_orders(&self) -> Result<Vec<IronTradeOrder>> {
+        self.run(|client| client.get_orders()).await
+    }
+
+    async fn get_buying_power(&self) -> Result<Num> {
+        self.run(|client| client.get_buying_power()).await
+    }
+
+    async fn get_cash(&self) -> Result<Num> {
+        self.run(|client| client.get_cash()).await
+    }
+
+    async fn get_open_position(&self, asset_symbol: &str) -> Result<IronTradeOpenPosition> {
+        self.run(|client| client.get_open_position(asset_symbol)).await
+    }
+}
+
+impl AlpacaClient {
+    pub fn set_api_budget(&mut self, api_budget: Option<ApiBudget>) {
+        self.api_budget = api_budget;
+    }
+
+    /// Client for `strategy`, limited to its share of the API budget if one is set.
+    pub fn strategy(&self, strategy: impl Into<String>) -> StrategyClient<'_> {
+        StrategyClient {
+            client: self,
+            strategy: strategy.into(),
+        }
+    }
+
+    /// Waits for budget when called on behalf of a strategy, see [`AlpacaClient::strategy`].
+    /// Calls made outside of one aren't budgeted.
+    pub(crate) async fn acquire_budget(&self, kind: BudgetKind) -> Result<()> {
+        let Some(api_budget) = &self.api_budget else {
+            return Ok(());
+        };
+        match STRATEGY.try_with(Clone::clone) {
+            Ok(strategy) => api_budget.acquire(&strategy, kind).await,
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_over_allocation() -> Result<()> {
+        let mut budget = ApiBudget::new(200, 60);
+        budget.allocate("momentum", 60)?;
+
+        assert!(budget.allocate("momentum", 10).is_err());
+        assert!(budget.allocate("mean_reversion", 50).is_err());
+        budget.allocate("mean_reversion", 40)?;
+
+        // 1% of 50 orders a minute would never add up to a whole order
+        let mut budget = ApiBudget::new(200, 50);
+        assert!(budget.allocate("tiny", 1).is_err());
+        budget.allocate("small", 2)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn strategies_draw_from_their_own_share() -> Result<()> {
+        let mut budget = ApiBudget::new(120, 6);
+        budget.allocate("noisy", 50)?;
+        budget.allocate("quiet", 50)?;
+
+        // Three orders per minute each, the noisy strategy burns through its own
+        for _ in 0..3 {
+            budget.acquire("noisy", BudgetKind::Order).await?;
+        }
+
+        let started = Instant::now();
+        budget.acquire("quiet", BudgetKind::Order).await?;
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        assert!(budget.acquire("unknown", BudgetKind::Request).await.is_err());
+
+        Ok(())
+    }
+}
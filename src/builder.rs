@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::env::{DEFAULT_API_BASE_URL, LIVE_API_BASE_URL};
+use crate::http::HttpConfig;
+use anyhow::{Context, Result, anyhow};
+use apca::ApiInfo;
+use std::time::Duration;
+
+/// Builds a client from credentials the application got hold of itself, e.g. from its own
+/// config, instead of from the environment:
+///
+/// ```no_run
+/// # use irontrade_alpaca::client::AlpacaClient;
+/// # use std::time::Duration;
+/// let client = AlpacaClient::builder()
+///     .key_id("AK...")
+///     .secret("...")
+///     .live()
+///     .timeout(Duration::from_secs(10))
+///     .user_agent("mybot/1.0")
+///     .build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// Timeouts and the user agent apply to the requests this crate issues directly, trading
+/// requests sent through apca keep its defaults.
+#[derive(Clone, Debug, Default)]
+pub struct AlpacaClientBuilder {
+    key_id: Option<String>,
+    secret: Option<String>,
+    api_base_url: Option<String>,
+    live: bool,
+    http_config: HttpConfig,
+}
+
+impl AlpacaClientBuilder {
+    pub fn key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    pub fn secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Overrides the paper or live API url, e.g. for a proxy.
+    pub fn api_base_url(mut self, api_base_url: impl Into<String>) -> Self {
+        self.api_base_url = Some(api_base_url.into());
+        self
+    }
+
+    /// Trades on the paper account, the default.
+    pub fn paper(mut self) -> Self {
+        self.live = false;
+        self
+    }
+
+    /// Trades with real money.
+    pub fn live(mut self) -> Self {
+        self.live = true;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http_config.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.http_config.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.http_config.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Replaces all connection settings, including any timeout or user agent set before.
+    pub fn http_config(mut self, http_config: HttpConfig) -> Self {
+        self.http_config = http_config;
+        self
+    }
+
+    pub fn build(self) -> Result<AlpacaClient> {
+        let key_id = self
+            .key_id
+            .ok_or_else(|| anyhow!("no API key id was given"))?;
+        let secret = self
+            .secret
+            .ok_or_else(|| anyhow!("no API secret was given"))?;
+        let api_base_url = self.api_base_url.unwrap_or_else(|| {
+            let api_base_url = if self.live {
+                LIVE_API_BASE_URL
+            } else {
+                DEFAULT_API_BASE_URL
+            };
+            api_base_url.into()
+        });
+
+        let api_info = ApiInfo::from_parts(&api_base_url, key_id, secret)
+            .with_context(|| format!("{api_base_url} is not a valid url"))?;
+        let mut client = AlpacaClient::new(api_info);
+        client.set_http_config(self.http_config)?;
+        Ok(client)
+    }
+}
+
+impl AlpacaClient {
+    pub fn builder() -> AlpacaClientBuilder {
+        AlpacaClientBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_the_account() -> Result<()> {
+        let builder = AlpacaClient::builder().key_id("key").secret("secret");
+
+        let paper = builder.clone().build()?;
+        assert_eq!(
+            paper.apca_client.api_info().api_base_url.as_str(),
+            "https://paper-api.alpaca.markets/"
+        );
+        let live = builder.live().timeout(Duration::from_secs(10)).build()?;
+        assert_eq!(
+            live.apca_client.api_info().api_base_url.as_str(),
+            "https://api.alpaca.markets/"
+        );
+
+        let error = AlpacaClient::builder().key_id("key").build().unwrap_err();
+        assert!(error.to_string().contains("secret"));
+
+        Ok(())
+    }
+}
@@ -1,101 +1,624 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use crate::convert::{Amount, OpenPosition, Order};
-use anyhow::Result;
+use crate::audit::{AuditAction, AuditContext, AuditLog, AuditOutcome};
+use crate::account::{Account, DEFAULT_ACCOUNT_CACHE_TTL};
+use crate::budget::{ApiBudget, BudgetKind};
+use crate::coalesce::SingleFlight;
+use crate::cooldown::{CooldownRule, TradeLogSync};
+use crate::convert::{Amount, OpenPosition, Order, OrderId, OrderSide as ConvertedOrderSide};
+use crate::corporate_actions::{SymbolEventCache, SymbolEventGuard};
+use crate::error::{AlpacaError, ResponseError};
+use crate::hedge::Hedger;
+use crate::http::HttpConfig;
+use crate::idempotency::{IdempotencyStore, SubmissionOutcome};
+use crate::notional_fallback::{NotionalConversions, NotionalFallback};
+use crate::order_defaults::{
+    AssetClassDefaults, DefaultOrderType, OrderOptions, OrderTimeInForce, check_extended_hours,
+    check_time_in_force,
+};
+use crate::order_templates::OrderTemplates;
+use crate::positions::AssetClass;
+use crate::rate_limit::RateLimiter;
+use crate::remediation::{OrderRejection, RemediationPolicies};
+use crate::restrictions::SymbolRestrictions;
+use crate::shutdown::ShutdownLog;
+use crate::retry::{Idempotency, RetryOverride, RetryPolicy};
+use crate::spread_guard::SpreadGuard;
+use crate::stale_data::{MarketDataAges, StaleDataGuard};
+use crate::supervisor::Supervisor;
+use crate::symbol::SymbolAliases;
+use crate::symbol_stats::SessionStats;
+use crate::tracker::OrderTracker;
+use crate::trading_window::{SessionCache, TradingSchedule};
+use anyhow::{Context, Result, bail};
 use apca::api::v2::asset::Symbol;
-use apca::api::v2::order::{Side, TimeInForce, Type};
+use apca::api::v2::order::{Amount as ApcaAmount, Order as ApcaOrder, Side, Type};
 use apca::api::v2::orders::{ListReq, Status};
-use apca::api::v2::{account, order, orders, position};
-use apca::{ApiInfo, Client};
+use apca::api::v2::{order, orders, position, positions};
+use apca::{ApiInfo, Client, RequestError};
 use irontrade::api::client::IronTradeClient;
 use irontrade::api::common::{OpenPosition as IronTradeOpenPosition, Order as IronTradeOrder, OrderSide};
 use irontrade::api::request::OrderRequest;
 use num_decimal::Num;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// How submitting `request` is recorded in the audit log.
+pub(crate) fn submit_action(asset_symbol: &str, request: &order::CreateReq) -> AuditAction {
+    let (quantity, notional) = match &request.amount {
+        ApcaAmount::Quantity { quantity } => (Some(quantity.clone()), None),
+        ApcaAmount::Notional { notional } => (None, Some(notional.clone())),
+    };
+    let side = match request.side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    };
+    AuditAction::Submit {
+        asset_symbol: asset_symbol.to_string(),
+        side: side.into(),
+        quantity,
+        notional,
+        limit_price: request.limit_price.clone(),
+        stop_price: request.stop_price.clone(),
+    }
+}
 
 pub struct AlpacaClient {
-    apca_client: Client,
+    pub(crate) apca_client: Client,
+    pub(crate) http_client: reqwest::Client,
+    pub(crate) spread_guard: Option<SpreadGuard>,
+    order_tracker: Mutex<OrderTracker>,
+    pub(crate) config_path: Option<PathBuf>,
+    pub(crate) http_config: HttpConfig,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) supervisor: Supervisor,
+    pub(crate) audit_log: Option<Mutex<AuditLog>>,
+    pub(crate) audit_context: AuditContext,
+    pub(crate) symbol_restrictions: SymbolRestrictions,
+    pub(crate) trading_schedule: Option<TradingSchedule>,
+    pub(crate) session_cache: SessionCache,
+    pub(crate) api_budget: Option<ApiBudget>,
+    pub(crate) account_flight: SingleFlight<Account>,
+    pub(crate) account_cache_ttl: Duration,
+    pub(crate) hedger: Hedger,
+    pub(crate) stale_data_guard: Option<StaleDataGuard>,
+    pub(crate) market_data_ages: MarketDataAges,
+    pub(crate) session_stats: SessionStats,
+    pub(crate) cooldown_rule: Option<CooldownRule>,
+    pub(crate) trade_log_sync: TradeLogSync,
+    pub(crate) order_defaults: AssetClassDefaults,
+    pub(crate) symbol_event_guard: Option<SymbolEventGuard>,
+    pub(crate) symbol_events: SymbolEventCache,
+    pub(crate) symbol_aliases: SymbolAliases,
+    pub(crate) notional_fallback: Option<NotionalFallback>,
+    pub(crate) notional_conversions: NotionalConversions,
+    pub(crate) remediation_policies: RemediationPolicies,
+    pub(crate) rate_limiter: RateLimiter,
+    pub(crate) idempotency_store: Option<Mutex<IdempotencyStore>>,
+    pub(crate) order_templates: OrderTemplates,
+    pub(crate) shutdown_log: ShutdownLog,
 }
 
 impl AlpacaClient {
-    fn new(api_info: ApiInfo) -> Self {
+    pub(crate) fn new(api_info: ApiInfo) -> Self {
         Self {
             apca_client: Client::new(api_info),
+            http_client: reqwest::Client::new(),
+            spread_guard: None,
+            order_tracker: Mutex::default(),
+            config_path: None,
+            http_config: HttpConfig::default(),
+            retry_policy: RetryPolicy::default(),
+            supervisor: Supervisor::new(),
+            audit_log: None,
+            audit_context: AuditContext::default(),
+            symbol_restrictions: SymbolRestrictions::default(),
+            trading_schedule: None,
+            session_cache: SessionCache::default(),
+            api_budget: None,
+            account_flight: SingleFlight::default(),
+            account_cache_ttl: DEFAULT_ACCOUNT_CACHE_TTL,
+            hedger: Hedger::default(),
+            stale_data_guard: None,
+            market_data_ages: MarketDataAges::default(),
+            session_stats: SessionStats::default(),
+            cooldown_rule: None,
+            trade_log_sync: TradeLogSync::default(),
+            order_defaults: AssetClassDefaults::default(),
+            symbol_event_guard: None,
+            symbol_events: SymbolEventCache::default(),
+            symbol_aliases: SymbolAliases::default(),
+            notional_fallback: None,
+            notional_conversions: NotionalConversions::default(),
+            remediation_policies: RemediationPolicies::default(),
+            rate_limiter: RateLimiter::default(),
+            idempotency_store: None,
+            order_templates: OrderTemplates::default(),
+            shutdown_log: ShutdownLog::default(),
         }
     }
-}
 
-impl IronTradeClient for AlpacaClient {
-    async fn place_order(&mut self, req: OrderRequest) -> Result<String> {
+    /// Orders placed through this client and their last seen fill state.
+    pub fn order_tracker(&self) -> MutexGuard<'_, OrderTracker> {
+        self.order_tracker.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn order_tracker_mut(&mut self) -> &mut OrderTracker {
+        self.order_tracker.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) async fn list_open_positions(&self) -> Result<Vec<IronTradeOpenPosition>> {
+        let positions = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<positions::List>(&())
+            })
+            .await?
+            .into_iter()
+            .map(|position| {
+                let open_position: OpenPosition = position.into();
+                open_position.0
+            })
+            .collect();
+
+        Ok(positions)
+    }
+
+    /// Brings the tracker up to date with a single fetched order, its legs included, and converts
+    /// it with the symbol under its alias.
+    pub(crate) fn record_order(&self, apca_order: ApcaOrder) -> IronTradeOrder {
+        let mut order_tracker = self.order_tracker();
+        for leg in &apca_order.legs {
+            order_tracker.update(&Order::from(leg).0);
+        }
+        let Order(mut order) = apca_order.into();
+        order_tracker.update(&order);
+        order.asset_symbol = self.symbol_aliases.alias(&order.asset_symbol).to_string();
+        order
+    }
+
+    /// Brings the tracker up to date with listed orders and converts them, legs of nested orders
+    /// following their parent.
+    pub(crate) fn record_listed_orders(&self, apca_orders: Vec<ApcaOrder>) -> Vec<IronTradeOrder> {
+        let mut order_tracker = self.order_tracker();
+        // Replacements made elsewhere, e.g. in the dashboard, still extend the chain
+        for order in &apca_orders {
+            if let Some(replaced_by) = &order.replaced_by {
+                order_tracker.replace(&order.id.to_string(), replaced_by.to_string());
+            }
+        }
+
+        apca_orders
+            .into_iter()
+            .flat_map(|mut order| {
+                let legs = std::mem::take(&mut order.legs);
+                std::iter::once(order).chain(legs)
+            })
+            .map(|order| {
+                let Order(mut order) = order.into();
+                order_tracker.update(&order);
+                order.asset_symbol = self.symbol_aliases.alias(&order.asset_symbol).to_string();
+                order
+            })
+            .collect()
+    }
+
+    /// Local compliance checks every order has to pass before it's sent.
+    pub(crate) async fn check_order_allowed(&self, asset_symbol: &str, side: &OrderSide) -> Result<()> {
+        self.symbol_restrictions.check(asset_symbol)?;
+        self.check_trading_schedule().await?;
+        self.check_stale_data(asset_symbol)?;
+        if AssetClass::of_symbol(asset_symbol) == AssetClass::Equity {
+            self.check_symbol_events(asset_symbol).await?;
+        }
+        if self.cooldown_rule.is_some() {
+            self.sync_trade_log().await?;
+            self.check_cooldown(asset_symbol, side)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the local checks on a fully built order, records it in the audit log, submits it and
+    /// starts tracking it. Every order placed by the client goes through here.
+    pub(crate) async fn submit_order(
+        &self,
+        asset_symbol: &str,
+        request: &order::CreateReq,
+        retry: RetryOverride,
+    ) -> Result<String> {
+        let order = self.submit_apca_order(asset_symbol, request, retry).await?;
+        Ok(order.id.to_string())
+    }
+
+    /// [`AlpacaClient::submit_order`] returning the created order as Alpaca sent it back, legs
+    /// of multi-leg orders included. With an idempotency store, a client order id submitted
+    /// before returns the order it placed or the rejection it got instead of submitting again.
+    pub(crate) async fn submit_apca_order(
+        &self,
+        asset_symbol: &str,
+        request: &order::CreateReq,
+        retry: RetryOverride,
+    ) -> Result<ApcaOrder> {
+        let Some(client_order_id) = &request.client_order_id else {
+            return self.submit_remediated(asset_symbol, request, retry).await;
+        };
+        match self.recorded_submission(client_order_id) {
+            Some(SubmissionOutcome::Placed { order_id }) => return self.fetch_apca_order(&order_id).await,
+            Some(SubmissionOutcome::Rejected { error }) => {
+                bail!("order {client_order_id} was already submitted and rejected: {error}")
+            }
+            None => {}
+        }
+
+        let result = self.submit_remediated(asset_symbol, request, retry).await;
+        self.record_submission(client_order_id, &result, |order| order.id.to_string());
+        result
+    }
+
+    /// Submits the order, and when it's rejected for a reason with a remediation policy,
+    /// remediates, records that in the audit log and resubmits once.
+    async fn submit_remediated(
+        &self,
+        asset_symbol: &str,
+        request: &order::CreateReq,
+        retry: RetryOverride,
+    ) -> Result<ApcaOrder> {
+        let error = match self.submit_apca_order_once(asset_symbol, request, retry).await {
+            Ok(order) => return Ok(order),
+            Err(error) => error,
+        };
+        let Some(rejection) = OrderRejection::classify(&error) else {
+            return Err(error);
+        };
+        let Some(remediation) = self.remediation_policies.policy(rejection) else {
+            return Err(error);
+        };
+
+        let mut remediated = request.clone();
+        let details = if let Some(delay) = remediation.delay() {
+            format!("resubmitting after {} seconds", delay.as_secs())
+        } else if let Some(amount) = remediation.downsize(&request.amount) {
+            let details = format!("amount downsized from {:?} to {:?}", request.amount, amount);
+            remediated.amount = amount;
+            details
+        } else {
+            return Err(error);
+        };
+        self.audit(
+            &AuditAction::Remediate {
+                asset_symbol: asset_symbol.to_string(),
+                rejection: rejection.to_string(),
+                details,
+            },
+            AuditOutcome::Requested,
+        )?;
+        if let Some(delay) = remediation.delay() {
+            sleep(delay).await;
+        }
+
+        self.submit_apca_order_once(asset_symbol, &remediated, retry).await
+    }
+
+    async fn submit_apca_order_once(
+        &self,
+        asset_symbol: &str,
+        request: &order::CreateReq,
+        retry: RetryOverride,
+    ) -> Result<ApcaOrder> {
+        let action = submit_action(asset_symbol, request);
+
+        let order_side: ConvertedOrderSide = request.side.into();
+        if let Err(error) = self.check_order_allowed(asset_symbol, &order_side.0).await {
+            let _ = self.audit(
+                &action,
+                AuditOutcome::Failed {
+                    error: error.to_string(),
+                },
+            );
+            return Err(error);
+        }
+
+        // Alpaca refuses a second order with the same client order id, which makes resubmitting
+        // safe once the caller picked one
+        let idempotency = match request.client_order_id {
+            Some(_) => Idempotency::Idempotent,
+            None => Idempotency::NonIdempotent,
+        };
+        self.acquire_budget(BudgetKind::Order).await?;
+        let create = async {
+            let error = match self
+                .retrying(idempotency, retry, || self.create_order(asset_symbol, request))
+                .await
+            {
+                Ok(order) => return Ok(order),
+                Err(error) => error,
+            };
+            let Some(client_order_id) = &request.client_order_id else {
+                return Err(error);
+            };
+            // The first attempt may have gone through with only its response lost, in which case
+            // the retry was refused as a duplicate and the order to return is the first one
+            match self.fetch_apca_order_by_client_id(client_order_id).await {
+                Ok(Some(order)) => Ok(order),
+                _ => Err(error),
+            }
+        };
+        let order = self
+            .audited(action, |order: &ApcaOrder| Some(order.id.to_string()), create)
+            .await?;
+        // Buying power changes as soon as the order is accepted
+        self.account_flight.invalidate().await;
+
+        let mut order_tracker = self.order_tracker();
+        order_tracker.track(order.id.to_string(), asset_symbol.to_string());
+        order_tracker.identify(&order.id.to_string(), order.client_order_id.clone());
+        for leg in &order.legs {
+            order_tracker.track(leg.id.to_string(), asset_symbol.to_string());
+            order_tracker.identify(&leg.id.to_string(), leg.client_order_id.clone());
+        }
+
+        Ok(order)
+    }
+
+    /// `POST /v2/orders` on the crate's own HTTP client rather than apca's, so order submissions
+    /// use the connection pool and keep-alive settings of [`HttpConfig`].
+    async fn create_order(&self, asset_symbol: &str, request: &order::CreateReq) -> Result<ApcaOrder> {
+        let api_info = self.apca_client.api_info();
+        let url = api_info.api_base_url.join("v2/orders")?;
+
+        let response = self
+            .http_client
+            .post(url.as_str())
+            .header("APCA-API-KEY-ID", &api_info.key_id)
+            .header("APCA-API-SECRET-KEY", &api_info.secret)
+            .json(request)
+            .send()
+            .await?;
+
+        let response = ResponseError::check(response)
+            .await
+            .with_context(|| format!("submitting the order for {asset_symbol} failed"))?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Same as [`IronTradeClient::place_order`], with control over whether a failed submission
+    /// is retried. Order creation isn't retried by default since a lost response could otherwise
+    /// create a duplicate order.
+    pub async fn place_order_with_retry(&mut self, req: OrderRequest, retry: RetryOverride) -> Result<String> {
+        let options = OrderOptions {
+            retry,
+            ..Default::default()
+        };
+        self.place_order_with_options(req, options).await
+    }
+
+    /// Same as [`IronTradeClient::place_order`], with the order parameters that
+    /// [`OrderRequest`] doesn't carry. Unset options come from the defaults of the symbol's
+    /// asset class.
+    pub async fn place_order_with_options(&mut self, req: OrderRequest, options: OrderOptions) -> Result<String> {
+        let retry = options.retry;
+        let (asset_symbol, request) = self.create_request(req, options).await?;
+        self.submit_order(&asset_symbol, &request, retry).await
+    }
+
+    /// Same as [`IronTradeClient::place_order`] under the caller's own unique id of up to 128
+    /// characters. A failed submission is retried like a read, since Alpaca refuses a second
+    /// order with the same id, and when that turns out to have already created the order, the
+    /// existing order's id is returned. Resubmitting an id thus never duplicates an order.
+    pub async fn place_order_with_client_id(&mut self, req: OrderRequest, client_order_id: &str) -> Result<String> {
+        let options = OrderOptions {
+            client_order_id: Some(client_order_id.to_string()),
+            ..Default::default()
+        };
+        self.place_order_with_options(req, options).await
+    }
+
+    /// Places a day limit order for a stock that may also fill in the pre and post market
+    /// sessions. Fails without a limit price.
+    pub async fn place_extended_hours_order(&mut self, req: OrderRequest) -> Result<String> {
+        let options = OrderOptions {
+            time_in_force: Some(OrderTimeInForce::Day),
+            extended_hours: Some(true),
+            ..Default::default()
+        };
+        self.place_order_with_options(req, options).await
+    }
+
+    /// Current state of a single order, without listing all of them. The legs of a multi-leg
+    /// order update their tracked state along with it, [`AlpacaClient::get_linked_order`]
+    /// returns them.
+    pub async fn get_order(&self, order_id: &str) -> Result<IronTradeOrder> {
+        let order = self.fetch_apca_order(order_id).await?;
+        Ok(self.record_order(order))
+    }
+
+    /// The order placed under `client_order_id`, `None` when there's none.
+    pub async fn get_order_by_client_id(&self, client_order_id: &str) -> Result<Option<IronTradeOrder>> {
+        let order = self.fetch_apca_order_by_client_id(client_order_id).await?;
+        Ok(order.map(|order| self.record_order(order)))
+    }
+
+    /// Every open position in the account, under its alias where one is configured.
+    pub async fn get_open_positions(&self) -> Result<Vec<IronTradeOpenPosition>> {
+        let mut positions = self.list_open_positions().await?;
+        for position in &mut positions {
+            position.asset_symbol = self.symbol_aliases.alias(&position.asset_symbol).to_string();
+        }
+        Ok(positions)
+    }
+
+    /// The open position in `asset_symbol`, `None` when the account holds none rather than
+    /// failing like [`IronTradeClient::get_open_position`].
+    pub async fn find_open_position(&self, asset_symbol: &str) -> Result<Option<IronTradeOpenPosition>> {
+        let symbol = Symbol::Sym(self.symbol_aliases.resolve(asset_symbol).into());
+        let fetched = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<position::Get>(&symbol)
+            })
+            .await;
+        let position = match fetched {
+            Ok(position) => position,
+            Err(error) => match error.downcast_ref::<RequestError<position::GetError>>() {
+                Some(RequestError::Endpoint(position::GetError::NotFound(_))) => return Ok(None),
+                _ => return Err(error),
+            },
+        };
+
+        let open_position: OpenPosition = position.into();
+        let mut open_position = open_position.0;
+        open_position.asset_symbol = self
+            .symbol_aliases
+            .alias(&open_position.asset_symbol)
+            .to_string();
+
+        Ok(Some(open_position))
+    }
+
+    /// Builds the Alpaca request [`AlpacaClient::place_order_with_options`] submits, for callers
+    /// that attach more to it before submitting. Returns it with the order's symbol.
+    pub(crate) async fn create_request(
+        &self,
+        req: OrderRequest,
+        options: OrderOptions,
+    ) -> Result<(String, order::CreateReq)> {
+        let req = self.resolve_order_request(req)?;
+        // Stop orders trigger on price already, the spread guard is for immediate executions
+        let is_stop = options.stop_price.is_some() || options.trail.is_some();
+        let mut req = if is_stop { req } else { self.apply_spread_guard(req).await? };
+
+        let asset_symbol = req.asset_pair.to_string();
+        let asset_class = AssetClass::of_symbol(&asset_symbol);
+        let defaults = self.order_defaults.for_class(asset_class).clone();
+        let time_in_force = options.time_in_force.unwrap_or(defaults.time_in_force);
+        check_time_in_force(asset_class, time_in_force)?;
+        let order_type = options.order_type.unwrap_or(defaults.order_type);
+
+        if req.limit_price.is_none() && !is_stop && order_type == DefaultOrderType::MarketableLimit {
+            let limit_price = self
+                .marketable_limit_price(&asset_symbol, &req.side, &defaults.limit_offset_bps)
+                .await?;
+            req.limit_price = Some(limit_price);
+        }
+        if !is_stop {
+            req = self.apply_notional_fallback(req).await?;
+        }
+
         let side: Side = match req.side {
             OrderSide::Buy => Side::Buy,
             OrderSide::Sell => Side::Sell,
         };
 
-        let type_: Type;
-
-        if req.limit_price.is_some() {
-            type_ = Type::Limit;
-        } else {
-            type_ = Type::Market;
+        let type_ = match (&req.limit_price, &options.stop_price) {
+            _ if options.trail.is_some() => Type::TrailingStop,
+            (Some(_), Some(_)) => Type::StopLimit,
+            (None, Some(_)) => Type::Stop,
+            (Some(_), None) => Type::Limit,
+            (None, None) => Type::Market,
+        };
+        if asset_class == AssetClass::Crypto && matches!(type_, Type::Stop | Type::TrailingStop) {
+            bail!("crypto only supports stop limit orders, set a limit price for {asset_symbol}");
+        }
+        if matches!(time_in_force, OrderTimeInForce::AtTheOpen | OrderTimeInForce::AtTheClose)
+            && !matches!(type_, Type::Market | Type::Limit)
+        {
+            bail!("auction orders can only be market or limit orders");
+        }
+        if matches!(type_, Type::TrailingStop) {
+            if req.limit_price.is_some() || options.stop_price.is_some() {
+                bail!("trailing stop orders take neither a limit nor a stop price");
+            }
+            if !matches!(time_in_force, OrderTimeInForce::Day | OrderTimeInForce::GoodTilCanceled) {
+                bail!("trailing stop orders are only accepted as day or good til canceled orders");
+            }
         }
 
+        // The class default only applies where Alpaca accepts it, asking for it on an order fails
+        let extended_hours = match options.extended_hours {
+            Some(true) => {
+                check_extended_hours(asset_class, type_, time_in_force)?;
+                true
+            }
+            Some(false) => false,
+            None => {
+                defaults.extended_hours && check_extended_hours(asset_class, type_, time_in_force).is_ok()
+            }
+        };
+
+        let (trail_price, trail_percent) = match options.trail {
+            Some(trail) => trail.into_apca_fields(),
+            None => (None, None),
+        };
         let amount = Amount(req.amount);
         let request = order::CreateReqInit {
             type_,
-            time_in_force: TimeInForce::UntilCanceled,
+            time_in_force: time_in_force.into(),
             limit_price: req.limit_price,
+            stop_price: options.stop_price,
+            trail_price,
+            trail_percent,
+            extended_hours,
+            client_order_id: options.client_order_id,
             ..Default::default()
         }
-        .init(req.asset_pair.to_string(), side, amount.into());
+        .init(asset_symbol.clone(), side, amount.into());
 
-        let order_id = self
-            .apca_client
-            .issue::<order::Create>(&request)
-            .await?
-            .id
-            .to_string();
+        Ok((asset_symbol, request))
+    }
 
-        Ok(order_id)
+    /// Asks Alpaca to cancel the order. Fails when the order can't be canceled anymore, e.g.
+    /// because it filled in the meantime.
+    pub async fn cancel_order(&self, order_id: &str) -> Result<()> {
+        let OrderId(id) = order_id.try_into()?;
+        let action = AuditAction::Cancel {
+            order_id: order_id.to_string(),
+        };
+        let cancel = self.retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+            self.apca_client.issue::<order::Delete>(&id)
+        });
+        self.audited(action, |_| Some(order_id.to_string()), cancel).await?;
+        self.account_flight.invalidate().await;
+        Ok(())
+    }
+}
+
+impl IronTradeClient for AlpacaClient {
+    async fn place_order(&mut self, req: OrderRequest) -> Result<String> {
+        self.place_order_with_retry(req, RetryOverride::Default).await
     }
 
     async fn get_orders(&self) -> Result<Vec<IronTradeOrder>> {
-        let orders: Vec<IronTradeOrder> = self
-            .apca_client
-            .issue::<orders::List>(&ListReq {
-                status: Status::All,
-                ..Default::default()
-            })
-            .await?
-            .iter()
-            .map(|order| {
-                let order: Order = order.clone().into();
-                order.0
+        let request = ListReq {
+            status: Status::All,
+            ..Default::default()
+        };
+        let apca_orders = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<orders::List>(&request)
             })
-            .collect();
+            .await?;
 
-        Ok(orders)
+        Ok(self.record_listed_orders(apca_orders))
     }
 
     async fn get_buying_power(&self) -> Result<Num> {
-        let buying_power = self.apca_client.issue::<account::Get>(&()).await?.buying_power;
+        let buying_power = self.get_balances().await?.buying_power;
         Ok(buying_power)
     }
 
     async fn get_cash(&self) -> Result<Num> {
-        let cash = self.apca_client.issue::<account::Get>(&()).await?.cash;
+        let cash = self.get_balances().await?.cash;
         Ok(cash)
     }
 
+    /// Fails with [`AlpacaError::PositionNotFound`] when there's no open position, see
+    /// [`AlpacaClient::find_open_position`] to tell a flat account from a failed request.
     async fn get_open_position(&self, asset_symbol: &str) -> Result<IronTradeOpenPosition> {
-        let position = self
-            .apca_client
-            .issue::<position::Get>(&Symbol::Sym(asset_symbol.into()))
-            .await?;
-
-        let open_position: OpenPosition = position.into();
-        let open_position = open_position.0;
-
-        Ok(open_position)
+        self.find_open_position(asset_symbol).await?.ok_or_else(|| {
+            anyhow::Error::new(AlpacaError::PositionNotFound)
+                .context(format!("no open position for {asset_symbol}"))
+        })
     }
 }
 
@@ -103,98 +626,105 @@ impl IronTradeClient for AlpacaClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::account::AccountStatus;
+    use crate::data::TimeFrame;
+    use crate::order_query::OrderQuery;
+    use crate::positions::CloseAmount;
+    use crate::replace::OrderChange;
+    use crate::stream::{MarketDataFeed, MarketDataSubscription};
+    use crate::testing::TestHarness;
+    use crate::wait::WaitOutcome;
     use apca::ApiInfo;
+    use chrono::Utc;
     use irontrade::api::common::{Amount, AssetPair, OrderStatus};
     use num_decimal::Num;
     use std::str::FromStr;
-    use std::time::Duration;
-    use tokio::time::sleep;
+    use std::time::{Duration, Instant};
+    use tokio_util::sync::CancellationToken;
 
     #[tokio::test]
     async fn buy_market_returns_order_id() -> Result<()> {
-        let mut client = create_client();
-        let order_id = client
-            .place_order(OrderRequest {
-                asset_pair: AssetPair::from_str("BTC/USD")?,
-                amount: Amount::Notional {
-                    notional: Num::from(20),
-                },
-                side: OrderSide::Buy,
-                limit_price: None,
-            })
-            .await?;
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("BTC/USD")?,
+                        amount: Amount::Notional {
+                            notional: Num::from(20),
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: None,
+                    })
+                    .await?;
 
-        assert_ne!(order_id, "");
+                assert_ne!(order_id, "");
 
-        Ok(())
+                Ok(())
+            })
+            .await
     }
 
     #[tokio::test]
     async fn sell_market_returns_order_id() -> Result<()> {
-        let mut client = create_client();
-
-        let buy_order_id = client
-            .place_order(OrderRequest {
-                asset_pair: AssetPair::from_str("AAVE/USD")?,
-                amount: Amount::Notional {
-                    notional: Num::from(20),
-                },
-                side: OrderSide::Buy,
-                limit_price: None,
-            })
-            .await?;
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let buy_order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("AAVE/USD")?,
+                        amount: Amount::Notional {
+                            notional: Num::from(20),
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: None,
+                    })
+                    .await?;
 
-        loop {
-            let orders = client.get_orders().await?;
-            let buy_order = orders
-                .iter()
-                .find(|order| order.order_id == buy_order_id)
-                .unwrap();
-            if matches!(buy_order.status, OrderStatus::Filled) {
-                break;
-            }
-            sleep(Duration::from_secs(1)).await;
-        }
+                let outcome = harness
+                    .client()
+                    .wait_for_fill_with_timeout(&buy_order_id, Duration::from_secs(30), Duration::from_secs(1))
+                    .await?;
+                assert!(matches!(outcome, WaitOutcome::Filled(_)));
 
-        let order_id = client
-            .place_order(OrderRequest {
-                asset_pair: AssetPair::from_str("AAVE/USD")?,
-                amount: Amount::Notional {
-                    notional: Num::from(10),
-                },
-                side: OrderSide::Sell,
-                limit_price: None,
-            })
-            .await?;
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("AAVE/USD")?,
+                        amount: Amount::Notional {
+                            notional: Num::from(10),
+                        },
+                        side: OrderSide::Sell,
+                        limit_price: None,
+                    })
+                    .await?;
 
-        assert_ne!(order_id, "");
+                assert_ne!(order_id, "");
 
-        Ok(())
+                Ok(())
+            })
+            .await
     }
 
-    // TODO: Run this test atomically
     #[tokio::test]
     async fn get_orders() -> Result<()> {
-        let mut client = create_client();
-        let pre_existing_orders = client.get_orders().await?;
-
-        if pre_existing_orders.is_empty() {
-            client
-                .place_order(OrderRequest {
-                    asset_pair: AssetPair::from_str("BTC/USD")?,
-                    amount: Amount::Notional {
-                        notional: Num::from(20),
-                    },
-                    side: OrderSide::Buy,
-                    limit_price: None,
-                }).await?;
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("BTC/USD")?,
+                        amount: Amount::Notional {
+                            notional: Num::from(20),
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: None,
+                    })
+                    .await?;
 
-            let orders = client.get_orders().await?;
+                let orders = harness.client().get_orders().await?;
 
-            assert!(orders.len() > 0);
-        }
+                assert!(orders.iter().any(|order| order.order_id == order_id));
 
-        Ok(())
+                Ok(())
+            })
+            .await
     }
 
     #[tokio::test]
@@ -213,38 +743,438 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn get_balances_shares_concurrent_request() -> Result<()> {
+        let client = create_client();
+        let (balances, cash) = tokio::join!(client.get_balances(), client.get_cash());
+        assert_eq!(balances?.cash, cash?);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn get_open_position() -> Result<()> {
-        let mut client = create_client();
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let buy_order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("BTC/USD")?,
+                        amount: Amount::Notional {
+                            notional: Num::from(20),
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: None,
+                    })
+                    .await?;
 
-        let buy_order_id = client
-            .place_order(OrderRequest {
-                asset_pair: AssetPair::from_str("BTC/USD")?,
-                amount: Amount::Notional {
-                    notional: Num::from(20),
-                },
-                side: OrderSide::Buy,
-                limit_price: None,
+                let outcome = harness
+                    .client()
+                    .wait_for_fill_with_timeout(&buy_order_id, Duration::from_secs(30), Duration::from_secs(1))
+                    .await?;
+                assert!(matches!(outcome, WaitOutcome::Filled(_)));
+
+                let position = harness.client().get_open_position("BTC/USD").await?;
+
+                assert_eq!(position.asset_symbol, "BTCUSD");
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn get_open_positions_lists_the_book() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("BTC/USD")?,
+                        amount: Amount::Notional {
+                            notional: Num::from(20),
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: None,
+                    })
+                    .await?;
+                harness
+                    .client()
+                    .wait_for_fill(&order_id, Duration::from_secs(1), &CancellationToken::new())
+                    .await?;
+
+                let positions = harness.client().get_open_positions().await?;
+                assert!(positions.iter().any(|position| position.asset_symbol == "BTCUSD"));
+
+                Ok(())
             })
+            .await
+    }
+
+    #[tokio::test]
+    async fn get_bars_returns_historical_bars() -> Result<()> {
+        let client = create_client();
+        let end = Utc::now();
+
+        let bars = client
+            .get_bars("BTC/USD", TimeFrame::OneHour, end - chrono::TimeDelta::days(2), end)
             .await?;
 
-        loop {
-            let orders = client.get_orders().await?;
-            let buy_order = orders
-                .iter()
-                .find(|order| order.order_id == buy_order_id)
-                .unwrap();
-            if matches!(buy_order.status, OrderStatus::Filled) {
-                break;
-            }
-            sleep(Duration::from_secs(1)).await;
-        }
+        assert!(!bars.is_empty());
+        assert!(bars.iter().all(|bar| bar.symbol == "BTC/USD"));
+        assert!(bars.windows(2).all(|pair| pair[0].time < pair[1].time));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_market_data_subscribes() -> Result<()> {
+        let client = create_client();
+        let subscription = MarketDataSubscription {
+            quotes: vec!["AAPL".into()],
+            trades: vec!["AAPL".into()],
+            bars: vec!["AAPL".into()],
+        };
+
+        let _updates = client.stream_market_data(MarketDataFeed::Iex, &subscription).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_latest_quote_and_trade() -> Result<()> {
+        let client = create_client();
+
+        let quote = client.get_latest_quote("BTC/USD").await?;
+        assert_eq!(quote.symbol, "BTC/USD");
+        assert!(quote.bid_price <= quote.ask_price);
+
+        let trade = client.get_latest_trade("AAPL").await?;
+        assert_eq!(trade.symbol, "AAPL");
+        assert!(trade.price.is_positive());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_account_returns_the_account() -> Result<()> {
+        let client = create_client();
+
+        let account = client.get_account().await?;
+
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(account.equity.is_positive());
+        assert!(account.margin_multiplier >= Num::from(1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn closing_a_position_refreshes_the_cached_account() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                harness.client().set_account_cache_ttl(Duration::from_secs(600));
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("ETH/USD")?,
+                        amount: Amount::Notional {
+                            notional: Num::from(20),
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: None,
+                    })
+                    .await?;
+                let outcome = harness
+                    .client()
+                    .wait_for_fill_with_timeout(&order_id, Duration::from_secs(30), Duration::from_secs(1))
+                    .await?;
+                assert!(matches!(outcome, WaitOutcome::Filled(_)));
+                let cash = harness.client().get_cash().await?;
+
+                let close = harness.client().close_position("ETH/USD", CloseAmount::All).await?;
+                let outcome = harness
+                    .client()
+                    .wait_for_fill_with_timeout(&close.order_id, Duration::from_secs(30), Duration::from_secs(1))
+                    .await?;
+                assert!(matches!(outcome, WaitOutcome::Filled(_)));
+
+                // The cached account from before the close would still show the cash without the sale
+                assert!(harness.client().get_cash().await? > cash);
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn wait_for_fill_with_timeout_gives_up() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let outcome = harness
+                    .client()
+                    .wait_for_fill_with_timeout(
+                        "00000000-0000-0000-0000-000000000000",
+                        Duration::ZERO,
+                        Duration::from_secs(1),
+                    )
+                    .await?;
+
+                assert!(matches!(outcome, WaitOutcome::TimedOut(None)));
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn wait_for_fill_returns_filled_order() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("BTC/USD")?,
+                        amount: Amount::Notional {
+                            notional: Num::from(20),
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: None,
+                    })
+                    .await?;
+
+                let outcome = harness
+                    .client()
+                    .wait_for_fill(&order_id, Duration::from_secs(1), &CancellationToken::new())
+                    .await?;
+
+                assert!(matches!(outcome, WaitOutcome::Filled(order) if order.order_id == order_id));
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn wait_for_fill_stops_on_cancellation() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let cancellation = CancellationToken::new();
+                cancellation.cancel();
+
+                let outcome = harness
+                    .client()
+                    .wait_for_fill(
+                        "00000000-0000-0000-0000-000000000000",
+                        Duration::from_secs(1),
+                        &cancellation,
+                    )
+                    .await?;
+
+                assert!(matches!(outcome, WaitOutcome::Cancelled(None)));
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn place_order_with_deadline_cancels_unfilled_order() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let fill = harness
+                    .place_order_with_deadline(
+                        OrderRequest {
+                            asset_pair: AssetPair::from_str("BTC/USD")?,
+                            amount: Amount::Quantity {
+                                quantity: Num::from_str("0.0002")?,
+                            },
+                            side: OrderSide::Buy,
+                            limit_price: Some(Num::from(1000)),
+                        },
+                        Instant::now() + Duration::from_secs(2),
+                        &CancellationToken::new(),
+                    )
+                    .await?;
+
+                assert!(fill.canceled_at_deadline);
+                assert!(!fill.is_fully_filled());
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn cancel_order_cancels_resting_order() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("BTC/USD")?,
+                        amount: Amount::Quantity {
+                            quantity: Num::from_str("0.0002")?,
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: Some(Num::from(1000)),
+                    })
+                    .await?;
+
+                harness.client().cancel_order(&order_id).await?;
+
+                let order = harness.client().fetch_apca_order(&order_id).await?;
+                assert!(matches!(order.status, order::Status::Canceled | order::Status::PendingCancel));
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn replace_order_amends_resting_order() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("BTC/USD")?,
+                        amount: Amount::Quantity {
+                            quantity: Num::from_str("0.0002")?,
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: Some(Num::from(1000)),
+                    })
+                    .await?;
+
+                let change = OrderChange {
+                    limit_price: Some(Num::from(1100)),
+                    ..Default::default()
+                };
+                let new_order_id = harness.client().replace_order(&order_id, change).await?;
+                assert_ne!(new_order_id, order_id);
+
+                // The replacement isn't tagged, cancel it here rather than in the cleanup
+                let order = harness.client().fetch_apca_order(&new_order_id).await?;
+                harness.client().cancel_order(&new_order_id).await?;
+                assert_eq!(order.limit_price, Some(Num::from(1100)));
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn get_order_returns_a_single_order() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let order_id = harness
+                    .place_order(OrderRequest {
+                        asset_pair: AssetPair::from_str("BTC/USD")?,
+                        amount: Amount::Quantity {
+                            quantity: Num::from_str("0.0002")?,
+                        },
+                        side: OrderSide::Buy,
+                        limit_price: Some(Num::from(1000)),
+                    })
+                    .await?;
+
+                let order = harness.client().get_order(&order_id).await?;
+                assert_eq!(order.order_id, order_id);
+                assert_eq!(order.limit_price, Some(Num::from(1000)));
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn resubmitting_a_client_order_id_returns_the_existing_order() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let asset_pair = AssetPair::from_str("BTC/USD")?;
+                let req = || OrderRequest {
+                    asset_pair: asset_pair.clone(),
+                    amount: Amount::Quantity {
+                        quantity: Num::from_str("0.0002").unwrap(),
+                    },
+                    side: OrderSide::Buy,
+                    limit_price: Some(Num::from(1000)),
+                };
+                let order_id = harness.place_order(req()).await?;
+                let client_order_id = format!("{}-1", harness.tag());
+
+                let resubmitted = harness.client().place_order_with_client_id(req(), &client_order_id).await?;
+                assert_eq!(resubmitted, order_id);
+                let order = harness.client().get_order_by_client_id(&client_order_id).await?;
+                assert!(order.is_some_and(|order| order.order_id == order_id));
+                let unknown = format!("{}-unknown", harness.tag());
+                assert!(harness.client().get_order_by_client_id(&unknown).await?.is_none());
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn query_orders_filters_and_limits() -> Result<()> {
+        let client = create_client();
+        let query = OrderQuery {
+            symbols: vec!["BTC/USD".into()],
+            limit: Some(3),
+            ..Default::default()
+        };
+        let orders = client.query_orders(&query).await?;
+
+        assert!(orders.len() <= 3);
+        assert!(orders.iter().all(|order| order.asset_symbol.replace('/', "") == "BTCUSD"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn prepared_order_is_placed() -> Result<()> {
+        TestHarness::new(create_client())
+            .run(async |harness| {
+                let options = OrderOptions {
+                    client_order_id: Some(format!("{}-prepared", harness.tag())),
+                    ..Default::default()
+                };
+                let prepared = harness
+                    .client()
+                    .prepare_order(
+                        OrderRequest {
+                            asset_pair: AssetPair::from_str("BTC/USD")?,
+                            amount: Amount::Quantity {
+                                quantity: Num::from_str("0.0002")?,
+                            },
+                            side: OrderSide::Buy,
+                            limit_price: Some(Num::from(1000)),
+                        },
+                        options,
+                    )
+                    .await?;
+
+                let order_id = harness.client().place_order_prepared(&prepared).await?;
+                assert_eq!(harness.client().get_order(&order_id).await?.limit_price, Some(Num::from(1000)));
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn get_transfers() -> Result<()> {
+        let client = create_client();
+        let transfers = client.get_transfers(None).await?;
+        assert!(transfers.iter().all(|transfer| transfer.amount >= Num::from(0)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn warm_up_fetches_bars_and_account_state() -> Result<()> {
+        let client = create_client();
+        let symbols = vec!["BTC/USD".to_string(), "AAPL".to_string()];
 
-        let position = client
-            .get_open_position("BTC/USD".into())
+        let snapshot = client
+            .warm_up(&symbols, chrono::TimeDelta::days(7), TimeFrame::OneDay)
             .await?;
 
-        assert_eq!(position.asset_symbol, "BTCUSD");
+        assert!(!snapshot.bars["BTC/USD"].is_empty());
+        assert!(snapshot.bars.contains_key("AAPL"));
+        assert!(snapshot.open_orders.iter().all(|order| !matches!(order.status, OrderStatus::Filled)));
 
         Ok(())
     }
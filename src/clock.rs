@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::auction::MarketSession;
+use crate::client::AlpacaClient;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::America::New_York;
+use std::sync::{Mutex, PoisonError};
+use tokio::time::sleep;
+
+/// How far ahead the calendar is searched for the next open, long enough for any market holiday.
+const SESSION_LOOKAHEAD: TimeDelta = TimeDelta::days(10);
+
+/// The time as a strategy sees it. Live trading uses the wall clock through [`AlpacaClient`],
+/// backtests a [`SimulatedClock`], so time-dependent strategy code runs unchanged in both.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Returns once `time` has come, right away if it already has.
+    fn sleep_until(&self, time: DateTime<Utc>) -> impl Future<Output = ()> + Send;
+
+    /// The first regular session open after now.
+    fn next_session_open(&self) -> impl Future<Output = Result<DateTime<Utc>>> + Send;
+}
+
+impl Clock for AlpacaClient {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep_until(&self, time: DateTime<Utc>) {
+        let wait = (time - Utc::now()).to_std().unwrap_or_default();
+        sleep(wait).await;
+    }
+
+    async fn next_session_open(&self) -> Result<DateTime<Utc>> {
+        let now = Utc::now();
+        let today = now.with_timezone(&New_York).date_naive();
+        let sessions = self.get_market_sessions(today, today + SESSION_LOOKAHEAD).await?;
+        next_open(&sessions, now)
+    }
+}
+
+fn next_open(sessions: &[MarketSession], now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    sessions
+        .iter()
+        .map(|session| session.open)
+        .filter(|open| *open > now)
+        .min()
+        .ok_or_else(|| anyhow!("no market session opens after {now}"))
+}
+
+/// Time that only moves when told to, for backtests and replays, e.g. next to a
+/// [`crate::mock::MockClient`]. Sleeping jumps straight to the wake-up time, and session opens
+/// come from the calendar the clock was created with.
+pub struct SimulatedClock {
+    now: Mutex<DateTime<Utc>>,
+    sessions: Vec<MarketSession>,
+}
+
+impl SimulatedClock {
+    pub fn new(start: DateTime<Utc>, sessions: Vec<MarketSession>) -> Self {
+        Self {
+            now: Mutex::new(start),
+            sessions,
+        }
+    }
+
+    /// Moves the clock forward to `time`, never backwards.
+    pub fn advance_to(&self, time: DateTime<Utc>) {
+        let mut now = self.now.lock().unwrap_or_else(PoisonError::into_inner);
+        *now = (*now).max(time);
+    }
+
+    pub fn advance(&self, by: TimeDelta) {
+        self.advance_to(self.now() + by);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    async fn sleep_until(&self, time: DateTime<Utc>) {
+        self.advance_to(time);
+    }
+
+    async fn next_session_open(&self) -> Result<DateTime<Utc>> {
+        next_open(&self.sessions, self.now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auction::new_york_time;
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn session(day: u32) -> MarketSession {
+        let date = NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        MarketSession {
+            date,
+            open: new_york_time(date, NaiveTime::from_hms_opt(9, 30, 0).unwrap()),
+            close: new_york_time(date, NaiveTime::from_hms_opt(16, 0, 0).unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn simulated_clock_sleeps_to_the_next_open() -> Result<()> {
+        // Friday the 12th, then Tuesday the 16th after the holiday
+        let sessions = vec![session(12), session(16)];
+        let clock = SimulatedClock::new(sessions[0].close, sessions);
+
+        let open = clock.next_session_open().await?;
+        assert_eq!(open, session(16).open);
+
+        clock.sleep_until(open).await;
+        assert_eq!(clock.now(), open);
+        clock.sleep_until(open - TimeDelta::hours(1)).await;
+        assert_eq!(clock.now(), open);
+        assert!(clock.next_session_open().await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Lets concurrent callers of the same request share one round trip: whoever arrives while a
+/// fetch is in flight waits for it and gets its result instead of issuing another request.
+/// Callers arriving after a fetch completed reuse its result for `max_age`, then start a new one.
+#[derive(Debug)]
+pub(crate) struct SingleFlight<T> {
+    last: Mutex<Option<(Instant, T)>>,
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            last: Mutex::new(None),
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub(crate) async fn run<F, Fut>(&self, max_age: Duration, fetch: F) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let arrived = Instant::now();
+        let mut last = self.last.lock().await;
+
+        if let Some((completed, value)) = &*last
+            && *completed + max_age >= arrived
+        {
+            return Ok(value.clone());
+        }
+
+        let value = fetch().await?;
+        *last = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    /// Makes the next caller fetch again, e.g. after a change the last result doesn't reflect.
+    pub(crate) async fn invalidate(&self) {
+        *self.last.lock().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_fetch() -> Result<()> {
+        let flight = SingleFlight::default();
+        let fetches = AtomicU32::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            sleep(Duration::from_millis(50)).await;
+            Ok(42)
+        };
+
+        let (first, second) = tokio::join!(
+            flight.run(Duration::ZERO, fetch),
+            flight.run(Duration::ZERO, fetch)
+        );
+        assert_eq!((first?, second?), (42, 42));
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        // Once completed, the next caller fetches again
+        flight.run(Duration::ZERO, fetch).await?;
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sequential_callers_reuse_a_recent_fetch() -> Result<()> {
+        let flight = SingleFlight::default();
+        let fetches = AtomicU32::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            Ok(42)
+        };
+
+        flight.run(Duration::from_secs(60), fetch).await?;
+        flight.run(Duration::from_secs(60), fetch).await?;
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        flight.invalidate().await;
+        flight.run(Duration::from_secs(60), fetch).await?;
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+
+        Ok(())
+    }
+}
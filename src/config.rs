@@ -0,0 +1,318 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::budget::ApiBudgetConfig;
+use crate::client::AlpacaClient;
+use crate::cooldown::CooldownRule;
+use crate::corporate_actions::SymbolEventGuard;
+use crate::notional_fallback::NotionalFallback;
+use crate::order_defaults::AssetClassDefaults;
+use crate::order_templates::OrderTemplates;
+use crate::rate_limit::RateLimitConfig;
+use crate::remediation::RemediationPolicies;
+use crate::restrictions::SymbolRestrictions;
+use crate::spread_guard::SpreadGuard;
+use crate::stale_data::StaleDataGuard;
+use crate::symbol::SymbolAliases;
+use crate::trading_window::TradingSchedule;
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use tokio::signal::unix::{Signal, SignalKind, signal};
+
+/// Settings that can be changed while the client is running, read from a TOML file. Notifiers
+/// aren't among them since they're handed to the calls that notify rather than kept by the
+/// client. For example:
+///
+/// ```toml
+/// [spread_guard]
+/// max_spread_bps = "25"
+/// action = "convert_to_limit"
+/// limit_offset_bps = "5"
+///
+/// [symbol_restrictions]
+/// deny = ["GME", "AMC"]
+///
+/// [trading_schedule]
+/// windows = [{ start = "09:35", end = "15:55" }]
+/// skip_last_minutes = 5
+///
+/// [stale_data_guard]
+/// max_age_ms = 5000
+/// action = "reject"
+///
+/// [cooldown]
+/// minutes = 30
+///
+/// [order_defaults.crypto]
+/// order_type = "marketable_limit"
+/// limit_offset_bps = "5"
+///
+/// [symbol_event_guard]
+/// action = "warn"
+/// halt_cache_ms = 30000
+///
+/// [symbol_aliases]
+/// BTC = "BTC/USD"
+/// "BRK.B" = "BRK-B"
+///
+/// [notional_fallback]
+/// min_quantities = { "BTC/USD" = "0.0001" }
+/// max_slippage_bps = "10"
+///
+/// [remediation]
+/// insufficient_buying_power = { action = "downsize", percent = "20" }
+/// wash_trade = { action = "delay", seconds = 30 }
+///
+/// [order_templates.scalp-entry]
+/// side = "buy"
+/// order_type = "limit"
+/// time_in_force = "ioc"
+/// extended_hours = false
+/// limit_offset_bps = "10"
+///
+/// [rate_limit]
+/// requests_per_minute = 150
+///
+/// [api_budget]
+/// requests_per_minute = 150
+/// orders_per_minute = 50
+/// allocations = { momentum = 60, mean_reversion = 40 }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    pub spread_guard: Option<SpreadGuard>,
+    pub symbol_restrictions: SymbolRestrictions,
+    pub trading_schedule: Option<TradingSchedule>,
+    pub stale_data_guard: Option<StaleDataGuard>,
+    pub cooldown: Option<CooldownRule>,
+    pub order_defaults: AssetClassDefaults,
+    pub symbol_event_guard: Option<SymbolEventGuard>,
+    pub symbol_aliases: SymbolAliases,
+    pub notional_fallback: Option<NotionalFallback>,
+    pub remediation: RemediationPolicies,
+    pub order_templates: OrderTemplates,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub api_budget: Option<ApiBudgetConfig>,
+}
+
+impl RuntimeConfig {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let toml = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let config: Self = toml::from_str(&toml).with_context(|| format!("invalid config in {}", path.display()))?;
+        config
+            .check()
+            .with_context(|| format!("invalid config in {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Checks the settings that parse but can still be invalid.
+    pub fn check(&self) -> Result<()> {
+        self.order_defaults.check().context("invalid order defaults")?;
+        if let Some(api_budget) = &self.api_budget {
+            api_budget.build().context("invalid API budget")?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves every time the process receives SIGHUP, to trigger [`AlpacaClient::reload_config`]
+/// from the strategy loop.
+#[cfg(unix)]
+pub struct ReloadSignal(Signal);
+
+#[cfg(unix)]
+impl ReloadSignal {
+    pub fn new() -> Result<Self> {
+        Ok(Self(signal(SignalKind::hangup())?))
+    }
+
+    pub async fn recv(&mut self) {
+        self.0.recv().await;
+    }
+}
+
+impl AlpacaClient {
+    /// Applies every setting of `config`, or none of them if it doesn't pass
+    /// [`RuntimeConfig::check`]. The throttles are only touched by a `[rate_limit]` or
+    /// `[api_budget]` section, without one the rate limit and API budget set through
+    /// [`AlpacaClient::set_rate_limit`] and [`AlpacaClient::set_api_budget`] stay in place. A
+    /// changed API budget starts with full allowances, an unchanged rate limit keeps its pace.
+    pub fn apply_config(&mut self, config: RuntimeConfig) -> Result<()> {
+        config.order_defaults.check().context("invalid order defaults")?;
+        let api_budget = config
+            .api_budget
+            .as_ref()
+            .map(ApiBudgetConfig::build)
+            .transpose()
+            .context("invalid API budget")?;
+
+        let requests_per_minute = config.rate_limit.map(|rate_limit| rate_limit.requests_per_minute);
+        if requests_per_minute.is_some() && requests_per_minute != self.rate_limiter.requests_per_minute() {
+            self.set_rate_limit(requests_per_minute);
+        }
+        if let Some(api_budget) = api_budget {
+            self.api_budget = Some(api_budget);
+        }
+        self.spread_guard = config.spread_guard;
+        self.symbol_restrictions = config.symbol_restrictions;
+        self.trading_schedule = config.trading_schedule;
+        self.stale_data_guard = config.stale_data_guard;
+        self.cooldown_rule = config.cooldown;
+        self.order_defaults = config.order_defaults;
+        self.symbol_event_guard = config.symbol_event_guard;
+        self.symbol_aliases = config.symbol_aliases;
+        self.notional_fallback = config.notional_fallback;
+        self.remediation_policies = config.remediation;
+        self.order_templates = config.order_templates;
+        Ok(())
+    }
+
+    /// Loads the config file and remembers its path for later [`AlpacaClient::reload_config`] calls.
+    pub fn load_config(&mut self, path: impl Into<PathBuf>) -> Result<()> {
+        let path = path.into();
+        let config = RuntimeConfig::from_file(&path)?;
+        self.apply_config(config)?;
+        self.config_path = Some(path);
+        Ok(())
+    }
+
+    /// Re-reads the config file in place, keeping connections and local state. An invalid file
+    /// leaves the current settings untouched.
+    pub fn reload_config(&mut self) -> Result<()> {
+        let path = self
+            .config_path
+            .as_ref()
+            .ok_or_else(|| anyhow!("no config file has been loaded"))?;
+        let config = RuntimeConfig::from_file(path)?;
+        self.apply_config(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::ApiBudget;
+    use crate::spread_guard::WideSpreadAction;
+    use num_decimal::Num;
+
+    #[test]
+    fn parses_spread_guard() -> Result<()> {
+        let config: RuntimeConfig = toml::from_str(
+            r#"
+            [spread_guard]
+            max_spread_bps = "25"
+            action = "reject"
+            limit_offset_bps = "0"
+            "#,
+        )?;
+
+        let spread_guard = config.spread_guard.unwrap();
+        assert_eq!(spread_guard.max_spread_bps, Num::from(25));
+        assert_eq!(spread_guard.action, WideSpreadAction::Reject);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_trading_schedule() -> Result<()> {
+        let config: RuntimeConfig = toml::from_str(
+            r#"
+            [trading_schedule]
+            windows = [{ start = "09:45", end = "15:45" }]
+            skip_first_minutes = 5
+            blocked_weekdays = ["Fri"]
+            "#,
+        )?;
+
+        let trading_schedule = config.trading_schedule.unwrap();
+        assert_eq!(trading_schedule.windows.len(), 1);
+        assert_eq!(trading_schedule.skip_first_minutes, 5);
+        assert_eq!(trading_schedule.timezone, chrono_tz::America::New_York);
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_config_disables_everything() -> Result<()> {
+        let config: RuntimeConfig = toml::from_str("")?;
+        assert!(config.spread_guard.is_none());
+        assert!(config.symbol_restrictions.check("GME").is_ok());
+        assert!(config.trading_schedule.is_none());
+        assert!(config.stale_data_guard.is_none());
+        assert!(config.cooldown.is_none());
+        assert_eq!(config.order_defaults, AssetClassDefaults::default());
+        assert!(config.symbol_event_guard.is_none());
+        assert_eq!(config.symbol_aliases, SymbolAliases::default());
+        assert!(config.notional_fallback.is_none());
+        assert_eq!(config.remediation, RemediationPolicies::default());
+        assert_eq!(config.order_templates, OrderTemplates::default());
+        assert!(config.rate_limit.is_none());
+        assert!(config.api_budget.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn parses_throttles() -> Result<()> {
+        let config: RuntimeConfig = toml::from_str(
+            r#"
+            [rate_limit]
+            requests_per_minute = 150
+
+            [api_budget]
+            requests_per_minute = 150
+            orders_per_minute = 50
+            allocations = { momentum = 60, mean_reversion = 50 }
+            "#,
+        )?;
+
+        assert_eq!(config.rate_limit.map(|rate_limit| rate_limit.requests_per_minute), Some(150));
+        // 110% of the budget can't be handed out
+        assert!(config.api_budget.unwrap().build().is_err());
+
+        Ok(())
+    }
+
+    fn offline_client() -> Result<AlpacaClient> {
+        AlpacaClient::builder().key_id("key").secret("secret").build()
+    }
+
+    #[test]
+    fn missing_throttle_sections_keep_programmatic_throttles() -> Result<()> {
+        let mut client = offline_client()?;
+        client.set_rate_limit(Some(100));
+        client.set_api_budget(Some(ApiBudget::new(100, 20)));
+
+        client.apply_config(toml::from_str("")?)?;
+
+        assert_eq!(client.rate_limiter.requests_per_minute(), Some(100));
+        assert!(client.api_budget.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_config_without_applying_any_of_it() -> Result<()> {
+        let mut client = offline_client()?;
+        client.set_rate_limit(Some(100));
+        let config: RuntimeConfig = toml::from_str(
+            r#"
+            [rate_limit]
+            requests_per_minute = 150
+
+            [api_budget]
+            requests_per_minute = 150
+            orders_per_minute = 50
+            allocations = { momentum = 60, mean_reversion = 50 }
+            "#,
+        )?;
+
+        assert!(client.apply_config(config).is_err());
+        assert_eq!(client.rate_limiter.requests_per_minute(), Some(100));
+        assert!(client.api_budget.is_none());
+        Ok(())
+    }
+}
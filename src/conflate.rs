@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::data::Quote;
+use futures::{Stream, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU32;
+use std::time::Duration;
+use tokio::time::{Instant, sleep_until};
+
+/// Limits quote updates to `max_per_second` per symbol. Updates arriving faster replace each
+/// other and only the latest one is passed on once the symbol's interval is up, so consumers
+/// always see the current book, just less often.
+#[derive(Debug)]
+pub struct QuoteConflator {
+    interval: Duration,
+    last_emitted: HashMap<String, Instant>,
+    pending: HashMap<String, Quote>,
+}
+
+impl QuoteConflator {
+    pub fn new(max_per_second: NonZeroU32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / max_per_second.get(),
+            last_emitted: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Returns the quote right away if its symbol's interval is up, otherwise holds on to it
+    /// until [`QuoteConflator::take_due`] releases it.
+    pub fn offer(&mut self, quote: Quote, now: Instant) -> Option<Quote> {
+        let due = self
+            .last_emitted
+            .get(&quote.symbol)
+            .is_none_or(|last_emitted| now >= *last_emitted + self.interval);
+        if !due {
+            self.pending.insert(quote.symbol.clone(), quote);
+            return None;
+        }
+
+        self.pending.remove(&quote.symbol);
+        self.last_emitted.insert(quote.symbol.clone(), now);
+        Some(quote)
+    }
+
+    /// When the next held quote is due, if any.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending
+            .keys()
+            .filter_map(|symbol| self.last_emitted.get(symbol))
+            .map(|last_emitted| *last_emitted + self.interval)
+            .min()
+    }
+
+    /// Releases the held quotes whose interval is up.
+    pub fn take_due(&mut self, now: Instant) -> Vec<Quote> {
+        let due: Vec<String> = self
+            .pending
+            .keys()
+            .filter(|symbol| {
+                self.last_emitted
+                    .get(*symbol)
+                    .is_none_or(|last_emitted| now >= *last_emitted + self.interval)
+            })
+            .cloned()
+            .collect();
+
+        due.into_iter()
+            .filter_map(|symbol| {
+                self.last_emitted.insert(symbol.clone(), now);
+                self.pending.remove(&symbol)
+            })
+            .collect()
+    }
+
+    fn drain(&mut self) -> Vec<Quote> {
+        self.pending.drain().map(|(_, quote)| quote).collect()
+    }
+}
+
+struct Conflation<S> {
+    quotes: S,
+    conflator: QuoteConflator,
+    ready: VecDeque<Quote>,
+    ended: bool,
+}
+
+/// Conflates a quote stream, e.g. one fed from the market data websocket, to at most
+/// `max_per_second` updates per symbol. The latest held quotes are flushed when `quotes` ends.
+pub fn conflate_quotes(
+    quotes: impl Stream<Item = Quote> + Unpin,
+    max_per_second: NonZeroU32,
+) -> impl Stream<Item = Quote> {
+    let conflation = Conflation {
+        quotes,
+        conflator: QuoteConflator::new(max_per_second),
+        ready: VecDeque::new(),
+        ended: false,
+    };
+
+    futures::stream::unfold(conflation, |mut conflation| async move {
+        loop {
+            if let Some(quote) = conflation.ready.pop_front() {
+                return Some((quote, conflation));
+            }
+            if conflation.ended {
+                return None;
+            }
+
+            let next_due = conflation.conflator.next_due();
+            tokio::select! {
+                quote = conflation.quotes.next() => match quote {
+                    Some(quote) => {
+                        let quote = conflation.conflator.offer(quote, Instant::now());
+                        conflation.ready.extend(quote);
+                    }
+                    None => {
+                        conflation.ended = true;
+                        let held = conflation.conflator.drain();
+                        conflation.ready.extend(held);
+                    }
+                },
+                _ = sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                    let due = conflation.conflator.take_due(Instant::now());
+                    conflation.ready.extend(due);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use num_decimal::Num;
+
+    fn quote(symbol: &str, bid_price: i64) -> Quote {
+        Quote {
+            symbol: symbol.into(),
+            exchange: None,
+            time: Utc::now(),
+            bid_price: Num::from(bid_price),
+            bid_size: Num::from(1),
+            ask_price: Num::from(bid_price + 1),
+            ask_size: Num::from(1),
+        }
+    }
+
+    #[test]
+    fn keeps_latest_quote_per_interval() {
+        let mut conflator = QuoteConflator::new(NonZeroU32::new(2).unwrap());
+        let start = Instant::now();
+
+        assert!(conflator.offer(quote("AAPL", 100), start).is_some());
+        assert!(conflator.offer(quote("MSFT", 300), start).is_some());
+        assert!(conflator.offer(quote("AAPL", 101), start + Duration::from_millis(100)).is_none());
+        assert!(conflator.offer(quote("AAPL", 102), start + Duration::from_millis(200)).is_none());
+        assert_eq!(conflator.next_due(), Some(start + Duration::from_millis(500)));
+
+        assert!(conflator.take_due(start + Duration::from_millis(400)).is_empty());
+        let due = conflator.take_due(start + Duration::from_millis(500));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].bid_price, Num::from(102));
+        assert_eq!(conflator.next_due(), None);
+    }
+
+    #[tokio::test]
+    async fn flushes_held_quotes_when_stream_ends() {
+        let quotes = futures::stream::iter([quote("AAPL", 100), quote("AAPL", 101), quote("AAPL", 102)]);
+        let conflated: Vec<Quote> = conflate_quotes(quotes, NonZeroU32::new(1).unwrap()).collect().await;
+
+        let bid_prices: Vec<Num> = conflated.into_iter().map(|quote| quote.bid_price).collect();
+        assert_eq!(bid_prices, [Num::from(100), Num::from(102)]);
+    }
+}
@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use apca::api::v2::order::{Amount as ApcaAmount, Side};
+use crate::order_class::{LinkedOrder, OrderClass, OrderLeg};
+use crate::precision::precision_policy;
+use anyhow::Context;
+use apca::api::v2::order::Id as ApcaOrderId;
+use apca::api::v2::order::{Amount as ApcaAmount, Class, Side};
 use apca::api::v2::order::Order as ApcaOrder;
 use apca::api::v2::order::Status as ApcaOrderStatus;
 use apca::api::v2::order::Side as ApcaOrderSide;
@@ -9,14 +13,53 @@ use apca::api::v2::position::Position;
 use irontrade::api::common::{Amount as IronTradeAmount, OpenPosition as IronTradeOpenPosition, Order as IronTradeOrder,
     OrderStatus as IronTradeOrderStatus, OrderType as IronTradeOrderType, OrderSide as IronTradeOrderSide
 };
+use num_decimal::Num;
+
+pub struct OrderId(pub ApcaOrderId);
+
+/// The id in its hyphenated form, written straight into a string of the right size instead of
+/// through the formatter.
+fn order_id_string(order_id: &ApcaOrderId) -> String {
+    let mut buffer = uuid::Uuid::encode_buffer();
+    String::from(order_id.0.hyphenated().encode_lower(&mut buffer))
+}
+
+impl TryFrom<&str> for OrderId {
+    type Error = anyhow::Error;
+
+    fn try_from(order_id: &str) -> anyhow::Result<Self> {
+        let uuid = uuid::Uuid::parse_str(order_id).with_context(|| format!("{order_id} is not a valid order id"))?;
+        Ok(Self(ApcaOrderId(uuid)))
+    }
+}
 
 pub struct Amount(pub IronTradeAmount);
 
 impl From<ApcaAmount> for Amount {
     fn from(amount: ApcaAmount) -> Self {
+        let policy = precision_policy();
         match amount {
-            ApcaAmount::Quantity { quantity } => Amount(IronTradeAmount::Quantity { quantity }),
-            ApcaAmount::Notional { notional } => Amount(IronTradeAmount::Notional { notional }),
+            ApcaAmount::Quantity { quantity } => Amount(IronTradeAmount::Quantity {
+                quantity: policy.round_quantity(quantity),
+            }),
+            ApcaAmount::Notional { notional } => Amount(IronTradeAmount::Notional {
+                notional: policy.round_notional(notional),
+            }),
+        }
+    }
+}
+
+/// Clones only the number the amount holds.
+impl From<&ApcaAmount> for Amount {
+    fn from(amount: &ApcaAmount) -> Self {
+        let policy = precision_policy();
+        match amount {
+            ApcaAmount::Quantity { quantity } => Amount(IronTradeAmount::Quantity {
+                quantity: policy.round_quantity(quantity.clone()),
+            }),
+            ApcaAmount::Notional { notional } => Amount(IronTradeAmount::Notional {
+                notional: policy.round_notional(notional.clone()),
+            }),
         }
     }
 }
@@ -34,29 +77,87 @@ pub struct OpenPosition(pub IronTradeOpenPosition);
 
 impl From<Position> for OpenPosition {
     fn from(position: Position) -> Self {
+        let policy = precision_policy();
+        Self(IronTradeOpenPosition {
+            asset_symbol: position.symbol.to_string(),
+            average_entry_price: Some(policy.round_price(position.average_entry_price)),
+            quantity: policy.round_quantity(position.quantity),
+            market_value: position
+                .market_value
+                .map(|market_value| policy.round_notional(market_value)),
+        })
+    }
+}
+
+/// Copies only the fields that end up in the converted position.
+impl From<&Position> for OpenPosition {
+    fn from(position: &Position) -> Self {
+        let policy = precision_policy();
         Self(IronTradeOpenPosition {
             asset_symbol: position.symbol.to_string(),
-            average_entry_price: Some(position.average_entry_price),
-            quantity: position.quantity,
-            market_value: position.market_value,
+            average_entry_price: Some(policy.round_price(position.average_entry_price.clone())),
+            quantity: policy.round_quantity(position.quantity.clone()),
+            market_value: position
+                .market_value
+                .as_ref()
+                .map(|market_value| policy.round_notional(market_value.clone())),
         })
     }
 }
 
 pub struct OrderStatus(pub IronTradeOrderStatus);
 
+/// irontrade knows orders as working, filled or ended. Orders Alpaca still works, including
+/// ones held, stopped, suspended or pending a cancel or replace, are `New`, and orders that
+/// ended without filling completely, whether canceled, rejected, replaced or done for the day,
+/// are `Expired`. `Calculated` orders completed for the day, filled or not, and are taken for
+/// partially filled since the status alone doesn't tell, converting whole orders does. Only
+/// statuses Alpaca adds later stay `Unimplemented`.
 impl From<ApcaOrderStatus> for OrderStatus {
     fn from(status: ApcaOrderStatus) -> Self {
         match status {
-            ApcaOrderStatus::New => OrderStatus(IronTradeOrderStatus::New),
+            ApcaOrderStatus::New
+            | ApcaOrderStatus::Accepted
+            | ApcaOrderStatus::PendingNew
+            | ApcaOrderStatus::AcceptedForBidding
+            | ApcaOrderStatus::Held
+            | ApcaOrderStatus::Stopped
+            | ApcaOrderStatus::Suspended
+            | ApcaOrderStatus::PendingCancel
+            | ApcaOrderStatus::PendingReplace => OrderStatus(IronTradeOrderStatus::New),
             ApcaOrderStatus::PartiallyFilled => OrderStatus(IronTradeOrderStatus::PartiallyFilled),
             ApcaOrderStatus::Filled => OrderStatus(IronTradeOrderStatus::Filled),
-            ApcaOrderStatus::Expired => OrderStatus(IronTradeOrderStatus::Expired),
+            ApcaOrderStatus::Expired
+            | ApcaOrderStatus::Canceled
+            | ApcaOrderStatus::Rejected
+            | ApcaOrderStatus::Replaced
+            | ApcaOrderStatus::DoneForDay => OrderStatus(IronTradeOrderStatus::Expired),
+            ApcaOrderStatus::Calculated => OrderStatus(IronTradeOrderStatus::PartiallyFilled),
             _ => OrderStatus(IronTradeOrderStatus::Unimplemented),
         }
     }
 }
 
+/// The status of an order going by its fills as well. Orders Alpaca still works are partially
+/// filled once anything filled, since e.g. a pending cancel doesn't say whether the order filled
+/// in part. `Calculated` orders are filled when their whole quantity filled, partially filled
+/// when some of it did and expired otherwise.
+fn working_status(status: ApcaOrderStatus, filled_quantity: &Num, amount: &ApcaAmount) -> IronTradeOrderStatus {
+    if status == ApcaOrderStatus::Calculated {
+        return match amount {
+            ApcaAmount::Quantity { quantity } if filled_quantity >= quantity => IronTradeOrderStatus::Filled,
+            _ if filled_quantity.is_positive() => IronTradeOrderStatus::PartiallyFilled,
+            _ => IronTradeOrderStatus::Expired,
+        };
+    }
+
+    let OrderStatus(status) = status.into();
+    match status {
+        IronTradeOrderStatus::New if filled_quantity.is_positive() => IronTradeOrderStatus::PartiallyFilled,
+        status => status,
+    }
+}
+
 pub struct OrderSide(pub IronTradeOrderSide);
 
 impl From<ApcaOrderSide> for OrderSide {
@@ -75,37 +176,119 @@ impl From<Type> for OrderType {
         match type_ {
             Type::Market => OrderType(IronTradeOrderType::Market),
             Type::Limit => OrderType(IronTradeOrderType::Limit),
-            _ => todo!(),
+            Type::Stop => OrderType(IronTradeOrderType::Stop),
+            Type::StopLimit => OrderType(IronTradeOrderType::StopLimit),
+            // irontrade has no trailing variant, the trail is in `trailing::TrailingStop`
+            Type::TrailingStop => OrderType(IronTradeOrderType::Stop),
+            // Orders of types Alpaca adds later are converted by their prices, see `order_type`
+            _ => OrderType(IronTradeOrderType::Market),
         }
     }
 }
 
+/// The order's type, going by which prices it has when Alpaca sent a type apca doesn't know,
+/// so listing an account with such an order doesn't fail.
+fn order_type(order: &ApcaOrder) -> IronTradeOrderType {
+    match (order.type_, &order.limit_price, &order.stop_price) {
+        (Type::Market | Type::Limit | Type::Stop | Type::StopLimit | Type::TrailingStop, _, _) => {
+            OrderType::from(order.type_).0
+        }
+        (_, Some(_), Some(_)) => IronTradeOrderType::StopLimit,
+        (_, None, Some(_)) => IronTradeOrderType::Stop,
+        (_, Some(_), None) => IronTradeOrderType::Limit,
+        (_, None, None) => IronTradeOrderType::Market,
+    }
+}
+
 pub struct Order(pub IronTradeOrder);
 
 impl From<ApcaOrder> for Order {
     fn from(order: ApcaOrder) -> Self {
+        let type_ = order_type(&order);
+
+        let status = working_status(order.status, &order.filled_quantity, &order.amount);
+
         let amount: Amount = order.amount.into();
         let amount = amount.0;
 
-        let status: OrderStatus = order.status.into();
-        let status = status.0;
+        let side: OrderSide = order.side.into();
+        let side = side.0;
+
+        let policy = precision_policy();
+
+        Self(IronTradeOrder {
+            order_id: order_id_string(&order.id),
+            asset_symbol: order.symbol,
+            filled_quantity: policy.round_quantity(order.filled_quantity),
+            amount,
+            average_fill_price: order.average_fill_price.map(|price| policy.round_price(price)),
+            status,
+            type_,
+            limit_price: order.limit_price.map(|price| policy.round_price(price)),
+            side
+        })
+    }
+}
+
+/// Copies only the fields that end up in the converted order, for callers that need to keep
+/// the original around. Being infallible it also provides `Order::try_from(&order)`.
+impl From<&ApcaOrder> for Order {
+    fn from(order: &ApcaOrder) -> Self {
+        let amount: Amount = (&order.amount).into();
+        let amount = amount.0;
+
+        let status = working_status(order.status, &order.filled_quantity, &order.amount);
 
-        let type_: OrderType = order.type_.into();
-        let type_ = type_.0;
+        let type_ = order_type(order);
 
         let side: OrderSide = order.side.into();
         let side = side.0;
 
+        let policy = precision_policy();
+
         Self(IronTradeOrder {
-            order_id: order.id.to_string(),
-            asset_symbol: order.symbol,
-            filled_quantity: order.filled_quantity,
+            order_id: order_id_string(&order.id),
+            asset_symbol: order.symbol.clone(),
+            filled_quantity: policy.round_quantity(order.filled_quantity.clone()),
             amount,
-            average_fill_price: order.average_fill_price,
+            average_fill_price: order.average_fill_price.as_ref().map(|price| policy.round_price(price.clone())),
             status,
             type_,
-            limit_price: order.limit_price,
+            limit_price: order.limit_price.as_ref().map(|price| policy.round_price(price.clone())),
             side
         })
     }
 }
+
+impl From<Class> for OrderClass {
+    fn from(class: Class) -> Self {
+        match class {
+            Class::Simple => OrderClass::Simple,
+            Class::Bracket => OrderClass::Bracket,
+            Class::OneCancelsOther => OrderClass::OneCancelsOther,
+            Class::OneTriggersOther => OrderClass::OneTriggersOther,
+        }
+    }
+}
+
+/// Converts the order and its legs, linking each leg to it.
+impl From<&ApcaOrder> for LinkedOrder {
+    fn from(order: &ApcaOrder) -> Self {
+        let parent_order_id = order.id.to_string();
+        let legs = order
+            .legs
+            .iter()
+            .map(|leg| OrderLeg {
+                parent_order_id: parent_order_id.clone(),
+                order: Order::from(leg).0,
+                held: matches!(leg.status, ApcaOrderStatus::Held),
+            })
+            .collect();
+
+        Self {
+            class: order.class.into(),
+            order: Order::from(order).0,
+            legs,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::trade_log::TradeLog;
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use irontrade::api::common::OrderSide;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Blocks re-entering a symbol for `minutes` after a round trip on it closed at a loss, e.g. a
+/// stop-out. Orders reducing a position that is still open are never blocked.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CooldownRule {
+    pub minutes: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CooldownError {
+    pub symbol: String,
+    pub until: DateTime<Utc>,
+}
+
+impl Display for CooldownError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "re-entering {} is blocked until {} after a losing exit",
+            self.symbol,
+            self.until.to_rfc3339()
+        )
+    }
+}
+
+impl std::error::Error for CooldownError {}
+
+impl CooldownRule {
+    pub fn check(
+        &self,
+        trade_log: &TradeLog,
+        symbol: &str,
+        side: &OrderSide,
+        now: DateTime<Utc>,
+    ) -> Result<(), CooldownError> {
+        let open_quantity = trade_log.open_quantity(symbol);
+        let reduces_position = match side {
+            OrderSide::Buy => open_quantity.is_negative(),
+            OrderSide::Sell => open_quantity.is_positive(),
+        };
+        if reduces_position {
+            return Ok(());
+        }
+
+        let last_loss = trade_log
+            .for_symbol(symbol)
+            .filter(|round_trip| round_trip.is_loss())
+            .map(|round_trip| round_trip.exit_time)
+            .max();
+
+        match last_loss {
+            Some(exit_time) if now < exit_time + TimeDelta::minutes(self.minutes.into()) => Err(CooldownError {
+                symbol: symbol.to_string(),
+                until: exit_time + TimeDelta::minutes(self.minutes.into()),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Trade log kept up to date from the account fills, for rules that look at recent exits.
+#[derive(Debug, Default)]
+pub struct TradeLogSync {
+    trade_log: Mutex<TradeLog>,
+    synced_until: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl AlpacaClient {
+    pub fn set_cooldown_rule(&mut self, cooldown_rule: Option<CooldownRule>) {
+        self.cooldown_rule = cooldown_rule;
+    }
+
+    /// Round trips of the fills seen by [`AlpacaClient::sync_trade_log`].
+    pub fn trade_log(&self) -> MutexGuard<'_, TradeLog> {
+        self.trade_log_sync.trade_log.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Adds the fills booked since the last sync to the client's trade log. The first sync reads
+    /// the whole fill history so exits pair with their entries.
+    pub async fn sync_trade_log(&self) -> Result<()> {
+        let synced_until = *self
+            .trade_log_sync
+            .synced_until
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let fills = self.get_fills(synced_until).await?;
+
+        let mut trade_log = self.trade_log();
+        let mut synced_until = self
+            .trade_log_sync
+            .synced_until
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for fill in &fills {
+            // A concurrent sync may have added these already
+            if synced_until.is_some_and(|synced_until| fill.time <= synced_until) {
+                continue;
+            }
+            trade_log.add_fill(fill);
+            *synced_until = Some(fill.time);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_cooldown(&self, symbol: &str, side: &OrderSide) -> Result<(), CooldownError> {
+        match &self.cooldown_rule {
+            Some(cooldown_rule) => cooldown_rule.check(&self.trade_log(), symbol, side, Utc::now()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trade_log::Fill;
+    use chrono::TimeZone;
+    use num_decimal::Num;
+
+    fn fill(side: OrderSide, price: i64, minute: u32) -> Fill {
+        Fill {
+            order_id: format!("{minute}"),
+            original_order_id: format!("{minute}"),
+            asset_symbol: "BTCUSD".into(),
+            side,
+            quantity: Num::from(1),
+            price: Num::from(price),
+            fees: Num::from(0),
+            time: Utc.with_ymd_and_hms(2024, 1, 18, 15, minute, 0).unwrap(),
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn blocks_reentry_after_losing_exit() {
+        let trade_log = TradeLog::from_fills(&[fill(OrderSide::Buy, 100, 0), fill(OrderSide::Sell, 90, 10)]);
+        let rule = CooldownRule { minutes: 30 };
+        let during = Utc.with_ymd_and_hms(2024, 1, 18, 15, 20, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 1, 18, 15, 40, 0).unwrap();
+
+        assert!(rule.check(&trade_log, "BTC/USD", &OrderSide::Buy, during).is_err());
+        assert!(rule.check(&trade_log, "BTC/USD", &OrderSide::Sell, during).is_err());
+        assert!(rule.check(&trade_log, "BTC/USD", &OrderSide::Buy, after).is_ok());
+    }
+
+    #[test]
+    fn allows_reentry_after_winning_exit_and_exits() {
+        let trade_log = TradeLog::from_fills(&[
+            fill(OrderSide::Buy, 100, 0),
+            fill(OrderSide::Sell, 110, 10),
+            fill(OrderSide::Buy, 100, 11),
+            fill(OrderSide::Buy, 100, 12),
+            fill(OrderSide::Sell, 90, 13),
+        ]);
+        let rule = CooldownRule { minutes: 30 };
+        let now = Utc.with_ymd_and_hms(2024, 1, 18, 15, 14, 0).unwrap();
+
+        // Still long one unit after the losing partial exit, selling it is fine
+        assert!(rule.check(&trade_log, "BTC/USD", &OrderSide::Sell, now).is_ok());
+        assert!(rule.check(&trade_log, "BTC/USD", &OrderSide::Buy, now).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::Result;
+use apca::api::v2::asset::{self, Symbol};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::America::New_York;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+const EVENT_CAPACITY: usize = 64;
+
+fn default_halt_cache_ms() -> u64 {
+    30_000
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolEventAction {
+    Reject,
+    /// Submit anyway and publish a [`SymbolEventError`] on the warning channel
+    Warn,
+}
+
+/// Refuses or flags stock orders on symbols with a split or symbol change taking effect today
+/// or with trading halted, whose prices from before the event are no longer meaningful.
+/// Corporate actions are looked up once a day per symbol, the halt status is cached for
+/// `halt_cache_ms`.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SymbolEventGuard {
+    pub action: SymbolEventAction,
+    #[serde(default = "default_halt_cache_ms")]
+    pub halt_cache_ms: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SymbolEvent {
+    Split { old_rate: String, new_rate: String },
+    SymbolChange { new_symbol: String },
+    /// Alpaca reports the asset as not tradable
+    Halted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolEventError {
+    pub symbol: String,
+    pub event: SymbolEvent,
+}
+
+impl Display for SymbolEventError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.event {
+            SymbolEvent::Split { old_rate, new_rate } => {
+                write!(f, "{} splits {old_rate} to {new_rate} today", self.symbol)
+            }
+            SymbolEvent::SymbolChange { new_symbol } => {
+                write!(f, "{} changes its symbol to {new_symbol} today", self.symbol)
+            }
+            SymbolEvent::Halted => write!(f, "trading in {} is halted", self.symbol),
+        }
+    }
+}
+
+impl std::error::Error for SymbolEventError {}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CorporateActions {
+    forward_splits: Vec<Split>,
+    reverse_splits: Vec<Split>,
+    name_changes: Vec<NameChange>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Split {
+    symbol: String,
+    ex_date: NaiveDate,
+    old_rate: serde_json::Number,
+    new_rate: serde_json::Number,
+}
+
+#[derive(Debug, Deserialize)]
+struct NameChange {
+    old_symbol: String,
+    new_symbol: String,
+    process_date: NaiveDate,
+}
+
+#[derive(Deserialize)]
+struct CorporateActionsPage {
+    corporate_actions: CorporateActions,
+}
+
+impl CorporateActions {
+    fn events(&self, symbol: &str, date: NaiveDate) -> Vec<SymbolEvent> {
+        let splits = self
+            .forward_splits
+            .iter()
+            .chain(&self.reverse_splits)
+            .filter(|split| split.symbol == symbol && split.ex_date == date)
+            .map(|split| SymbolEvent::Split {
+                old_rate: split.old_rate.to_string(),
+                new_rate: split.new_rate.to_string(),
+            });
+        let name_changes = self
+            .name_changes
+            .iter()
+            .filter(|name_change| name_change.old_symbol == symbol && name_change.process_date == date)
+            .map(|name_change| SymbolEvent::SymbolChange {
+                new_symbol: name_change.new_symbol.clone(),
+            });
+        splits.chain(name_changes).collect()
+    }
+}
+
+/// Corporate actions per symbol and day and the last seen halt status per symbol.
+pub struct SymbolEventCache {
+    corporate_actions: Mutex<HashMap<String, (NaiveDate, Vec<SymbolEvent>)>>,
+    halts: Mutex<HashMap<String, (Instant, bool)>>,
+    warnings: broadcast::Sender<SymbolEventError>,
+}
+
+impl Default for SymbolEventCache {
+    fn default() -> Self {
+        Self {
+            corporate_actions: Mutex::default(),
+            halts: Mutex::default(),
+            warnings: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl AlpacaClient {
+    pub fn set_symbol_event_guard(&mut self, symbol_event_guard: Option<SymbolEventGuard>) {
+        self.symbol_event_guard = symbol_event_guard;
+    }
+
+    /// Orders submitted despite a symbol event while the guard is set to warn.
+    pub fn subscribe_symbol_event_warnings(&self) -> broadcast::Receiver<SymbolEventError> {
+        self.symbol_events.warnings.subscribe()
+    }
+
+    async fn corporate_action_events(&self, symbol: &str, today: NaiveDate) -> Result<Vec<SymbolEvent>> {
+        let corporate_actions = &self.symbol_events.corporate_actions;
+        {
+            let cached = corporate_actions.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some((date, events)) = cached.get(symbol)
+                && *date == today
+            {
+                return Ok(events.clone());
+            }
+        }
+
+        let date = today.to_string();
+        let query = [
+            ("symbols", symbol.to_string()),
+            ("types", "forward_split,reverse_split,name_change".to_string()),
+            ("start", date.clone()),
+            ("end", date),
+        ];
+        let page: CorporateActionsPage = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.get_data("v1/corporate-actions", &query)
+            })
+            .await?;
+        let events = page.corporate_actions.events(symbol, today);
+
+        let mut cached = corporate_actions.lock().unwrap_or_else(PoisonError::into_inner);
+        cached.insert(symbol.to_string(), (today, events.clone()));
+        Ok(events)
+    }
+
+    async fn is_halted(&self, symbol: &str, max_age: Duration) -> Result<bool> {
+        let halts = &self.symbol_events.halts;
+        {
+            let cached = halts.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some((checked_at, halted)) = cached.get(symbol)
+                && checked_at.elapsed() <= max_age
+            {
+                return Ok(*halted);
+            }
+        }
+
+        let request = Symbol::Sym(symbol.to_string());
+        let asset = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<asset::Get>(&request)
+            })
+            .await?;
+        let halted = !asset.tradable;
+
+        let mut cached = halts.lock().unwrap_or_else(PoisonError::into_inner);
+        cached.insert(symbol.to_string(), (Instant::now(), halted));
+        Ok(halted)
+    }
+
+    /// Checks a stock symbol against the symbol event guard, if one is set.
+    pub(crate) async fn check_symbol_events(&self, symbol: &str) -> Result<()> {
+        let Some(symbol_event_guard) = &self.symbol_event_guard else {
+            return Ok(());
+        };
+
+        let today = Utc::now().with_timezone(&New_York).date_naive();
+        let mut events = self.corporate_action_events(symbol, today).await?;
+        let halt_cache = Duration::from_millis(symbol_event_guard.halt_cache_ms);
+        if self.is_halted(symbol, halt_cache).await? {
+            events.push(SymbolEvent::Halted);
+        }
+
+        for event in events {
+            let error = SymbolEventError {
+                symbol: symbol.to_string(),
+                event,
+            };
+            match symbol_event_guard.action {
+                SymbolEventAction::Reject => return Err(error.into()),
+                SymbolEventAction::Warn => {
+                    // Nobody listening isn't an error
+                    let _ = self.symbol_events.warnings.send(error);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_same_day_splits_and_symbol_changes() -> Result<()> {
+        let page: CorporateActionsPage = serde_json::from_str(
+            r#"{"corporate_actions":{
+                "forward_splits":[{"symbol":"NVDA","ex_date":"2024-06-10","old_rate":1,"new_rate":10}],
+                "name_changes":[{"old_symbol":"FB","new_symbol":"META","process_date":"2024-06-11"}]
+            },"next_page_token":null}"#,
+        )?;
+        let actions = page.corporate_actions;
+        let june = |day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+
+        let split = SymbolEvent::Split {
+            old_rate: "1".into(),
+            new_rate: "10".into(),
+        };
+        assert_eq!(actions.events("NVDA", june(10)), [split]);
+        assert!(actions.events("NVDA", june(11)).is_empty());
+        let change = SymbolEvent::SymbolChange {
+            new_symbol: "META".into(),
+        };
+        assert_eq!(actions.events("FB", june(11)), [change]);
+
+        Ok(())
+    }
+}
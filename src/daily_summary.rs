@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::auction::new_york_time;
+use crate::client::AlpacaClient;
+use crate::format::format_money;
+use crate::notify::Notifier;
+use crate::positions::PositionView;
+use crate::retry::{Idempotency, RetryOverride};
+use crate::trade_log::{Fill, RoundTrip, TradeLog};
+use anyhow::Result;
+use apca::api::v2::orders::{ListReq, Status};
+use apca::api::v2::{account, orders, positions};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use chrono_tz::America::New_York;
+use num_decimal::Num;
+use serde::Serialize;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+/// Most orders Alpaca returns per list request.
+const MAX_ORDERS: usize = 500;
+
+/// What happened in the account over one New York trading day.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub orders_placed: usize,
+    pub fills: usize,
+    pub filled_notional: Num,
+    pub fees: Num,
+    /// Net of fees, of the round trips closed during the day
+    pub realized_pnl: Num,
+    /// Of the positions held when the summary was compiled
+    pub unrealized_pnl: Num,
+    /// Equity at the previous close
+    pub last_equity: Num,
+    pub equity: Num,
+}
+
+impl DailySummary {
+    pub fn compile<'a>(
+        date: NaiveDate,
+        orders_placed: usize,
+        fills: &[Fill],
+        closed: impl IntoIterator<Item = &'a RoundTrip>,
+        positions: &[PositionView],
+        last_equity: Num,
+        equity: Num,
+    ) -> Self {
+        let zero = Num::from(0);
+        Self {
+            date,
+            orders_placed,
+            fills: fills.len(),
+            filled_notional: fills.iter().fold(zero.clone(), |sum, fill| sum + &fill.quantity * &fill.price),
+            fees: fills.iter().fold(zero.clone(), |sum, fill| sum + &fill.fees),
+            realized_pnl: closed.into_iter().fold(zero.clone(), |sum, round_trip| sum + &round_trip.pnl),
+            unrealized_pnl: positions
+                .iter()
+                .filter_map(|position| Some(position.market_value.as_ref()? - &position.cost_basis))
+                .fold(zero, |sum, pnl| sum + pnl),
+            last_equity,
+            equity,
+        }
+    }
+
+    pub fn equity_change(&self) -> Num {
+        &self.equity - &self.last_equity
+    }
+
+    pub fn to_text(&self) -> String {
+        [
+            format!("Orders placed: {}", self.orders_placed),
+            format!("Fills: {}", self.fills),
+            format!("Filled notional: {}", format_money(&self.filled_notional)),
+            format!("Fees: {}", format_money(&self.fees)),
+            format!("Realized P/L: {}", format_money(&self.realized_pnl)),
+            format!("Unrealized P/L: {}", format_money(&self.unrealized_pnl)),
+            format!(
+                "Equity: {} ({} since the previous close)",
+                format_money(&self.equity),
+                format_money(&self.equity_change())
+            ),
+        ]
+        .join("\n")
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl AlpacaClient {
+    /// Compiles the summary of `date`, a New York date. Realized P/L pairs the whole fill history
+    /// so positions opened on earlier days are matched against their entries.
+    pub async fn daily_summary(&self, date: NaiveDate) -> Result<DailySummary> {
+        let start = new_york_time(date, NaiveTime::MIN);
+        let end = new_york_time(date + TimeDelta::days(1), NaiveTime::MIN);
+
+        let request = ListReq {
+            status: Status::All,
+            after: Some(start),
+            until: Some(end),
+            limit: Some(MAX_ORDERS),
+            ..Default::default()
+        };
+        let orders = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<orders::List>(&request)
+            })
+            .await?;
+
+        let history = self.get_fills(None).await?;
+        let trade_log = TradeLog::from_fills(&history);
+        let fills: Vec<Fill> = history
+            .into_iter()
+            .filter(|fill| start <= fill.time && fill.time < end)
+            .collect();
+
+        let positions: Vec<PositionView> = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<positions::List>(&())
+            })
+            .await?
+            .iter()
+            .map(PositionView::from)
+            .collect();
+        let account = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<account::Get>(&())
+            })
+            .await?;
+
+        Ok(DailySummary::compile(
+            date,
+            orders.len(),
+            &fills,
+            trade_log.closed_between(start, end),
+            &positions,
+            account.last_equity,
+            account.equity,
+        ))
+    }
+
+    /// Compiles the summary of `date` and delivers it as text through `notifier`.
+    pub async fn send_daily_summary(&self, date: NaiveDate, notifier: &impl Notifier) -> Result<DailySummary> {
+        let summary = self.daily_summary(date).await?;
+        notifier
+            .notify(&format!("Daily summary for {date}"), &summary.to_text())
+            .await?;
+        Ok(summary)
+    }
+
+    /// Sends the summary of the day every day at `at` New York time, e.g. after the close, until
+    /// `cancellation` fires or a summary fails to go out.
+    pub async fn run_daily_summaries(
+        &self,
+        at: NaiveTime,
+        notifier: &impl Notifier,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        loop {
+            let now = Utc::now();
+            let next = next_run(now, at);
+            let wait = (next - now).to_std().unwrap_or_default();
+            tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => return Ok(()),
+                _ = sleep(wait) => {}
+            }
+
+            let date = next.with_timezone(&New_York).date_naive();
+            self.send_daily_summary(date, notifier).await?;
+        }
+    }
+}
+
+/// The first time after `now` that is `at` in New York.
+fn next_run(now: DateTime<Utc>, at: NaiveTime) -> DateTime<Utc> {
+    let today = now.with_timezone(&New_York).date_naive();
+    let run = new_york_time(today, at);
+    if run > now { run } else { new_york_time(today + TimeDelta::days(1), at) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::positions::AssetClass;
+    use chrono::TimeZone;
+    use irontrade::api::common::OrderSide;
+
+    fn fill(order_id: &str, side: OrderSide, price: i64, hour: u32) -> Fill {
+        Fill {
+            order_id: order_id.into(),
+            original_order_id: order_id.into(),
+            asset_symbol: "AAPL".into(),
+            side,
+            quantity: Num::from(10),
+            price: Num::from(price),
+            fees: Num::from(1),
+            time: Utc.with_ymd_and_hms(2024, 1, 18, hour, 0, 0).unwrap(),
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn compiles_the_day() {
+        let fills = [fill("a", OrderSide::Buy, 100, 15), fill("b", OrderSide::Sell, 105, 16)];
+        let trade_log = TradeLog::from_fills(&fills);
+        let position = PositionView {
+            asset_symbol: "MSFT".into(),
+            asset_class: AssetClass::Equity,
+            quantity: Num::from(2),
+            cost_basis: Num::from(800),
+            market_value: Some(Num::from(810)),
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 1, 18).unwrap();
+
+        let summary = DailySummary::compile(
+            date,
+            3,
+            &fills,
+            trade_log.round_trips(),
+            &[position],
+            Num::from(10_000),
+            Num::from(10_058),
+        );
+
+        assert_eq!(summary.filled_notional, Num::from(2050));
+        assert_eq!(summary.fees, Num::from(2));
+        assert_eq!(summary.realized_pnl, Num::from(48));
+        assert_eq!(summary.unrealized_pnl, Num::from(10));
+        assert!(summary.to_text().ends_with("Equity: 10058.00 (58.00 since the previous close)"));
+    }
+
+    #[test]
+    fn next_run_is_in_new_york_time() {
+        let at = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+        let before = Utc.with_ymd_and_hms(2024, 1, 18, 21, 0, 0).unwrap();
+        assert_eq!(next_run(before, at), Utc.with_ymd_and_hms(2024, 1, 18, 22, 0, 0).unwrap());
+        let after = Utc.with_ymd_and_hms(2024, 1, 18, 22, 0, 0).unwrap();
+        assert_eq!(next_run(after, at), Utc.with_ymd_and_hms(2024, 1, 19, 22, 0, 0).unwrap());
+    }
+}
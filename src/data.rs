@@ -0,0 +1,612 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::budget::BudgetKind;
+use crate::client::AlpacaClient;
+use crate::error::ResponseError;
+use crate::symbol::SymbolRenames;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use num_decimal::Num;
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bar {
+    pub symbol: String,
+    /// Venue code the bar was aggregated on, e.g. `us-1` for crypto. Only crypto data, which is
+    /// fetched over REST, has one: stock bars are consolidated across exchanges
+    pub exchange: Option<String>,
+    pub time: DateTime<Utc>,
+    pub open: Num,
+    pub high: Num,
+    pub low: Num,
+    pub close: Num,
+    pub volume: Num,
+    pub vwap: Option<Num>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quote {
+    pub symbol: String,
+    /// Venue code of the crypto quote, `None` for stock quotes, which are consolidated across
+    /// exchanges, whether fetched or streamed
+    pub exchange: Option<String>,
+    pub time: DateTime<Utc>,
+    pub bid_price: Num,
+    pub bid_size: Num,
+    pub ask_price: Num,
+    pub ask_size: Num,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trade {
+    pub symbol: String,
+    /// Venue code of the crypto trade, `None` for stock trades, whether fetched or streamed
+    pub exchange: Option<String>,
+    pub time: DateTime<Utc>,
+    pub price: Num,
+    pub size: Num,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimeFrame {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    OneDay,
+}
+
+impl TimeFrame {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            TimeFrame::OneMinute => "1Min",
+            TimeFrame::FiveMinutes => "5Min",
+            TimeFrame::FifteenMinutes => "15Min",
+            TimeFrame::OneHour => "1Hour",
+            TimeFrame::OneDay => "1Day",
+        }
+    }
+
+    pub fn duration(&self) -> TimeDelta {
+        match self {
+            TimeFrame::OneMinute => TimeDelta::minutes(1),
+            TimeFrame::FiveMinutes => TimeDelta::minutes(5),
+            TimeFrame::FifteenMinutes => TimeDelta::minutes(15),
+            TimeFrame::OneHour => TimeDelta::hours(1),
+            TimeFrame::OneDay => TimeDelta::days(1),
+        }
+    }
+}
+
+/// Crypto venues Alpaca serves market data for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CryptoExchange {
+    Alpaca,
+    KrakenUs,
+    KrakenEu,
+}
+
+impl CryptoExchange {
+    pub const ALL: [CryptoExchange; 3] = [CryptoExchange::Alpaca, CryptoExchange::KrakenUs, CryptoExchange::KrakenEu];
+
+    /// The location code used by the crypto data endpoints and reported in converted data.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CryptoExchange::Alpaca => "us",
+            CryptoExchange::KrakenUs => "us-1",
+            CryptoExchange::KrakenEu => "eu-1",
+        }
+    }
+}
+
+impl FromStr for CryptoExchange {
+    type Err = anyhow::Error;
+
+    fn from_str(code: &str) -> Result<Self> {
+        CryptoExchange::ALL
+            .into_iter()
+            .find(|exchange| exchange.code() == code)
+            .ok_or_else(|| anyhow!("unknown crypto exchange {code}"))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CryptoDataReq {
+    pub symbols: Vec<String>,
+    /// Venues to query, each is queried separately. Empty means Alpaca's own venue only.
+    pub exchanges: Vec<CryptoExchange>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    /// Maximum number of data points returned per exchange
+    pub limit: Option<usize>,
+}
+
+impl CryptoDataReq {
+    fn exchanges(&self) -> Vec<CryptoExchange> {
+        if self.exchanges.is_empty() {
+            vec![CryptoExchange::Alpaca]
+        } else {
+            self.exchanges.clone()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    #[serde(alias = "bars", alias = "quotes", alias = "trades")]
+    items: HashMap<String, Vec<T>>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawBar {
+    #[serde(rename = "t")]
+    time: DateTime<Utc>,
+    #[serde(rename = "o", deserialize_with = "deserialize_num")]
+    open: Num,
+    #[serde(rename = "h", deserialize_with = "deserialize_num")]
+    high: Num,
+    #[serde(rename = "l", deserialize_with = "deserialize_num")]
+    low: Num,
+    #[serde(rename = "c", deserialize_with = "deserialize_num")]
+    close: Num,
+    #[serde(rename = "v", deserialize_with = "deserialize_num")]
+    volume: Num,
+    #[serde(rename = "vw", deserialize_with = "deserialize_num")]
+    vwap: Num,
+}
+
+impl RawBar {
+    fn into_bar(self, symbol: String, exchange: Option<String>) -> Bar {
+        Bar {
+            symbol,
+            exchange,
+            time: self.time,
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+            volume: self.volume,
+            vwap: Some(self.vwap),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawQuote {
+    #[serde(rename = "t")]
+    time: DateTime<Utc>,
+    #[serde(rename = "bp", deserialize_with = "deserialize_num")]
+    bid_price: Num,
+    #[serde(rename = "bs", deserialize_with = "deserialize_num")]
+    bid_size: Num,
+    #[serde(rename = "ap", deserialize_with = "deserialize_num")]
+    ask_price: Num,
+    #[serde(rename = "as", deserialize_with = "deserialize_num")]
+    ask_size: Num,
+}
+
+#[derive(Deserialize)]
+struct RawTrade {
+    #[serde(rename = "t")]
+    time: DateTime<Utc>,
+    #[serde(rename = "p", deserialize_with = "deserialize_num")]
+    price: Num,
+    #[serde(rename = "s", deserialize_with = "deserialize_num")]
+    size: Num,
+}
+
+/// Alpaca's data API encodes prices and sizes as JSON numbers, which serde_json reads as f64
+/// when they have a fraction. The shortest text of that f64 is the decimal Alpaca sent for any
+/// number of up to 15 significant digits, so that's what's converted to `Num` rather than the
+/// binary value, with exponents expanded exactly.
+pub(crate) fn deserialize_num<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Num, D::Error> {
+    let number = serde_json::Number::deserialize(deserializer)?;
+    let number = number.to_string();
+
+    let number = match number.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => {
+            let exponent = i32::from_str(exponent).map_err(D::Error::custom)?;
+            expand_exponent(mantissa, exponent)
+        }
+        None => number,
+    };
+
+    Num::from_str(&number).map_err(|_| D::Error::custom(format!("{number} is not a decimal number")))
+}
+
+/// `mantissa` times ten to the `exponent` as a plain decimal, e.g. `1.5` and `-7` to
+/// `0.00000015`.
+fn expand_exponent(mantissa: &str, exponent: i32) -> String {
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{integer}{fraction}");
+    let point = i64::try_from(integer.len()).unwrap_or(i64::MAX) + i64::from(exponent);
+
+    match usize::try_from(point) {
+        Ok(point) if point >= digits.len() => format!("{sign}{digits}{}", "0".repeat(point - digits.len())),
+        Ok(0) => format!("{sign}0.{digits}"),
+        Ok(point) => format!("{sign}{}.{}", &digits[..point], &digits[point..]),
+        Err(_) => format!("{sign}0.{}{digits}", "0".repeat(point.unsigned_abs() as usize)),
+    }
+}
+
+impl AlpacaClient {
+    pub(crate) async fn get_data<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+        self.acquire_budget(BudgetKind::Request).await?;
+        let api_info = self.apca_client.api_info();
+        let url = api_info.data_base_url.join(path)?;
+
+        let response = self
+            .http_client
+            .get(url.as_str())
+            .query(query)
+            .header("APCA-API-KEY-ID", &api_info.key_id)
+            .header("APCA-API-SECRET-KEY", &api_info.secret)
+            .send()
+            .await?;
+
+        let response = ResponseError::check(response)
+            .await
+            .with_context(|| format!("data request to {path} failed"))?;
+
+        Ok(response.json().await?)
+    }
+
+    fn record_quote_ages(&self, quotes: &[Quote]) {
+        self.market_data_ages
+            .record_all(quotes.iter().map(|quote| (quote.symbol.as_str(), quote.time)));
+    }
+
+    /// `req` with aliases replaced by the Alpaca symbols they stand for.
+    fn resolve_data_req(&self, req: &CryptoDataReq) -> (CryptoDataReq, SymbolRenames) {
+        let (symbols, renames) = self.symbol_aliases.resolve_all(&req.symbols);
+        let req = CryptoDataReq {
+            symbols,
+            ..req.clone()
+        };
+        (req, renames)
+    }
+
+    async fn get_crypto_pages<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        exchange: CryptoExchange,
+        req: &CryptoDataReq,
+        params: &[(&str, String)],
+    ) -> Result<Vec<(String, T)>> {
+        let path = format!("v1beta3/crypto/{}/{endpoint}", exchange.code());
+        let mut items = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut query = params.to_vec();
+            query.push(("symbols", req.symbols.join(",")));
+            query.push(("sort", "asc".into()));
+            if let Some(start) = req.start {
+                query.push(("start", start.to_rfc3339_opts(SecondsFormat::Secs, true)));
+            }
+            if let Some(end) = req.end {
+                query.push(("end", end.to_rfc3339_opts(SecondsFormat::Secs, true)));
+            }
+            if let Some(page_token) = page_token.take() {
+                query.push(("page_token", page_token));
+            }
+
+            let page: Page<T> = self.get_data(&path, &query).await?;
+            for (symbol, symbol_items) in page.items {
+                items.extend(symbol_items.into_iter().map(|item| (symbol.clone(), item)));
+            }
+
+            if let Some(limit) = req.limit
+                && items.len() >= limit
+            {
+                items.truncate(limit);
+                break;
+            }
+
+            match page.next_page_token {
+                Some(next_page_token) => page_token = Some(next_page_token),
+                None => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Returns the crypto bars of the requested symbols from each of the requested exchanges.
+    pub async fn get_crypto_bars(&self, req: &CryptoDataReq, time_frame: TimeFrame) -> Result<Vec<Bar>> {
+        let (req, renames) = self.resolve_data_req(req);
+        let mut bars = Vec::new();
+
+        for exchange in req.exchanges() {
+            let params = [("timeframe", time_frame.as_str().to_string())];
+            let raw_bars = self.get_crypto_pages::<RawBar>("bars", exchange, &req, &params).await?;
+
+            bars.extend(
+                raw_bars
+                    .into_iter()
+                    .map(|(symbol, bar)| bar.into_bar(symbol, Some(exchange.code().to_string()))),
+            );
+        }
+
+        bars.sort_by(|a, b| a.time.cmp(&b.time));
+        bars.iter_mut().for_each(|bar| renames.restore(&mut bar.symbol));
+        Ok(bars)
+    }
+
+    /// The bars of a stock or crypto pair from `start` until `end`, oldest first, following the
+    /// pages to the end.
+    pub async fn get_bars(
+        &self,
+        symbol: &str,
+        time_frame: TimeFrame,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Bar>> {
+        let mut bars = Vec::new();
+        let mut page_token = None;
+        loop {
+            let (page, next_page_token) = self.get_bar_page(symbol, time_frame, start, end, page_token).await?;
+            bars.extend(page);
+            match next_page_token {
+                Some(next_page_token) => page_token = Some(next_page_token),
+                None => break,
+            }
+        }
+        Ok(bars)
+    }
+
+    /// One page of the bars of a single stock or crypto pair between `start` and `end`, oldest
+    /// first, and the token of the next page if there is one.
+    pub(crate) async fn get_bar_page(
+        &self,
+        symbol: &str,
+        time_frame: TimeFrame,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        page_token: Option<String>,
+    ) -> Result<(Vec<Bar>, Option<String>)> {
+        let alpaca_symbol = self.symbol_aliases.resolve(symbol);
+        let (path, exchange) = if alpaca_symbol.contains('/') {
+            let exchange = CryptoExchange::Alpaca.code();
+            (format!("v1beta3/crypto/{exchange}/bars"), Some(exchange.to_string()))
+        } else {
+            // Stock bars are consolidated across exchanges
+            ("v2/stocks/bars".to_string(), None)
+        };
+
+        let mut query = vec![
+            ("symbols", alpaca_symbol.to_string()),
+            ("timeframe", time_frame.as_str().to_string()),
+            ("start", start.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            ("end", end.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            ("sort", "asc".into()),
+        ];
+        if let Some(page_token) = page_token {
+            query.push(("page_token", page_token));
+        }
+
+        let page: Page<RawBar> = self.get_data(&path, &query).await?;
+        let bars = page
+            .items
+            .into_values()
+            .flatten()
+            .map(|bar| bar.into_bar(symbol.to_string(), exchange.clone()))
+            .collect();
+        Ok((bars, page.next_page_token))
+    }
+
+    /// Returns the crypto quotes of the requested symbols from each of the requested exchanges.
+    pub async fn get_crypto_quotes(&self, req: &CryptoDataReq) -> Result<Vec<Quote>> {
+        let (req, renames) = self.resolve_data_req(req);
+        let mut quotes = Vec::new();
+
+        for exchange in req.exchanges() {
+            let raw_quotes = self.get_crypto_pages::<RawQuote>("quotes", exchange, &req, &[]).await?;
+
+            quotes.extend(raw_quotes.into_iter().map(|(symbol, quote)| Quote {
+                symbol,
+                exchange: Some(exchange.code().to_string()),
+                time: quote.time,
+                bid_price: quote.bid_price,
+                bid_size: quote.bid_size,
+                ask_price: quote.ask_price,
+                ask_size: quote.ask_size,
+            }));
+        }
+
+        quotes.sort_by(|a, b| a.time.cmp(&b.time));
+        self.record_quote_ages(&quotes);
+        quotes.iter_mut().for_each(|quote| renames.restore(&mut quote.symbol));
+        Ok(quotes)
+    }
+
+    /// Returns the crypto trades of the requested symbols from each of the requested exchanges.
+    pub async fn get_crypto_trades(&self, req: &CryptoDataReq) -> Result<Vec<Trade>> {
+        let (req, renames) = self.resolve_data_req(req);
+        let mut trades = Vec::new();
+
+        for exchange in req.exchanges() {
+            let raw_trades = self.get_crypto_pages::<RawTrade>("trades", exchange, &req, &[]).await?;
+
+            trades.extend(raw_trades.into_iter().map(|(symbol, trade)| Trade {
+                symbol,
+                exchange: Some(exchange.code().to_string()),
+                time: trade.time,
+                price: trade.price,
+                size: trade.size,
+            }));
+        }
+
+        trades.sort_by(|a, b| a.time.cmp(&b.time));
+        self.market_data_ages
+            .record_all(trades.iter().map(|trade| (trade.symbol.as_str(), trade.time)));
+        self.session_stats.record_all(&trades);
+        trades.iter_mut().for_each(|trade| renames.restore(&mut trade.symbol));
+        Ok(trades)
+    }
+
+    /// Returns the current top of book of each requested crypto symbol on the given exchange.
+    pub async fn get_latest_crypto_quotes(&self, symbols: &[String], exchange: CryptoExchange) -> Result<Vec<Quote>> {
+        #[derive(Deserialize)]
+        struct LatestQuotes {
+            quotes: HashMap<String, RawQuote>,
+        }
+
+        let (symbols, renames) = self.symbol_aliases.resolve_all(symbols);
+        let path = format!("v1beta3/crypto/{}/latest/quotes", exchange.code());
+        let latest: LatestQuotes = self.get_data(&path, &[("symbols", symbols.join(","))]).await?;
+
+        let mut quotes: Vec<Quote> = latest
+            .quotes
+            .into_iter()
+            .map(|(symbol, quote)| Quote {
+                symbol,
+                exchange: Some(exchange.code().to_string()),
+                time: quote.time,
+                bid_price: quote.bid_price,
+                bid_size: quote.bid_size,
+                ask_price: quote.ask_price,
+                ask_size: quote.ask_size,
+            })
+            .collect();
+
+        self.record_quote_ages(&quotes);
+        quotes.iter_mut().for_each(|quote| renames.restore(&mut quote.symbol));
+        Ok(quotes)
+    }
+
+    /// Returns the current top of book of every requested symbol, stocks and crypto pairs alike,
+    /// with one request per asset class instead of one per symbol. Crypto quotes come from
+    /// Alpaca's own venue.
+    pub async fn get_latest_quotes(&self, symbols: &[String]) -> Result<Vec<Quote>> {
+        #[derive(Deserialize)]
+        struct LatestQuotes {
+            quotes: HashMap<String, RawQuote>,
+        }
+
+        let (symbols, renames) = self.symbol_aliases.resolve_all(symbols);
+        let (crypto, stocks): (Vec<String>, Vec<String>) =
+            symbols.iter().cloned().partition(|symbol| symbol.contains('/'));
+
+        let mut quotes = Vec::with_capacity(symbols.len());
+        if !crypto.is_empty() {
+            quotes.extend(self.get_latest_crypto_quotes(&crypto, CryptoExchange::Alpaca).await?);
+        }
+        if !stocks.is_empty() {
+            let latest: LatestQuotes = self
+                .get_data("v2/stocks/quotes/latest", &[("symbols", stocks.join(","))])
+                .await?;
+            quotes.extend(latest.quotes.into_iter().map(|(symbol, quote)| Quote {
+                symbol,
+                // Stock quotes are consolidated across exchanges
+                exchange: None,
+                time: quote.time,
+                bid_price: quote.bid_price,
+                bid_size: quote.bid_size,
+                ask_price: quote.ask_price,
+                ask_size: quote.ask_size,
+            }));
+        }
+
+        self.record_quote_ages(&quotes);
+        quotes.iter_mut().for_each(|quote| renames.restore(&mut quote.symbol));
+        Ok(quotes)
+    }
+
+    /// The current top of book of a stock or crypto pair.
+    pub async fn get_latest_quote(&self, symbol: &str) -> Result<Quote> {
+        self.get_latest_quotes(&[symbol.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no quote for {symbol}"))
+    }
+
+    /// The last trade of a stock or crypto pair. Crypto trades come from Alpaca's own venue.
+    pub async fn get_latest_trade(&self, symbol: &str) -> Result<Trade> {
+        #[derive(Deserialize)]
+        struct LatestTrades {
+            trades: HashMap<String, RawTrade>,
+        }
+
+        let alpaca_symbol = self.symbol_aliases.resolve(symbol);
+        let (path, exchange) = if alpaca_symbol.contains('/') {
+            let exchange = CryptoExchange::Alpaca.code();
+            (format!("v1beta3/crypto/{exchange}/latest/trades"), Some(exchange.to_string()))
+        } else {
+            ("v2/stocks/trades/latest".to_string(), None)
+        };
+
+        let latest: LatestTrades = self.get_data(&path, &[("symbols", alpaca_symbol.to_string())]).await?;
+        let trade = latest
+            .trades
+            .into_values()
+            .next()
+            .ok_or_else(|| anyhow!("no trade for {symbol}"))?;
+
+        self.market_data_ages.record(alpaca_symbol, trade.time);
+        Ok(Trade {
+            symbol: symbol.to_string(),
+            exchange,
+            time: trade.time,
+            price: trade.price,
+            size: trade.size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crypto_exchange_round_trips_through_code() -> Result<()> {
+        for exchange in CryptoExchange::ALL {
+            assert_eq!(CryptoExchange::from_str(exchange.code())?, exchange);
+        }
+        assert!(CryptoExchange::from_str("cbse").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parses_stock_quotes() -> Result<()> {
+        let quote: RawQuote = serde_json::from_str(
+            r#"{"t":"2024-01-02T15:04:05.123Z","ax":"V","ap":185.5,"as":2,"bx":"V","bp":185.49,"bs":3,"c":["R"],"z":"C"}"#,
+        )?;
+
+        assert_eq!(quote.ask_price, Num::from_str("185.5")?);
+        assert_eq!(quote.bid_size, Num::from(3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_numbers_exactly() -> Result<()> {
+        let page: Page<RawTrade> = serde_json::from_str(
+            r#"{"trades":{"BTC/USD":[{"t":"2024-01-02T03:04:05Z","p":42123.45,"s":0.000012,"tks":"B","i":1}]},"next_page_token":null}"#,
+        )?;
+        let trade = &page.items["BTC/USD"][0];
+
+        assert_eq!(trade.price, Num::from_str("42123.45")?);
+        assert_eq!(trade.size, Num::from_str("0.000012")?);
+        assert_eq!(page.next_page_token, None);
+
+        assert_eq!(expand_exponent("1.5", -13), "0.00000000000015");
+        assert_eq!(expand_exponent("-2.25", 3), "-2250");
+        assert_eq!(expand_exponent("1.25", 1), "12.5");
+        assert_eq!(expand_exponent("1.5", -1), "0.15");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::wait::{WaitOutcome, is_terminal};
+use anyhow::{Result, anyhow};
+use apca::api::v2::order::Status as ApcaOrderStatus;
+use irontrade::api::client::IronTradeClient;
+use irontrade::api::common::{Order as IronTradeOrder, OrderStatus};
+use irontrade::api::request::OrderRequest;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+const DEADLINE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Result of an order placed with a deadline. `order` holds the final state including the
+/// filled quantity and average fill price of whatever got filled.
+#[derive(Clone, Debug)]
+pub struct DeadlineFill {
+    pub order: IronTradeOrder,
+    /// Whether the remainder had to be canceled because the deadline passed
+    pub canceled_at_deadline: bool,
+}
+
+impl DeadlineFill {
+    pub fn is_fully_filled(&self) -> bool {
+        matches!(self.order.status, OrderStatus::Filled)
+    }
+}
+
+impl AlpacaClient {
+    /// Places the order and, if it isn't completely filled by `deadline`, cancels the remainder.
+    /// Firing `cancellation` brings the deadline forward, e.g. on shutdown.
+    pub async fn place_order_with_deadline(
+        &mut self,
+        req: OrderRequest,
+        deadline: Instant,
+        cancellation: &CancellationToken,
+    ) -> Result<DeadlineFill> {
+        let order_id = self.place_order(req).await?;
+        self.settle_by_deadline(&order_id, deadline, cancellation).await
+    }
+
+    /// Waits for the order to fill until `deadline` or `cancellation`, and cancels whatever is
+    /// left after it.
+    pub(crate) async fn settle_by_deadline(
+        &self,
+        order_id: &str,
+        deadline: Instant,
+        cancellation: &CancellationToken,
+    ) -> Result<DeadlineFill> {
+        match self
+            .poll_until_terminal(order_id, DEADLINE_POLL_INTERVAL, cancellation, Some(deadline.into()))
+            .await?
+        {
+            WaitOutcome::Filled(order) | WaitOutcome::Ended(order) => {
+                return Ok(DeadlineFill {
+                    order,
+                    canceled_at_deadline: false,
+                });
+            }
+            WaitOutcome::Cancelled(_) | WaitOutcome::TimedOut(_) => {}
+        }
+
+        // The order may fill between the deadline and the cancel request, in which case the
+        // cancel is rejected and the final state below tells what happened
+        let cancel_result = self.cancel_order(order_id).await;
+
+        let order = loop {
+            let order = self.fetch_apca_order(order_id).await?;
+            if is_terminal(&order.status) {
+                break order;
+            }
+            if let Err(error) = &cancel_result
+                && !matches!(order.status, ApcaOrderStatus::PendingCancel)
+            {
+                return Err(anyhow!("failed to cancel order {order_id} at deadline: {error}"));
+            }
+            sleep(DEADLINE_POLL_INTERVAL).await;
+        };
+
+        let canceled_at_deadline = !matches!(order.status, ApcaOrderStatus::Filled);
+
+        Ok(DeadlineFill {
+            order: self.record_order(order),
+            canceled_at_deadline,
+        })
+    }
+}
@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::data::{Bar, TimeFrame};
+use crate::format::{format_price, format_quantity};
+use crate::positions::AssetClass;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::{MissedTickBehavior, interval};
+use tokio_util::sync::CancellationToken;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Bars of a whole universe to download into a cache directory, one CSV file per symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BarDownload {
+    pub symbols: Vec<String>,
+    pub time_frame: TimeFrame,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Pace of data requests, below the account's limit to leave room for trading
+    pub requests_per_minute: u32,
+}
+
+/// Which symbols of a download are complete, kept next to the bars so an interrupted download
+/// resumes where it left off.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    time_frame: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    completed: BTreeSet<String>,
+}
+
+impl Manifest {
+    fn for_download(download: &BarDownload) -> Self {
+        Self {
+            time_frame: download.time_frame.as_str().to_string(),
+            start: download.start,
+            end: download.end,
+            completed: BTreeSet::new(),
+        }
+    }
+
+    fn load_or_create(directory: &Path, download: &BarDownload) -> Result<Self> {
+        let path = directory.join(MANIFEST_FILE);
+        let expected = Self::for_download(download);
+        if !path.exists() {
+            return Ok(expected);
+        }
+
+        let json = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let manifest: Self =
+            serde_json::from_slice(&json).with_context(|| format!("invalid manifest in {}", path.display()))?;
+        if (&manifest.time_frame, manifest.start, manifest.end) != (&expected.time_frame, expected.start, expected.end) {
+            bail!("{} holds a download of a different time frame or range", directory.display());
+        }
+        Ok(manifest)
+    }
+
+    fn save(&self, directory: &Path) -> Result<()> {
+        write_atomically(&directory.join(MANIFEST_FILE), &serde_json::to_vec_pretty(self)?)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DownloadReport {
+    /// Symbols downloaded by this run
+    pub downloaded: Vec<String>,
+    /// Symbols a previous run already downloaded
+    pub already_cached: Vec<String>,
+    /// False when cancelled before every symbol was downloaded
+    pub complete: bool,
+}
+
+/// The cache file of `symbol`, e.g. `BTC_USD.csv` for `BTC/USD`.
+pub fn bar_file(directory: &Path, symbol: &str) -> PathBuf {
+    directory.join(format!("{}.csv", symbol.replace('/', "_")))
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    // Write next to the target and rename so a crash mid-write never leaves a torn file
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents).with_context(|| format!("failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+fn write_bars_csv(bars: &[Bar], mut writer: impl Write) -> Result<()> {
+    writeln!(writer, "time,open,high,low,close,volume,vwap")?;
+    for bar in bars {
+        let asset_class = AssetClass::of_symbol(&bar.symbol);
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            bar.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            format_price(&bar.open, asset_class),
+            format_price(&bar.high, asset_class),
+            format_price(&bar.low, asset_class),
+            format_price(&bar.close, asset_class),
+            format_quantity(&bar.volume),
+            bar.vwap
+                .as_ref()
+                .map(|vwap| format_price(vwap, asset_class))
+                .unwrap_or_default(),
+        )?;
+    }
+    Ok(())
+}
+
+impl AlpacaClient {
+    /// Downloads the bars of every symbol of `download` into `directory`, one page at a time at
+    /// the configured pace. Each symbol's file is written once all its pages are in, and recorded
+    /// in the directory's manifest; a later run with the same time frame and range skips the
+    /// recorded symbols, so a failed or cancelled download is resumed by running it again.
+    pub async fn download_bars(
+        &self,
+        download: &BarDownload,
+        directory: impl AsRef<Path>,
+        cancellation: &CancellationToken,
+    ) -> Result<DownloadReport> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory).with_context(|| format!("failed to create {}", directory.display()))?;
+        let mut manifest = Manifest::load_or_create(directory, download)?;
+
+        let mut pace = interval(Duration::from_secs(60) / download.requests_per_minute.max(1));
+        pace.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut report = DownloadReport::default();
+        for symbol in &download.symbols {
+            if manifest.completed.contains(symbol) {
+                report.already_cached.push(symbol.clone());
+                continue;
+            }
+
+            let mut bars = Vec::new();
+            let mut page_token = None;
+            loop {
+                tokio::select! {
+                    biased;
+                    _ = cancellation.cancelled() => return Ok(report),
+                    _ = pace.tick() => {}
+                }
+                let (page, next_page_token) = self
+                    .get_bar_page(symbol, download.time_frame, download.start, download.end, page_token)
+                    .await
+                    .with_context(|| format!("failed to download the bars of {symbol}"))?;
+                bars.extend(page);
+                page_token = next_page_token;
+                if page_token.is_none() {
+                    break;
+                }
+            }
+
+            let mut csv = Vec::new();
+            write_bars_csv(&bars, &mut csv)?;
+            write_atomically(&bar_file(directory, symbol), &csv)?;
+            manifest.completed.insert(symbol.clone());
+            manifest.save(directory)?;
+            report.downloaded.push(symbol.clone());
+        }
+
+        report.complete = true;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::unique_temp_path;
+    use chrono::TimeZone;
+    use num_decimal::Num;
+
+    fn download() -> BarDownload {
+        BarDownload {
+            symbols: vec!["AAPL".into(), "BTC/USD".into()],
+            time_frame: TimeFrame::OneDay,
+            start: Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            requests_per_minute: 100,
+        }
+    }
+
+    #[test]
+    fn manifest_resumes_only_the_same_download() -> Result<()> {
+        let directory = unique_temp_path("download_manifest");
+        fs::create_dir_all(&directory)?;
+
+        let mut manifest = Manifest::load_or_create(&directory, &download())?;
+        manifest.completed.insert("AAPL".into());
+        manifest.save(&directory)?;
+
+        let resumed = Manifest::load_or_create(&directory, &download())?;
+        assert!(resumed.completed.contains("AAPL"));
+
+        let other_range = BarDownload {
+            end: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            ..download()
+        };
+        assert!(Manifest::load_or_create(&directory, &other_range).is_err());
+
+        fs::remove_dir_all(&directory)?;
+        Ok(())
+    }
+
+    #[test]
+    fn writes_bars_as_csv() -> Result<()> {
+        let bar = Bar {
+            symbol: "BTC/USD".into(),
+            exchange: Some("us".into()),
+            time: Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap(),
+            open: Num::new(421_015, 10),
+            high: Num::from(43000),
+            low: Num::from(42000),
+            close: Num::from(42500),
+            volume: Num::new(15, 10),
+            vwap: None,
+        };
+
+        let mut csv = Vec::new();
+        write_bars_csv(&[bar], &mut csv)?;
+        let csv = String::from_utf8(csv)?;
+        assert_eq!(csv.lines().nth(1), Some("2024-01-02T00:00:00Z,42101.50,43000.00,42000.00,42500.00,1.5,"));
+        assert_eq!(bar_file(Path::new("cache"), "BTC/USD"), Path::new("cache").join("BTC_USD.csv"));
+        Ok(())
+    }
+}
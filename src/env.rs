@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use anyhow::{Context, Result};
+use apca::ApiInfo;
+use std::env;
+
+pub(crate) const DEFAULT_API_BASE_URL: &str = "https://paper-api.alpaca.markets";
+pub(crate) const LIVE_API_BASE_URL: &str = "https://api.alpaca.markets";
+
+/// Names of the environment variables holding the Alpaca credentials. The defaults are the ones
+/// apca reads, use [`EnvVars::with_prefix`] to run several bots with different accounts side by side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvVars {
+    pub key_id: String,
+    pub secret: String,
+    /// Falls back to the paper trading API when unset
+    pub api_base_url: String,
+}
+
+impl Default for EnvVars {
+    fn default() -> Self {
+        Self {
+            key_id: "APCA_API_KEY_ID".into(),
+            secret: "APCA_API_SECRET_KEY".into(),
+            api_base_url: "APCA_API_BASE_URL".into(),
+        }
+    }
+}
+
+impl EnvVars {
+    /// `EnvVars::with_prefix("MYBOT_ALPACA")` reads `MYBOT_ALPACA_KEY`, `MYBOT_ALPACA_SECRET` and
+    /// `MYBOT_ALPACA_BASE_URL`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            key_id: format!("{prefix}_KEY"),
+            secret: format!("{prefix}_SECRET"),
+            api_base_url: format!("{prefix}_BASE_URL"),
+        }
+    }
+
+    pub fn api_info(&self) -> Result<ApiInfo> {
+        let key_id = env::var(&self.key_id).with_context(|| format!("{} is not set", self.key_id))?;
+        let secret = env::var(&self.secret).with_context(|| format!("{} is not set", self.secret))?;
+        let api_base_url = env::var(&self.api_base_url).unwrap_or_else(|_| DEFAULT_API_BASE_URL.into());
+
+        let api_info = ApiInfo::from_parts(&api_base_url, key_id, secret)
+            .with_context(|| format!("{} holds an invalid url", self.api_base_url))?;
+
+        Ok(api_info)
+    }
+}
+
+impl AlpacaClient {
+    pub fn from_env() -> Result<Self> {
+        Self::from_env_vars(&EnvVars::default())
+    }
+
+    pub fn from_env_vars(env_vars: &EnvVars) -> Result<Self> {
+        Ok(Self::new(env_vars.api_info()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixed_names() {
+        let env_vars = EnvVars::with_prefix("MYBOT_ALPACA");
+
+        assert_eq!(env_vars.key_id, "MYBOT_ALPACA_KEY");
+        assert_eq!(env_vars.secret, "MYBOT_ALPACA_SECRET");
+        assert_eq!(env_vars.api_base_url, "MYBOT_ALPACA_BASE_URL");
+    }
+
+    #[test]
+    fn missing_variable_is_named_in_error() {
+        let env_vars = EnvVars::with_prefix("IRONTRADE_ALPACA_SURELY_UNSET");
+        let error = env_vars.api_info().unwrap_err();
+
+        assert!(error.to_string().contains("IRONTRADE_ALPACA_SURELY_UNSET_KEY"));
+    }
+}
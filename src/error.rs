@@ -0,0 +1,316 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use apca::api::v2::{account, account_activities, asset, calendar, order, orders, position, positions};
+use apca::{ApiError, RequestError};
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+
+/// Why a request to Alpaca failed, read from the status and Alpaca's error message of the
+/// response or from the transport error. irontrade reports errors as `anyhow::Error`, classify
+/// one to branch on why it failed instead of matching its message:
+///
+/// ```no_run
+/// # use irontrade_alpaca::error::AlpacaError;
+/// # fn handle(error: anyhow::Error) {
+/// match AlpacaError::classify(&error) {
+///     AlpacaError::InsufficientBuyingPower => { /* size down */ }
+///     AlpacaError::RateLimited => { /* back off */ }
+///     _ => { /* give up */ }
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AlpacaError {
+    InsufficientBuyingPower,
+    /// Selling more than the position holds, or than isn't already held for other orders
+    InsufficientQuantity,
+    /// The order could cross an open order of the account on the other side
+    WashTrade,
+    PositionNotFound,
+    OrderNotFound,
+    /// The order filled or ended before it could be canceled or replaced
+    OrderNotCancelable,
+    RateLimited,
+    /// Invalid credentials, or a key without access to the endpoint
+    Auth,
+    /// Refused as invalid, with Alpaca's message
+    InvalidRequest(String),
+    /// Alpaca failed to handle the request
+    Server,
+    /// No response came back, e.g. a reset connection or a timeout
+    Network,
+    Other,
+}
+
+/// An unsuccessful response to one of the crate's own REST calls, with Alpaca's error code and
+/// message when the body carried them and the body as is otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseError {
+    pub status: StatusCode,
+    pub code: Option<u64>,
+    pub message: String,
+}
+
+/// Alpaca's JSON error body, e.g. `{"code":40310000,"message":"insufficient buying power"}`.
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: u64,
+    message: String,
+}
+
+impl ResponseError {
+    pub(crate) fn new(status: StatusCode, body: String) -> Self {
+        match serde_json::from_str::<ErrorBody>(&body) {
+            Ok(ErrorBody { code, message }) => Self {
+                status,
+                code: Some(code),
+                message,
+            },
+            Err(_) => Self {
+                status,
+                code: None,
+                message: body,
+            },
+        }
+    }
+
+    /// Passes a successful response through and turns any other into an error.
+    pub(crate) async fn check(response: Response) -> Result<Response, ResponseError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        Err(Self::new(status, response.text().await.unwrap_or_default()))
+    }
+}
+
+impl Display for ResponseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.status, self.message)
+    }
+}
+
+impl StdError for ResponseError {}
+
+/// Status and Alpaca message of a failed response found in an error chain.
+struct Failure<'a> {
+    status: u16,
+    message: Option<&'a str>,
+}
+
+/// Reads the failed response out of apca's endpoint errors, for every endpoint the crate calls
+/// through apca. Only their `UnexpectedStatus` variant carries the status, responses an endpoint
+/// has a variant of its own for are classified by their message.
+macro_rules! apca_failure {
+    ($($module:ident::$error:ident),* $(,)?) => {
+        fn apca_failure(cause: &(dyn StdError + 'static)) -> Option<Failure<'_>> {
+            $(
+                if let Some(RequestError::Endpoint($module::$error::UnexpectedStatus(status, body))) =
+                    cause.downcast_ref::<RequestError<$module::$error>>()
+                {
+                    return Some(Failure {
+                        status: status.as_u16(),
+                        message: body.as_ref().ok().map(|error: &ApiError| error.message.as_str()),
+                    });
+                }
+            )*
+            None
+        }
+    };
+}
+
+apca_failure!(
+    account::GetError,
+    account_activities::GetError,
+    asset::GetError,
+    calendar::ListError,
+    order::ChangeError,
+    order::DeleteError,
+    order::GetByClientIdError,
+    order::GetError,
+    orders::ListError,
+    position::GetError,
+    positions::ListError,
+);
+
+/// The failed response behind `cause`, if it is one.
+fn failure(cause: &(dyn StdError + 'static)) -> Option<Failure<'_>> {
+    if let Some(error) = cause.downcast_ref::<ResponseError>() {
+        return Some(Failure {
+            status: error.status.as_u16(),
+            message: Some(&error.message),
+        });
+    }
+    if let Some(status) = cause.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status) {
+        return Some(Failure {
+            status: status.as_u16(),
+            message: None,
+        });
+    }
+    apca_failure(cause)
+}
+
+/// Whether the io error is the connection failing rather than e.g. a missing file.
+fn is_connection_error(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::TimedOut
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+impl AlpacaError {
+    pub fn classify(error: &anyhow::Error) -> Self {
+        if let Some(error) = error.downcast_ref::<AlpacaError>() {
+            return error.clone();
+        }
+        let mut failed = None;
+        for cause in error.chain() {
+            if let Some(error) = cause.downcast_ref::<std::io::Error>()
+                && is_connection_error(error)
+            {
+                return AlpacaError::Network;
+            }
+            if let Some(error) = cause.downcast_ref::<reqwest::Error>()
+                && (error.is_timeout() || error.is_connect())
+            {
+                return AlpacaError::Network;
+            }
+            failed = failed.or_else(|| failure(cause));
+        }
+
+        // Alpaca's message says why when there is one, errors that aren't a typed response
+        // (e.g. apca's endpoint variants or the mock client) only have their own text
+        let message = match failed.as_ref().and_then(|failure| failure.message) {
+            Some(message) => message.to_lowercase(),
+            None => format!("{error:#}").to_lowercase(),
+        };
+        let mentions = |patterns: &[&str]| patterns.iter().any(|pattern| message.contains(pattern));
+        if mentions(&["insufficient buying power"]) {
+            return AlpacaError::InsufficientBuyingPower;
+        }
+        if mentions(&["wash trade"]) {
+            return AlpacaError::WashTrade;
+        }
+        if mentions(&["insufficient qty", "insufficient quantity"]) {
+            return AlpacaError::InsufficientQuantity;
+        }
+        if mentions(&["position does not exist", "position not found"]) {
+            return AlpacaError::PositionNotFound;
+        }
+        if mentions(&["order not found"]) {
+            return AlpacaError::OrderNotFound;
+        }
+        if mentions(&["not cancelable", "not replaceable"]) {
+            return AlpacaError::OrderNotCancelable;
+        }
+
+        match failed {
+            Some(Failure { status: 429, .. }) => AlpacaError::RateLimited,
+            Some(Failure { status: 401 | 403, .. }) => AlpacaError::Auth,
+            Some(Failure { status: 422, message }) => {
+                let message = message.map_or_else(|| error.root_cause().to_string(), str::to_string);
+                AlpacaError::InvalidRequest(message)
+            }
+            Some(Failure { status: 500..=599, .. }) => AlpacaError::Server,
+            _ => AlpacaError::Other,
+        }
+    }
+}
+
+impl Display for AlpacaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlpacaError::InsufficientBuyingPower => write!(f, "insufficient buying power"),
+            AlpacaError::InsufficientQuantity => write!(f, "insufficient quantity"),
+            AlpacaError::WashTrade => write!(f, "potential wash trade"),
+            AlpacaError::PositionNotFound => write!(f, "position not found"),
+            AlpacaError::OrderNotFound => write!(f, "order not found"),
+            AlpacaError::OrderNotCancelable => write!(f, "order can no longer be canceled"),
+            AlpacaError::RateLimited => write!(f, "rate limited"),
+            AlpacaError::Auth => write!(f, "not authorized"),
+            AlpacaError::InvalidRequest(message) => write!(f, "invalid request: {message}"),
+            AlpacaError::Server => write!(f, "Alpaca server error"),
+            AlpacaError::Network => write!(f, "network error"),
+            AlpacaError::Other => write!(f, "request failed"),
+        }
+    }
+}
+
+impl std::error::Error for AlpacaError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn response(status: StatusCode, body: &str) -> anyhow::Error {
+        anyhow::Error::new(ResponseError::new(status, body.into())).context("submitting the order for AAPL failed")
+    }
+
+    #[test]
+    fn classifies_alpaca_errors() {
+        let classify = |error: anyhow::Error| AlpacaError::classify(&error);
+
+        assert_eq!(
+            classify(response(
+                StatusCode::FORBIDDEN,
+                r#"{"code":40310000,"message":"insufficient buying power"}"#
+            )),
+            AlpacaError::InsufficientBuyingPower
+        );
+        assert_eq!(
+            classify(response(
+                StatusCode::TOO_MANY_REQUESTS,
+                r#"{"code": 42910000, "message": "rate limit exceeded"}"#
+            )),
+            AlpacaError::RateLimited
+        );
+        assert_eq!(classify(response(StatusCode::UNAUTHORIZED, "")), AlpacaError::Auth);
+        assert_eq!(
+            classify(response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                r#"{"code":42210000,"message":"qty 500 must be a whole number"}"#
+            )),
+            AlpacaError::InvalidRequest("qty 500 must be a whole number".into())
+        );
+        assert_eq!(
+            classify(anyhow!("order is not cancelable")),
+            AlpacaError::OrderNotCancelable
+        );
+        assert_eq!(classify(anyhow!("something else")), AlpacaError::Other);
+
+        let unavailable = RequestError::Endpoint(orders::ListError::UnexpectedStatus(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Err(Vec::new()),
+        ));
+        assert_eq!(classify(anyhow::Error::new(unavailable)), AlpacaError::Server);
+
+        let reset = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        let error = anyhow::Error::from(reset).context("failed to get account");
+        assert_eq!(AlpacaError::classify(&error), AlpacaError::Network);
+        let error = anyhow::Error::from(AlpacaError::OrderNotFound).context("failed to cancel");
+        assert_eq!(AlpacaError::classify(&error), AlpacaError::OrderNotFound);
+    }
+
+    #[test]
+    fn ignores_statuses_in_messages() {
+        let echoed = anyhow::Error::new(ResponseError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            r#"{"code":42210000,"message":"invalid symbol"}"#.into(),
+        ))
+        .context("submitting the order for 500 Internal Server Error failed");
+        assert!(matches!(AlpacaError::classify(&echoed), AlpacaError::InvalidRequest(_)));
+
+        let text = anyhow!("request failed with 503 Service Unavailable");
+        assert_eq!(AlpacaError::classify(&text), AlpacaError::Other);
+    }
+}
@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// An event stamped by the [`EventBus`](crate::event_bus::EventBus) as it published it. All
+/// consumers of a bus share one sequence, increasing by one per event, so a gap means events were
+/// skipped by the consumer's filter or lost to lag, and a number at or below the last one seen
+/// means an event was delivered twice. Events Alpaca sends again after a reconnect are new to the
+/// bus and get new numbers, their order state tells them apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sequenced<T> {
+    pub sequence: u64,
+    pub received_at: DateTime<Utc>,
+    /// Monotonic receive time, unaffected by wall clock adjustments
+    pub received_instant: Instant,
+    pub event: T,
+}
+
+impl<T> Sequenced<T> {
+    /// Time since the event was received, i.e. the internal latency until it got consumed.
+    pub fn age(&self) -> Duration {
+        self.received_instant.elapsed()
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Sequenced<U> {
+        Sequenced {
+            sequence: self.sequence,
+            received_at: self.received_at,
+            received_instant: self.received_instant,
+            event: f(self.event),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Sequencer {
+    next: AtomicU64,
+}
+
+impl Sequencer {
+    pub(crate) fn stamp<T>(&self, event: T) -> Sequenced<T> {
+        Sequenced {
+            sequence: self.next.fetch_add(1, Ordering::Relaxed),
+            received_at: Utc::now(),
+            received_instant: Instant::now(),
+            event,
+        }
+    }
+}
+
+/// Consumer side bookkeeping to detect gaps and drop events delivered twice.
+#[derive(Clone, Debug, Default)]
+pub struct SequenceTracker {
+    last: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SequenceCheck {
+    InOrder,
+    /// This many events were skipped before this one
+    Gap(u64),
+    Duplicate,
+}
+
+impl SequenceTracker {
+    pub fn check<T>(&mut self, event: &Sequenced<T>) -> SequenceCheck {
+        let check = match self.last {
+            Some(last) if event.sequence <= last => return SequenceCheck::Duplicate,
+            Some(last) if event.sequence > last + 1 => SequenceCheck::Gap(event.sequence - last - 1),
+            _ => SequenceCheck::InOrder,
+        };
+        self.last = Some(event.sequence);
+        check
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequences_increase_monotonically() {
+        let sequencer = Sequencer::default();
+        let first = sequencer.stamp("a");
+        let second = sequencer.stamp("b");
+
+        assert_eq!(second.sequence, first.sequence + 1);
+        assert!(second.received_instant >= first.received_instant);
+    }
+
+    #[test]
+    fn tracker_detects_gaps_and_duplicates() {
+        let sequencer = Sequencer::default();
+        let events: Vec<_> = (0..4).map(|i| sequencer.stamp(i)).collect();
+        let mut tracker = SequenceTracker::default();
+
+        assert_eq!(tracker.check(&events[0]), SequenceCheck::InOrder);
+        assert_eq!(tracker.check(&events[2]), SequenceCheck::Gap(1));
+        assert_eq!(tracker.check(&events[1]), SequenceCheck::Duplicate);
+        assert_eq!(tracker.check(&events[3]), SequenceCheck::InOrder);
+    }
+}
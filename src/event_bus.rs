@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::event::{Sequenced, Sequencer};
+use crate::stream::{MarketDataEvent, MarketDataFeed, MarketDataSubscription, TradeUpdate};
+use anyhow::{Result, anyhow};
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::pin::pin;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+
+const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug)]
+pub enum BusEvent {
+    MarketData(MarketDataEvent),
+    TradeUpdate(TradeUpdate),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BusEventKind {
+    Quote,
+    Trade,
+    Bar,
+    Reconnected,
+    TradeUpdate,
+}
+
+impl BusEvent {
+    pub fn kind(&self) -> BusEventKind {
+        match self {
+            BusEvent::MarketData(MarketDataEvent::Quote(_)) => BusEventKind::Quote,
+            BusEvent::MarketData(MarketDataEvent::Trade(_)) => BusEventKind::Trade,
+            BusEvent::MarketData(MarketDataEvent::Bar(_)) => BusEventKind::Bar,
+            BusEvent::MarketData(MarketDataEvent::Reconnected) => BusEventKind::Reconnected,
+            BusEvent::TradeUpdate(_) => BusEventKind::TradeUpdate,
+        }
+    }
+
+    /// `None` for events about the connection rather than a symbol.
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            BusEvent::MarketData(MarketDataEvent::Quote(quote)) => Some(&quote.symbol),
+            BusEvent::MarketData(MarketDataEvent::Trade(trade)) => Some(&trade.symbol),
+            BusEvent::MarketData(MarketDataEvent::Bar(bar)) => Some(&bar.symbol),
+            BusEvent::MarketData(MarketDataEvent::Reconnected) => None,
+            BusEvent::TradeUpdate(update) => Some(&update.order.asset_symbol),
+        }
+    }
+}
+
+/// The slice of the bus a consumer sees. Every criterion left unset lets all events through,
+/// events without a symbol pass any symbol filter so consumers learn about reconnects.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    pub symbols: Option<HashSet<String>>,
+    pub kinds: Option<HashSet<BusEventKind>>,
+    /// Only trade updates of orders whose client order id starts with the tag, market data is
+    /// shared by all strategies and always passes
+    pub strategy_tag: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &BusEvent) -> bool {
+        let symbol_matches = match (&self.symbols, event.symbol()) {
+            (Some(symbols), Some(symbol)) => symbols.contains(symbol),
+            _ => true,
+        };
+        let kind_matches = self
+            .kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.contains(&event.kind()));
+        let strategy_matches = match (&self.strategy_tag, event) {
+            (Some(strategy_tag), BusEvent::TradeUpdate(update)) => {
+                update.client_order_id.starts_with(strategy_tag.as_str())
+            }
+            _ => true,
+        };
+        symbol_matches && kind_matches && strategy_matches
+    }
+}
+
+/// A subscription to the bus, receiving only the events matching its filter.
+pub struct FilteredReceiver {
+    receiver: broadcast::Receiver<Sequenced<BusEvent>>,
+    filter: EventFilter,
+}
+
+impl FilteredReceiver {
+    /// The next matching event. Sequence numbers are shared by all consumers, so skipped events
+    /// show up as gaps, while [`RecvError::Lagged`] means matching events may have been lost.
+    pub async fn recv(&mut self) -> Result<Sequenced<BusEvent>, RecvError> {
+        loop {
+            let event = self.receiver.recv().await?;
+            if self.filter.matches(&event.event) {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+/// Fans one set of websocket connections out to any number of strategy tasks, each subscribing
+/// to the slice of the events it needs. Events are stamped with their sequence number and receive
+/// time here, once, whichever stream they come from.
+pub struct EventBus {
+    sender: broadcast::Sender<Sequenced<BusEvent>>,
+    sequencer: Sequencer,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+            sequencer: Sequencer::default(),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&self, filter: EventFilter) -> FilteredReceiver {
+        FilteredReceiver {
+            receiver: self.sender.subscribe(),
+            filter,
+        }
+    }
+
+    pub fn publish(&self, event: BusEvent) {
+        // Nobody listening isn't an error
+        let _ = self.sender.send(self.sequencer.stamp(event));
+    }
+
+    /// Publishes the market data of `subscription` and the account's trade updates until
+    /// `cancellation` fires. Fails when the trade updates connection ends, the market data
+    /// stream reconnects by itself.
+    pub async fn run(
+        &self,
+        client: &AlpacaClient,
+        feed: MarketDataFeed,
+        subscription: &MarketDataSubscription,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let mut market_data = pin!(client.stream_market_data(feed, subscription).await?);
+        let mut trade_updates = pin!(client.stream_trade_updates().await?);
+
+        loop {
+            tokio::select! {
+                _ = cancellation.cancelled() => return Ok(()),
+                Some(event) = market_data.next() => self.publish(BusEvent::MarketData(event)),
+                update = trade_updates.next() => match update {
+                    Some(update) => self.publish(BusEvent::TradeUpdate(update?)),
+                    None => return Err(anyhow!("the trade updates stream ended")),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Quote;
+    use crate::stream::TradeUpdateKind;
+    use chrono::Utc;
+    use irontrade::api::common::{Amount, Order, OrderSide, OrderStatus, OrderType};
+    use num_decimal::Num;
+
+    fn quote(symbol: &str) -> BusEvent {
+        BusEvent::MarketData(MarketDataEvent::Quote(Quote {
+            symbol: symbol.into(),
+            exchange: None,
+            time: Utc::now(),
+            bid_price: Num::from(99),
+            bid_size: Num::from(1),
+            ask_price: Num::from(101),
+            ask_size: Num::from(1),
+        }))
+    }
+
+    fn fill(symbol: &str, client_order_id: &str) -> BusEvent {
+        BusEvent::TradeUpdate(TradeUpdate {
+            kind: TradeUpdateKind::Fill,
+            order: Order {
+                order_id: "61e69015-8549-4bfd-b9c3-01e75843f47d".into(),
+                asset_symbol: symbol.into(),
+                amount: Amount::Quantity {
+                    quantity: Num::from(1),
+                },
+                limit_price: None,
+                filled_quantity: Num::from(1),
+                average_fill_price: Some(Num::from(100)),
+                status: OrderStatus::Filled,
+                type_: OrderType::Market,
+                side: OrderSide::Buy,
+            },
+            client_order_id: client_order_id.into(),
+        })
+    }
+
+    #[tokio::test]
+    async fn routes_filtered_slices() {
+        let bus = EventBus::default();
+        let mut aapl = bus.subscribe(EventFilter {
+            symbols: Some(HashSet::from(["AAPL".to_string()])),
+            ..Default::default()
+        });
+        let mut momentum_fills = bus.subscribe(EventFilter {
+            kinds: Some(HashSet::from([BusEventKind::TradeUpdate])),
+            strategy_tag: Some("momentum-".into()),
+            ..Default::default()
+        });
+
+        bus.publish(quote("MSFT"));
+        bus.publish(fill("AAPL", "meanrev-1"));
+        bus.publish(quote("AAPL"));
+        bus.publish(fill("MSFT", "momentum-1"));
+
+        let event = aapl.recv().await.unwrap();
+        assert_eq!(
+            (event.sequence, event.event.kind()),
+            (1, BusEventKind::TradeUpdate)
+        );
+        assert_eq!(aapl.recv().await.unwrap().event.kind(), BusEventKind::Quote);
+
+        let event = momentum_fills.recv().await.unwrap();
+        assert_eq!(event.sequence, 3);
+        assert_eq!(event.event.symbol(), Some("MSFT"));
+    }
+}
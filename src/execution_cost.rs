@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::budget::BudgetKind;
+use crate::client::AlpacaClient;
+use crate::data::{CryptoExchange, Quote};
+use crate::error::ResponseError;
+use crate::positions::AssetClass;
+use anyhow::{Context, Result, anyhow, bail};
+use irontrade::api::common::{Amount, OrderSide};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use serde::Deserialize;
+
+/// Maker and taker fees in bps of Alpaca's crypto fee tiers 1 to 8, by 30 day volume.
+const CRYPTO_FEE_TIERS: [(i64, i64); 8] = [
+    (15, 25),
+    (12, 22),
+    (10, 20),
+    (8, 18),
+    (5, 15),
+    (2, 13),
+    (2, 12),
+    (0, 10),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CryptoFeeTier {
+    pub tier: u8,
+    pub maker_bps: Num,
+    pub taker_bps: Num,
+}
+
+impl CryptoFeeTier {
+    /// The fees of tier 1 to 8, tiers out of range are clamped.
+    pub fn tier(tier: u8) -> Self {
+        let tier = tier.clamp(1, CRYPTO_FEE_TIERS.len() as u8);
+        let (maker_bps, taker_bps) = CRYPTO_FEE_TIERS[usize::from(tier) - 1];
+        Self {
+            tier,
+            maker_bps: Num::from(maker_bps),
+            taker_bps: Num::from(taker_bps),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Liquidity {
+    /// Rests on the book, a limit order priced inside the spread or behind the touch
+    Maker,
+    /// Executes against the book, a market order or a marketable limit order
+    Taker,
+}
+
+/// What an order is expected to cost on top of the mid price, in dollars and in bps of its
+/// notional at the mid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExecutionCostEstimate {
+    pub liquidity: Liquidity,
+    pub notional: Num,
+    pub fee_bps: Num,
+    pub fee: Num,
+    /// Half the spread for takers, who cross from the mid to the far touch, zero for makers
+    pub spread_bps: Num,
+    pub spread_cost: Num,
+    pub total_bps: Num,
+    pub total: Num,
+}
+
+/// Estimates the cost of `req` against `quote`. Limit orders that wouldn't execute right away
+/// are assumed to fill as makers at their limit price.
+pub fn estimate_execution_cost(
+    req: &OrderRequest,
+    quote: &Quote,
+    fee_tier: &CryptoFeeTier,
+) -> Result<ExecutionCostEstimate> {
+    let mid = (&quote.bid_price + &quote.ask_price) / Num::from(2);
+    if !quote.bid_price.is_positive() || !mid.is_positive() {
+        bail!(
+            "no two-sided quote for {} to estimate against",
+            quote.symbol
+        );
+    }
+
+    let marketable = match (&req.limit_price, &req.side) {
+        (None, _) => true,
+        (Some(limit_price), OrderSide::Buy) => *limit_price >= quote.ask_price,
+        (Some(limit_price), OrderSide::Sell) => *limit_price <= quote.bid_price,
+    };
+    let (liquidity, fee_bps, spread_bps) = if marketable {
+        let half_spread_bps = (&quote.ask_price - &quote.bid_price) / &mid * Num::from(5_000);
+        (
+            Liquidity::Taker,
+            fee_tier.taker_bps.clone(),
+            half_spread_bps,
+        )
+    } else {
+        (Liquidity::Maker, fee_tier.maker_bps.clone(), Num::from(0))
+    };
+
+    let notional = match &req.amount {
+        Amount::Quantity { quantity } => quantity * &mid,
+        Amount::Notional { notional } => notional.clone(),
+    };
+    let cost = |bps: &Num| &notional * bps / Num::from(10_000);
+    let total_bps = &fee_bps + &spread_bps;
+
+    Ok(ExecutionCostEstimate {
+        liquidity,
+        fee: cost(&fee_bps),
+        spread_cost: cost(&spread_bps),
+        total: cost(&total_bps),
+        notional,
+        fee_bps,
+        spread_bps,
+        total_bps,
+    })
+}
+
+impl AlpacaClient {
+    /// The account's crypto fee tier, from its 30 day crypto volume.
+    pub async fn get_crypto_fee_tier(&self) -> Result<CryptoFeeTier> {
+        #[derive(Deserialize)]
+        struct AccountTier {
+            crypto_tier: Option<u8>,
+        }
+
+        self.acquire_budget(BudgetKind::Request).await?;
+        let api_info = self.apca_client.api_info();
+        let url = api_info.api_base_url.join("v2/account")?;
+        let response = self
+            .http_client
+            .get(url.as_str())
+            .header("APCA-API-KEY-ID", &api_info.key_id)
+            .header("APCA-API-SECRET-KEY", &api_info.secret)
+            .send()
+            .await?;
+
+        let response = ResponseError::check(response).await.context("account request failed")?;
+
+        let account: AccountTier = response.json().await?;
+        Ok(CryptoFeeTier::tier(account.crypto_tier.unwrap_or(1)))
+    }
+
+    /// Estimates the fees and spread cost of a crypto order before submitting it, from the
+    /// account's fee tier and the current quote on Alpaca's venue.
+    pub async fn estimate_execution_cost(
+        &self,
+        req: &OrderRequest,
+    ) -> Result<ExecutionCostEstimate> {
+        let symbol = req.asset_pair.to_string();
+        if AssetClass::of_symbol(&symbol) != AssetClass::Crypto {
+            bail!("execution cost estimates are for crypto orders, {symbol} is not a crypto pair");
+        }
+
+        let (fee_tier, quotes) = tokio::try_join!(
+            self.get_crypto_fee_tier(),
+            self.get_latest_crypto_quotes(&[symbol.clone()], CryptoExchange::Alpaca),
+        )?;
+        let quote = quotes
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no quote available for {symbol}"))?;
+        estimate_execution_cost(req, &quote, &fee_tier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use irontrade::api::common::AssetPair;
+    use std::str::FromStr;
+
+    fn request(side: OrderSide, limit_price: Option<i64>) -> Result<OrderRequest> {
+        Ok(OrderRequest {
+            asset_pair: AssetPair::from_str("BTC/USD")?,
+            amount: Amount::Quantity {
+                quantity: Num::from(2),
+            },
+            side,
+            limit_price: limit_price.map(Num::from),
+        })
+    }
+
+    #[test]
+    fn takers_pay_half_the_spread() -> Result<()> {
+        let quote = Quote {
+            symbol: "BTC/USD".into(),
+            exchange: None,
+            time: Utc::now(),
+            bid_price: Num::from(9_990),
+            bid_size: Num::from(1),
+            ask_price: Num::from(10_010),
+            ask_size: Num::from(1),
+        };
+        let fee_tier = CryptoFeeTier::tier(1);
+
+        let market = estimate_execution_cost(&request(OrderSide::Buy, None)?, &quote, &fee_tier)?;
+        assert_eq!(market.liquidity, Liquidity::Taker);
+        assert_eq!(market.notional, Num::from(20_000));
+        assert_eq!(market.spread_bps, Num::from(10));
+        assert_eq!(market.total_bps, Num::from(35));
+        assert_eq!(market.total, Num::from(70));
+
+        let resting =
+            estimate_execution_cost(&request(OrderSide::Sell, Some(10_005))?, &quote, &fee_tier)?;
+        assert_eq!(resting.liquidity, Liquidity::Maker);
+        assert_eq!(resting.fee, Num::from(30));
+        assert_eq!(resting.spread_cost, Num::from(0));
+
+        assert_eq!(CryptoFeeTier::tier(9).taker_bps, Num::from(10));
+        Ok(())
+    }
+}
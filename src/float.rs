@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Lossy float views of the decimal money types, for plotting and numeric pipelines.
+//!
+//! `Num` is an exact rational while `f64` carries about 15-17 significant digits, so large
+//! notional values lose cents, and values like `0.1` come back as their nearest binary
+//! approximation. Never feed the results back into orders or accounting.
+
+use irontrade::api::common::Amount;
+use num_decimal::Num;
+
+pub trait AsF64 {
+    type Output;
+
+    fn as_f64(&self) -> Self::Output;
+}
+
+impl AsF64 for Num {
+    type Output = f64;
+
+    /// Returns `NaN` for values outside of the `f64` range.
+    fn as_f64(&self) -> f64 {
+        self.to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+impl AsF64 for Option<Num> {
+    type Output = Option<f64>;
+
+    fn as_f64(&self) -> Option<f64> {
+        self.as_ref().map(AsF64::as_f64)
+    }
+}
+
+impl AsF64 for Amount {
+    type Output = f64;
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Amount::Quantity { quantity } => quantity.as_f64(),
+            Amount::Notional { notional } => notional.as_f64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn converts_num() {
+        assert_eq!(Num::from_str("42.5").unwrap().as_f64(), 42.5);
+        assert_eq!(Some(Num::from(3)).as_f64(), Some(3.0));
+        assert_eq!(None::<Num>.as_f64(), None);
+    }
+
+    #[test]
+    fn converts_amount() {
+        let amount = Amount::Notional {
+            notional: Num::from(20),
+        };
+        assert_eq!(amount.as_f64(), 20.0);
+    }
+}
@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::positions::AssetClass;
+use crate::precision::{Rounding, RoundingMode};
+use num_decimal::Num;
+
+/// Decimal places shown for crypto prices and for quantities, as many as Alpaca accepts.
+const MAX_DECIMAL_PLACES: u32 = 9;
+
+/// `value` rounded half up to `decimal_places` and written out in full, always with a `.` and
+/// without digit grouping whatever the locale, e.g. `1234.50`.
+pub fn format_fixed(value: &Num, decimal_places: u32) -> String {
+    let rounded = Rounding::new(decimal_places, RoundingMode::HalfUp).apply(value);
+    let magnitude = if rounded.is_negative() { -&rounded } else { rounded.clone() };
+    let integer = magnitude.trunc();
+    let scale = (0..decimal_places).fold(Num::from(1), |scale, _| scale * Num::from(10));
+    let fraction = ((&magnitude - &integer) * scale).trunc();
+
+    let sign = if rounded.is_negative() { "-" } else { "" };
+    if decimal_places == 0 {
+        return format!("{sign}{integer}");
+    }
+    let width = decimal_places as usize;
+    format!("{sign}{integer}.{:0>width$}", fraction.to_string())
+}
+
+/// Like [`format_fixed`] with up to `max_decimal_places`, dropping trailing zeros down to
+/// `min_decimal_places`.
+pub fn format_trimmed(value: &Num, min_decimal_places: u32, max_decimal_places: u32) -> String {
+    let mut formatted = format_fixed(value, max_decimal_places);
+    if let Some(point) = formatted.find('.') {
+        let min_len = point + 1 + min_decimal_places as usize;
+        while formatted.len() > min_len && formatted.ends_with('0') {
+            formatted.pop();
+        }
+        if formatted.ends_with('.') {
+            formatted.pop();
+        }
+    }
+    formatted
+}
+
+/// Dollar amounts such as notional, fees and P/L, to the cent.
+pub fn format_money(value: &Num) -> String {
+    format_fixed(value, 2)
+}
+
+/// Prices in the precision Alpaca quotes them in: cents for stocks and options, sub-penny below
+/// a dollar, and up to 9 decimal places for crypto.
+pub fn format_price(value: &Num, asset_class: AssetClass) -> String {
+    match asset_class {
+        AssetClass::Crypto => format_trimmed(value, 2, MAX_DECIMAL_PLACES),
+        _ if *value < Num::from(1) && *value > Num::from(-1) => format_fixed(value, 4),
+        _ => format_fixed(value, 2),
+    }
+}
+
+/// Quantities with as many decimal places as they have, up to 9.
+pub fn format_quantity(value: &Num) -> String {
+    format_trimmed(value, 0, MAX_DECIMAL_PLACES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn num(value: &str) -> Num {
+        Num::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn formats_fixed_decimal_places() {
+        assert_eq!(format_fixed(&num("1234.5"), 2), "1234.50");
+        assert_eq!(format_fixed(&num("-0.005"), 2), "-0.01");
+        assert_eq!(format_fixed(&num("0.05"), 1), "0.1");
+        assert_eq!(format_fixed(&Num::new(1, 3), 4), "0.3333");
+        assert_eq!(format_fixed(&num("2.5"), 0), "3");
+    }
+
+    #[test]
+    fn formats_by_asset_class() {
+        assert_eq!(format_price(&num("187.1"), AssetClass::Equity), "187.10");
+        assert_eq!(format_price(&num("0.12345"), AssetClass::Equity), "0.1235");
+        assert_eq!(format_price(&num("42123.5"), AssetClass::Crypto), "42123.50");
+        assert_eq!(format_price(&num("0.000012345"), AssetClass::Crypto), "0.000012345");
+        assert_eq!(format_quantity(&Num::new(2, 3)), "0.666666667");
+        assert_eq!(format_quantity(&num("10")), "10");
+        assert_eq!(format_money(&num("-101")), "-101.00");
+    }
+}
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::options::OptionContract;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use irontrade::api::common::OpenPosition as IronTradeOpenPosition;
+use num_decimal::Num;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Number of underlying shares controlled by one standard equity option contract.
+const CONTRACT_MULTIPLIER: i64 = 100;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Greeks {
+    pub delta: Num,
+    pub gamma: Num,
+    pub theta: Num,
+    pub vega: Num,
+}
+
+impl Greeks {
+    fn add_scaled(&mut self, other: &Greeks, scale: &Num) {
+        self.delta += &other.delta * scale;
+        self.gamma += &other.gamma * scale;
+        self.theta += &other.theta * scale;
+        self.vega += &other.vega * scale;
+    }
+}
+
+/// Supplies per-contract greek snapshots, e.g. from an options data feed.
+#[allow(async_fn_in_trait)]
+pub trait GreeksSource {
+    /// Returns the greeks of one contract keyed by OCC symbol. Symbols without data may be omitted.
+    async fn get_greeks(&self, option_symbols: &[String]) -> Result<HashMap<String, Greeks>>;
+}
+
+#[derive(Clone, Debug)]
+pub struct PortfolioGreeks {
+    /// Net exposure per underlying, share positions contribute their quantity as delta
+    pub by_underlying: HashMap<String, Greeks>,
+    pub total: Greeks,
+    /// Option positions that couldn't be included because the source had no greeks for them
+    pub missing: Vec<String>,
+    pub as_of: DateTime<Utc>,
+}
+
+impl PortfolioGreeks {
+    pub fn aggregate(positions: &[IronTradeOpenPosition], greeks: &HashMap<String, Greeks>) -> Self {
+        let mut by_underlying: HashMap<String, Greeks> = HashMap::new();
+        let mut total = Greeks::default();
+        let mut missing = Vec::new();
+
+        for position in positions {
+            let (underlying, exposure, scale) = match OptionContract::from_str(&position.asset_symbol) {
+                Ok(contract) => match greeks.get(&position.asset_symbol) {
+                    Some(contract_greeks) => (
+                        contract.underlying,
+                        contract_greeks.clone(),
+                        &position.quantity * Num::from(CONTRACT_MULTIPLIER),
+                    ),
+                    None => {
+                        missing.push(position.asset_symbol.clone());
+                        continue;
+                    }
+                },
+                Err(_) => (
+                    position.asset_symbol.clone(),
+                    Greeks {
+                        delta: Num::from(1),
+                        ..Default::default()
+                    },
+                    position.quantity.clone(),
+                ),
+            };
+
+            by_underlying
+                .entry(underlying)
+                .or_default()
+                .add_scaled(&exposure, &scale);
+            total.add_scaled(&exposure, &scale);
+        }
+
+        Self {
+            by_underlying,
+            total,
+            missing,
+            as_of: Utc::now(),
+        }
+    }
+}
+
+/// Keeps the portfolio greeks up to date, only hitting the API once the refresh interval has
+/// elapsed since the last aggregation.
+pub struct PortfolioGreeksMonitor<S: GreeksSource> {
+    source: S,
+    refresh_interval: Duration,
+    latest: Option<(Instant, PortfolioGreeks)>,
+}
+
+impl<S: GreeksSource> PortfolioGreeksMonitor<S> {
+    pub fn new(source: S, refresh_interval: Duration) -> Self {
+        Self {
+            source,
+            refresh_interval,
+            latest: None,
+        }
+    }
+
+    pub fn latest(&self) -> Option<&PortfolioGreeks> {
+        self.latest.as_ref().map(|(_, greeks)| greeks)
+    }
+
+    pub async fn get(&mut self, client: &AlpacaClient) -> Result<&PortfolioGreeks> {
+        let is_stale = match &self.latest {
+            Some((refreshed_at, _)) => refreshed_at.elapsed() >= self.refresh_interval,
+            None => true,
+        };
+
+        if is_stale {
+            let greeks = client.get_portfolio_greeks(&self.source).await?;
+            self.latest = Some((Instant::now(), greeks));
+        }
+
+        Ok(self.latest().unwrap())
+    }
+}
+
+impl AlpacaClient {
+    pub async fn get_portfolio_greeks(&self, source: &impl GreeksSource) -> Result<PortfolioGreeks> {
+        let positions = self.list_open_positions().await?;
+        let option_symbols: Vec<String> = positions
+            .iter()
+            .filter(|position| OptionContract::from_str(&position.asset_symbol).is_ok())
+            .map(|position| position.asset_symbol.clone())
+            .collect();
+
+        let greeks = if option_symbols.is_empty() {
+            HashMap::new()
+        } else {
+            source.get_greeks(&option_symbols).await?
+        };
+
+        Ok(PortfolioGreeks::aggregate(&positions, &greeks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_nets_options_and_shares_per_underlying() -> Result<()> {
+        let positions = [
+            open_position("AAPL240119C00150000", 2),
+            open_position("AAPL", -50),
+            open_position("SPY240119P00470000", 1),
+        ];
+        let greeks = HashMap::from([(
+            "AAPL240119C00150000".to_string(),
+            Greeks {
+                delta: Num::from_str("0.5")?,
+                gamma: Num::from_str("0.01")?,
+                theta: Num::from_str("-0.02")?,
+                vega: Num::from_str("0.1")?,
+            },
+        )]);
+
+        let portfolio = PortfolioGreeks::aggregate(&positions, &greeks);
+        let aapl = &portfolio.by_underlying["AAPL"];
+
+        assert_eq!(aapl.delta, Num::from(50));
+        assert_eq!(aapl.gamma, Num::from(2));
+        assert_eq!(aapl.theta, Num::from(-4));
+        assert_eq!(aapl.vega, Num::from(20));
+        assert_eq!(portfolio.total, *aapl);
+        assert_eq!(portfolio.missing, vec!["SPY240119P00470000".to_string()]);
+
+        Ok(())
+    }
+
+    fn open_position(asset_symbol: &str, quantity: i64) -> IronTradeOpenPosition {
+        IronTradeOpenPosition {
+            asset_symbol: asset_symbol.into(),
+            average_entry_price: None,
+            quantity: Num::from(quantity),
+            market_value: None,
+        }
+    }
+}
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::intent::{OrderAnnotation, OrderIntent};
+use crate::precision::{Rounding, RoundingMode};
+use crate::symbol::same_symbol;
+use anyhow::Result;
+use irontrade::api::common::{Amount, AssetPair, OpenPosition as IronTradeOpenPosition, OrderSide};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 64;
+
+/// Keeps the net delta of a group of symbols within `max_abs_delta` by trading a hedge
+/// instrument, e.g. keep a basket of altcoins delta neutral with BTC.
+#[derive(Clone, Debug)]
+pub struct HedgeRule {
+    pub name: String,
+    /// Delta contributed by one unit of each symbol in the group
+    pub deltas: HashMap<String, Num>,
+    pub hedge: AssetPair,
+    /// Delta of one unit of the hedge instrument
+    pub hedge_delta: Num,
+    pub max_abs_delta: Num,
+    /// Hedge orders are truncated to this many decimal places
+    pub quantity_decimals: u32,
+}
+
+impl HedgeRule {
+    pub fn net_delta(&self, positions: &[IronTradeOpenPosition]) -> Num {
+        let hedge_symbol = self.hedge.to_string();
+        positions.iter().fold(Num::from(0), |net, position| {
+            let symbol = &position.asset_symbol;
+            let delta = if same_symbol(symbol, &hedge_symbol) {
+                Some(&self.hedge_delta)
+            } else {
+                self.deltas
+                    .iter()
+                    .find(|(group_symbol, _)| same_symbol(group_symbol, symbol))
+                    .map(|(_, delta)| delta)
+            };
+            match delta {
+                Some(delta) => net + &position.quantity * delta,
+                None => net,
+            }
+        })
+    }
+
+    /// The order bringing the group back to flat, if its net delta is past the limit.
+    pub fn offsetting_order(&self, positions: &[IronTradeOpenPosition]) -> Option<OrderRequest> {
+        let net_delta = self.net_delta(positions);
+        let abs_delta = if net_delta.is_negative() { -&net_delta } else { net_delta.clone() };
+        if abs_delta <= self.max_abs_delta || self.hedge_delta.is_zero() {
+            return None;
+        }
+
+        let rounding = Rounding::new(self.quantity_decimals, RoundingMode::Down);
+        let quantity = rounding.apply(&(&net_delta / &self.hedge_delta));
+        if quantity.is_zero() {
+            return None;
+        }
+
+        let (side, quantity) = if quantity.is_positive() {
+            (OrderSide::Sell, quantity)
+        } else {
+            (OrderSide::Buy, -quantity)
+        };
+
+        Some(OrderRequest {
+            asset_pair: self.hedge.clone(),
+            amount: Amount::Quantity { quantity },
+            side,
+            limit_price: None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HedgeEvent {
+    Submitted {
+        rule: String,
+        net_delta: Num,
+        order_id: String,
+    },
+    Failed {
+        rule: String,
+        net_delta: Num,
+        error: String,
+    },
+}
+
+/// Registered hedge rules and the channel their actions are reported on.
+pub struct Hedger {
+    rules: Vec<HedgeRule>,
+    sender: broadcast::Sender<HedgeEvent>,
+}
+
+impl Default for Hedger {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl Hedger {
+    fn emit(&self, event: HedgeEvent) {
+        // Nobody listening isn't an error
+        let _ = self.sender.send(event);
+    }
+}
+
+impl AlpacaClient {
+    pub fn register_hedge_rule(&mut self, rule: HedgeRule) {
+        self.hedger.rules.push(rule);
+    }
+
+    pub fn subscribe_hedge_events(&self) -> broadcast::Receiver<HedgeEvent> {
+        self.hedger.sender.subscribe()
+    }
+
+    /// Checks every hedge rule against the current positions and submits the offsetting orders
+    /// for the ones past their limit. Call it whenever fills change the positions, e.g. after
+    /// `wait_for_fill` returns. Failures are reported as events so one rule can't block another.
+    pub async fn rebalance_hedges(&mut self) -> Result<Vec<HedgeEvent>> {
+        if self.hedger.rules.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let positions = self.list_open_positions().await?;
+        let mut events = Vec::new();
+
+        for rule in self.hedger.rules.clone() {
+            let Some(order) = rule.offsetting_order(&positions) else {
+                continue;
+            };
+            let net_delta = rule.net_delta(&positions);
+
+            let annotation = OrderAnnotation::with_note(OrderIntent::Hedge, &rule.name);
+            let event = match self.place_order_annotated(order, annotation).await {
+                Ok(order_id) => HedgeEvent::Submitted {
+                    rule: rule.name,
+                    net_delta,
+                    order_id,
+                },
+                Err(error) => HedgeEvent::Failed {
+                    rule: rule.name,
+                    net_delta,
+                    error: format!("{error:#}"),
+                },
+            };
+            self.hedger.emit(event.clone());
+            events.push(event);
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn position(asset_symbol: &str, quantity: i64) -> IronTradeOpenPosition {
+        IronTradeOpenPosition {
+            asset_symbol: asset_symbol.into(),
+            average_entry_price: None,
+            quantity: Num::from(quantity),
+            market_value: None,
+        }
+    }
+
+    fn rule() -> Result<HedgeRule> {
+        Ok(HedgeRule {
+            name: "alts".into(),
+            deltas: HashMap::from([("ETH/USD".into(), Num::from(1)), ("SOL/USD".into(), Num::new(12, 10))]),
+            hedge: AssetPair::from_str("BTC/USD")?,
+            hedge_delta: Num::from(2),
+            max_abs_delta: Num::from(50),
+            quantity_decimals: 0,
+        })
+    }
+
+    #[test]
+    fn stays_put_within_limit() -> Result<()> {
+        let positions = [position("ETHUSD", 30), position("SOLUSD", 10), position("DOGEUSD", 1000)];
+        assert_eq!(rule()?.net_delta(&positions), Num::from(42));
+        assert!(rule()?.offsetting_order(&positions).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn sells_hedge_when_too_long() -> Result<()> {
+        let positions = [position("ETHUSD", 100), position("BTCUSD", -20)];
+        let order = rule()?.offsetting_order(&positions).unwrap();
+
+        assert!(matches!(order.side, OrderSide::Sell));
+        assert!(matches!(order.amount, Amount::Quantity { quantity } if quantity == Num::from(30)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use anyhow::{Context, Result};
+use reqwest::{Certificate, tls};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+/// TLS settings of the crate's own REST calls, i.e. order submissions, market data, position
+/// closes and prepared orders, for deployments that reach Alpaca through an inspecting proxy with
+/// a private CA. They don't reach apca's trading calls or the websockets.
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfig {
+    /// PEM files with additional root certificates to trust
+    pub root_certificates: Vec<PathBuf>,
+    pub min_tls_version: Option<TlsVersion>,
+}
+
+/// Connection settings of the HTTP client shared by all requests this crate issues directly, so
+/// the data and trading hosts reuse one pool. Order submissions go through it too, the other
+/// trading calls through apca's client. `None` keeps the reqwest default.
+#[derive(Clone, Debug, Default)]
+pub struct HttpConfig {
+    pub tls: TlsConfig,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub pool_idle_timeout: Option<Duration>,
+    pub tcp_keepalive: Option<Duration>,
+    pub tcp_nodelay: Option<bool>,
+    pub connect_timeout: Option<Duration>,
+    /// Limit on a whole request, from connecting to reading the response body
+    pub timeout: Option<Duration>,
+    pub user_agent: Option<String>,
+    /// Interval of HTTP/2 pings keeping idle connections warm
+    pub http2_keep_alive_interval: Option<Duration>,
+    pub http2_adaptive_window: Option<bool>,
+}
+
+pub(crate) fn build_http_client(http_config: &HttpConfig) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder();
+
+    for path in &http_config.tls.root_certificates {
+        let pem = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let certificate =
+            Certificate::from_pem(&pem).with_context(|| format!("invalid certificate in {}", path.display()))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    if let Some(min_tls_version) = http_config.tls.min_tls_version {
+        builder = builder.min_tls_version(match min_tls_version {
+            TlsVersion::Tls12 => tls::Version::TLS_1_2,
+            TlsVersion::Tls13 => tls::Version::TLS_1_3,
+        });
+    }
+
+    if let Some(pool_max_idle_per_host) = http_config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(pool_max_idle_per_host);
+    }
+    if let Some(pool_idle_timeout) = http_config.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(pool_idle_timeout);
+    }
+    if let Some(tcp_keepalive) = http_config.tcp_keepalive {
+        builder = builder.tcp_keepalive(tcp_keepalive);
+    }
+    if let Some(tcp_nodelay) = http_config.tcp_nodelay {
+        builder = builder.tcp_nodelay(tcp_nodelay);
+    }
+    if let Some(connect_timeout) = http_config.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    if let Some(timeout) = http_config.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(user_agent) = &http_config.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+    if let Some(http2_keep_alive_interval) = http_config.http2_keep_alive_interval {
+        builder = builder
+            .http2_keep_alive_interval(http2_keep_alive_interval)
+            .http2_keep_alive_while_idle(true);
+    }
+    if let Some(http2_adaptive_window) = http_config.http2_adaptive_window {
+        builder = builder.http2_adaptive_window(http2_adaptive_window);
+    }
+
+    Ok(builder.build()?)
+}
+
+impl AlpacaClient {
+    pub fn set_http_config(&mut self, http_config: HttpConfig) -> Result<()> {
+        self.http_client = build_http_client(&http_config)?;
+        self.http_config = http_config;
+        Ok(())
+    }
+
+    /// Sets the TLS settings of the crate's own REST calls, see [`TlsConfig`].
+    pub fn set_rest_tls_config(&mut self, tls_config: TlsConfig) -> Result<()> {
+        self.set_http_config(HttpConfig {
+            tls: tls_config,
+            ..self.http_config.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_certificate_is_reported() {
+        let http_config = HttpConfig {
+            tls: TlsConfig {
+                root_certificates: vec!["/nonexistent/ca.pem".into()],
+                min_tls_version: Some(TlsVersion::Tls12),
+            },
+            ..Default::default()
+        };
+
+        let error = build_http_client(&http_config).unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/ca.pem"));
+    }
+
+    #[test]
+    fn builds_tuned_client() {
+        let http_config = HttpConfig {
+            pool_max_idle_per_host: Some(4),
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            tcp_keepalive: Some(Duration::from_secs(30)),
+            tcp_nodelay: Some(true),
+            http2_keep_alive_interval: Some(Duration::from_secs(15)),
+            ..Default::default()
+        };
+
+        assert!(build_http_client(&http_config).is_ok());
+    }
+}
@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::error::AlpacaError;
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// How long a client order id is remembered after its order was submitted.
+pub const DEFAULT_IDEMPOTENCY_RETENTION: TimeDelta = TimeDelta::days(7);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum SubmissionOutcome {
+    Placed {
+        order_id: String,
+    },
+    /// Alpaca refused the order, resubmitting the same request would be refused again
+    Rejected {
+        error: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Submission {
+    #[serde(flatten)]
+    pub outcome: SubmissionOutcome,
+    pub time: DateTime<Utc>,
+}
+
+/// The outcomes of orders submitted under a client order id, kept in a JSON file so that a bot
+/// restarted after a crash and re-driving the same submission gets the original order back
+/// instead of placing it twice.
+#[derive(Debug)]
+pub struct IdempotencyStore {
+    path: PathBuf,
+    retention: TimeDelta,
+    submissions: BTreeMap<String, Submission>,
+}
+
+impl IdempotencyStore {
+    /// Opens the store at `path`, creating it on the first recorded submission.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let submissions = if path.exists() {
+            let json =
+                fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+            serde_json::from_slice(&json)
+                .with_context(|| format!("failed to parse {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path,
+            retention: DEFAULT_IDEMPOTENCY_RETENTION,
+            submissions,
+        })
+    }
+
+    pub fn with_retention(mut self, retention: TimeDelta) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn get(&self, client_order_id: &str) -> Option<&Submission> {
+        self.submissions
+            .get(client_order_id)
+            .filter(|submission| Utc::now() - submission.time < self.retention)
+    }
+
+    /// Records the outcome and rewrites the file, dropping submissions past the retention.
+    pub fn record(&mut self, client_order_id: &str, outcome: SubmissionOutcome) -> Result<()> {
+        let now = Utc::now();
+        self.submissions
+            .retain(|_, submission| now - submission.time < self.retention);
+        self.submissions.insert(
+            client_order_id.to_string(),
+            Submission { outcome, time: now },
+        );
+        write(&self.path, &self.submissions)
+    }
+}
+
+fn write(path: &Path, submissions: &BTreeMap<String, Submission>) -> Result<()> {
+    let json = serde_json::to_vec_pretty(submissions)?;
+
+    // Write next to the target and rename so a crash mid-write never leaves a torn file
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, json)
+        .with_context(|| format!("failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Whether the failure is Alpaca's answer to the order rather than the request not getting
+/// through, only answers are recorded.
+fn is_rejection(error: &anyhow::Error) -> bool {
+    !matches!(
+        AlpacaError::classify(error),
+        AlpacaError::Network | AlpacaError::RateLimited | AlpacaError::Server | AlpacaError::Other
+    )
+}
+
+impl AlpacaClient {
+    /// Remembers the outcome of every order submitted with a client order id in `store`.
+    pub fn set_idempotency_store(&mut self, store: IdempotencyStore) {
+        self.idempotency_store = Some(Mutex::new(store));
+    }
+
+    /// The recorded outcome of an earlier submission under `client_order_id`.
+    pub(crate) fn recorded_submission(&self, client_order_id: &str) -> Option<SubmissionOutcome> {
+        let store = self.idempotency_store.as_ref()?;
+        let store = store.lock().unwrap_or_else(PoisonError::into_inner);
+        store
+            .get(client_order_id)
+            .map(|submission| submission.outcome.clone())
+    }
+
+    /// Records how submitting under `client_order_id` ended, on a best effort basis since the
+    /// submission already happened.
+    pub(crate) fn record_submission<T>(
+        &self,
+        client_order_id: &str,
+        result: &Result<T>,
+        order_id: impl Fn(&T) -> String,
+    ) {
+        let Some(store) = &self.idempotency_store else {
+            return;
+        };
+        let outcome = match result {
+            Ok(order) => SubmissionOutcome::Placed {
+                order_id: order_id(order),
+            },
+            Err(error) if is_rejection(error) => SubmissionOutcome::Rejected {
+                error: format!("{error:#}"),
+            },
+            Err(_) => return,
+        };
+        let recorded = store
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(client_order_id, outcome.clone());
+        if let Err(error) = recorded {
+            self.record_unflushed(
+                "idempotency store",
+                format!("{client_order_id} {outcome:?}"),
+                &error,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::unique_temp_path;
+
+    #[test]
+    fn survives_reopening() -> Result<()> {
+        let path = unique_temp_path("idempotency_reopen.json");
+
+        let mut store = IdempotencyStore::open(&path)?;
+        let placed = SubmissionOutcome::Placed {
+            order_id: "61e69015-8549-4bfd-b9c3-01e75843f47d".into(),
+        };
+        store.record("bot-1", placed.clone())?;
+        drop(store);
+
+        let store = IdempotencyStore::open(&path)?;
+        assert_eq!(
+            store.get("bot-1").map(|submission| &submission.outcome),
+            Some(&placed)
+        );
+        assert!(store.get("bot-2").is_none());
+
+        let expired = IdempotencyStore::open(&path)?.with_retention(TimeDelta::zero());
+        fs::remove_file(&path)?;
+        assert!(expired.get("bot-1").is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::retry::RetryOverride;
+use anyhow::Result;
+use irontrade::api::request::OrderRequest;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderIntent {
+    Entry,
+    Exit,
+    Hedge,
+    Rebalance,
+}
+
+impl Display for OrderIntent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let intent = match self {
+            OrderIntent::Entry => "entry",
+            OrderIntent::Exit => "exit",
+            OrderIntent::Hedge => "hedge",
+            OrderIntent::Rebalance => "rebalance",
+        };
+        f.write_str(intent)
+    }
+}
+
+/// Why an order exists, kept with the tracked order and echoed in fills and round trips.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderAnnotation {
+    pub intent: OrderIntent,
+    pub note: Option<String>,
+}
+
+impl OrderAnnotation {
+    pub fn new(intent: OrderIntent) -> Self {
+        Self { intent, note: None }
+    }
+
+    pub fn with_note(intent: OrderIntent, note: impl Into<String>) -> Self {
+        Self {
+            intent,
+            note: Some(note.into()),
+        }
+    }
+}
+
+impl AlpacaClient {
+    /// Places the order and records `annotation` with it in the order tracker.
+    pub async fn place_order_annotated(&mut self, req: OrderRequest, annotation: OrderAnnotation) -> Result<String> {
+        let order_id = self.place_order_with_retry(req, RetryOverride::Default).await?;
+        self.order_tracker_mut().annotate(&order_id, annotation);
+        Ok(order_id)
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::convert::Order;
+use crate::precision::{Rounding, RoundingMode};
+use crate::replace::OrderChange;
+use anyhow::{Result, bail};
+use irontrade::api::client::IronTradeClient;
+use irontrade::api::common::{Amount, AssetPair, Order as IronTradeOrder, OrderSide};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LadderRung {
+    pub limit_price: Num,
+    pub quantity: Num,
+}
+
+/// An entry or exit split into limit order tranches at different price levels.
+#[derive(Clone, Debug)]
+pub struct LadderRequest {
+    pub asset_pair: AssetPair,
+    pub side: OrderSide,
+    pub rungs: Vec<LadderRung>,
+}
+
+impl LadderRequest {
+    /// Spreads `total_quantity` evenly over `limit_prices`, truncated to `quantity_decimals`
+    /// with the remainder going to the last rung.
+    pub fn evenly(
+        asset_pair: AssetPair,
+        side: OrderSide,
+        total_quantity: &Num,
+        limit_prices: Vec<Num>,
+        quantity_decimals: u32,
+    ) -> Self {
+        let count = limit_prices.len();
+        let rounding = Rounding::new(quantity_decimals, RoundingMode::Down);
+        let tranche = rounding.apply(&(total_quantity / Num::from(count.max(1))));
+
+        let rungs = limit_prices
+            .into_iter()
+            .enumerate()
+            .map(|(index, limit_price)| {
+                let quantity = if index + 1 == count {
+                    total_quantity - &tranche * Num::from(index)
+                } else {
+                    tranche.clone()
+                };
+                LadderRung { limit_price, quantity }
+            })
+            .collect();
+
+        Self {
+            asset_pair,
+            side,
+            rungs,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LadderOrder {
+    pub order_id: String,
+    pub rung: LadderRung,
+}
+
+/// Handle to the orders of a placed ladder, to follow, shift or cancel them as a group.
+#[derive(Clone, Debug)]
+pub struct Ladder {
+    pub asset_pair: AssetPair,
+    pub side: OrderSide,
+    pub orders: Vec<LadderOrder>,
+}
+
+impl AlpacaClient {
+    /// Places one limit order per rung. If a rung fails, the ones already placed are canceled so
+    /// no partial ladder is left behind.
+    pub async fn place_ladder(&mut self, req: LadderRequest) -> Result<Ladder> {
+        if req.rungs.is_empty() {
+            bail!("a ladder needs at least one rung");
+        }
+
+        let mut orders: Vec<LadderOrder> = Vec::with_capacity(req.rungs.len());
+        for rung in req.rungs {
+            let placed = self
+                .place_order(OrderRequest {
+                    asset_pair: req.asset_pair.clone(),
+                    amount: Amount::Quantity {
+                        quantity: rung.quantity.clone(),
+                    },
+                    side: req.side.clone(),
+                    limit_price: Some(rung.limit_price.clone()),
+                })
+                .await;
+
+            match placed {
+                Ok(order_id) => orders.push(LadderOrder { order_id, rung }),
+                Err(error) => {
+                    for order in &orders {
+                        let _ = self.cancel_order(&order.order_id).await;
+                    }
+                    return Err(error.context("failed to place ladder, canceled the rungs already placed"));
+                }
+            }
+        }
+
+        Ok(Ladder {
+            asset_pair: req.asset_pair,
+            side: req.side,
+            orders,
+        })
+    }
+
+    /// Current state of every rung of the ladder.
+    pub async fn get_ladder_orders(&self, ladder: &Ladder) -> Result<Vec<IronTradeOrder>> {
+        let mut orders = Vec::with_capacity(ladder.orders.len());
+        for order in &ladder.orders {
+            let order: Order = self.fetch_apca_order(&order.order_id).await?.into();
+            orders.push(order.0);
+        }
+        Ok(orders)
+    }
+
+    /// Cancels every rung, attempting all of them even when some fail.
+    pub async fn cancel_ladder(&self, ladder: &Ladder) -> Result<()> {
+        let mut failed = Vec::new();
+        for order in &ladder.orders {
+            if let Err(error) = self.cancel_order(&order.order_id).await {
+                failed.push(format!("{}: {error:#}", order.order_id));
+            }
+        }
+
+        if !failed.is_empty() {
+            bail!("failed to cancel ladder rungs: {}", failed.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Moves every rung's limit price by `offset`, replacing the orders and updating the handle
+    /// with the replacement ids.
+    pub async fn shift_ladder(&self, ladder: &mut Ladder, offset: &Num) -> Result<()> {
+        for order in &mut ladder.orders {
+            let limit_price = &order.rung.limit_price + offset;
+            let change = OrderChange {
+                limit_price: Some(limit_price.clone()),
+                ..Default::default()
+            };
+            order.order_id = self.change_order(&order.order_id, change).await?;
+            order.rung.limit_price = limit_price;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn splits_quantity_evenly_with_remainder_on_last_rung() -> Result<()> {
+        let req = LadderRequest::evenly(
+            AssetPair::from_str("BTC/USD")?,
+            OrderSide::Buy,
+            &Num::from(1),
+            vec![Num::from(100), Num::from(99), Num::from(98)],
+            2,
+        );
+
+        let quantities: Vec<Num> = req.rungs.into_iter().map(|rung| rung.quantity).collect();
+        assert_eq!(quantities, vec![Num::new(33, 100), Num::new(33, 100), Num::new(34, 100)]);
+
+        Ok(())
+    }
+}
@@ -1,4 +1,84 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod account;
+pub mod amend;
+pub mod auction;
+pub mod audit;
+pub mod balances;
+pub mod bracket;
+pub mod builder;
+pub mod budget;
 pub mod client;
-mod convert;
\ No newline at end of file
+pub mod clock;
+mod coalesce;
+pub mod config;
+pub mod conflate;
+pub mod cooldown;
+// Public for the benchmarks only
+#[doc(hidden)]
+pub mod convert;
+pub mod corporate_actions;
+pub mod daily_summary;
+pub mod data;
+pub mod deadline;
+pub mod download;
+pub mod env;
+pub mod error;
+pub mod event;
+pub mod event_bus;
+pub mod execution_cost;
+#[cfg(feature = "f64")]
+pub mod float;
+pub mod format;
+pub mod greeks;
+pub mod hedge;
+pub mod http;
+pub mod idempotency;
+pub mod intent;
+pub mod ladder;
+pub mod migration;
+pub mod mock;
+pub mod multi_leg;
+pub mod multi_timeframe;
+pub mod notional_fallback;
+pub mod notify;
+pub mod options;
+pub mod portfolio;
+pub mod order_class;
+pub mod order_defaults;
+pub mod order_fills;
+pub mod order_poller;
+pub mod order_query;
+pub mod order_templates;
+pub mod orderbook;
+pub mod positions;
+pub mod precision;
+pub mod prepared;
+pub mod rate_limit;
+pub mod remediation;
+pub mod replace;
+pub mod restrictions;
+pub mod retry;
+pub mod runner;
+pub mod secrets;
+#[cfg(feature = "serde")]
+mod serde_support;
+pub mod shutdown;
+pub mod spread_guard;
+pub mod stale_data;
+pub mod staging;
+pub mod state;
+pub mod stops;
+pub mod stream;
+pub mod supervisor;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
+pub mod symbol;
+pub mod symbol_stats;
+pub mod trade_log;
+pub mod tracker;
+pub mod trailing;
+pub mod trading_window;
+pub mod transfers;
+pub mod wait;
+pub mod warm_up;
\ No newline at end of file
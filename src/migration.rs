@@ -0,0 +1,302 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::positions::AssetClass;
+use crate::retry::RetryOverride;
+use crate::symbol::{same_symbol, unslashed};
+use anyhow::{Context, Result};
+use apca::api::v2::order::{Amount as ApcaAmount, CreateReqInit, Side, Type};
+use irontrade::api::common::{
+    Amount, OpenPosition as IronTradeOpenPosition, Order as IronTradeOrder, OrderSide, OrderType,
+};
+use num_decimal::Num;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrationStep {
+    /// A market order bringing the target's position in line with the source's
+    Position,
+    /// The remaining quantity of an open order of the source
+    OpenOrder,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlannedOrder {
+    pub step: MigrationStep,
+    pub asset_symbol: String,
+    pub side: OrderSide,
+    pub quantity: Num,
+    pub limit_price: Option<Num>,
+    /// The open order of the source the order copies
+    pub source_order_id: Option<String>,
+}
+
+/// The orders replicating the positions and open orders of one account on another, positions
+/// first. Displaying it gives a preview of what [`AlpacaClient::execute_migration`] submits.
+#[derive(Clone, Debug, Default)]
+pub struct MigrationPlan {
+    pub orders: Vec<PlannedOrder>,
+    /// Open orders of the source that can't be copied, e.g. stop orders whose stop price isn't
+    /// known, by order id with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+impl Display for MigrationPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for order in &self.orders {
+            let side = match order.side {
+                OrderSide::Buy => "buy",
+                OrderSide::Sell => "sell",
+            };
+            write!(f, "{side} {} {}", order.quantity, order.asset_symbol)?;
+            match &order.limit_price {
+                Some(limit_price) => write!(f, " limit {limit_price}")?,
+                None => write!(f, " at market")?,
+            }
+            if let Some(source_order_id) = &order.source_order_id {
+                write!(f, " (copies {source_order_id})")?;
+            }
+            writeln!(f)?;
+        }
+        for (order_id, reason) in &self.skipped {
+            writeln!(f, "skip {order_id}: {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+fn same_side(a: &OrderSide, b: &OrderSide) -> bool {
+    matches!(
+        (a, b),
+        (OrderSide::Buy, OrderSide::Buy) | (OrderSide::Sell, OrderSide::Sell)
+    )
+}
+
+/// Plans the orders that make the target account hold what the source holds. Orders already
+/// open on the target with the same symbol, side, limit price and remaining quantity count as
+/// copied.
+pub fn plan_migration(
+    source_positions: &[IronTradeOpenPosition],
+    source_open_orders: &[IronTradeOrder],
+    target_positions: &[IronTradeOpenPosition],
+    target_open_orders: &[IronTradeOrder],
+) -> MigrationPlan {
+    let mut plan = MigrationPlan::default();
+
+    let mut quantity_changes: BTreeMap<String, (String, Num)> = BTreeMap::new();
+    for (positions, sign) in [(source_positions, 1), (target_positions, -1)] {
+        for position in positions {
+            let (_, change) = quantity_changes
+                .entry(unslashed(&position.asset_symbol).to_uppercase())
+                .or_insert_with(|| (position.asset_symbol.clone(), Num::from(0)));
+            *change += &position.quantity * Num::from(sign);
+        }
+    }
+    for (asset_symbol, change) in quantity_changes.into_values() {
+        if change.is_zero() {
+            continue;
+        }
+        let side = if change.is_positive() {
+            OrderSide::Buy
+        } else {
+            OrderSide::Sell
+        };
+        plan.orders.push(PlannedOrder {
+            step: MigrationStep::Position,
+            asset_symbol,
+            side,
+            quantity: if change.is_negative() {
+                -change
+            } else {
+                change
+            },
+            limit_price: None,
+            source_order_id: None,
+        });
+    }
+
+    let remaining = |order: &IronTradeOrder| match &order.amount {
+        Amount::Quantity { quantity } => Some(quantity - &order.filled_quantity),
+        Amount::Notional { .. } => None,
+    };
+    let mut unmatched: Vec<&IronTradeOrder> = target_open_orders.iter().collect();
+    for order in source_open_orders {
+        if !matches!(order.type_, OrderType::Market | OrderType::Limit) {
+            plan.skipped
+                .push((order.order_id.clone(), "stop orders can't be copied".into()));
+            continue;
+        }
+        let Some(quantity) = remaining(order) else {
+            plan.skipped.push((
+                order.order_id.clone(),
+                "notional orders can't be copied".into(),
+            ));
+            continue;
+        };
+        if !quantity.is_positive() {
+            continue;
+        }
+
+        let copied = unmatched.iter().position(|target| {
+            same_symbol(&target.asset_symbol, &order.asset_symbol)
+                && same_side(&target.side, &order.side)
+                && target.limit_price == order.limit_price
+                && remaining(target).as_ref() == Some(&quantity)
+        });
+        if let Some(index) = copied {
+            unmatched.swap_remove(index);
+            continue;
+        }
+
+        plan.orders.push(PlannedOrder {
+            step: MigrationStep::OpenOrder,
+            asset_symbol: order.asset_symbol.clone(),
+            side: order.side.clone(),
+            quantity,
+            limit_price: order.limit_price.clone(),
+            source_order_id: Some(order.order_id.clone()),
+        });
+    }
+
+    plan
+}
+
+impl AlpacaClient {
+    /// Reads the positions and open orders of `source`, e.g. the paper account a strategy ran
+    /// on, and plans replicating them on this client's account. Nothing is submitted, print the
+    /// plan to preview it. Local state such as tracked orders refers to the source's order ids
+    /// and doesn't carry over.
+    pub async fn plan_migration_from(&self, source: &AlpacaClient) -> Result<MigrationPlan> {
+        let (source_positions, source_open_orders, target_positions, target_open_orders) = tokio::try_join!(
+            source.list_open_positions(),
+            source.list_open_orders(),
+            self.list_open_positions(),
+            self.list_open_orders(),
+        )?;
+        Ok(plan_migration(
+            &source_positions,
+            &source_open_orders,
+            &target_positions,
+            &target_open_orders,
+        ))
+    }
+
+    /// Submits the orders of `plan` in order, with the time in force of the order defaults of
+    /// each asset class, and returns their ids. Stops at the first failure, the error tells how
+    /// many orders went through.
+    pub async fn execute_migration(&self, plan: &MigrationPlan) -> Result<Vec<String>> {
+        let mut order_ids = Vec::with_capacity(plan.orders.len());
+        for order in &plan.orders {
+            let time_in_force = self
+                .order_defaults
+                .for_class(AssetClass::of_symbol(&order.asset_symbol))
+                .time_in_force;
+            let side = match order.side {
+                OrderSide::Buy => Side::Buy,
+                OrderSide::Sell => Side::Sell,
+            };
+            let request = CreateReqInit {
+                type_: if order.limit_price.is_some() {
+                    Type::Limit
+                } else {
+                    Type::Market
+                },
+                limit_price: order.limit_price.clone(),
+                time_in_force: time_in_force.into(),
+                ..Default::default()
+            }
+            .init(
+                order.asset_symbol.clone(),
+                side,
+                ApcaAmount::Quantity {
+                    quantity: order.quantity.clone(),
+                },
+            );
+
+            let order_id = self
+                .submit_order(&order.asset_symbol, &request, RetryOverride::Default)
+                .await
+                .with_context(|| {
+                    format!(
+                        "migration stopped after {} of {} orders",
+                        order_ids.len(),
+                        plan.orders.len()
+                    )
+                })?;
+            order_ids.push(order_id);
+        }
+        Ok(order_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use irontrade::api::common::OrderStatus;
+
+    fn position(asset_symbol: &str, quantity: i64) -> IronTradeOpenPosition {
+        IronTradeOpenPosition {
+            asset_symbol: asset_symbol.into(),
+            average_entry_price: None,
+            quantity: Num::from(quantity),
+            market_value: None,
+        }
+    }
+
+    fn limit_order(order_id: &str, quantity: i64, limit_price: i64) -> IronTradeOrder {
+        IronTradeOrder {
+            order_id: order_id.into(),
+            asset_symbol: "AAPL".into(),
+            amount: Amount::Quantity {
+                quantity: Num::from(quantity),
+            },
+            limit_price: Some(Num::from(limit_price)),
+            filled_quantity: Num::from(0),
+            average_fill_price: None,
+            status: OrderStatus::New,
+            type_: OrderType::Limit,
+            side: OrderSide::Buy,
+        }
+    }
+
+    #[test]
+    fn plans_the_difference() {
+        let plan = plan_migration(
+            &[position("AAPL", 10), position("BTCUSD", 2)],
+            &[
+                limit_order("source-1", 5, 180),
+                limit_order("source-2", 5, 170),
+            ],
+            &[
+                position("AAPL", 4),
+                position("MSFT", 3),
+                position("BTC/USD", 2),
+            ],
+            &[limit_order("target-1", 5, 170)],
+        );
+
+        let orders: Vec<_> = plan
+            .orders
+            .iter()
+            .map(|order| {
+                (
+                    order.step,
+                    order.asset_symbol.as_str(),
+                    matches!(order.side, OrderSide::Buy),
+                    order.quantity.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            orders,
+            [
+                (MigrationStep::Position, "AAPL", true, Num::from(6)),
+                (MigrationStep::Position, "MSFT", false, Num::from(3)),
+                (MigrationStep::OpenOrder, "AAPL", true, Num::from(5)),
+            ]
+        );
+        assert_eq!(plan.orders[2].source_order_id.as_deref(), Some("source-1"));
+        assert!(plan.to_string().starts_with("buy 6 AAPL at market\n"));
+    }
+}
@@ -0,0 +1,441 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::error::AlpacaError;
+use crate::precision::{Rounding, RoundingMode};
+use crate::symbol::same_symbol;
+use anyhow::{Result, bail};
+use irontrade::api::client::IronTradeClient;
+use irontrade::api::common::{
+    Amount, OpenPosition as IronTradeOpenPosition, Order as IronTradeOrder, OrderSide, OrderStatus, OrderType,
+};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 64;
+
+const PARTIAL_FILL_ROUNDING: Rounding = Rounding::new(6, RoundingMode::Down);
+
+/// Broker misbehavior injected by [`MockClient`]. Rates are probabilities between 0 and 1, and
+/// the same seed replays the same sequence of faults.
+#[derive(Clone, Debug, Default)]
+pub struct FaultConfig {
+    /// Every call is delayed by a random latency within `[min_latency, max_latency]`
+    pub min_latency: Duration,
+    pub max_latency: Duration,
+    pub reject_rate: f64,
+    /// Fills executing only part of the remaining quantity
+    pub partial_fill_rate: f64,
+    /// Calls dropping the connection, every call fails until `reconnect_after` elapsed
+    pub disconnect_rate: f64,
+    pub reconnect_after: Duration,
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockEvent {
+    Rejected { order_id: String },
+    PartiallyFilled { order_id: String, filled_quantity: Num },
+    Filled { order_id: String },
+    Disconnected,
+    Reconnected,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MockDisconnected;
+
+impl Display for MockDisconnected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("mock broker connection lost")
+    }
+}
+
+impl std::error::Error for MockDisconnected {}
+
+/// Xorshift generator, so fault sequences are reproducible from the seed.
+struct FaultRng(u64);
+
+impl FaultRng {
+    fn new(seed: u64) -> Self {
+        // An all zero state would only ever produce zeros
+        Self(seed.max(1))
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, rate: f64) -> bool {
+        rate > 0.0 && self.next_f64() < rate
+    }
+}
+
+struct MockPosition {
+    quantity: Num,
+    cost_basis: Num,
+}
+
+struct MockState {
+    cash: Num,
+    prices: BTreeMap<String, Num>,
+    positions: BTreeMap<String, MockPosition>,
+    orders: Vec<IronTradeOrder>,
+    next_order_id: u64,
+    rng: FaultRng,
+    disconnected_until: Option<Instant>,
+}
+
+impl MockState {
+    /// Fills whatever the current price allows of the order at `index`.
+    fn execute(&mut self, index: usize, partial_fill_rate: f64) -> Option<MockEvent> {
+        let order = &mut self.orders[index];
+        let price = self.prices.get(&order.asset_symbol)?.clone();
+        let marketable = match (&order.limit_price, &order.side) {
+            (None, _) => true,
+            (Some(limit_price), OrderSide::Buy) => price <= *limit_price,
+            (Some(limit_price), OrderSide::Sell) => price >= *limit_price,
+        };
+        if !marketable {
+            return None;
+        }
+
+        let total = match &order.amount {
+            Amount::Quantity { quantity } => quantity.clone(),
+            Amount::Notional { notional } => notional / &price,
+        };
+        let remaining = &total - &order.filled_quantity;
+        let mut quantity = remaining.clone();
+        if self.rng.chance(partial_fill_rate) {
+            let percent = (self.rng.next_f64() * 99.0) as i64 + 1;
+            let partial = PARTIAL_FILL_ROUNDING.apply(&(&remaining * Num::new(percent, 100)));
+            if partial.is_positive() {
+                quantity = partial;
+            }
+        }
+
+        let previous_cost = match &order.average_fill_price {
+            Some(average_fill_price) => average_fill_price * &order.filled_quantity,
+            None => Num::from(0),
+        };
+        order.filled_quantity += &quantity;
+        order.average_fill_price = Some((previous_cost + &price * &quantity) / &order.filled_quantity);
+        order.status = if order.filled_quantity >= total {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+
+        let signed_quantity = match order.side {
+            OrderSide::Buy => quantity,
+            OrderSide::Sell => -quantity,
+        };
+        self.cash -= &signed_quantity * &price;
+
+        let position = self.positions.entry(order.asset_symbol.clone()).or_insert(MockPosition {
+            quantity: Num::from(0),
+            cost_basis: Num::from(0),
+        });
+        let quantity = &position.quantity + &signed_quantity;
+        let flipped = quantity.is_positive() != position.quantity.is_positive();
+        position.cost_basis = if position.quantity.is_zero() || flipped {
+            // Opened or flipped, the rest is at this price
+            &quantity * &price
+        } else if signed_quantity.is_positive() == position.quantity.is_positive() {
+            &position.cost_basis + &signed_quantity * &price
+        } else {
+            // Reducing keeps the average entry price
+            &position.cost_basis * &quantity / &position.quantity
+        };
+        position.quantity = quantity;
+        if position.quantity.is_zero() {
+            self.positions.remove(&order.asset_symbol);
+        }
+
+        let order_id = order.order_id.clone();
+        Some(match order.status {
+            OrderStatus::Filled => MockEvent::Filled { order_id },
+            _ => MockEvent::PartiallyFilled {
+                order_id,
+                filled_quantity: order.filled_quantity.clone(),
+            },
+        })
+    }
+}
+
+/// In-memory broker implementing [`IronTradeClient`], to stress test strategies against
+/// latency, rejects, partial fills and disconnects before they touch the paper API. Orders fill
+/// at the price last set for their symbol, limit orders once the price crosses their limit.
+pub struct MockClient {
+    faults: FaultConfig,
+    state: Mutex<MockState>,
+    sender: broadcast::Sender<MockEvent>,
+}
+
+impl MockClient {
+    pub fn new(cash: Num, faults: FaultConfig) -> Self {
+        let rng = FaultRng::new(faults.seed);
+        Self {
+            faults,
+            state: Mutex::new(MockState {
+                cash,
+                prices: BTreeMap::new(),
+                positions: BTreeMap::new(),
+                orders: Vec::new(),
+                next_order_id: 0,
+                rng,
+                disconnected_until: None,
+            }),
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<MockEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Moves the market price of `asset_symbol` and fills the resting orders it crosses.
+    pub fn set_price(&self, asset_symbol: &str, price: Num) {
+        let mut state = self.state();
+        state.prices.insert(asset_symbol.to_string(), price);
+
+        let resting: Vec<usize> = state
+            .orders
+            .iter()
+            .enumerate()
+            .filter(|(_, order)| order.asset_symbol == asset_symbol)
+            .filter(|(_, order)| matches!(order.status, OrderStatus::New | OrderStatus::PartiallyFilled))
+            .map(|(index, _)| index)
+            .collect();
+        let events: Vec<MockEvent> = resting
+            .into_iter()
+            .filter_map(|index| state.execute(index, self.faults.partial_fill_rate))
+            .collect();
+        drop(state);
+
+        for event in events {
+            self.emit(event);
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn emit(&self, event: MockEvent) {
+        // Nobody listening isn't an error
+        let _ = self.sender.send(event);
+    }
+
+    /// Applies the injected latency and connection faults ahead of every call.
+    async fn simulate_call(&self) -> Result<()> {
+        let latency = {
+            let mut state = self.state();
+            let spread = self.faults.max_latency.saturating_sub(self.faults.min_latency);
+            self.faults.min_latency + spread.mul_f64(state.rng.next_f64())
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        let mut state = self.state();
+        let now = Instant::now();
+        let event = match state.disconnected_until {
+            Some(until) if now < until => return Err(MockDisconnected.into()),
+            Some(_) => {
+                state.disconnected_until = None;
+                Some(MockEvent::Reconnected)
+            }
+            None => None,
+        };
+        let disconnect = state.rng.chance(self.faults.disconnect_rate);
+        if disconnect {
+            state.disconnected_until = Some(now + self.faults.reconnect_after);
+        }
+        drop(state);
+
+        if let Some(event) = event {
+            self.emit(event);
+        }
+        if disconnect {
+            self.emit(MockEvent::Disconnected);
+            return Err(MockDisconnected.into());
+        }
+        Ok(())
+    }
+}
+
+impl IronTradeClient for MockClient {
+    async fn place_order(&mut self, req: OrderRequest) -> Result<String> {
+        self.simulate_call().await?;
+
+        let mut state = self.state();
+        state.next_order_id += 1;
+        let order_id = format!("mock-{}", state.next_order_id);
+
+        if state.rng.chance(self.faults.reject_rate) {
+            drop(state);
+            self.emit(MockEvent::Rejected {
+                order_id: order_id.clone(),
+            });
+            bail!("mock broker rejected order {order_id}");
+        }
+
+        state.orders.push(IronTradeOrder {
+            order_id: order_id.clone(),
+            asset_symbol: req.asset_pair.to_string(),
+            filled_quantity: Num::from(0),
+            amount: req.amount,
+            average_fill_price: None,
+            status: OrderStatus::New,
+            type_: if req.limit_price.is_some() {
+                OrderType::Limit
+            } else {
+                OrderType::Market
+            },
+            limit_price: req.limit_price,
+            side: req.side,
+        });
+        let index = state.orders.len() - 1;
+        let event = state.execute(index, self.faults.partial_fill_rate);
+        drop(state);
+
+        if let Some(event) = event {
+            self.emit(event);
+        }
+        Ok(order_id)
+    }
+
+    async fn get_orders(&self) -> Result<Vec<IronTradeOrder>> {
+        self.simulate_call().await?;
+        Ok(self.state().orders.clone())
+    }
+
+    async fn get_buying_power(&self) -> Result<Num> {
+        self.simulate_call().await?;
+        Ok(self.state().cash.clone())
+    }
+
+    async fn get_cash(&self) -> Result<Num> {
+        self.simulate_call().await?;
+        Ok(self.state().cash.clone())
+    }
+
+    async fn get_open_position(&self, asset_symbol: &str) -> Result<IronTradeOpenPosition> {
+        self.simulate_call().await?;
+
+        let state = self.state();
+        let (symbol, position) = state
+            .positions
+            .iter()
+            .find(|(symbol, _)| same_symbol(symbol, asset_symbol))
+            .ok_or_else(|| {
+                anyhow::Error::new(AlpacaError::PositionNotFound)
+                    .context(format!("no open position for {asset_symbol}"))
+            })?;
+
+        Ok(IronTradeOpenPosition {
+            asset_symbol: symbol.clone(),
+            average_entry_price: Some(&position.cost_basis / &position.quantity),
+            quantity: position.quantity.clone(),
+            market_value: state.prices.get(symbol).map(|price| price * &position.quantity),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use irontrade::api::common::AssetPair;
+    use std::str::FromStr;
+
+    fn buy(quantity: i64, limit_price: Option<Num>) -> Result<OrderRequest> {
+        Ok(OrderRequest {
+            asset_pair: AssetPair::from_str("BTC/USD")?,
+            amount: Amount::Quantity {
+                quantity: Num::from(quantity),
+            },
+            side: OrderSide::Buy,
+            limit_price,
+        })
+    }
+
+    #[tokio::test]
+    async fn fills_at_the_set_price() -> Result<()> {
+        let mut client = MockClient::new(Num::from(1000), FaultConfig::default());
+        client.set_price("BTC/USD", Num::from(100));
+
+        client.place_order(buy(2, None)?).await?;
+        client.place_order(buy(1, Some(Num::from(90)))?).await?;
+
+        assert_eq!(client.get_cash().await?, Num::from(800));
+        let orders = client.get_orders().await?;
+        assert!(matches!(orders[0].status, OrderStatus::Filled));
+        assert!(matches!(orders[1].status, OrderStatus::New));
+
+        client.set_price("BTC/USD", Num::from(90));
+
+        let position = client.get_open_position("BTCUSD").await?;
+        assert_eq!(position.quantity, Num::from(3));
+        assert_eq!(position.average_entry_price, Some(Num::new(290, 3)));
+        let flat = client.get_open_position("ETHUSD").await.unwrap_err();
+        assert_eq!(AlpacaError::classify(&flat), AlpacaError::PositionNotFound);
+        assert_eq!(client.get_cash().await?, Num::from(710));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn injects_rejects_and_partial_fills() -> Result<()> {
+        let mut rejecting = MockClient::new(
+            Num::from(1000),
+            FaultConfig {
+                reject_rate: 1.0,
+                ..Default::default()
+            },
+        );
+        let mut events = rejecting.subscribe_events();
+        assert!(rejecting.place_order(buy(1, None)?).await.is_err());
+        assert!(matches!(events.recv().await?, MockEvent::Rejected { .. }));
+
+        let mut partial = MockClient::new(
+            Num::from(1000),
+            FaultConfig {
+                partial_fill_rate: 1.0,
+                ..Default::default()
+            },
+        );
+        partial.set_price("BTC/USD", Num::from(100));
+        partial.place_order(buy(1, None)?).await?;
+
+        let order = &partial.get_orders().await?[0];
+        assert!(matches!(order.status, OrderStatus::PartiallyFilled));
+        assert!(order.filled_quantity < Num::from(1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_calls_until_reconnected() -> Result<()> {
+        let client = MockClient::new(
+            Num::from(1000),
+            FaultConfig {
+                disconnect_rate: 1.0,
+                reconnect_after: Duration::from_secs(60),
+                ..Default::default()
+            },
+        );
+
+        let error = client.get_cash().await.unwrap_err();
+        assert!(error.downcast_ref::<MockDisconnected>().is_some());
+        assert!(client.get_cash().await.is_err());
+
+        Ok(())
+    }
+}
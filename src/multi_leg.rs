@@ -0,0 +1,336 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::options::{OptionContract, OptionRight};
+use anyhow::Result;
+use irontrade::api::client::IronTradeClient;
+use irontrade::api::common::OrderSide;
+use num_decimal::Num;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Alpaca accepts at most this many legs in a single multi-leg order.
+const MAX_LEGS: usize = 4;
+const CONTRACT_MULTIPLIER: i64 = 100;
+
+pub struct OptionLeg {
+    pub symbol: String,
+    pub side: OrderSide,
+    pub ratio_quantity: u32,
+}
+
+pub struct MultiLegOrderRequest {
+    pub legs: Vec<OptionLeg>,
+    /// Number of spread units, each leg trades `quantity * ratio_quantity` contracts
+    pub quantity: Num,
+    /// Net price per spread unit, positive for a debit and negative for a credit
+    pub limit_price: Option<Num>,
+    /// Calendar and diagonal spreads combine legs with different expirations
+    pub allow_mixed_expirations: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MultiLegValidationError {
+    LegCount { count: usize },
+    InvalidQuantity,
+    InvalidSymbol { leg: usize, symbol: String },
+    DuplicateLeg { leg: usize, symbol: String },
+    ZeroRatio { leg: usize },
+    RatioNotReduced { gcd: u32 },
+    UnderlyingMismatch { leg: usize, expected: String, found: String },
+    ExpirationMismatch { leg: usize, symbol: String },
+    /// The spread loses without bound as the underlying rises, caused by this uncovered short call
+    UncoveredShortCall { leg: usize, symbol: String },
+    InsufficientBuyingPower { required: Num, available: Num },
+}
+
+impl Display for MultiLegValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LegCount { count } => write!(f, "multi-leg orders need 2 to {MAX_LEGS} legs, got {count}"),
+            Self::InvalidQuantity => write!(f, "multi-leg order quantity must be positive"),
+            Self::InvalidSymbol { leg, symbol } => write!(f, "leg {leg}: {symbol} is not an option contract"),
+            Self::DuplicateLeg { leg, symbol } => write!(f, "leg {leg}: {symbol} appears in more than one leg"),
+            Self::ZeroRatio { leg } => write!(f, "leg {leg}: ratio quantity must be at least 1"),
+            Self::RatioNotReduced { gcd } => {
+                write!(f, "leg ratios must be in simplest form, all are divisible by {gcd}")
+            }
+            Self::UnderlyingMismatch { leg, expected, found } => {
+                write!(f, "leg {leg}: underlying {found} doesn't match {expected}")
+            }
+            Self::ExpirationMismatch { leg, symbol } => {
+                write!(f, "leg {leg}: {symbol} expires on a different date than the first leg")
+            }
+            Self::UncoveredShortCall { leg, symbol } => {
+                write!(f, "leg {leg}: short call {symbol} is uncovered, risk is unbounded")
+            }
+            Self::InsufficientBuyingPower { required, available } => {
+                write!(f, "order requires {required} buying power but only {available} is available")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MultiLegValidationError {}
+
+impl MultiLegOrderRequest {
+    /// Validates the legs and returns the estimated buying power the order will tie up, which is
+    /// the worst loss at expiry plus any net debit paid.
+    pub fn validate(&self) -> Result<Num, MultiLegValidationError> {
+        if !(2..=MAX_LEGS).contains(&self.legs.len()) {
+            return Err(MultiLegValidationError::LegCount { count: self.legs.len() });
+        }
+
+        if !self.quantity.is_positive() {
+            return Err(MultiLegValidationError::InvalidQuantity);
+        }
+
+        let mut contracts: Vec<OptionContract> = Vec::with_capacity(self.legs.len());
+
+        for (leg, order_leg) in self.legs.iter().enumerate() {
+            let contract = OptionContract::from_str(&order_leg.symbol).map_err(|_| {
+                MultiLegValidationError::InvalidSymbol {
+                    leg,
+                    symbol: order_leg.symbol.clone(),
+                }
+            })?;
+
+            if order_leg.ratio_quantity == 0 {
+                return Err(MultiLegValidationError::ZeroRatio { leg });
+            }
+
+            if self.legs[..leg].iter().any(|other| other.symbol == order_leg.symbol) {
+                return Err(MultiLegValidationError::DuplicateLeg {
+                    leg,
+                    symbol: order_leg.symbol.clone(),
+                });
+            }
+
+            if let Some(first) = contracts.first() {
+                if first.underlying != contract.underlying {
+                    return Err(MultiLegValidationError::UnderlyingMismatch {
+                        leg,
+                        expected: first.underlying.clone(),
+                        found: contract.underlying,
+                    });
+                }
+                if !self.allow_mixed_expirations && first.expiration != contract.expiration {
+                    return Err(MultiLegValidationError::ExpirationMismatch {
+                        leg,
+                        symbol: order_leg.symbol.clone(),
+                    });
+                }
+            }
+
+            contracts.push(contract);
+        }
+
+        let gcd = self
+            .legs
+            .iter()
+            .fold(0, |gcd, leg| greatest_common_divisor(gcd, leg.ratio_quantity));
+        if gcd > 1 {
+            return Err(MultiLegValidationError::RatioNotReduced { gcd });
+        }
+
+        let max_loss = self.max_loss_at_expiry(&contracts)?;
+        let debit = match &self.limit_price {
+            Some(limit_price) => limit_price * &self.quantity * Num::from(CONTRACT_MULTIPLIER),
+            None => Num::from(0),
+        };
+        let required = max_loss + debit;
+
+        Ok(if required.is_negative() { Num::from(0) } else { required })
+    }
+
+    fn signed_contracts(&self, leg: &OptionLeg) -> Num {
+        let contracts = &self.quantity * Num::from(leg.ratio_quantity);
+        match leg.side {
+            OrderSide::Buy => contracts,
+            OrderSide::Sell => -contracts,
+        }
+    }
+
+    /// The payoff is piecewise linear in the underlying price with kinks at the strikes, so the
+    /// worst case is either at a strike, at zero, or unbounded as the price rises.
+    fn max_loss_at_expiry(&self, contracts: &[OptionContract]) -> Result<Num, MultiLegValidationError> {
+        let call_slope = self
+            .legs
+            .iter()
+            .zip(contracts)
+            .filter(|(_, contract)| contract.right == OptionRight::Call)
+            .fold(Num::from(0), |slope, (leg, _)| slope + self.signed_contracts(leg));
+
+        if call_slope.is_negative() {
+            let (leg, order_leg) = self
+                .legs
+                .iter()
+                .enumerate()
+                .find(|(leg, order_leg)| {
+                    matches!(order_leg.side, OrderSide::Sell) && contracts[*leg].right == OptionRight::Call
+                })
+                .expect("a negative call slope requires a short call");
+
+            return Err(MultiLegValidationError::UncoveredShortCall {
+                leg,
+                symbol: order_leg.symbol.clone(),
+            });
+        }
+
+        let payoff_at = |price: &Num| {
+            self.legs
+                .iter()
+                .zip(contracts)
+                .fold(Num::from(0), |payoff, (leg, contract)| {
+                    let intrinsic = match contract.right {
+                        OptionRight::Call => price - &contract.strike,
+                        OptionRight::Put => &contract.strike - price,
+                    };
+                    if intrinsic.is_positive() {
+                        payoff + intrinsic * self.signed_contracts(leg)
+                    } else {
+                        payoff
+                    }
+                })
+        };
+
+        let worst_payoff = contracts
+            .iter()
+            .map(|contract| payoff_at(&contract.strike))
+            .chain([payoff_at(&Num::from(0))])
+            .min()
+            .unwrap();
+
+        Ok(if worst_payoff.is_negative() {
+            -worst_payoff * Num::from(CONTRACT_MULTIPLIER)
+        } else {
+            Num::from(0)
+        })
+    }
+}
+
+fn greatest_common_divisor(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { greatest_common_divisor(b, a % b) }
+}
+
+impl AlpacaClient {
+    /// Validates a multi-leg order locally and checks its estimated requirement against the
+    /// account buying power. Validation failures are returned as [`MultiLegValidationError`].
+    pub async fn validate_multi_leg_order(&self, req: &MultiLegOrderRequest) -> Result<Num> {
+        let required = req.validate()?;
+        let available = self.get_buying_power().await?;
+
+        if required > available {
+            return Err(MultiLegValidationError::InsufficientBuyingPower { required, available }.into());
+        }
+
+        Ok(required)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bull_call_spread_requires_debit_only() {
+        let req = request(
+            vec![
+                leg("AAPL240119C00150000", OrderSide::Buy, 1),
+                leg("AAPL240119C00155000", OrderSide::Sell, 1),
+            ],
+            Some(Num::new(3, 2)),
+        );
+
+        assert_eq!(req.validate(), Ok(Num::from(150)));
+    }
+
+    #[test]
+    fn bull_put_credit_spread_requires_width_minus_credit() {
+        let req = request(
+            vec![
+                leg("AAPL240119P00150000", OrderSide::Sell, 1),
+                leg("AAPL240119P00145000", OrderSide::Buy, 1),
+            ],
+            Some(Num::from(-1)),
+        );
+
+        assert_eq!(req.validate(), Ok(Num::from(400)));
+    }
+
+    #[test]
+    fn rejects_uncovered_ratio_call_spread() {
+        let req = request(
+            vec![
+                leg("AAPL240119C00150000", OrderSide::Buy, 1),
+                leg("AAPL240119C00155000", OrderSide::Sell, 2),
+            ],
+            None,
+        );
+
+        assert_eq!(
+            req.validate(),
+            Err(MultiLegValidationError::UncoveredShortCall {
+                leg: 1,
+                symbol: "AAPL240119C00155000".into()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_underlying_and_expiration() {
+        let req = request(
+            vec![
+                leg("AAPL240119C00150000", OrderSide::Buy, 1),
+                leg("MSFT240119C00155000", OrderSide::Sell, 1),
+            ],
+            None,
+        );
+        assert!(matches!(
+            req.validate(),
+            Err(MultiLegValidationError::UnderlyingMismatch { leg: 1, .. })
+        ));
+
+        let req = request(
+            vec![
+                leg("AAPL240119C00150000", OrderSide::Buy, 1),
+                leg("AAPL240216C00155000", OrderSide::Sell, 1),
+            ],
+            None,
+        );
+        assert!(matches!(
+            req.validate(),
+            Err(MultiLegValidationError::ExpirationMismatch { leg: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unreduced_ratios() {
+        let req = request(
+            vec![
+                leg("AAPL240119C00150000", OrderSide::Buy, 2),
+                leg("AAPL240119C00155000", OrderSide::Sell, 2),
+            ],
+            None,
+        );
+
+        assert_eq!(req.validate(), Err(MultiLegValidationError::RatioNotReduced { gcd: 2 }));
+    }
+
+    fn request(legs: Vec<OptionLeg>, limit_price: Option<Num>) -> MultiLegOrderRequest {
+        MultiLegOrderRequest {
+            legs,
+            quantity: Num::from(1),
+            limit_price,
+            allow_mixed_expirations: false,
+        }
+    }
+
+    fn leg(symbol: &str, side: OrderSide, ratio_quantity: u32) -> OptionLeg {
+        OptionLeg {
+            symbol: symbol.into(),
+            side,
+            ratio_quantity,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::data::{Bar, TimeFrame};
+use crate::stream::{MarketDataEvent, MarketDataFeed, MarketDataSubscription};
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::{Stream, StreamExt};
+use num_decimal::Num;
+use std::collections::HashMap;
+
+/// A bar of `time_frame` that just closed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BarClose {
+    pub time_frame: TimeFrame,
+    pub bar: Bar,
+}
+
+/// Start of the `time_frame` bucket containing `time`, buckets being aligned to the Unix epoch
+/// in UTC, so daily bars run from midnight UTC.
+fn bucket_start(time: DateTime<Utc>, time_frame: TimeFrame) -> DateTime<Utc> {
+    let length = time_frame.duration().num_seconds();
+    let seconds = time.timestamp();
+    DateTime::from_timestamp(seconds - seconds.rem_euclid(length), 0).unwrap_or(time)
+}
+
+/// A bar being built from minute bars, with the volume weighted price sum for its vwap.
+#[derive(Debug)]
+struct PartialBar {
+    bar: Bar,
+    /// `None` once a minute bar without a vwap was added
+    price_volume: Option<Num>,
+}
+
+impl PartialBar {
+    fn new(bar: &Bar, start: DateTime<Utc>) -> Self {
+        Self {
+            price_volume: bar.vwap.as_ref().map(|vwap| vwap * &bar.volume),
+            bar: Bar {
+                time: start,
+                ..bar.clone()
+            },
+        }
+    }
+
+    fn add(&mut self, bar: &Bar) {
+        if bar.high > self.bar.high {
+            self.bar.high = bar.high.clone();
+        }
+        if bar.low < self.bar.low {
+            self.bar.low = bar.low.clone();
+        }
+        self.bar.close = bar.close.clone();
+        self.bar.volume += &bar.volume;
+        self.price_volume = match (self.price_volume.take(), &bar.vwap) {
+            (Some(price_volume), Some(vwap)) => Some(price_volume + vwap * &bar.volume),
+            _ => None,
+        };
+    }
+
+    fn close(mut self, time_frame: TimeFrame) -> BarClose {
+        self.bar.vwap = self
+            .price_volume
+            .filter(|_| self.bar.volume.is_positive())
+            .map(|price_volume| price_volume / &self.bar.volume);
+        BarClose {
+            time_frame,
+            bar: self.bar,
+        }
+    }
+}
+
+/// Resamples the minute bars of each symbol to the timeframes subscribed for it. Minute bars
+/// are passed on as they are when subscribed, longer bars close with the last minute of their
+/// bucket, or with the first minute bar of a later bucket when minutes are missing.
+#[derive(Debug)]
+pub struct MultiTimeFrameBars {
+    time_frames: HashMap<String, Vec<TimeFrame>>,
+    partial: HashMap<(String, TimeFrame), PartialBar>,
+}
+
+impl MultiTimeFrameBars {
+    pub fn new(subscriptions: HashMap<String, Vec<TimeFrame>>) -> Self {
+        let time_frames = subscriptions
+            .into_iter()
+            .map(|(symbol, mut time_frames)| {
+                time_frames.sort_by_key(|time_frame| time_frame.duration());
+                time_frames.dedup();
+                (symbol, time_frames)
+            })
+            .collect();
+        Self {
+            time_frames,
+            partial: HashMap::new(),
+        }
+    }
+
+    pub fn symbols(&self) -> Vec<String> {
+        self.time_frames.keys().cloned().collect()
+    }
+
+    /// Adds a minute bar and returns the bars it closed, earliest close first and shorter
+    /// timeframes first among bars closing together.
+    pub fn push(&mut self, bar: Bar) -> Vec<BarClose> {
+        let Some(time_frames) = self.time_frames.get(&bar.symbol) else {
+            return Vec::new();
+        };
+
+        let mut stale = Vec::new();
+        let mut closed = Vec::new();
+        for &time_frame in time_frames {
+            if time_frame == TimeFrame::OneMinute {
+                closed.push(BarClose {
+                    time_frame,
+                    bar: bar.clone(),
+                });
+                continue;
+            }
+
+            let start = bucket_start(bar.time, time_frame);
+            let key = (bar.symbol.clone(), time_frame);
+            if self
+                .partial
+                .get(&key)
+                .is_some_and(|partial| partial.bar.time != start)
+                && let Some(partial) = self.partial.remove(&key)
+            {
+                stale.push(partial.close(time_frame));
+            }
+            self.partial
+                .entry(key.clone())
+                .and_modify(|partial| partial.add(&bar))
+                .or_insert_with(|| PartialBar::new(&bar, start));
+
+            if bar.time + TimeDelta::minutes(1) >= start + time_frame.duration()
+                && let Some(partial) = self.partial.remove(&key)
+            {
+                closed.push(partial.close(time_frame));
+            }
+        }
+
+        stale.sort_by_key(|close| close.bar.time + close.time_frame.duration());
+        stale.extend(closed);
+        stale
+    }
+}
+
+impl AlpacaClient {
+    /// Streams the minute bars of each symbol and the bars resampled from them, see
+    /// [`MultiTimeFrameBars`], so a strategy gets the closes of all its timeframes in order from
+    /// one stream. Bars missed while the connection was down are not backfilled.
+    pub async fn stream_multi_timeframe_bars(
+        &self,
+        feed: MarketDataFeed,
+        subscriptions: HashMap<String, Vec<TimeFrame>>,
+    ) -> Result<impl Stream<Item = BarClose> + '_> {
+        let mut bars = MultiTimeFrameBars::new(subscriptions);
+        let subscription = MarketDataSubscription {
+            bars: bars.symbols(),
+            ..Default::default()
+        };
+        let events = self.stream_market_data(feed, &subscription).await?;
+
+        Ok(events.flat_map(move |event| {
+            let closed = match event {
+                MarketDataEvent::Bar(bar) => bars.push(bar),
+                _ => Vec::new(),
+            };
+            futures::stream::iter(closed)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minute_bar(minute: i64, close: i64) -> Bar {
+        let open_time = DateTime::from_timestamp(1_705_590_000, 0).unwrap();
+        Bar {
+            symbol: "AAPL".into(),
+            exchange: None,
+            time: open_time + TimeDelta::minutes(minute),
+            open: Num::from(close),
+            high: Num::from(close + 1),
+            low: Num::from(close - 1),
+            close: Num::from(close),
+            volume: Num::from(10),
+            vwap: Some(Num::from(close)),
+        }
+    }
+
+    #[test]
+    fn resamples_and_orders_closes() {
+        let mut bars = MultiTimeFrameBars::new(HashMap::from([(
+            "AAPL".to_string(),
+            vec![
+                TimeFrame::OneHour,
+                TimeFrame::FiveMinutes,
+                TimeFrame::OneMinute,
+            ],
+        )]));
+
+        for minute in 0..4 {
+            let closed = bars.push(minute_bar(minute, 100 + minute));
+            assert_eq!(closed.len(), 1);
+        }
+        let closed = bars.push(minute_bar(4, 104));
+        let time_frames: Vec<_> = closed.iter().map(|close| close.time_frame).collect();
+        assert_eq!(time_frames, [TimeFrame::OneMinute, TimeFrame::FiveMinutes]);
+        let five_minutes = &closed[1].bar;
+        assert_eq!(five_minutes.open, Num::from(100));
+        assert_eq!(five_minutes.high, Num::from(105));
+        assert_eq!(five_minutes.close, Num::from(104));
+        assert_eq!(five_minutes.volume, Num::from(50));
+        assert_eq!(five_minutes.vwap, Some(Num::from(102)));
+
+        // Minutes 5 to 7 are missing, the open five minute bar closes before minute 11 is passed on
+        bars.push(minute_bar(5, 105));
+        let closed = bars.push(minute_bar(11, 111));
+        let time_frames: Vec<_> = closed.iter().map(|close| close.time_frame).collect();
+        assert_eq!(time_frames, [TimeFrame::FiveMinutes, TimeFrame::OneMinute]);
+        assert_eq!(closed[0].bar.volume, Num::from(10));
+    }
+}
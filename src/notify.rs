@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use anyhow::{Result, bail};
+use serde_json::json;
+
+/// Delivers messages meant for the operator, such as the daily summary.
+pub trait Notifier {
+    fn notify(&self, subject: &str, body: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Posts messages as `{"text": ...}` JSON, the format Slack and most chat webhooks accept.
+#[derive(Clone, Debug)]
+pub struct WebhookNotifier {
+    url: String,
+    http_client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, subject: &str, body: &str) -> Result<()> {
+        let response = self
+            .http_client
+            .post(&self.url)
+            .json(&json!({ "text": format!("*{subject}*\n{body}") }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            bail!("notification webhook failed with {status}");
+        }
+        Ok(())
+    }
+}
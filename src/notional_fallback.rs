@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::data::Quote;
+use crate::positions::AssetClass;
+use anyhow::{Result, anyhow, bail};
+use irontrade::api::common::{Amount, OrderSide};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 64;
+
+fn default_min_notional() -> Num {
+    Num::from(1)
+}
+
+/// Submits crypto quantity orders below Alpaca's minimum order size as market orders for the
+/// same value instead, priced at the latest far touch.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NotionalFallback {
+    /// Smallest quantity Alpaca accepts per pair, e.g. `"BTC/USD" = "0.0001"`
+    pub min_quantities: HashMap<String, Num>,
+    /// Smallest order value Alpaca accepts, in USD
+    #[serde(default = "default_min_notional")]
+    pub min_notional: Num,
+    /// How much worse than the order's limit price, or the mid without one, the touch it gets
+    /// converted at may be
+    pub max_slippage_bps: Num,
+}
+
+/// A quantity order that went out as a notional order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NotionalConversion {
+    pub asset_symbol: String,
+    pub quantity: Num,
+    pub notional: Num,
+    /// The touch the notional was computed at
+    pub price: Num,
+}
+
+impl NotionalFallback {
+    /// The notional to submit a `quantity` order as, `None` when the quantity is accepted as is.
+    pub fn notional_for(
+        &self,
+        symbol: &str,
+        side: &OrderSide,
+        quantity: &Num,
+        limit_price: Option<&Num>,
+        quote: &Quote,
+    ) -> Result<Option<NotionalConversion>> {
+        let below_minimum = self
+            .min_quantities
+            .get(symbol)
+            .is_some_and(|min_quantity| quantity < min_quantity);
+        if !below_minimum {
+            return Ok(None);
+        }
+
+        let (price, slippage) = match side {
+            OrderSide::Buy => (&quote.ask_price, &quote.ask_price - &quote.bid_price),
+            OrderSide::Sell => (&quote.bid_price, &quote.ask_price - &quote.bid_price),
+        };
+        if !price.is_positive() {
+            bail!("no price to convert {quantity} {symbol} to a notional order at");
+        }
+        let reference = match limit_price {
+            Some(limit_price) => limit_price.clone(),
+            None => (&quote.bid_price + &quote.ask_price) / Num::from(2),
+        };
+        let slippage = match (side, limit_price) {
+            (_, None) => slippage / Num::from(2),
+            (OrderSide::Buy, Some(_)) => price - &reference,
+            (OrderSide::Sell, Some(_)) => &reference - price,
+        };
+        let slippage_bps = slippage / &reference * Num::from(10_000);
+        if slippage_bps > self.max_slippage_bps {
+            bail!(
+                "a notional order for {quantity} {symbol} at {price} would slip {slippage_bps:.2}bps, more than {}bps",
+                self.max_slippage_bps
+            );
+        }
+
+        let notional = (quantity * price).round_with(2);
+        if notional < self.min_notional {
+            bail!("{quantity} {symbol} is worth {notional}, less than the {} minimum", self.min_notional);
+        }
+        Ok(Some(NotionalConversion {
+            asset_symbol: symbol.to_string(),
+            quantity: quantity.clone(),
+            notional,
+            price: price.clone(),
+        }))
+    }
+}
+
+/// Where the conversions made by the fallback are reported.
+pub struct NotionalConversions {
+    sender: broadcast::Sender<NotionalConversion>,
+}
+
+impl Default for NotionalConversions {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl AlpacaClient {
+    pub fn set_notional_fallback(&mut self, notional_fallback: Option<NotionalFallback>) {
+        self.notional_fallback = notional_fallback;
+    }
+
+    /// Quantity orders submitted as notional orders by the fallback.
+    pub fn subscribe_notional_conversions(&self) -> broadcast::Receiver<NotionalConversion> {
+        self.notional_conversions.sender.subscribe()
+    }
+
+    /// Turns a crypto quantity order below the minimum order size into a market order for the
+    /// same value, if the fallback is set.
+    pub(crate) async fn apply_notional_fallback(&self, mut req: OrderRequest) -> Result<OrderRequest> {
+        let Some(notional_fallback) = &self.notional_fallback else {
+            return Ok(req);
+        };
+        let symbol = req.asset_pair.to_string();
+        let Amount::Quantity { quantity } = &req.amount else {
+            return Ok(req);
+        };
+        if AssetClass::of_symbol(&symbol) != AssetClass::Crypto
+            || !notional_fallback.min_quantities.contains_key(&symbol)
+        {
+            return Ok(req);
+        }
+
+        let quote = self
+            .get_latest_quotes(std::slice::from_ref(&symbol))
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no quote available for {symbol}"))?;
+        let conversion =
+            notional_fallback.notional_for(&symbol, &req.side, quantity, req.limit_price.as_ref(), &quote)?;
+        let Some(conversion) = conversion else {
+            return Ok(req);
+        };
+
+        req.amount = Amount::Notional {
+            notional: conversion.notional.clone(),
+        };
+        req.limit_price = None;
+        // Nobody listening isn't an error
+        let _ = self.notional_conversions.sender.send(conversion);
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn quote(bid_price: Num, ask_price: Num) -> Quote {
+        Quote {
+            symbol: "BTC/USD".into(),
+            exchange: None,
+            time: Utc::now(),
+            bid_price,
+            bid_size: Num::from(1),
+            ask_price,
+            ask_size: Num::from(1),
+        }
+    }
+
+    #[test]
+    fn converts_only_quantities_below_the_minimum() -> Result<()> {
+        let fallback = NotionalFallback {
+            min_quantities: HashMap::from([("BTC/USD".into(), Num::new(1, 10_000))]),
+            min_notional: Num::from(1),
+            max_slippage_bps: Num::from(10),
+        };
+        let quote = quote(Num::from(49_990), Num::from(50_010));
+        let below = Num::new(1, 20_000);
+
+        assert!(fallback.notional_for("BTC/USD", &OrderSide::Buy, &Num::from(1), None, &quote)?.is_none());
+        let conversion = fallback.notional_for("BTC/USD", &OrderSide::Buy, &below, None, &quote)?;
+        assert_eq!(conversion.map(|conversion| conversion.notional), Some(Num::new(5, 2)));
+
+        // The ask is 2bps over a 50000 limit
+        let limit_price = Num::from(50_000);
+        assert!(fallback.notional_for("BTC/USD", &OrderSide::Buy, &below, Some(&limit_price), &quote).is_ok());
+        let strict = NotionalFallback {
+            max_slippage_bps: Num::from(1),
+            ..fallback.clone()
+        };
+        assert!(strict.notional_for("BTC/USD", &OrderSide::Buy, &below, Some(&limit_price), &quote).is_err());
+
+        let dust = Num::new(1, 100_000_000);
+        assert!(fallback.notional_for("BTC/USD", &OrderSide::Sell, &dust, None, &quote).is_err());
+        Ok(())
+    }
+}
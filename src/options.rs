@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use irontrade::api::common::OpenPosition as IronTradeOpenPosition;
+use num_decimal::Num;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionRight {
+    Call,
+    Put,
+}
+
+/// An options contract decoded from its OCC symbol, e.g. `AAPL240119C00150000`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OptionContract {
+    pub underlying: String,
+    pub expiration: NaiveDate,
+    pub right: OptionRight,
+    pub strike: Num,
+}
+
+impl OptionContract {
+    /// Regular options stop trading at 16:00 New York time on their expiration date.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        let close = self.expiration.and_time(NaiveTime::from_hms_opt(16, 0, 0).unwrap());
+        New_York
+            .from_local_datetime(&close)
+            .earliest()
+            .map(|expiry| expiry.with_timezone(&Utc))
+            .unwrap_or_else(|| close.and_utc())
+    }
+}
+
+impl FromStr for OptionContract {
+    type Err = anyhow::Error;
+
+    fn from_str(symbol: &str) -> Result<Self> {
+        // <underlying><yymmdd><C|P><strike * 1000, 8 digits>
+        if symbol.len() <= 15 || !symbol.is_ascii() {
+            bail!("{symbol} is not an OCC option symbol");
+        }
+
+        let (underlying, contract) = symbol.split_at(symbol.len() - 15);
+        let expiration = NaiveDate::parse_from_str(&contract[..6], "%y%m%d")
+            .map_err(|_| anyhow!("{symbol} has an invalid expiration date"))?;
+        let right = match &contract[6..7] {
+            "C" => OptionRight::Call,
+            "P" => OptionRight::Put,
+            _ => bail!("{symbol} has an invalid option right"),
+        };
+        let strike = contract[7..]
+            .parse::<u64>()
+            .map_err(|_| anyhow!("{symbol} has an invalid strike"))?;
+
+        Ok(Self {
+            underlying: underlying.trim().to_string(),
+            expiration,
+            right,
+            strike: Num::new(strike, 1000),
+        })
+    }
+}
+
+/// When to warn about and when to close option positions ahead of their expiry.
+#[derive(Clone, Debug)]
+pub struct ExpiryPolicy {
+    pub warn_before: Duration,
+    /// Positions get closed once they are this close to expiry, `None` only warns
+    pub close_before: Option<Duration>,
+}
+
+impl Default for ExpiryPolicy {
+    fn default() -> Self {
+        Self {
+            warn_before: Duration::days(2),
+            close_before: Some(Duration::hours(2)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ExpiryAction {
+    Warn {
+        position: IronTradeOpenPosition,
+        contract: OptionContract,
+        time_to_expiry: Duration,
+    },
+    Close {
+        position: IronTradeOpenPosition,
+        contract: OptionContract,
+        time_to_expiry: Duration,
+    },
+}
+
+impl ExpiryPolicy {
+    /// Returns the actions due for the given positions, ignoring anything that isn't an option.
+    pub fn evaluate(&self, positions: &[IronTradeOpenPosition], now: DateTime<Utc>) -> Vec<ExpiryAction> {
+        positions
+            .iter()
+            .filter_map(|position| {
+                let contract = OptionContract::from_str(&position.asset_symbol).ok()?;
+                let time_to_expiry = contract.expires_at() - now;
+
+                if self
+                    .close_before
+                    .is_some_and(|close_before| time_to_expiry <= close_before)
+                {
+                    Some(ExpiryAction::Close {
+                        position: position.clone(),
+                        contract,
+                        time_to_expiry,
+                    })
+                } else if time_to_expiry <= self.warn_before {
+                    Some(ExpiryAction::Warn {
+                        position: position.clone(),
+                        contract,
+                        time_to_expiry,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl AlpacaClient {
+    /// Applies the expiry policy to the held option positions, closing the ones that are due, and
+    /// returns every action taken so warnings can be surfaced by the caller.
+    pub async fn enforce_expiry_policy(&self, policy: &ExpiryPolicy) -> Result<Vec<ExpiryAction>> {
+        let positions = self.list_open_positions().await?;
+        let actions = policy.evaluate(&positions, Utc::now());
+
+        for action in &actions {
+            if let ExpiryAction::Close { position, .. } = action {
+                self.close_position_by_symbol(&position.asset_symbol).await?;
+            }
+        }
+
+        Ok(actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_occ_symbol() -> Result<()> {
+        let contract = OptionContract::from_str("AAPL240119C00150500")?;
+
+        assert_eq!(contract.underlying, "AAPL");
+        assert_eq!(contract.expiration, NaiveDate::from_ymd_opt(2024, 1, 19).unwrap());
+        assert_eq!(contract.right, OptionRight::Call);
+        assert_eq!(contract.strike, Num::from_str("150.5")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_non_option_symbol() {
+        assert!(OptionContract::from_str("AAPL").is_err());
+        assert!(OptionContract::from_str("BTC/USD").is_err());
+    }
+
+    #[test]
+    fn evaluate_warns_then_closes() -> Result<()> {
+        let position = open_position("SPY240119P00470000");
+        let contract = OptionContract::from_str(&position.asset_symbol)?;
+        let policy = ExpiryPolicy::default();
+
+        let far = policy.evaluate(&[position.clone()], contract.expires_at() - Duration::days(5));
+        assert!(far.is_empty());
+
+        let near = policy.evaluate(&[position.clone()], contract.expires_at() - Duration::days(1));
+        assert!(matches!(near.as_slice(), [ExpiryAction::Warn { .. }]));
+
+        let due = policy.evaluate(&[position], contract.expires_at() - Duration::hours(1));
+        assert!(matches!(due.as_slice(), [ExpiryAction::Close { .. }]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn evaluate_ignores_stocks() {
+        let policy = ExpiryPolicy::default();
+        assert!(policy.evaluate(&[open_position("AAPL")], Utc::now()).is_empty());
+    }
+
+    fn open_position(asset_symbol: &str) -> IronTradeOpenPosition {
+        IronTradeOpenPosition {
+            asset_symbol: asset_symbol.into(),
+            average_entry_price: Some(Num::from(1)),
+            quantity: Num::from(1),
+            market_value: None,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::order_defaults::{OrderOptions, OrderTimeInForce};
+use crate::positions::AssetClass;
+use anyhow::{Result, bail};
+use apca::api::v2::order::{Class, StopLoss, TakeProfit, Type};
+use irontrade::api::common::{Amount, AssetPair, Order as IronTradeOrder, OrderSide};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderClass {
+    Simple,
+    Bracket,
+    /// Two exits where the first to fill cancels the other
+    OneCancelsOther,
+    /// An entry that submits its exit once it fills
+    OneTriggersOther,
+}
+
+/// An order attached to a parent order.
+#[derive(Clone, Debug)]
+pub struct OrderLeg {
+    pub parent_order_id: String,
+    pub order: IronTradeOrder,
+    /// Waiting for the parent to fill before it goes live
+    pub held: bool,
+}
+
+/// An order with the legs Alpaca manages for it server-side.
+#[derive(Clone, Debug)]
+pub struct LinkedOrder {
+    pub class: OrderClass,
+    pub order: IronTradeOrder,
+    pub legs: Vec<OrderLeg>,
+}
+
+/// Checks that a take-profit and a stop-loss closing with `exit_side` don't cross: a sell takes
+/// profit above its stop, a buy below it.
+pub(crate) fn check_exit_prices(exit_side: &OrderSide, take_profit: &Num, stop_loss: &Num) -> Result<()> {
+    let ordered = match exit_side {
+        OrderSide::Sell => take_profit > stop_loss,
+        OrderSide::Buy => take_profit < stop_loss,
+    };
+    if !ordered {
+        bail!("take-profit {take_profit} is on the wrong side of stop-loss {stop_loss}");
+    }
+    Ok(())
+}
+
+pub(crate) fn stop_loss(stop_price: Num, limit_price: Option<Num>) -> StopLoss {
+    match limit_price {
+        Some(limit_price) => StopLoss::StopLimit(stop_price, limit_price),
+        None => StopLoss::Stop(stop_price),
+    }
+}
+
+pub(crate) fn multi_leg_time_in_force(time_in_force: Option<OrderTimeInForce>) -> Result<OrderTimeInForce> {
+    let time_in_force = time_in_force.unwrap_or(OrderTimeInForce::Day);
+    if !matches!(time_in_force, OrderTimeInForce::Day | OrderTimeInForce::GoodTilCanceled) {
+        bail!("multi-leg orders are only accepted as day or good til canceled orders");
+    }
+    Ok(time_in_force)
+}
+
+pub(crate) fn check_equity(asset_symbol: &str) -> Result<()> {
+    if AssetClass::of_symbol(asset_symbol) != AssetClass::Equity {
+        bail!("multi-leg orders are only supported for stocks, not {asset_symbol}");
+    }
+    Ok(())
+}
+
+/// Take-profit and stop-loss exits of an existing position, e.g. one entered outside of a
+/// bracket order.
+#[derive(Clone, Debug)]
+pub struct OcoRequest {
+    pub asset_pair: AssetPair,
+    /// Sell to exit a long, buy to exit a short
+    pub side: OrderSide,
+    pub quantity: Num,
+    pub take_profit_limit_price: Num,
+    pub stop_loss_stop_price: Num,
+    /// Makes the stop-loss a stop limit order
+    pub stop_loss_limit_price: Option<Num>,
+    pub time_in_force: Option<OrderTimeInForce>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OtoExit {
+    TakeProfit { limit_price: Num },
+    StopLoss { stop_price: Num, limit_price: Option<Num> },
+}
+
+/// An entry that triggers a single exit once it fills.
+#[derive(Clone, Debug)]
+pub struct OtoRequest {
+    /// A market order, or a limit order with a limit price
+    pub entry: OrderRequest,
+    pub exit: OtoExit,
+    pub time_in_force: Option<OrderTimeInForce>,
+}
+
+impl AlpacaClient {
+    /// Places a take-profit limit order and a stop-loss on an existing position, Alpaca cancels
+    /// whichever doesn't fill first.
+    pub async fn place_oco_order(&mut self, req: OcoRequest) -> Result<LinkedOrder> {
+        let asset_symbol = self.symbol_aliases.resolve(&req.asset_pair.to_string()).to_string();
+        check_equity(&asset_symbol)?;
+        check_exit_prices(&req.side, &req.take_profit_limit_price, &req.stop_loss_stop_price)?;
+
+        let take_profit = OrderRequest {
+            asset_pair: req.asset_pair,
+            amount: Amount::Quantity { quantity: req.quantity },
+            side: req.side,
+            limit_price: Some(req.take_profit_limit_price.clone()),
+        };
+        let options = OrderOptions {
+            time_in_force: Some(multi_leg_time_in_force(req.time_in_force)?),
+            extended_hours: Some(false),
+            ..Default::default()
+        };
+        let retry = options.retry;
+        let (_, mut request) = self.create_request(take_profit, options).await?;
+
+        // The take-profit price goes with the leg, not the order
+        request.limit_price = None;
+        request.class = Class::OneCancelsOther;
+        request.take_profit = Some(TakeProfit::Limit(req.take_profit_limit_price));
+        request.stop_loss = Some(stop_loss(req.stop_loss_stop_price, req.stop_loss_limit_price));
+
+        let order = self.submit_apca_order(&asset_symbol, &request, retry).await?;
+        Ok(LinkedOrder::from(&order))
+    }
+
+    /// Places an entry whose exit Alpaca submits once the entry fills.
+    pub async fn place_oto_order(&mut self, req: OtoRequest) -> Result<LinkedOrder> {
+        let asset_symbol = self.symbol_aliases.resolve(&req.entry.asset_pair.to_string()).to_string();
+        check_equity(&asset_symbol)?;
+
+        let options = OrderOptions {
+            time_in_force: Some(multi_leg_time_in_force(req.time_in_force)?),
+            extended_hours: Some(false),
+            ..Default::default()
+        };
+        let retry = options.retry;
+        let (_, mut request) = self.create_request(req.entry, options).await?;
+        if !matches!(request.type_, Type::Market | Type::Limit) {
+            bail!("the entry of a one-triggers-other order has to be a market or limit order");
+        }
+
+        request.class = Class::OneTriggersOther;
+        match req.exit {
+            OtoExit::TakeProfit { limit_price } => request.take_profit = Some(TakeProfit::Limit(limit_price)),
+            OtoExit::StopLoss { stop_price, limit_price } => {
+                request.stop_loss = Some(stop_loss(stop_price, limit_price))
+            }
+        }
+
+        let order = self.submit_apca_order(&asset_symbol, &request, retry).await?;
+        Ok(LinkedOrder::from(&order))
+    }
+
+    /// Current state of an order and its legs.
+    pub async fn get_linked_order(&self, order_id: &str) -> Result<LinkedOrder> {
+        let order = self.fetch_apca_order(order_id).await?;
+        Ok(LinkedOrder::from(&order))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_prices_depend_on_the_exit_side() {
+        assert!(check_exit_prices(&OrderSide::Sell, &Num::from(110), &Num::from(90)).is_ok());
+        assert!(check_exit_prices(&OrderSide::Sell, &Num::from(90), &Num::from(110)).is_err());
+        assert!(check_exit_prices(&OrderSide::Buy, &Num::from(90), &Num::from(110)).is_ok());
+    }
+}
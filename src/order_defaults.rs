@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::positions::AssetClass;
+use crate::retry::RetryOverride;
+use crate::trailing::Trail;
+use anyhow::{Result, anyhow, bail};
+use apca::api::v2::order::{TimeInForce, Type};
+use irontrade::api::common::OrderSide;
+use num_decimal::Num;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum OrderTimeInForce {
+    #[serde(rename = "day")]
+    Day,
+    #[serde(rename = "gtc")]
+    GoodTilCanceled,
+    #[serde(rename = "ioc")]
+    ImmediateOrCancel,
+    #[serde(rename = "fok")]
+    FillOrKill,
+    /// Market or limit on open, executes in the opening auction
+    #[serde(rename = "opg")]
+    AtTheOpen,
+    /// Market or limit on close, executes in the closing auction
+    #[serde(rename = "cls")]
+    AtTheClose,
+}
+
+impl From<OrderTimeInForce> for TimeInForce {
+    fn from(time_in_force: OrderTimeInForce) -> Self {
+        match time_in_force {
+            OrderTimeInForce::Day => TimeInForce::Day,
+            OrderTimeInForce::GoodTilCanceled => TimeInForce::UntilCanceled,
+            OrderTimeInForce::ImmediateOrCancel => TimeInForce::ImmediateOrCancel,
+            OrderTimeInForce::FillOrKill => TimeInForce::FillOrKill,
+            OrderTimeInForce::AtTheOpen => TimeInForce::UntilMarketOpen,
+            OrderTimeInForce::AtTheClose => TimeInForce::UntilMarketClose,
+        }
+    }
+}
+
+impl OrderTimeInForce {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderTimeInForce::Day => "day",
+            OrderTimeInForce::GoodTilCanceled => "gtc",
+            OrderTimeInForce::ImmediateOrCancel => "ioc",
+            OrderTimeInForce::FillOrKill => "fok",
+            OrderTimeInForce::AtTheOpen => "opg",
+            OrderTimeInForce::AtTheClose => "cls",
+        }
+    }
+
+    /// Whether Alpaca accepts orders of `asset_class` with this time in force: crypto only
+    /// trades good til canceled and immediate or cancel, options only day orders.
+    pub fn is_supported_for(&self, asset_class: AssetClass) -> bool {
+        match asset_class {
+            AssetClass::Equity => true,
+            AssetClass::Crypto => matches!(
+                self,
+                OrderTimeInForce::GoodTilCanceled | OrderTimeInForce::ImmediateOrCancel
+            ),
+            AssetClass::Option => matches!(self, OrderTimeInForce::Day),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsupportedTimeInForce {
+    pub asset_class: AssetClass,
+    pub time_in_force: OrderTimeInForce,
+}
+
+impl Display for UnsupportedTimeInForce {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let asset_class = match self.asset_class {
+            AssetClass::Equity => "stock",
+            AssetClass::Crypto => "crypto",
+            AssetClass::Option => "option",
+        };
+        write!(f, "{asset_class} orders can't have time in force {}", self.time_in_force.as_str())
+    }
+}
+
+impl std::error::Error for UnsupportedTimeInForce {}
+
+pub fn check_time_in_force(
+    asset_class: AssetClass,
+    time_in_force: OrderTimeInForce,
+) -> Result<(), UnsupportedTimeInForce> {
+    if time_in_force.is_supported_for(asset_class) {
+        Ok(())
+    } else {
+        Err(UnsupportedTimeInForce {
+            asset_class,
+            time_in_force,
+        })
+    }
+}
+
+/// Checks an order may trade in the pre and post market sessions: Alpaca only takes extended
+/// hours on day limit orders for stocks.
+pub fn check_extended_hours(asset_class: AssetClass, type_: Type, time_in_force: OrderTimeInForce) -> Result<()> {
+    if asset_class != AssetClass::Equity {
+        bail!("only stock orders can trade in extended hours");
+    }
+    if !matches!(type_, Type::Limit) || time_in_force != OrderTimeInForce::Day {
+        bail!("extended hours orders must be day limit orders");
+    }
+    Ok(())
+}
+
+/// How requests without a limit price are submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(rename_all = "snake_case")]
+pub enum DefaultOrderType {
+    Market,
+    /// Limit order at the far touch of the latest quote, shifted by `limit_offset_bps`
+    MarketableLimit,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(default, deny_unknown_fields)]
+pub struct OrderDefaults {
+    pub time_in_force: OrderTimeInForce,
+    /// Only sent on limit orders with day time in force, the only ones Alpaca accepts it on
+    pub extended_hours: bool,
+    pub order_type: DefaultOrderType,
+    /// How far through the touch a marketable limit order is priced
+    pub limit_offset_bps: Num,
+}
+
+impl Default for OrderDefaults {
+    fn default() -> Self {
+        Self {
+            time_in_force: OrderTimeInForce::GoodTilCanceled,
+            extended_hours: false,
+            order_type: DefaultOrderType::Market,
+            limit_offset_bps: Num::from(0),
+        }
+    }
+}
+
+/// Order parameters filled in for requests that don't set them, by the asset class of the
+/// symbol. Equities and options default to day orders, crypto to good til canceled. A class
+/// configured with only some fields takes the remaining ones from [`OrderDefaults::default`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(default, deny_unknown_fields)]
+pub struct AssetClassDefaults {
+    pub equity: OrderDefaults,
+    pub crypto: OrderDefaults,
+    pub option: OrderDefaults,
+}
+
+impl Default for AssetClassDefaults {
+    fn default() -> Self {
+        let day = OrderDefaults {
+            time_in_force: OrderTimeInForce::Day,
+            ..Default::default()
+        };
+        Self {
+            equity: day.clone(),
+            crypto: OrderDefaults::default(),
+            option: day,
+        }
+    }
+}
+
+impl AssetClassDefaults {
+    pub fn for_class(&self, asset_class: AssetClass) -> &OrderDefaults {
+        match asset_class {
+            AssetClass::Equity => &self.equity,
+            AssetClass::Crypto => &self.crypto,
+            AssetClass::Option => &self.option,
+        }
+    }
+
+    /// Checks each class's default time in force is one Alpaca accepts for it.
+    pub fn check(&self) -> Result<(), UnsupportedTimeInForce> {
+        for asset_class in [AssetClass::Equity, AssetClass::Crypto, AssetClass::Option] {
+            check_time_in_force(asset_class, self.for_class(asset_class).time_in_force)?;
+        }
+        Ok(())
+    }
+}
+
+/// Per order settings, anything left unset comes from the client's [`AssetClassDefaults`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderOptions {
+    pub retry: RetryOverride,
+    pub time_in_force: Option<OrderTimeInForce>,
+    /// Set explicitly, orders that can't trade in extended hours fail instead of going out
+    /// without it, see [`check_extended_hours`]
+    pub extended_hours: Option<bool>,
+    pub order_type: Option<DefaultOrderType>,
+    /// Makes the order a stop order, or a stop limit order together with a limit price
+    pub stop_price: Option<Num>,
+    /// Makes the order a trailing stop order, which takes neither a limit nor a stop price
+    pub trail: Option<Trail>,
+    /// Unique id of the order of up to 128 characters, generated by Alpaca when unset
+    pub client_order_id: Option<String>,
+}
+
+impl AlpacaClient {
+    pub fn set_order_defaults(&mut self, order_defaults: AssetClassDefaults) {
+        self.order_defaults = order_defaults;
+    }
+
+    pub fn order_defaults(&self) -> &AssetClassDefaults {
+        &self.order_defaults
+    }
+
+    /// Price of a limit order that executes right away against the latest quote.
+    pub(crate) async fn marketable_limit_price(&self, symbol: &str, side: &OrderSide, offset_bps: &Num) -> Result<Num> {
+        let quote = self
+            .get_latest_quotes(&[symbol.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no quote available for {symbol}"))?;
+
+        let offset = offset_bps / Num::from(10_000);
+        let limit_price = match side {
+            OrderSide::Buy => &quote.ask_price * (Num::from(1) + offset),
+            OrderSide::Sell => &quote.bid_price * (Num::from(1) - offset),
+        };
+        Ok(round_limit_price(symbol, limit_price))
+    }
+}
+
+/// Rounds a limit price to the precision Alpaca accepts for `symbol`.
+pub(crate) fn round_limit_price(symbol: &str, limit_price: Num) -> Num {
+    // Alpaca takes crypto prices to 9 decimals and stock prices to cents, or 4 decimals below $1
+    let decimals = match AssetClass::of_symbol(symbol) {
+        AssetClass::Crypto => 9,
+        _ if limit_price < Num::from(1) => 4,
+        _ => 2,
+    };
+    limit_price.round_with(decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_by_asset_class() -> Result<()> {
+        let defaults: AssetClassDefaults = toml::from_str(
+            r#"
+            [crypto]
+            order_type = "marketable_limit"
+            limit_offset_bps = "5"
+            "#,
+        )?;
+
+        assert_eq!(defaults.for_class(AssetClass::Equity).time_in_force, OrderTimeInForce::Day);
+        assert_eq!(defaults.for_class(AssetClass::Crypto).time_in_force, OrderTimeInForce::GoodTilCanceled);
+        assert_eq!(defaults.crypto.order_type, DefaultOrderType::MarketableLimit);
+        assert_eq!(defaults.crypto.limit_offset_bps, Num::from(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn time_in_force_by_asset_class() {
+        assert!(check_time_in_force(AssetClass::Equity, OrderTimeInForce::AtTheClose).is_ok());
+        assert!(check_time_in_force(AssetClass::Crypto, OrderTimeInForce::ImmediateOrCancel).is_ok());
+        assert!(check_time_in_force(AssetClass::Crypto, OrderTimeInForce::Day).is_err());
+        assert!(check_time_in_force(AssetClass::Option, OrderTimeInForce::GoodTilCanceled).is_err());
+        assert!(AssetClassDefaults::default().check().is_ok());
+    }
+
+    #[test]
+    fn extended_hours_only_on_day_limit_stock_orders() {
+        assert!(check_extended_hours(AssetClass::Equity, Type::Limit, OrderTimeInForce::Day).is_ok());
+        assert!(check_extended_hours(AssetClass::Equity, Type::Market, OrderTimeInForce::Day).is_err());
+        assert!(check_extended_hours(AssetClass::Equity, Type::Limit, OrderTimeInForce::GoodTilCanceled).is_err());
+        assert!(check_extended_hours(AssetClass::Crypto, Type::Limit, OrderTimeInForce::Day).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use irontrade::api::common::{Amount, Order as IronTradeOrder, OrderStatus};
+use num_decimal::Num;
+
+/// Fill figures derived from an order's amount, filled quantity and average fill price, so
+/// partial fill handling computes them the same way everywhere.
+pub trait OrderFills {
+    /// Value of what got filled so far at the average fill price, zero before the first fill.
+    fn filled_notional(&self) -> Num;
+
+    /// Quantity still to be filled. `None` for notional orders that aren't filled yet, their
+    /// remaining quantity depends on the price of the next fills.
+    fn remaining_quantity(&self) -> Option<Num>;
+}
+
+impl OrderFills for IronTradeOrder {
+    fn filled_notional(&self) -> Num {
+        match &self.average_fill_price {
+            Some(average_fill_price) => average_fill_price * &self.filled_quantity,
+            None => Num::from(0),
+        }
+    }
+
+    fn remaining_quantity(&self) -> Option<Num> {
+        if matches!(self.status, OrderStatus::Filled) {
+            return Some(Num::from(0));
+        }
+
+        match &self.amount {
+            Amount::Quantity { quantity } => {
+                let remaining = quantity - &self.filled_quantity;
+                Some(if remaining.is_negative() { Num::from(0) } else { remaining })
+            }
+            Amount::Notional { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use irontrade::api::common::{OrderSide, OrderType};
+
+    fn order(
+        amount: Amount,
+        filled_quantity: i64,
+        average_fill_price: Option<i64>,
+        status: OrderStatus,
+    ) -> IronTradeOrder {
+        IronTradeOrder {
+            order_id: "a".into(),
+            asset_symbol: "AAPL".into(),
+            filled_quantity: Num::from(filled_quantity),
+            amount,
+            average_fill_price: average_fill_price.map(Num::from),
+            status,
+            type_: OrderType::Market,
+            limit_price: None,
+            side: OrderSide::Buy,
+        }
+    }
+
+    #[test]
+    fn partially_filled_quantity_order() {
+        let amount = Amount::Quantity {
+            quantity: Num::from(10),
+        };
+        let order = order(amount, 4, Some(25), OrderStatus::PartiallyFilled);
+
+        assert_eq!(order.filled_notional(), Num::from(100));
+        assert_eq!(order.remaining_quantity(), Some(Num::from(6)));
+    }
+
+    #[test]
+    fn notional_order_remaining_quantity() {
+        let amount = || Amount::Notional {
+            notional: Num::from(100),
+        };
+
+        assert_eq!(order(amount(), 0, None, OrderStatus::New).filled_notional(), Num::from(0));
+        assert_eq!(order(amount(), 0, None, OrderStatus::New).remaining_quantity(), None);
+        assert_eq!(order(amount(), 2, Some(50), OrderStatus::Filled).remaining_quantity(), Some(Num::from(0)));
+    }
+}
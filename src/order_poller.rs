@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::retry::{Idempotency, RetryOverride};
+use crate::wait::is_terminal;
+use anyhow::Result;
+use apca::api::v2::order::{Order as ApcaOrder, Status as ApcaOrderStatus, Type};
+use apca::api::v2::orders::{self, ListReq, Status};
+use irontrade::api::common::Order as IronTradeOrder;
+use num_decimal::Num;
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+pub const DEFAULT_HOT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+pub const DEFAULT_COLD_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Most orders one list call returns.
+const LIST_LIMIT: usize = 500;
+
+/// Below this share of the rate limit left, polling slows down in proportion.
+const HEADROOM_TARGET: f64 = 0.5;
+/// Polling never slows down more than this.
+const MAX_SLOWDOWN: f64 = 10.0;
+
+#[derive(Clone, Debug)]
+struct WatchedOrder {
+    asset_symbol: String,
+    last_seen: Option<(ApcaOrderStatus, Num)>,
+    hot: bool,
+    polled_at: Option<Instant>,
+}
+
+/// Orders likely to change soon: unseen ones, market orders, partial fills and pending
+/// cancels or replaces. Resting limit orders are cold.
+fn is_hot(status: ApcaOrderStatus, type_: Type) -> bool {
+    matches!(type_, Type::Market)
+        || matches!(
+            status,
+            ApcaOrderStatus::PartiallyFilled
+                | ApcaOrderStatus::PendingCancel
+                | ApcaOrderStatus::PendingReplace
+                | ApcaOrderStatus::PendingNew
+                | ApcaOrderStatus::AcceptedForBidding
+        )
+}
+
+/// How much slower than its interval to poll with `headroom` of the rate limit left.
+fn slowdown(headroom: f64) -> f64 {
+    if headroom >= HEADROOM_TARGET {
+        1.0
+    } else {
+        (HEADROOM_TARGET / headroom.max(HEADROOM_TARGET / MAX_SLOWDOWN)).min(MAX_SLOWDOWN)
+    }
+}
+
+/// Watches many orders without websockets. Due orders are refreshed together by one
+/// `orders::List` call over their symbols, orders likely to change soon are polled at the hot
+/// interval and the rest at the cold one, both stretched when the client's rate limit runs low.
+#[derive(Clone, Debug)]
+pub struct OrderPoller {
+    watched: HashMap<String, WatchedOrder>,
+    hot_interval: Duration,
+    cold_interval: Duration,
+}
+
+impl Default for OrderPoller {
+    fn default() -> Self {
+        Self::new(DEFAULT_HOT_POLL_INTERVAL, DEFAULT_COLD_POLL_INTERVAL)
+    }
+}
+
+impl OrderPoller {
+    pub fn new(hot_interval: Duration, cold_interval: Duration) -> Self {
+        Self {
+            watched: HashMap::new(),
+            hot_interval,
+            cold_interval,
+        }
+    }
+
+    pub fn watch(&mut self, order_id: impl Into<String>, asset_symbol: impl Into<String>) {
+        self.watched.insert(
+            order_id.into(),
+            WatchedOrder {
+                asset_symbol: asset_symbol.into(),
+                last_seen: None,
+                hot: true,
+                polled_at: None,
+            },
+        );
+    }
+
+    pub fn unwatch(&mut self, order_id: &str) {
+        self.watched.remove(order_id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty()
+    }
+
+    fn interval(&self, order: &WatchedOrder, slowdown: f64) -> Duration {
+        let interval = if order.hot {
+            self.hot_interval
+        } else {
+            self.cold_interval
+        };
+        interval.mul_f64(slowdown)
+    }
+
+    /// Ids of the orders due for a poll at `now`.
+    fn due(&self, now: Instant, slowdown: f64) -> Vec<String> {
+        self.watched
+            .iter()
+            .filter(|(_, order)| {
+                order
+                    .polled_at
+                    .is_none_or(|polled_at| now >= polled_at + self.interval(order, slowdown))
+            })
+            .map(|(order_id, _)| order_id.clone())
+            .collect()
+    }
+
+    /// How long until the next order is due, zero when one already is.
+    fn next_due_in(&self, now: Instant, slowdown: f64) -> Option<Duration> {
+        self.watched
+            .values()
+            .map(|order| match order.polled_at {
+                Some(polled_at) => {
+                    (polled_at + self.interval(order, slowdown)).saturating_duration_since(now)
+                }
+                None => Duration::ZERO,
+            })
+            .min()
+    }
+
+    /// Records a polled state of the order and returns whether it changed. Terminal orders stop
+    /// being watched.
+    fn observe(
+        &mut self,
+        order_id: &str,
+        status: ApcaOrderStatus,
+        filled_quantity: &Num,
+        type_: Type,
+        now: Instant,
+    ) -> bool {
+        let Some(order) = self.watched.get_mut(order_id) else {
+            return false;
+        };
+        let changed = order
+            .last_seen
+            .as_ref()
+            .is_none_or(|(last_status, last_filled)| {
+                *last_status != status || last_filled != filled_quantity
+            });
+        order.last_seen = Some((status, filled_quantity.clone()));
+        order.hot = is_hot(status, type_);
+        order.polled_at = Some(now);
+
+        if is_terminal(&status) {
+            self.watched.remove(order_id);
+        }
+        changed
+    }
+}
+
+impl AlpacaClient {
+    /// Polls the orders of `poller` that are due, with one list call for all of them and single
+    /// fetches only for orders the list missed. Returns the orders whose status or fill changed.
+    pub async fn poll_orders(&self, poller: &mut OrderPoller) -> Result<Vec<IronTradeOrder>> {
+        let now = Instant::now();
+        let due = poller.due(now, slowdown(self.rate_limiter.headroom()));
+        if due.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let symbols: BTreeSet<String> = due
+            .iter()
+            .filter_map(|order_id| poller.watched.get(order_id))
+            .map(|order| self.symbol_aliases.resolve(&order.asset_symbol).to_string())
+            .collect();
+        let request = ListReq {
+            status: Status::All,
+            symbols: symbols.into_iter().collect(),
+            limit: Some(LIST_LIMIT),
+            ..Default::default()
+        };
+        let listed = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<orders::List>(&request)
+            })
+            .await?;
+
+        let mut polled: Vec<ApcaOrder> = listed
+            .into_iter()
+            .filter(|order| due.contains(&order.id.to_string()))
+            .collect();
+        for order_id in &due {
+            if !polled.iter().any(|order| order.id.to_string() == *order_id) {
+                polled.push(self.fetch_apca_order(order_id).await?);
+            }
+        }
+
+        let changed: Vec<ApcaOrder> = polled
+            .into_iter()
+            .filter(|order| {
+                poller.observe(
+                    &order.id.to_string(),
+                    order.status,
+                    &order.filled_quantity,
+                    order.type_,
+                    now,
+                )
+            })
+            .collect();
+        Ok(self.record_listed_orders(changed))
+    }
+
+    /// Polls the orders of `poller` as they come due and hands every change to `on_update`,
+    /// until no order is left to watch or `cancellation` fires.
+    pub async fn run_order_poller(
+        &self,
+        poller: &mut OrderPoller,
+        cancellation: &CancellationToken,
+        mut on_update: impl FnMut(IronTradeOrder),
+    ) -> Result<()> {
+        loop {
+            for order in self.poll_orders(poller).await? {
+                on_update(order);
+            }
+            let slowdown = slowdown(self.rate_limiter.headroom());
+            let Some(wait) = poller.next_due_in(Instant::now(), slowdown) else {
+                return Ok(());
+            };
+
+            tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => return Ok(()),
+                _ = sleep(wait) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polls_hot_orders_first_and_drops_terminal_ones() {
+        let mut poller = OrderPoller::new(Duration::from_secs(1), Duration::from_secs(10));
+        poller.watch("resting", "AAPL");
+        poller.watch("filling", "MSFT");
+
+        let start = Instant::now();
+        assert_eq!(poller.due(start, 1.0).len(), 2);
+        let quantity = Num::from(0);
+        assert!(poller.observe(
+            "resting",
+            ApcaOrderStatus::New,
+            &quantity,
+            Type::Limit,
+            start
+        ));
+        assert!(poller.observe(
+            "filling",
+            ApcaOrderStatus::PartiallyFilled,
+            &quantity,
+            Type::Limit,
+            start
+        ));
+
+        let later = start + Duration::from_secs(2);
+        assert_eq!(poller.due(later, 1.0), ["filling"]);
+        assert!(poller.due(later, slowdown(0.1)).is_empty());
+        assert_eq!(poller.next_due_in(start, 1.0), Some(Duration::from_secs(1)));
+
+        assert!(!poller.observe(
+            "filling",
+            ApcaOrderStatus::PartiallyFilled,
+            &quantity,
+            Type::Limit,
+            later
+        ));
+        assert!(poller.observe(
+            "filling",
+            ApcaOrderStatus::Filled,
+            &Num::from(1),
+            Type::Limit,
+            later
+        ));
+        assert_eq!(
+            poller.due(later + Duration::from_secs(20), 1.0),
+            ["resting"]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::Result;
+use apca::api::v2::order::Order as ApcaOrder;
+use apca::api::v2::orders::{self, ListReq, Status};
+use chrono::{DateTime, Utc};
+use irontrade::api::common::Order as IronTradeOrder;
+use std::collections::HashSet;
+
+/// Most orders Alpaca returns per list request.
+const PAGE_SIZE: usize = 500;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrderQueryStatus {
+    Open,
+    Closed,
+    #[default]
+    All,
+}
+
+impl From<OrderQueryStatus> for Status {
+    fn from(status: OrderQueryStatus) -> Self {
+        match status {
+            OrderQueryStatus::Open => Status::Open,
+            OrderQueryStatus::Closed => Status::Closed,
+            OrderQueryStatus::All => Status::All,
+        }
+    }
+}
+
+/// Which orders [`AlpacaClient::query_orders`] returns, newest first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderQuery {
+    pub status: OrderQueryStatus,
+    /// Only orders for these symbols, all symbols when empty
+    pub symbols: Vec<String>,
+    /// Only orders submitted after this time
+    pub after: Option<DateTime<Utc>>,
+    /// Only orders submitted until this time
+    pub until: Option<DateTime<Utc>>,
+    /// Most orders to return, `None` follows the pages to the end
+    pub limit: Option<usize>,
+    /// Return the legs of multi-leg orders too, each following its parent
+    pub nested: bool,
+}
+
+impl AlpacaClient {
+    /// Orders matching `query`, fetched page by page so long histories aren't cut off at
+    /// Alpaca's page size. Each page continues before the oldest order of the previous one.
+    pub async fn query_orders(&self, query: &OrderQuery) -> Result<Vec<IronTradeOrder>> {
+        let (symbols, _) = self.symbol_aliases.resolve_all(&query.symbols);
+        let mut orders: Vec<ApcaOrder> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut until = query.until;
+
+        loop {
+            let remaining = query.limit.map_or(PAGE_SIZE, |limit| limit - orders.len());
+            if remaining == 0 {
+                break;
+            }
+            let request = ListReq {
+                status: query.status.into(),
+                symbols: symbols.clone(),
+                after: query.after,
+                until,
+                limit: Some(remaining.min(PAGE_SIZE)),
+                nested: query.nested,
+                ..Default::default()
+            };
+            let page = self
+                .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                    self.apca_client.issue::<orders::List>(&request)
+                })
+                .await?;
+            let full_page = page.len() >= PAGE_SIZE.min(remaining);
+
+            let mut new_orders = 0;
+            for order in page {
+                until = Some(until.map_or(order.created_at, |until| until.min(order.created_at)));
+                // Orders submitted at the page boundary come back on the next page too
+                if seen.insert(order.id.to_string()) {
+                    orders.push(order);
+                    new_orders += 1;
+                }
+            }
+
+            if !full_page || new_orders == 0 {
+                break;
+            }
+        }
+
+        Ok(self.record_listed_orders(orders))
+    }
+}
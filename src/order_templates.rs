@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::order_defaults::{DefaultOrderType, OrderOptions, OrderTimeInForce, round_limit_price};
+use anyhow::{Result, anyhow};
+use irontrade::api::common::{Amount, AssetPair, OrderSide};
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateOrderType {
+    Market,
+    /// Limit order at the mid of the latest quote, shifted through it by `limit_offset_bps`
+    Limit,
+    /// Limit order at the far touch, like [`DefaultOrderType::MarketableLimit`]
+    MarketableLimit,
+}
+
+/// Order parameters a strategy reuses, filled in with a symbol and an amount when placing.
+/// Unset fields come from the order defaults of the symbol's asset class.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(deny_unknown_fields)]
+pub struct OrderTemplate {
+    pub side: TemplateSide,
+    pub order_type: TemplateOrderType,
+    pub time_in_force: Option<OrderTimeInForce>,
+    pub extended_hours: Option<bool>,
+    /// How far through the price a limit order is priced, positive towards the far touch
+    #[serde(default)]
+    pub limit_offset_bps: Num,
+}
+
+impl OrderTemplate {
+    fn side(&self) -> OrderSide {
+        match self.side {
+            TemplateSide::Buy => OrderSide::Buy,
+            TemplateSide::Sell => OrderSide::Sell,
+        }
+    }
+}
+
+/// Order templates by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(transparent)]
+pub struct OrderTemplates(BTreeMap<String, OrderTemplate>);
+
+impl OrderTemplates {
+    pub fn insert(&mut self, name: impl Into<String>, template: OrderTemplate) {
+        self.0.insert(name.into(), template);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OrderTemplate> {
+        self.0.get(name)
+    }
+}
+
+/// Mid price shifted by `offset_bps` in the direction of `side`, up for buys and down for sells.
+fn limit_price_through_mid(
+    bid_price: &Num,
+    ask_price: &Num,
+    side: &OrderSide,
+    offset_bps: &Num,
+) -> Num {
+    let mid = (bid_price + ask_price) / Num::from(2);
+    let offset = offset_bps / Num::from(10_000);
+    match side {
+        OrderSide::Buy => &mid * (Num::from(1) + offset),
+        OrderSide::Sell => &mid * (Num::from(1) - offset),
+    }
+}
+
+impl AlpacaClient {
+    pub fn set_order_templates(&mut self, order_templates: OrderTemplates) {
+        self.order_templates = order_templates;
+    }
+
+    /// Places an order from the template called `name` for `amount` of `asset_symbol`. Returns
+    /// the id of the order.
+    pub async fn place_from_template(
+        &mut self,
+        name: &str,
+        asset_symbol: &str,
+        amount: Amount,
+    ) -> Result<String> {
+        let template = self
+            .order_templates
+            .get(name)
+            .ok_or_else(|| anyhow!("no order template named {name}"))?
+            .clone();
+        let side = template.side();
+        let symbol = self.symbol_aliases.resolve(asset_symbol).to_string();
+
+        let (limit_price, order_type) = match template.order_type {
+            TemplateOrderType::Market => (None, Some(DefaultOrderType::Market)),
+            TemplateOrderType::MarketableLimit => (
+                Some(
+                    self.marketable_limit_price(&symbol, &side, &template.limit_offset_bps)
+                        .await?,
+                ),
+                None,
+            ),
+            TemplateOrderType::Limit => {
+                let quote = self
+                    .get_latest_quotes(&[symbol.clone()])
+                    .await?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("no quote available for {asset_symbol}"))?;
+                let limit_price = limit_price_through_mid(
+                    &quote.bid_price,
+                    &quote.ask_price,
+                    &side,
+                    &template.limit_offset_bps,
+                );
+                (Some(round_limit_price(&symbol, limit_price)), None)
+            }
+        };
+
+        let req = OrderRequest {
+            asset_pair: AssetPair::from_str(asset_symbol)?,
+            amount,
+            side,
+            limit_price,
+        };
+        let options = OrderOptions {
+            time_in_force: template.time_in_force,
+            extended_hours: template.extended_hours,
+            order_type,
+            ..Default::default()
+        };
+        self.place_order_with_options(req, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_templates_and_prices_through_mid() -> Result<()> {
+        let templates: OrderTemplates = toml::from_str(
+            r#"
+            [scalp-entry]
+            side = "buy"
+            order_type = "limit"
+            time_in_force = "ioc"
+            extended_hours = false
+            limit_offset_bps = "10"
+            "#,
+        )?;
+
+        let template = templates.get("scalp-entry").unwrap();
+        assert_eq!(template.order_type, TemplateOrderType::Limit);
+        assert_eq!(
+            template.time_in_force,
+            Some(OrderTimeInForce::ImmediateOrCancel)
+        );
+        assert!(templates.get("scalp-exit").is_none());
+
+        let limit_price = limit_price_through_mid(
+            &Num::from(99),
+            &Num::from(101),
+            &template.side(),
+            &template.limit_offset_bps,
+        );
+        assert_eq!(limit_price, Num::new(1001, 10));
+
+        Ok(())
+    }
+}
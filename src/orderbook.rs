@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::data::deserialize_num;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use num_decimal::Num;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// One price level of a book update. A size of zero removes the level.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct BookLevel {
+    #[serde(rename = "p", deserialize_with = "deserialize_num")]
+    pub price: Num,
+    #[serde(rename = "s", deserialize_with = "deserialize_num")]
+    pub size: Num,
+}
+
+/// A message of Alpaca's crypto orderbook stream, deserializable from its JSON.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct OrderBookUpdate {
+    #[serde(rename = "S")]
+    pub symbol: String,
+    #[serde(rename = "t")]
+    pub time: DateTime<Utc>,
+    #[serde(rename = "b", default)]
+    pub bids: Vec<BookLevel>,
+    #[serde(rename = "a", default)]
+    pub asks: Vec<BookLevel>,
+    /// A snapshot replacing the whole book rather than changes to it
+    #[serde(rename = "r", default)]
+    pub reset: bool,
+}
+
+/// The book of one symbol, size by price.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderBook {
+    bids: BTreeMap<Num, Num>,
+    asks: BTreeMap<Num, Num>,
+}
+
+impl OrderBook {
+    pub fn apply(&mut self, update: &OrderBookUpdate) {
+        if update.reset {
+            self.bids.clear();
+            self.asks.clear();
+        }
+        for (side, levels) in [
+            (&mut self.bids, &update.bids),
+            (&mut self.asks, &update.asks),
+        ] {
+            for level in levels {
+                if level.size.is_zero() {
+                    side.remove(&level.price);
+                } else {
+                    side.insert(level.price.clone(), level.size.clone());
+                }
+            }
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<&Num> {
+        self.bids.keys().next_back()
+    }
+
+    pub fn best_ask(&self) -> Option<&Num> {
+        self.asks.keys().next()
+    }
+
+    /// Size resting on the best `levels` bid prices.
+    pub fn bid_depth(&self, levels: usize) -> Num {
+        self.bids
+            .values()
+            .rev()
+            .take(levels)
+            .fold(Num::from(0), |depth, size| depth + size)
+    }
+
+    /// Size resting on the best `levels` ask prices.
+    pub fn ask_depth(&self, levels: usize) -> Num {
+        self.asks
+            .values()
+            .take(levels)
+            .fold(Num::from(0), |depth, size| depth + size)
+    }
+}
+
+/// Top of book and depth figures of a symbol after an update.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BookMetrics {
+    pub symbol: String,
+    pub time: DateTime<Utc>,
+    pub best_bid: Option<Num>,
+    pub best_ask: Option<Num>,
+    pub bid_depth: Num,
+    pub ask_depth: Num,
+    /// `(bid_depth - ask_depth) / (bid_depth + ask_depth)`, from -1 with only asks to 1 with only
+    /// bids, `None` for an empty book
+    pub imbalance: Option<Num>,
+    /// Mean imbalance of the symbol's recent updates
+    pub rolling_imbalance: Option<Num>,
+}
+
+/// Maintains the books of every symbol from their updates and derives [`BookMetrics`] from them,
+/// with depth over the best `depth_levels` prices and the rolling imbalance over the last
+/// `window` updates of a symbol.
+#[derive(Debug)]
+pub struct ImbalanceTracker {
+    depth_levels: usize,
+    window: usize,
+    books: HashMap<String, OrderBook>,
+    imbalances: HashMap<String, VecDeque<Num>>,
+}
+
+impl ImbalanceTracker {
+    pub fn new(depth_levels: usize, window: usize) -> Self {
+        Self {
+            depth_levels,
+            window: window.max(1),
+            books: HashMap::new(),
+            imbalances: HashMap::new(),
+        }
+    }
+
+    pub fn book(&self, symbol: &str) -> Option<&OrderBook> {
+        self.books.get(symbol)
+    }
+
+    pub fn apply(&mut self, update: &OrderBookUpdate) -> BookMetrics {
+        let book = self.books.entry(update.symbol.clone()).or_default();
+        book.apply(update);
+
+        let bid_depth = book.bid_depth(self.depth_levels);
+        let ask_depth = book.ask_depth(self.depth_levels);
+        let total_depth = &bid_depth + &ask_depth;
+        let imbalance = total_depth
+            .is_positive()
+            .then(|| (&bid_depth - &ask_depth) / &total_depth);
+
+        let recent = self.imbalances.entry(update.symbol.clone()).or_default();
+        if let Some(imbalance) = &imbalance {
+            if recent.len() == self.window {
+                recent.pop_front();
+            }
+            recent.push_back(imbalance.clone());
+        }
+        let rolling_imbalance = (!recent.is_empty()).then(|| {
+            recent
+                .iter()
+                .fold(Num::from(0), |sum, imbalance| sum + imbalance)
+                / Num::from(recent.len())
+        });
+
+        BookMetrics {
+            symbol: update.symbol.clone(),
+            time: update.time,
+            best_bid: book.best_bid().cloned(),
+            best_ask: book.best_ask().cloned(),
+            bid_depth,
+            ask_depth,
+            imbalance,
+            rolling_imbalance,
+        }
+    }
+}
+
+/// Turns a stream of orderbook updates, e.g. decoded from Alpaca's crypto orderbook stream, into
+/// a stream of the resulting [`BookMetrics`], one per update.
+pub fn book_imbalance(
+    updates: impl Stream<Item = OrderBookUpdate>,
+    depth_levels: usize,
+    window: usize,
+) -> impl Stream<Item = BookMetrics> {
+    let mut tracker = ImbalanceTracker::new(depth_levels, window);
+    updates.map(move |update| tracker.apply(&update))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(bids: &[(i64, i64)], asks: &[(i64, i64)], reset: bool) -> OrderBookUpdate {
+        let levels = |levels: &[(i64, i64)]| {
+            levels
+                .iter()
+                .map(|(price, size)| BookLevel {
+                    price: Num::from(*price),
+                    size: Num::from(*size),
+                })
+                .collect()
+        };
+        OrderBookUpdate {
+            symbol: "BTC/USD".into(),
+            time: Utc::now(),
+            bids: levels(bids),
+            asks: levels(asks),
+            reset,
+        }
+    }
+
+    #[test]
+    fn parses_alpaca_messages() {
+        let update: OrderBookUpdate = serde_json::from_str(
+            r#"{"T":"o","S":"BTC/USD","t":"2024-01-18T15:00:00.123Z","b":[{"p":42000.5,"s":0.25}],"a":[],"r":true}"#,
+        )
+        .unwrap();
+        assert!(update.reset);
+        assert_eq!(update.bids[0].price, Num::new(840_01, 2));
+    }
+
+    #[test]
+    fn tracks_depth_and_imbalance() {
+        let mut tracker = ImbalanceTracker::new(2, 2);
+
+        let metrics = tracker.apply(&update(
+            &[(99, 3), (98, 1), (97, 10)],
+            &[(101, 2), (102, 2)],
+            true,
+        ));
+        assert_eq!(metrics.best_bid, Some(Num::from(99)));
+        assert_eq!(metrics.best_ask, Some(Num::from(101)));
+        assert_eq!(metrics.bid_depth, Num::from(4));
+        assert_eq!(metrics.imbalance, Some(Num::from(0)));
+
+        // The best bid is taken out
+        let metrics = tracker.apply(&update(&[(99, 0)], &[], false));
+        assert_eq!(metrics.best_bid, Some(Num::from(98)));
+        assert_eq!(metrics.bid_depth, Num::from(11));
+        assert_eq!(metrics.imbalance, Some(Num::new(7, 15)));
+        assert_eq!(metrics.rolling_imbalance, Some(Num::new(7, 30)));
+    }
+}
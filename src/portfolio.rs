@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::positions::PositionView;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::{Context, Result};
+use apca::api::v2::{account, positions};
+use chrono::{DateTime, Utc};
+use num_decimal::Num;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// A held position as of a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotPosition {
+    pub asset_symbol: String,
+    /// Negative for short positions
+    pub quantity: Num,
+    pub market_value: Option<Num>,
+}
+
+impl SnapshotPosition {
+    /// Price the position was marked at, `None` without a market value.
+    pub fn price(&self) -> Option<Num> {
+        if self.quantity.is_zero() {
+            return None;
+        }
+        Some(self.market_value.as_ref()? / &self.quantity)
+    }
+}
+
+/// The account's positions and balances at one point in time, e.g. saved at every close to
+/// compare against the next morning with [`diff_snapshots`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortfolioSnapshot {
+    pub taken_at: DateTime<Utc>,
+    pub cash: Num,
+    pub equity: Num,
+    pub positions: Vec<SnapshotPosition>,
+}
+
+impl PortfolioSnapshot {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_vec_pretty(self)?;
+
+        // Write next to the target and rename so a crash mid-write never leaves a torn file
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json).with_context(|| format!("failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path).with_context(|| format!("failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_slice(&json).with_context(|| format!("invalid snapshot in {}", path.display()))
+    }
+
+    fn position(&self, asset_symbol: &str) -> Option<&SnapshotPosition> {
+        self.positions
+            .iter()
+            .find(|position| position.asset_symbol == asset_symbol)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionChange {
+    pub asset_symbol: String,
+    /// Zero when the position was opened in between
+    pub quantity_before: Num,
+    /// Zero when the position was closed in between
+    pub quantity_after: Num,
+}
+
+impl PositionChange {
+    pub fn quantity_change(&self) -> Num {
+        &self.quantity_after - &self.quantity_before
+    }
+}
+
+/// What changed between two snapshots. The equity change is attributed to the market for what
+/// the positions held at the first snapshot gained or lost at the prices of the second, and to
+/// trading for the rest: fills, fees, and positions closed in between, whose later price isn't
+/// known. Deposits and withdrawals in between show up as trading.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotDiff {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Positions whose quantity changed, by symbol
+    pub position_changes: Vec<PositionChange>,
+    /// Change of the cash balance, negative when cash went into positions
+    pub cash_flow: Num,
+    pub equity_change: Num,
+    pub market_pnl: Num,
+    pub trading_pnl: Num,
+}
+
+/// Compares snapshot `a` with the later snapshot `b`.
+pub fn diff_snapshots(a: &PortfolioSnapshot, b: &PortfolioSnapshot) -> SnapshotDiff {
+    let zero = Num::from(0);
+    let symbols: BTreeSet<&str> = a
+        .positions
+        .iter()
+        .chain(&b.positions)
+        .map(|position| position.asset_symbol.as_str())
+        .collect();
+
+    let mut position_changes = Vec::new();
+    let mut market_pnl = zero.clone();
+    for asset_symbol in symbols {
+        let before = a.position(asset_symbol);
+        let after = b.position(asset_symbol);
+        let quantity_before = before.map_or(zero.clone(), |position| position.quantity.clone());
+        let quantity_after = after.map_or(zero.clone(), |position| position.quantity.clone());
+
+        if let (Some(before), Some(after)) = (before, after)
+            && let (Some(price_before), Some(price_after)) = (before.price(), after.price())
+        {
+            market_pnl = market_pnl + &before.quantity * &(price_after - price_before);
+        }
+
+        if quantity_before != quantity_after {
+            position_changes.push(PositionChange {
+                asset_symbol: asset_symbol.to_string(),
+                quantity_before,
+                quantity_after,
+            });
+        }
+    }
+
+    let equity_change = &b.equity - &a.equity;
+    SnapshotDiff {
+        from: a.taken_at,
+        to: b.taken_at,
+        position_changes,
+        cash_flow: &b.cash - &a.cash,
+        trading_pnl: &equity_change - &market_pnl,
+        equity_change,
+        market_pnl,
+    }
+}
+
+impl AlpacaClient {
+    /// The current positions and balances, under their aliases where configured.
+    pub async fn portfolio_snapshot(&self) -> Result<PortfolioSnapshot> {
+        let taken_at = Utc::now();
+        let (positions, account) = tokio::try_join!(
+            self.retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<positions::List>(&())
+            }),
+            self.retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<account::Get>(&())
+            }),
+        )?;
+
+        let positions = positions
+            .iter()
+            .map(PositionView::from)
+            .map(|view| SnapshotPosition {
+                asset_symbol: self.symbol_aliases.alias(&view.asset_symbol).to_string(),
+                quantity: view.quantity,
+                market_value: view.market_value,
+            })
+            .collect();
+
+        Ok(PortfolioSnapshot {
+            taken_at,
+            cash: account.cash,
+            equity: account.equity,
+            positions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn position(asset_symbol: &str, quantity: i64, price: i64) -> SnapshotPosition {
+        SnapshotPosition {
+            asset_symbol: asset_symbol.into(),
+            quantity: Num::from(quantity),
+            market_value: Some(Num::from(quantity * price)),
+        }
+    }
+
+    #[test]
+    fn attributes_overnight_changes() {
+        let a = PortfolioSnapshot {
+            taken_at: Utc.with_ymd_and_hms(2024, 1, 18, 21, 0, 0).unwrap(),
+            cash: Num::from(1000),
+            equity: Num::from(3000),
+            positions: vec![position("AAPL", 10, 100), position("MSFT", 5, 200)],
+        };
+        // AAPL rose to 110 and 5 more were bought, MSFT was sold at 190
+        let b = PortfolioSnapshot {
+            taken_at: Utc.with_ymd_and_hms(2024, 1, 19, 14, 0, 0).unwrap(),
+            cash: Num::from(1400),
+            equity: Num::from(3050),
+            positions: vec![position("AAPL", 15, 110)],
+        };
+
+        let diff = diff_snapshots(&a, &b);
+        assert_eq!(diff.cash_flow, Num::from(400));
+        assert_eq!(diff.equity_change, Num::from(50));
+        assert_eq!(diff.market_pnl, Num::from(100));
+        assert_eq!(diff.trading_pnl, Num::from(-50));
+        assert_eq!(diff.position_changes.len(), 2);
+        assert_eq!(diff.position_changes[0].quantity_change(), Num::from(5));
+        assert_eq!(diff.position_changes[1].asset_symbol, "MSFT");
+        assert_eq!(diff.position_changes[1].quantity_after, Num::from(0));
+    }
+}
@@ -0,0 +1,467 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::audit::AuditAction;
+use crate::client::AlpacaClient;
+use crate::convert::Order;
+use crate::error::ResponseError;
+use crate::format::format_quantity;
+use crate::options::OptionContract;
+use crate::precision::{Rounding, RoundingMode};
+use crate::retry::{Idempotency, RetryOverride};
+use crate::symbol::{SymbolAliases, same_symbol, unslashed};
+use anyhow::{Context, Result, bail};
+use apca::api::v2::asset::Class;
+use apca::api::v2::order::{Amount, CreateReqInit, Order as ApcaOrder, Side, Type};
+use apca::api::v2::position::Position;
+use apca::api::v2::positions;
+use futures::future::join_all;
+use irontrade::api::client::IronTradeClient;
+use irontrade::api::common::Order as IronTradeOrder;
+use num_decimal::Num;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Alpaca takes fractional quantities with up to 9 decimal places, partial closes round down so
+/// they never close more than asked for.
+const CLOSE_QUANTITY_ROUNDING: Rounding = Rounding::new(9, RoundingMode::Down);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AssetClass {
+    Equity,
+    Crypto,
+    Option,
+}
+
+impl AssetClass {
+    /// Guesses the class from an order symbol: crypto pairs contain a slash, options use OCC
+    /// symbols and anything else is taken for a stock.
+    pub fn of_symbol(symbol: &str) -> Self {
+        if symbol.contains('/') {
+            AssetClass::Crypto
+        } else if OptionContract::from_str(symbol).is_ok() {
+            AssetClass::Option
+        } else {
+            AssetClass::Equity
+        }
+    }
+}
+
+/// The parts of a held position that filters look at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionView {
+    pub asset_symbol: String,
+    pub asset_class: AssetClass,
+    pub quantity: Num,
+    /// Negative for short positions
+    pub cost_basis: Num,
+    pub market_value: Option<Num>,
+}
+
+impl PositionView {
+    /// Unrealized profit or loss as a percentage of the cost basis, `None` without a market value.
+    pub fn unrealized_percent(&self) -> Option<Num> {
+        let market_value = self.market_value.as_ref()?;
+        if self.cost_basis.is_zero() {
+            return None;
+        }
+        let cost = if self.cost_basis.is_negative() {
+            -&self.cost_basis
+        } else {
+            self.cost_basis.clone()
+        };
+        Some((market_value - &self.cost_basis) / cost * Num::from(100))
+    }
+}
+
+impl From<&Position> for PositionView {
+    fn from(position: &Position) -> Self {
+        let asset_symbol = position.symbol.to_string();
+        let asset_class = match position.asset_class {
+            Class::Crypto => AssetClass::Crypto,
+            _ if OptionContract::from_str(&asset_symbol).is_ok() => AssetClass::Option,
+            _ => AssetClass::Equity,
+        };
+        Self {
+            asset_symbol,
+            asset_class,
+            quantity: position.quantity.clone(),
+            cost_basis: position.cost_basis.clone(),
+            market_value: position.market_value.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PositionFilter {
+    AssetClass(AssetClass),
+    Symbols(Vec<String>),
+    /// Positions down more than this percentage, e.g. `5` for a 5% unrealized loss
+    UnrealizedLossBeyond(Num),
+    All(Vec<PositionFilter>),
+    Any(Vec<PositionFilter>),
+}
+
+impl PositionFilter {
+    pub fn matches(&self, position: &PositionView) -> bool {
+        match self {
+            PositionFilter::AssetClass(asset_class) => position.asset_class == *asset_class,
+            PositionFilter::Symbols(symbols) => symbols
+                .iter()
+                .any(|symbol| same_symbol(symbol, &position.asset_symbol)),
+            PositionFilter::UnrealizedLossBeyond(percent) => position
+                .unrealized_percent()
+                .is_some_and(|unrealized| unrealized < -percent),
+            PositionFilter::All(filters) => filters.iter().all(|filter| filter.matches(position)),
+            PositionFilter::Any(filters) => filters.iter().any(|filter| filter.matches(position)),
+        }
+    }
+
+    /// The filter with aliases in `Symbols` replaced by the Alpaca symbols they stand for.
+    fn resolve(&self, symbol_aliases: &SymbolAliases) -> PositionFilter {
+        match self {
+            PositionFilter::Symbols(symbols) => PositionFilter::Symbols(
+                symbols
+                    .iter()
+                    .map(|symbol| symbol_aliases.resolve(symbol).to_string())
+                    .collect(),
+            ),
+            PositionFilter::All(filters) => {
+                PositionFilter::All(filters.iter().map(|filter| filter.resolve(symbol_aliases)).collect())
+            }
+            PositionFilter::Any(filters) => {
+                PositionFilter::Any(filters.iter().map(|filter| filter.resolve(symbol_aliases)).collect())
+            }
+            filter => filter.clone(),
+        }
+    }
+}
+
+/// Quantity of a long position of `held` worth `notional` at `price`, capped at the whole
+/// position.
+pub fn notional_sell_quantity(notional: &Num, price: &Num, held: &Num) -> Result<Num> {
+    if !notional.is_positive() {
+        bail!("notional to sell must be positive, got {notional}");
+    }
+    if !price.is_positive() {
+        bail!("can't size a sell at a price of {price}");
+    }
+
+    let quantity = CLOSE_QUANTITY_ROUNDING.apply(&(notional / price));
+    if !quantity.is_positive() {
+        bail!("{notional} at {price} rounds down to nothing");
+    }
+    Ok(if quantity > *held { held.clone() } else { quantity })
+}
+
+/// How much of a position [`AlpacaClient::close_position`] closes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CloseAmount {
+    All,
+    /// Unsigned quantity, at most the size of the position
+    Quantity(Num),
+    /// Share of the position within (0, 100], e.g. `50` for half of it
+    Percentage(Num),
+}
+
+impl CloseAmount {
+    fn query(&self) -> Result<Vec<(&'static str, String)>> {
+        Ok(match self {
+            CloseAmount::All => Vec::new(),
+            CloseAmount::Quantity(quantity) => {
+                if !quantity.is_positive() {
+                    bail!("quantity to close must be positive, got {quantity}");
+                }
+                vec![("qty", format_quantity(&CLOSE_QUANTITY_ROUNDING.apply(quantity)))]
+            }
+            CloseAmount::Percentage(percent) => {
+                if !percent.is_positive() || *percent > Num::from(100) {
+                    bail!("close percentage must be within (0, 100], got {percent}");
+                }
+                vec![("percentage", format_quantity(percent))]
+            }
+        })
+    }
+}
+
+/// One entry of Alpaca's bulk close response, the closing order or the reason there's none.
+#[derive(Deserialize)]
+struct BulkClose {
+    symbol: String,
+    status: u16,
+    body: BulkCloseBody,
+}
+
+#[derive(Deserialize)]
+struct BulkCloseBody {
+    id: Option<String>,
+    code: Option<u64>,
+    message: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct PositionClose {
+    pub asset_symbol: String,
+    /// Id of the closing order
+    pub result: Result<String>,
+}
+
+impl AlpacaClient {
+    /// Closes all or part of the position in `asset_symbol` through Alpaca's position endpoint,
+    /// which sizes the closing market order itself. Returns the closing order.
+    pub async fn close_position(&self, asset_symbol: &str, amount: CloseAmount) -> Result<IronTradeOrder> {
+        let asset_symbol = self.symbol_aliases.resolve(asset_symbol);
+        let query = amount.query()?;
+        let action = AuditAction::ClosePosition {
+            asset_symbol: asset_symbol.to_string(),
+        };
+        let close = self.retrying(Idempotency::NonIdempotent, RetryOverride::Default, || {
+            self.delete_position(asset_symbol, &query)
+        });
+        let order = self
+            .audited(action, |order: &ApcaOrder| Some(order.id.to_string()), close)
+            .await?;
+        self.account_flight.invalidate().await;
+        self.order_tracker().track(order.id.to_string(), asset_symbol.to_string());
+
+        let Order(mut order) = order.into();
+        order.asset_symbol = self.symbol_aliases.alias(&order.asset_symbol).to_string();
+        Ok(order)
+    }
+
+    /// `DELETE /v2/positions/{symbol}` with the `qty` or `percentage` the apca request lacks.
+    async fn delete_position(&self, asset_symbol: &str, query: &[(&str, String)]) -> Result<ApcaOrder> {
+        let api_info = self.apca_client.api_info();
+        let url = api_info
+            .api_base_url
+            .join(&format!("v2/positions/{}", unslashed(asset_symbol)))?;
+
+        let response = self
+            .http_client
+            .delete(url.as_str())
+            .query(query)
+            .header("APCA-API-KEY-ID", &api_info.key_id)
+            .header("APCA-API-SECRET-KEY", &api_info.secret)
+            .send()
+            .await?;
+
+        let response = ResponseError::check(response)
+            .await
+            .with_context(|| format!("closing the position in {asset_symbol} failed"))?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Flattens the whole book with Alpaca's bulk close, optionally canceling every open order
+    /// first so none of them reopens a position. Returns the outcome for each position.
+    pub async fn close_all_positions(&self, cancel_orders: bool) -> Result<Vec<PositionClose>> {
+        let action = AuditAction::KillSwitch {
+            details: format!("close all positions, cancel_orders={cancel_orders}"),
+        };
+        let close = self.retrying(Idempotency::NonIdempotent, RetryOverride::Default, || {
+            self.delete_all_positions(cancel_orders)
+        });
+        let closes = self.audited(action, |_| None, close).await?;
+        self.account_flight.invalidate().await;
+
+        Ok(closes
+            .into_iter()
+            .map(|close| {
+                let asset_symbol = self.symbol_aliases.alias(&close.symbol).to_string();
+                let result = match close.body.id {
+                    Some(order_id) if (200..300).contains(&close.status) => {
+                        self.order_tracker().track(order_id.clone(), close.symbol);
+                        Ok(order_id)
+                    }
+                    _ => {
+                        let status = StatusCode::from_u16(close.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                        let error = ResponseError {
+                            status,
+                            code: close.body.code,
+                            message: close.body.message.unwrap_or_default(),
+                        };
+                        Err(anyhow::Error::new(error).context(format!("closing {asset_symbol} failed")))
+                    }
+                };
+                PositionClose { asset_symbol, result }
+            })
+            .collect())
+    }
+
+    async fn delete_all_positions(&self, cancel_orders: bool) -> Result<Vec<BulkClose>> {
+        let api_info = self.apca_client.api_info();
+        let url = api_info.api_base_url.join("v2/positions")?;
+
+        let response = self
+            .http_client
+            .delete(url.as_str())
+            .query(&[("cancel_orders", cancel_orders)])
+            .header("APCA-API-KEY-ID", &api_info.key_id)
+            .header("APCA-API-SECRET-KEY", &api_info.secret)
+            .send()
+            .await?;
+
+        let response = ResponseError::check(response).await.context("closing all positions failed")?;
+
+        Ok(response.json().await?)
+    }
+
+    pub(crate) async fn close_position_by_symbol(&self, asset_symbol: &str) -> Result<String> {
+        let order = self.close_position(asset_symbol, CloseAmount::All).await?;
+        Ok(order.order_id)
+    }
+
+    /// Closes `percent` of the position in `asset_symbol`, e.g. `50` to sell half of a long or
+    /// buy back half of a short. Alpaca sizes the closing market order against the position as
+    /// it stands when the close is placed. Returns the id of the closing order.
+    pub async fn close_position_pct(&self, asset_symbol: &str, percent: &Num) -> Result<String> {
+        let order = self
+            .close_position(asset_symbol, CloseAmount::Percentage(percent.clone()))
+            .await?;
+        Ok(order.order_id)
+    }
+
+    /// Sells `notional` worth of the long position in `asset_symbol` with a market order sized in
+    /// quantity from the latest bid, instead of a notional order that Alpaca rejects for
+    /// insufficient quantity once the price moves against it near the full position. Asking for
+    /// the position's value or more closes the whole position. Returns the id of the order.
+    pub async fn sell_notional(&self, asset_symbol: &str, notional: &Num) -> Result<String> {
+        let asset_symbol = self.symbol_aliases.resolve(asset_symbol);
+        let position = self.get_open_position(asset_symbol).await?;
+        if !position.quantity.is_positive() {
+            bail!("no long position in {asset_symbol} to sell");
+        }
+
+        let quotes = self.get_latest_quotes(&[asset_symbol.to_string()]).await?;
+        let Some(quote) = quotes.into_iter().next() else {
+            bail!("no latest quote for {asset_symbol}");
+        };
+        let quantity = notional_sell_quantity(notional, &quote.bid_price, &position.quantity)?;
+        if quantity == position.quantity {
+            return self.close_position_by_symbol(asset_symbol).await;
+        }
+
+        let time_in_force = self
+            .order_defaults
+            .for_class(AssetClass::of_symbol(asset_symbol))
+            .time_in_force;
+        let request = CreateReqInit {
+            type_: Type::Market,
+            time_in_force: time_in_force.into(),
+            ..Default::default()
+        }
+        .init(asset_symbol.to_string(), Side::Sell, Amount::Quantity { quantity });
+
+        self.submit_order(asset_symbol, &request, RetryOverride::Default).await
+    }
+
+    /// Closes every held position matching `filter` concurrently, e.g. all crypto before the
+    /// weekend, and returns the outcome for each of them.
+    pub async fn close_positions_where(&self, filter: &PositionFilter) -> Result<Vec<PositionClose>> {
+        let positions = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<positions::List>(&())
+            })
+            .await?;
+
+        let filter = filter.resolve(&self.symbol_aliases);
+        let closes = positions
+            .iter()
+            .map(PositionView::from)
+            .filter(|position| filter.matches(position))
+            .map(|position| async move {
+                let result = self.close_position_by_symbol(&position.asset_symbol).await;
+                PositionClose {
+                    asset_symbol: position.asset_symbol,
+                    result,
+                }
+            });
+
+        Ok(join_all(closes).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(asset_symbol: &str, asset_class: AssetClass, cost_basis: i64, market_value: i64) -> PositionView {
+        PositionView {
+            asset_symbol: asset_symbol.into(),
+            asset_class,
+            quantity: Num::from(1),
+            cost_basis: Num::from(cost_basis),
+            market_value: Some(Num::from(market_value)),
+        }
+    }
+
+    #[test]
+    fn computes_unrealized_percent_for_longs_and_shorts() {
+        let long = view("AAPL", AssetClass::Equity, 200, 190);
+        let short = view("TSLA", AssetClass::Equity, -200, -220);
+
+        assert_eq!(long.unrealized_percent(), Some(Num::from(-5)));
+        assert_eq!(short.unrealized_percent(), Some(Num::from(-10)));
+    }
+
+    #[test]
+    fn parses_bulk_close_results() -> Result<()> {
+        let closes: Vec<BulkClose> = serde_json::from_str(
+            r#"[
+                {"symbol":"AAPL","status":200,"body":{"id":"61e69015-8549-4bfd-b9c3-01e75843f47d","symbol":"AAPL"}},
+                {"symbol":"GME","status":403,"body":{"code":40310000,"message":"insufficient qty available"}}
+            ]"#,
+        )?;
+
+        assert_eq!(closes[0].body.id.as_deref(), Some("61e69015-8549-4bfd-b9c3-01e75843f47d"));
+        assert_eq!(closes[1].status, 403);
+        assert_eq!(closes[1].body.message.as_deref(), Some("insufficient qty available"));
+        Ok(())
+    }
+
+    #[test]
+    fn close_amounts_become_query_parameters() -> Result<()> {
+        assert!(CloseAmount::All.query()?.is_empty());
+        assert_eq!(CloseAmount::Quantity(Num::new(1, 3)).query()?, [("qty", "0.333333333".to_string())]);
+        assert_eq!(CloseAmount::Percentage(Num::from(25)).query()?, [("percentage", "25".to_string())]);
+        assert!(CloseAmount::Percentage(Num::from(150)).query().is_err());
+        assert!(CloseAmount::Quantity(Num::from(0)).query().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn notional_sell_is_capped_at_the_position() -> Result<()> {
+        let held = Num::from_str("0.5")?;
+        assert_eq!(notional_sell_quantity(&Num::from(100), &Num::from(400), &held)?, Num::from_str("0.25")?);
+        assert_eq!(notional_sell_quantity(&Num::from(201), &Num::from(400), &held)?, held);
+        assert!(notional_sell_quantity(&Num::from(100), &Num::from(0), &held).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn combines_filters() {
+        let filter = PositionFilter::Any(vec![
+            PositionFilter::AssetClass(AssetClass::Crypto),
+            PositionFilter::All(vec![
+                PositionFilter::Symbols(vec!["AAPL".into(), "TSLA".into()]),
+                PositionFilter::UnrealizedLossBeyond(Num::from(7)),
+            ]),
+        ]);
+
+        assert!(filter.matches(&view("BTC/USD", AssetClass::Crypto, 100, 120)));
+        assert!(!filter.matches(&view("AAPL", AssetClass::Equity, 200, 190)));
+        assert!(filter.matches(&view("TSLA", AssetClass::Equity, -200, -220)));
+        assert!(!filter.matches(&view("MSFT", AssetClass::Equity, 100, 50)));
+
+        // Positions report crypto pairs without the slash
+        let mut symbol_aliases = SymbolAliases::default();
+        symbol_aliases.insert("BTC", "BTC/USD");
+        let filter = PositionFilter::Symbols(vec!["BTC".into(), "ETH/USD".into()]).resolve(&symbol_aliases);
+        assert!(filter.matches(&view("BTCUSD", AssetClass::Crypto, 100, 120)));
+        assert!(filter.matches(&view("ETHUSD", AssetClass::Crypto, 100, 120)));
+        assert!(!filter.matches(&view("SOLUSD", AssetClass::Crypto, 100, 120)));
+    }
+}
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use num_decimal::Num;
+use std::sync::RwLock;
+
+static PRECISION_POLICY: RwLock<PrecisionPolicy> = RwLock::new(PrecisionPolicy::UNROUNDED);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round to nearest, ties away from zero
+    HalfUp,
+    /// Round to nearest, ties to the even neighbour (banker's rounding)
+    HalfEven,
+    /// Towards zero
+    Down,
+    /// Away from zero
+    Up,
+    /// Towards negative infinity
+    Floor,
+    /// Towards positive infinity
+    Ceiling,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rounding {
+    pub decimal_places: u32,
+    pub mode: RoundingMode,
+}
+
+impl Rounding {
+    pub const fn new(decimal_places: u32, mode: RoundingMode) -> Self {
+        Self { decimal_places, mode }
+    }
+
+    pub fn apply(&self, value: &Num) -> Num {
+        let scale = (0..self.decimal_places).fold(Num::from(1), |scale, _| scale * Num::from(10));
+        let scaled = value * &scale;
+        let truncated = scaled.trunc();
+        let remainder = &scaled - &truncated;
+
+        if remainder.is_zero() {
+            return value.clone();
+        }
+
+        let remainder = if remainder.is_negative() { -remainder } else { remainder };
+        let half = Num::new(1, 2);
+
+        let away_from_zero = match self.mode {
+            RoundingMode::HalfUp => remainder >= half,
+            RoundingMode::HalfEven => remainder > half || (remainder == half && !is_even(&truncated)),
+            RoundingMode::Down => false,
+            RoundingMode::Up => true,
+            RoundingMode::Floor => value.is_negative(),
+            RoundingMode::Ceiling => value.is_positive(),
+        };
+
+        let rounded = match (away_from_zero, value.is_negative()) {
+            (false, _) => truncated,
+            (true, false) => truncated + Num::from(1),
+            (true, true) => truncated - Num::from(1),
+        };
+
+        rounded / scale
+    }
+}
+
+fn is_even(integer: &Num) -> bool {
+    let half = integer / Num::from(2);
+    half.trunc() == half
+}
+
+/// How values coming back from Alpaca are rounded before they are handed out, per field class.
+/// `None` leaves that class of fields untouched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrecisionPolicy {
+    pub prices: Option<Rounding>,
+    pub quantities: Option<Rounding>,
+    pub notional: Option<Rounding>,
+}
+
+impl PrecisionPolicy {
+    pub const UNROUNDED: PrecisionPolicy = PrecisionPolicy {
+        prices: None,
+        quantities: None,
+        notional: None,
+    };
+
+    pub fn round_price(&self, price: Num) -> Num {
+        round(self.prices, price)
+    }
+
+    pub fn round_quantity(&self, quantity: Num) -> Num {
+        round(self.quantities, quantity)
+    }
+
+    pub fn round_notional(&self, notional: Num) -> Num {
+        round(self.notional, notional)
+    }
+}
+
+impl Default for PrecisionPolicy {
+    fn default() -> Self {
+        Self::UNROUNDED
+    }
+}
+
+fn round(rounding: Option<Rounding>, value: Num) -> Num {
+    match rounding {
+        Some(rounding) => rounding.apply(&value),
+        None => value,
+    }
+}
+
+/// Sets the policy applied by every conversion from Alpaca types.
+pub fn set_precision_policy(policy: PrecisionPolicy) {
+    *PRECISION_POLICY.write().unwrap_or_else(|error| error.into_inner()) = policy;
+}
+
+pub fn precision_policy() -> PrecisionPolicy {
+    *PRECISION_POLICY.read().unwrap_or_else(|error| error.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn rounds_with_each_mode() {
+        let cases = [
+            (RoundingMode::HalfUp, "1.25", "1.3"),
+            (RoundingMode::HalfUp, "-1.25", "-1.3"),
+            (RoundingMode::HalfEven, "1.25", "1.2"),
+            (RoundingMode::HalfEven, "1.35", "1.4"),
+            (RoundingMode::Down, "1.29", "1.2"),
+            (RoundingMode::Down, "-1.29", "-1.2"),
+            (RoundingMode::Up, "1.21", "1.3"),
+            (RoundingMode::Floor, "-1.21", "-1.3"),
+            (RoundingMode::Floor, "1.29", "1.2"),
+            (RoundingMode::Ceiling, "1.21", "1.3"),
+            (RoundingMode::Ceiling, "-1.29", "-1.2"),
+        ];
+
+        for (mode, value, expected) in cases {
+            let rounded = Rounding::new(1, mode).apply(&Num::from_str(value).unwrap());
+            assert_eq!(rounded, Num::from_str(expected).unwrap(), "{mode:?} {value}");
+        }
+    }
+
+    #[test]
+    fn rounds_rational_artifacts() {
+        let third = Num::new(1, 3);
+        let rounded = Rounding::new(4, RoundingMode::HalfUp).apply(&third);
+        assert_eq!(rounded, Num::from_str("0.3333").unwrap());
+    }
+
+    #[test]
+    fn unrounded_policy_keeps_values() {
+        let third = Num::new(1, 3);
+        assert_eq!(PrecisionPolicy::UNROUNDED.round_price(third.clone()), third);
+    }
+}
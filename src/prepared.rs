@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::audit::{AuditAction, AuditOutcome};
+use crate::budget::BudgetKind;
+use crate::client::{AlpacaClient, submit_action};
+use crate::convert::OrderSide;
+use crate::error::ResponseError;
+use crate::order_defaults::OrderOptions;
+use anyhow::{Context, Result};
+use irontrade::api::request::OrderRequest;
+use reqwest::Url;
+use serde::Deserialize;
+
+/// An order validated, resolved and serialized ahead of time, so that placing it is a single
+/// request with the body ready to go. Checks depending on live data, e.g. the spread guard or a
+/// marketable limit price, are as of [`AlpacaClient::prepare_order`], time-based ones run again
+/// when it's placed.
+#[derive(Clone, Debug)]
+pub struct PreparedOrder {
+    asset_symbol: String,
+    action: AuditAction,
+    url: Url,
+    body: String,
+}
+
+impl PreparedOrder {
+    pub fn asset_symbol(&self) -> &str {
+        &self.asset_symbol
+    }
+
+    /// The JSON body the order is submitted with.
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
+
+/// The only field of the created order the fast path reads.
+#[derive(Deserialize)]
+struct CreatedOrder {
+    id: String,
+}
+
+impl AlpacaClient {
+    /// Does everything [`AlpacaClient::place_order_with_options`] does before sending the order:
+    /// symbol resolution, defaults, validation, the compliance checks and serialization.
+    pub async fn prepare_order(&self, req: OrderRequest, options: OrderOptions) -> Result<PreparedOrder> {
+        let (asset_symbol, request) = self.create_request(req, options).await?;
+        let OrderSide(side) = request.side.into();
+        self.check_order_allowed(&asset_symbol, &side).await?;
+
+        Ok(PreparedOrder {
+            action: submit_action(&asset_symbol, &request),
+            url: self.apca_client.api_info().api_base_url.join("v2/orders")?,
+            body: serde_json::to_string(&request)?,
+            asset_symbol,
+        })
+    }
+
+    /// Places an order prepared with [`AlpacaClient::prepare_order`]. Only the trading schedule
+    /// and market data age are checked again, and the submission isn't retried. Returns the id
+    /// of the order.
+    pub async fn place_order_prepared(&self, prepared: &PreparedOrder) -> Result<String> {
+        if let Err(error) = self.check_still_allowed(&prepared.asset_symbol).await {
+            let _ = self.audit(
+                &prepared.action,
+                AuditOutcome::Failed {
+                    error: error.to_string(),
+                },
+            );
+            return Err(error);
+        }
+
+        let submit = self.send_prepared(prepared);
+        let order_id = self
+            .audited(prepared.action.clone(), |order_id: &String| Some(order_id.clone()), submit)
+            .await?;
+        self.account_flight.invalidate().await;
+        self.order_tracker()
+            .track(order_id.clone(), prepared.asset_symbol.clone());
+        Ok(order_id)
+    }
+
+    async fn check_still_allowed(&self, asset_symbol: &str) -> Result<()> {
+        self.check_trading_schedule().await?;
+        self.check_stale_data(asset_symbol)?;
+        Ok(())
+    }
+
+    async fn send_prepared(&self, prepared: &PreparedOrder) -> Result<String> {
+        self.acquire_budget(BudgetKind::Order).await?;
+        self.acquire_budget(BudgetKind::Request).await?;
+        let api_info = self.apca_client.api_info();
+        let response = self
+            .http_client
+            .post(prepared.url.clone())
+            .header("APCA-API-KEY-ID", &api_info.key_id)
+            .header("APCA-API-SECRET-KEY", &api_info.secret)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(prepared.body.clone())
+            .send()
+            .await?;
+
+        let response = ResponseError::check(response)
+            .await
+            .with_context(|| format!("submitting the order for {} failed", prepared.asset_symbol))?;
+
+        let created: CreatedOrder = response.json().await?;
+        Ok(created.id)
+    }
+}
+
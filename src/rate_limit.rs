@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::budget::TokenBucket;
+use crate::client::AlpacaClient;
+use serde::Deserialize;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Alpaca's limit on trading API requests per account.
+pub const ALPACA_REQUESTS_PER_MINUTE: u32 = 200;
+
+/// The `[rate_limit]` config table, without it the limit stays as set on the client,
+/// [`ALPACA_REQUESTS_PER_MINUTE`] by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: u32,
+}
+
+/// Paces the trading API requests of a client below the account's rate limit, so a burst from a
+/// strategy loop waits its turn instead of coming back as 429s. Every attempt of a retried call
+/// counts as a request.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    requests_per_minute: Option<u32>,
+    bucket: Mutex<Option<TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(requests_per_minute: Option<u32>) -> Self {
+        Self {
+            requests_per_minute,
+            bucket: Mutex::new(requests_per_minute.map(|requests_per_minute| {
+                TokenBucket::per_minute(f64::from(requests_per_minute))
+            })),
+        }
+    }
+
+    pub(crate) fn requests_per_minute(&self) -> Option<u32> {
+        self.requests_per_minute
+    }
+
+    fn try_acquire(&self) -> Result<(), Duration> {
+        match &mut *self.bucket.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(bucket) => bucket.try_take(),
+            None => Ok(()),
+        }
+    }
+
+    /// Share of the rate limit left to use right now, 1 without a limit.
+    pub(crate) fn headroom(&self) -> f64 {
+        match &mut *self.bucket.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(bucket) => bucket.headroom(),
+            None => 1.0,
+        }
+    }
+
+    /// Waits until a request may be sent.
+    pub(crate) async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire() {
+            sleep(wait).await;
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(Some(ALPACA_REQUESTS_PER_MINUTE))
+    }
+}
+
+impl AlpacaClient {
+    /// Limits trading API requests to `requests_per_minute`, [`ALPACA_REQUESTS_PER_MINUTE`] by
+    /// default. Lower it when other processes share the account, `None` turns pacing off.
+    pub fn set_rate_limit(&mut self, requests_per_minute: Option<u32>) {
+        self.rate_limiter = RateLimiter::new(requests_per_minute);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_up_to_the_limit() {
+        let rate_limiter = RateLimiter::new(Some(120));
+        for _ in 0..120 {
+            assert!(rate_limiter.try_acquire().is_ok());
+        }
+
+        let wait = rate_limiter.try_acquire().unwrap_err();
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+
+        assert!(RateLimiter::new(None).try_acquire().is_ok());
+    }
+}
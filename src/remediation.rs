@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::error::AlpacaError;
+use crate::precision::{Rounding, RoundingMode};
+use apca::api::v2::order::Amount as ApcaAmount;
+use num_decimal::Num;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+const QUANTITY_ROUNDING: Rounding = Rounding::new(9, RoundingMode::Down);
+const NOTIONAL_ROUNDING: Rounding = Rounding::new(2, RoundingMode::Down);
+
+/// Reasons Alpaca rejects an order that a policy can work around.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OrderRejection {
+    InsufficientBuyingPower,
+    /// The order could cross an open order of the account on the other side
+    WashTrade,
+}
+
+impl OrderRejection {
+    /// The rejection behind `error`, if it's one a policy can work around.
+    pub fn classify(error: &anyhow::Error) -> Option<Self> {
+        match AlpacaError::classify(error) {
+            AlpacaError::InsufficientBuyingPower => Some(OrderRejection::InsufficientBuyingPower),
+            AlpacaError::WashTrade => Some(OrderRejection::WashTrade),
+            _ => None,
+        }
+    }
+}
+
+impl Display for OrderRejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderRejection::InsufficientBuyingPower => write!(f, "insufficient buying power"),
+            OrderRejection::WashTrade => write!(f, "potential wash trade"),
+        }
+    }
+}
+
+impl std::error::Error for OrderRejection {}
+
+/// What to do before resubmitting a rejected order. The order is resubmitted once, a second
+/// rejection is returned to the caller.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Remediation {
+    /// Shrink the quantity or notional by `percent`, rounded down
+    Downsize { percent: Num },
+    /// Wait before resubmitting unchanged
+    Delay { seconds: u64 },
+}
+
+impl Remediation {
+    /// The amount to resubmit, `None` if the policy leaves it unchanged or nothing would be left.
+    pub fn downsize(&self, amount: &ApcaAmount) -> Option<ApcaAmount> {
+        let Remediation::Downsize { percent } = self else {
+            return None;
+        };
+        let factor = Num::from(1) - percent / Num::from(100);
+        let amount = match amount {
+            ApcaAmount::Quantity { quantity } => ApcaAmount::Quantity {
+                quantity: QUANTITY_ROUNDING.apply(&(quantity * &factor)),
+            },
+            ApcaAmount::Notional { notional } => ApcaAmount::Notional {
+                notional: NOTIONAL_ROUNDING.apply(&(notional * &factor)),
+            },
+        };
+        match &amount {
+            ApcaAmount::Quantity { quantity: value } | ApcaAmount::Notional { notional: value }
+                if value.is_positive() =>
+            {
+                Some(amount)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn delay(&self) -> Option<Duration> {
+        match self {
+            Remediation::Delay { seconds } => Some(Duration::from_secs(*seconds)),
+            Remediation::Downsize { .. } => None,
+        }
+    }
+}
+
+/// Remediation by rejection, rejections without a policy are returned as they are.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(default, deny_unknown_fields)]
+pub struct RemediationPolicies {
+    pub insufficient_buying_power: Option<Remediation>,
+    pub wash_trade: Option<Remediation>,
+}
+
+impl RemediationPolicies {
+    pub fn policy(&self, rejection: OrderRejection) -> Option<&Remediation> {
+        match rejection {
+            OrderRejection::InsufficientBuyingPower => self.insufficient_buying_power.as_ref(),
+            OrderRejection::WashTrade => self.wash_trade.as_ref(),
+        }
+    }
+}
+
+impl AlpacaClient {
+    pub fn set_remediation_policies(&mut self, remediation_policies: RemediationPolicies) {
+        self.remediation_policies = remediation_policies;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn classifies_and_downsizes() {
+        let error = anyhow!("insufficient buying power").context("failed to submit order");
+        assert_eq!(
+            OrderRejection::classify(&error),
+            Some(OrderRejection::InsufficientBuyingPower)
+        );
+        assert_eq!(OrderRejection::classify(&anyhow!("market is closed")), None);
+
+        let downsize = Remediation::Downsize {
+            percent: Num::from(25),
+        };
+        assert_eq!(
+            downsize.downsize(&ApcaAmount::Notional {
+                notional: Num::new(1001, 10),
+            }),
+            Some(ApcaAmount::Notional {
+                notional: Num::new(7507, 100),
+            })
+        );
+        assert_eq!(
+            downsize.downsize(&ApcaAmount::Quantity {
+                quantity: Num::new(1, 1_000_000_000),
+            }),
+            None
+        );
+        assert_eq!(
+            Remediation::Delay { seconds: 5 }.delay(),
+            Some(Duration::from_secs(5))
+        );
+    }
+}
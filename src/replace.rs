@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::audit::AuditAction;
+use crate::client::AlpacaClient;
+use crate::convert::OrderId;
+use crate::order_defaults::{OrderTimeInForce, check_time_in_force};
+use crate::positions::AssetClass;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::{Result, bail};
+use apca::api::v2::order::{self, ChangeReq, TimeInForce};
+use num_decimal::Num;
+
+/// Fields to change on an open order, `None` keeps the current value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderChange {
+    pub quantity: Option<Num>,
+    pub limit_price: Option<Num>,
+    pub stop_price: Option<Num>,
+    pub time_in_force: Option<OrderTimeInForce>,
+}
+
+impl OrderChange {
+    pub fn is_empty(&self) -> bool {
+        self == &OrderChange::default()
+    }
+
+    /// Combines two changes, with the fields set in `newer` taking precedence.
+    pub fn merge(self, newer: OrderChange) -> OrderChange {
+        OrderChange {
+            quantity: newer.quantity.or(self.quantity),
+            limit_price: newer.limit_price.or(self.limit_price),
+            stop_price: newer.stop_price.or(self.stop_price),
+            time_in_force: newer.time_in_force.or(self.time_in_force),
+        }
+    }
+}
+
+impl From<OrderChange> for ChangeReq {
+    fn from(change: OrderChange) -> Self {
+        ChangeReq {
+            quantity: change.quantity,
+            limit_price: change.limit_price,
+            stop_price: change.stop_price,
+            time_in_force: change.time_in_force.map(TimeInForce::from),
+            ..Default::default()
+        }
+    }
+}
+
+impl AlpacaClient {
+    /// Amends the quantity, limit or stop price, or time in force of a resting order through
+    /// Alpaca's replace endpoint, so there's no window in which the order is canceled but its
+    /// replacement not placed yet. Returns the id of the replacement order, the original one
+    /// ends up replaced.
+    pub async fn replace_order(&self, order_id: &str, change: OrderChange) -> Result<String> {
+        if change.is_empty() {
+            bail!("nothing to change on order {order_id}");
+        }
+        if let Some(time_in_force) = change.time_in_force {
+            let tracked_symbol = self.order_tracker().get(order_id).map(|order| order.asset_symbol.clone());
+            let asset_symbol = match tracked_symbol {
+                Some(asset_symbol) => asset_symbol,
+                None => self.fetch_apca_order(order_id).await?.symbol,
+            };
+            check_time_in_force(AssetClass::of_symbol(&asset_symbol), time_in_force)?;
+        }
+
+        self.change_order(order_id, change).await
+    }
+
+    /// Replaces an open order in place, returning the id of the replacement order.
+    pub(crate) async fn change_order(&self, order_id: &str, change: OrderChange) -> Result<String> {
+        let OrderId(id) = order_id.try_into()?;
+        let action = AuditAction::Modify {
+            order_id: order_id.to_string(),
+            details: format!("{change:?}"),
+        };
+        let request = (id, ChangeReq::from(change));
+        let replace = async {
+            let order = self
+                .retrying(Idempotency::NonIdempotent, RetryOverride::Default, || {
+                    self.apca_client.issue::<order::Change>(&request)
+                })
+                .await?;
+            Ok(order.id.to_string())
+        };
+        let new_order_id = self
+            .audited(action, |order_id: &String| Some(order_id.clone()), replace)
+            .await?;
+        self.account_flight.invalidate().await;
+
+        self.order_tracker().replace(order_id, new_order_id.clone());
+
+        Ok(new_order_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_change_takes_precedence() {
+        let older = OrderChange {
+            quantity: Some(Num::from(5)),
+            limit_price: Some(Num::from(100)),
+            stop_price: None,
+            time_in_force: Some(OrderTimeInForce::Day),
+        };
+        let newer = OrderChange {
+            limit_price: Some(Num::from(101)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            older.merge(newer),
+            OrderChange {
+                quantity: Some(Num::from(5)),
+                limit_price: Some(Num::from(101)),
+                stop_price: None,
+                time_in_force: Some(OrderTimeInForce::Day),
+            }
+        );
+    }
+}
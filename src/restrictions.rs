@@ -0,0 +1,90 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+/// Symbols the client may or may not trade, checked locally on every order. Symbols are compared
+/// case-insensitively and crypto pairs are written as `BTC/USD`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(default, deny_unknown_fields)]
+pub struct SymbolRestrictions {
+    pub deny: BTreeSet<String>,
+    /// When set, only these symbols can be traded
+    pub allow: Option<BTreeSet<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestrictedSymbolError {
+    Denied(String),
+    NotAllowed(String),
+}
+
+impl Display for RestrictedSymbolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RestrictedSymbolError::Denied(symbol) => write!(f, "{symbol} is on the restricted symbol list"),
+            RestrictedSymbolError::NotAllowed(symbol) => write!(f, "{symbol} is not on the allowed symbol list"),
+        }
+    }
+}
+
+impl std::error::Error for RestrictedSymbolError {}
+
+fn contains(symbols: &BTreeSet<String>, symbol: &str) -> bool {
+    symbols.iter().any(|candidate| candidate.eq_ignore_ascii_case(symbol))
+}
+
+impl SymbolRestrictions {
+    pub fn check(&self, symbol: &str) -> Result<(), RestrictedSymbolError> {
+        if contains(&self.deny, symbol) {
+            return Err(RestrictedSymbolError::Denied(symbol.to_string()));
+        }
+        if let Some(allow) = &self.allow
+            && !contains(allow, symbol)
+        {
+            return Err(RestrictedSymbolError::NotAllowed(symbol.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl AlpacaClient {
+    pub fn set_symbol_restrictions(&mut self, symbol_restrictions: SymbolRestrictions) {
+        self.symbol_restrictions = symbol_restrictions;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(symbols: &[&str]) -> BTreeSet<String> {
+        symbols.iter().map(|symbol| symbol.to_string()).collect()
+    }
+
+    #[test]
+    fn deny_list_wins_over_allow_list() {
+        let restrictions = SymbolRestrictions {
+            deny: symbols(&["AAPL"]),
+            allow: Some(symbols(&["AAPL", "BTC/USD"])),
+        };
+
+        assert_eq!(
+            restrictions.check("aapl"),
+            Err(RestrictedSymbolError::Denied("aapl".into()))
+        );
+        assert_eq!(restrictions.check("BTC/USD"), Ok(()));
+        assert_eq!(
+            restrictions.check("ETH/USD"),
+            Err(RestrictedSymbolError::NotAllowed("ETH/USD".into()))
+        );
+    }
+
+    #[test]
+    fn no_restrictions_allow_everything() {
+        assert_eq!(SymbolRestrictions::default().check("TSLA"), Ok(()));
+    }
+}
@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::budget::BudgetKind;
+use crate::client::AlpacaClient;
+use crate::error::AlpacaError;
+use anyhow::Result;
+use std::cmp::min;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+use tokio::time::sleep;
+
+/// Whether repeating a call can have a different effect than issuing it once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Idempotency {
+    /// Reads and cancels, safe to repeat
+    Idempotent,
+    /// Order creation without a client order id, a retry after a lost response may duplicate it
+    NonIdempotent,
+}
+
+/// Per-call override of the retry behavior derived from the call's [`Idempotency`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RetryOverride {
+    #[default]
+    Default,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one, 1 disables retries
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Waits a random duration between half and all of the backoff, so clients failing together
+    /// don't retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        min(self.initial_backoff.saturating_mul(factor), self.max_backoff)
+    }
+
+    /// The wait before the attempt after `attempt`, with jitter if enabled.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        if !self.jitter {
+            return backoff;
+        }
+        backoff / 2 + (backoff / 2).mul_f64(random_fraction())
+    }
+}
+
+/// A number in `[0, 1)`, from the randomly keyed std hasher since nothing here needs more than
+/// spreading retries apart.
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Rate limiting, server errors and broken connections are worth another attempt, anything else
+/// (validation, auth, insufficient funds) will fail the same way again.
+pub(crate) fn is_transient(error: &anyhow::Error) -> bool {
+    matches!(
+        AlpacaError::classify(error),
+        AlpacaError::RateLimited | AlpacaError::Server | AlpacaError::Network
+    )
+}
+
+impl AlpacaClient {
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    pub(crate) async fn retrying<T, E, F, Fut>(
+        &self,
+        idempotency: Idempotency,
+        retry_override: RetryOverride,
+        mut operation: F,
+    ) -> Result<T>
+    where
+        E: Into<anyhow::Error>,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let may_retry = match retry_override {
+            RetryOverride::Default => idempotency == Idempotency::Idempotent,
+            RetryOverride::Always => true,
+            RetryOverride::Never => false,
+        };
+
+        let mut attempt = 1;
+        loop {
+            self.acquire_budget(BudgetKind::Request).await?;
+            self.rate_limiter.acquire().await;
+            let error = match operation().await {
+                Ok(output) => return Ok(output),
+                Err(error) => error.into(),
+            };
+
+            if !may_retry || attempt >= self.retry_policy.max_attempts || !is_transient(&error) {
+                if attempt > 1 {
+                    return Err(error.context(format!("failed after {attempt} attempts")));
+                }
+                return Err(error);
+            }
+
+            sleep(self.retry_policy.delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ResponseError;
+    use apca::RequestError;
+    use apca::api::v2::order;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            jitter: false,
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(4), Duration::from_millis(500));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+
+        let jittered = RetryPolicy {
+            jitter: true,
+            ..policy
+        };
+        for _ in 0..100 {
+            let delay = jittered.delay(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+    }
+
+    fn response(status: StatusCode, body: &str) -> anyhow::Error {
+        anyhow::Error::new(ResponseError::new(status, body.into())).context("submitting the order for AAPL failed")
+    }
+
+    #[test]
+    fn classifies_transient_errors() {
+        let reset = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(is_transient(&anyhow::Error::from(reset)));
+        assert!(is_transient(&response(StatusCode::SERVICE_UNAVAILABLE, "")));
+        let unavailable = RequestError::Endpoint(order::GetError::UnexpectedStatus(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Err(Vec::new()),
+        ));
+        assert!(is_transient(&anyhow::Error::new(unavailable)));
+        assert!(!is_transient(&response(
+            StatusCode::FORBIDDEN,
+            r#"{"code":40310000,"message":"insufficient buying power"}"#
+        )));
+        assert!(!is_transient(&response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            r#"{"code":42210000,"message":"qty 500 Internal Server Error is invalid"}"#
+        )));
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "no audit log");
+        assert!(!is_transient(&anyhow::Error::from(missing)));
+    }
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::data::{Bar, TimeFrame};
+use crate::stream::{MarketDataEvent, MarketDataFeed, MarketDataSubscription};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use futures::stream::{self, LocalBoxStream};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+/// Alpaca publishes a bar a few seconds after it closes.
+const BAR_PUBLISH_DELAY: Duration = Duration::from_secs(3);
+
+/// Start of the bar `time` falls in, bars are aligned to multiples of their time frame.
+fn bar_start(time: DateTime<Utc>, time_frame: TimeFrame) -> DateTime<Utc> {
+    let length = time_frame.duration().num_seconds();
+    let start = time.timestamp().div_euclid(length) * length;
+    DateTime::from_timestamp(start, 0).unwrap_or(time)
+}
+
+/// Picks the bars that closed by `now` and weren't delivered yet, oldest first and each once, and
+/// moves each symbol's cursor past them.
+fn take_completed(
+    bars: Vec<Bar>,
+    cursors: &mut HashMap<String, DateTime<Utc>>,
+    time_frame: TimeFrame,
+    now: DateTime<Utc>,
+) -> Vec<Bar> {
+    let mut completed: Vec<Bar> = bars
+        .into_iter()
+        .filter(|bar| bar.time + time_frame.duration() <= now)
+        .filter(|bar| cursors.get(&bar.symbol).is_none_or(|cursor| bar.time >= *cursor))
+        .collect();
+    completed.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.symbol.cmp(&b.symbol)));
+    // A streamed bar may also come back from the backfill
+    completed.dedup_by(|a, b| a.symbol == b.symbol && a.time == b.time);
+
+    for bar in &completed {
+        cursors.insert(bar.symbol.clone(), bar.time + time_frame.duration());
+    }
+    completed
+}
+
+/// Moves the cursors of `symbols` up to the bar `in_progress`, past closed bars that had no trades
+/// and so will never be published.
+fn skip_empty_bars(symbols: &[String], cursors: &mut HashMap<String, DateTime<Utc>>, in_progress: DateTime<Utc>) {
+    for symbol in symbols {
+        if let Some(cursor) = cursors.get_mut(symbol) {
+            *cursor = (*cursor).max(in_progress);
+        }
+    }
+}
+
+impl AlpacaClient {
+    /// Feeds every completed bar of `symbols` to `handler` as it closes, until `cancellation`
+    /// fires or the handler fails. Minute bars of stocks come live from the market data stream of
+    /// `feed`. Everything else, and whatever the stream missed, is fetched over REST once the bar
+    /// closes, so bars skipped by a reconnect are backfilled in order. A failed fetch is asked
+    /// again the next round. The first bar delivered is the one in progress when the loop starts.
+    ///
+    /// The handler gets the client by shared reference, orders can be placed through
+    /// [`AlpacaClient::strategy`].
+    pub async fn run_on_bars(
+        &self,
+        symbols: &[String],
+        time_frame: TimeFrame,
+        feed: MarketDataFeed,
+        cancellation: &CancellationToken,
+        mut handler: impl AsyncFnMut(&AlpacaClient, Bar) -> Result<()>,
+    ) -> Result<()> {
+        let first_bar = bar_start(Utc::now(), time_frame);
+        let mut cursors: HashMap<String, DateTime<Utc>> =
+            symbols.iter().map(|symbol| (symbol.clone(), first_bar)).collect();
+
+        // Alpaca streams minute bars of stocks only
+        let subscription = MarketDataSubscription {
+            bars: symbols.iter().filter(|symbol| !symbol.contains('/')).cloned().collect(),
+            ..Default::default()
+        };
+        let mut live_bars: LocalBoxStream<'_, MarketDataEvent> =
+            if time_frame == TimeFrame::OneMinute && !subscription.bars.is_empty() {
+                self.stream_market_data(feed, &subscription).await?.boxed_local()
+            } else {
+                stream::pending().boxed_local()
+            };
+
+        loop {
+            let now = Utc::now();
+            let next_close = bar_start(now, time_frame) + time_frame.duration();
+            let wait = (next_close - now).to_std().unwrap_or_default() + BAR_PUBLISH_DELAY;
+
+            // The bars to deliver and the symbols fetched up to the bar in progress
+            let (bars, fetched) = tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => return Ok(()),
+                Some(event) = live_bars.next() => match event {
+                    MarketDataEvent::Bar(bar) => match cursors.get(&bar.symbol) {
+                        Some(cursor) if bar.time == *cursor => (vec![bar], None),
+                        // Bars were missed before this one
+                        Some(cursor) if bar.time > *cursor => {
+                            match self.backfill(&[bar.symbol.clone()], &cursors, time_frame).await {
+                                Some(bars) => (bars.into_iter().chain([bar]).collect(), None),
+                                None => continue,
+                            }
+                        }
+                        _ => continue,
+                    },
+                    MarketDataEvent::Reconnected => match self.backfill(symbols, &cursors, time_frame).await {
+                        Some(bars) => (bars, None),
+                        None => continue,
+                    },
+                    _ => continue,
+                },
+                _ = sleep(wait) => {
+                    let in_progress = bar_start(Utc::now(), time_frame);
+                    let behind: Vec<String> = symbols
+                        .iter()
+                        .filter(|symbol| cursors.get(*symbol).is_some_and(|cursor| *cursor < in_progress))
+                        .cloned()
+                        .collect();
+                    if behind.is_empty() {
+                        continue;
+                    }
+                    match self.backfill(&behind, &cursors, time_frame).await {
+                        Some(bars) => (bars, Some((behind, in_progress))),
+                        None => continue,
+                    }
+                }
+            };
+
+            let completed = take_completed(bars, &mut cursors, time_frame, Utc::now());
+            if let Some((symbols, in_progress)) = fetched {
+                skip_empty_bars(&symbols, &mut cursors, in_progress);
+            }
+            for bar in completed {
+                if cancellation.is_cancelled() {
+                    return Ok(());
+                }
+                handler(self, bar).await?;
+            }
+        }
+    }
+
+    /// The bars of `symbols` from the oldest of their cursors until now, `None` if the request
+    /// failed. The cursors stay where they are then, so the next round asks again.
+    async fn backfill(
+        &self,
+        symbols: &[String],
+        cursors: &HashMap<String, DateTime<Utc>>,
+        time_frame: TimeFrame,
+    ) -> Option<Vec<Bar>> {
+        let start = symbols.iter().filter_map(|symbol| cursors.get(symbol)).min().copied()?;
+        self.get_bars_of(symbols, time_frame, start, Utc::now()).await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone};
+    use num_decimal::Num;
+
+    fn bar(symbol: &str, minute: u32) -> Bar {
+        Bar {
+            symbol: symbol.into(),
+            exchange: None,
+            time: Utc.with_ymd_and_hms(2024, 1, 18, 15, minute, 0).unwrap(),
+            open: Num::from(1),
+            high: Num::from(1),
+            low: Num::from(1),
+            close: Num::from(1),
+            volume: Num::from(1),
+            vwap: None,
+        }
+    }
+
+    #[test]
+    fn aligns_bar_starts() {
+        let time = Utc.with_ymd_and_hms(2024, 1, 18, 15, 7, 42).unwrap();
+        assert_eq!(bar_start(time, TimeFrame::FiveMinutes), Utc.with_ymd_and_hms(2024, 1, 18, 15, 5, 0).unwrap());
+        assert_eq!(bar_start(time, TimeFrame::OneHour), Utc.with_ymd_and_hms(2024, 1, 18, 15, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn delivers_each_completed_bar_once() {
+        let mut cursors = HashMap::from([("AAPL".to_string(), bar("AAPL", 1).time)]);
+        let now = Utc.with_ymd_and_hms(2024, 1, 18, 15, 3, 30).unwrap();
+
+        let bars = vec![bar("AAPL", 3), bar("AAPL", 0), bar("AAPL", 2), bar("AAPL", 1)];
+        let completed = take_completed(bars.clone(), &mut cursors, TimeFrame::OneMinute, now);
+
+        let minutes: Vec<_> = completed.iter().map(|bar| bar.time).collect();
+        assert_eq!(minutes, [bar("AAPL", 1).time, bar("AAPL", 2).time]);
+        assert!(take_completed(bars, &mut cursors, TimeFrame::OneMinute, now).is_empty());
+
+        // The same bar streamed and backfilled
+        let completed = take_completed(
+            vec![bar("AAPL", 3), bar("AAPL", 3)],
+            &mut cursors,
+            TimeFrame::OneMinute,
+            now + TimeDelta::minutes(1),
+        );
+        assert_eq!(completed.len(), 1);
+    }
+
+    #[test]
+    fn skips_bars_without_trades() {
+        let mut cursors = HashMap::from([
+            ("AAPL".to_string(), bar("AAPL", 1).time),
+            ("THIN".to_string(), bar("THIN", 1).time),
+        ]);
+        let now = Utc.with_ymd_and_hms(2024, 1, 18, 15, 4, 30).unwrap();
+
+        let bars = vec![bar("AAPL", 1), bar("AAPL", 2), bar("AAPL", 3)];
+        let completed = take_completed(bars, &mut cursors, TimeFrame::OneMinute, now);
+        let symbols = ["AAPL".to_string(), "THIN".to_string()];
+        skip_empty_bars(&symbols, &mut cursors, bar_start(now, TimeFrame::OneMinute));
+
+        assert_eq!(completed.len(), 3);
+        // Without a bar since the first one, the next round still starts at the bar in progress
+        assert_eq!(cursors["THIN"], bar("THIN", 4).time);
+        assert_eq!(cursors["AAPL"], bar("AAPL", 4).time);
+    }
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::env::{DEFAULT_API_BASE_URL, EnvVars};
+use anyhow::{Context, Result};
+use apca::{ApiInfo, Client};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Clone, Deserialize)]
+pub struct Credentials {
+    pub key_id: String,
+    pub secret: String,
+    /// Falls back to the paper trading API when unset
+    #[serde(default)]
+    pub api_base_url: Option<String>,
+}
+
+impl Credentials {
+    pub fn api_info(&self) -> Result<ApiInfo> {
+        let api_base_url = self.api_base_url.as_deref().unwrap_or(DEFAULT_API_BASE_URL);
+        let api_info = ApiInfo::from_parts(api_base_url, &self.key_id, &self.secret)
+            .with_context(|| format!("invalid api base url {api_base_url}"))?;
+        Ok(api_info)
+    }
+}
+
+/// Source of the Alpaca credentials, consulted when the client is built and whenever
+/// [`AlpacaClient::refresh_credentials`] is called after a key rotation.
+#[allow(async_fn_in_trait)]
+pub trait SecretProvider {
+    async fn credentials(&self) -> Result<Credentials>;
+}
+
+pub struct EnvSecretProvider(pub EnvVars);
+
+impl SecretProvider for EnvSecretProvider {
+    async fn credentials(&self) -> Result<Credentials> {
+        let api_info = self.0.api_info()?;
+        Ok(Credentials {
+            key_id: api_info.key_id,
+            secret: api_info.secret,
+            api_base_url: Some(api_info.api_base_url.to_string()),
+        })
+    }
+}
+
+/// Reads a TOML file with `key_id`, `secret` and optionally `api_base_url` entries, re-read on
+/// every refresh so rotated keys are picked up.
+pub struct FileSecretProvider(pub PathBuf);
+
+impl SecretProvider for FileSecretProvider {
+    async fn credentials(&self) -> Result<Credentials> {
+        let toml = fs::read_to_string(&self.0).with_context(|| format!("failed to read {}", self.0.display()))?;
+        toml::from_str(&toml).with_context(|| format!("invalid credentials in {}", self.0.display()))
+    }
+}
+
+/// Reads the credentials from a HashiCorp Vault KV v2 secret holding `key_id`, `secret` and
+/// optionally `api_base_url` fields.
+#[cfg(feature = "vault")]
+pub struct VaultSecretProvider {
+    pub address: String,
+    pub token: String,
+    pub mount: String,
+    pub path: String,
+}
+
+#[cfg(feature = "vault")]
+impl SecretProvider for VaultSecretProvider {
+    async fn credentials(&self) -> Result<Credentials> {
+        #[derive(Deserialize)]
+        struct KvData {
+            data: Credentials,
+        }
+
+        #[derive(Deserialize)]
+        struct KvResponse {
+            data: KvData,
+        }
+
+        let url = format!(
+            "{}/v1/{}/data/{}",
+            self.address.trim_end_matches('/'),
+            self.mount,
+            self.path
+        );
+        let response: KvResponse = reqwest::Client::new()
+            .get(&url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("failed to read vault secret {}/{}", self.mount, self.path))?
+            .json()
+            .await?;
+
+        Ok(response.data.data)
+    }
+}
+
+/// Reads the credentials from an AWS Secrets Manager secret whose string value is a JSON object
+/// with `key_id`, `secret` and optionally `api_base_url` fields.
+#[cfg(feature = "aws-secrets-manager")]
+pub struct AwsSecretsManagerProvider {
+    pub secret_id: String,
+}
+
+#[cfg(feature = "aws-secrets-manager")]
+impl SecretProvider for AwsSecretsManagerProvider {
+    async fn credentials(&self) -> Result<Credentials> {
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let secret = aws_sdk_secretsmanager::Client::new(&config)
+            .get_secret_value()
+            .secret_id(&self.secret_id)
+            .send()
+            .await
+            .with_context(|| format!("failed to read secret {}", self.secret_id))?;
+
+        let secret_string = secret
+            .secret_string()
+            .with_context(|| format!("secret {} has no string value", self.secret_id))?;
+
+        Ok(serde_json::from_str(secret_string)?)
+    }
+}
+
+impl AlpacaClient {
+    pub async fn from_secret_provider(provider: &impl SecretProvider) -> Result<Self> {
+        let credentials = provider.credentials().await?;
+        Ok(Self::new(credentials.api_info()?))
+    }
+
+    /// Fetches the credentials again and swaps the underlying connection, keeping all local state.
+    pub async fn refresh_credentials(&mut self, provider: &impl SecretProvider) -> Result<()> {
+        let credentials = provider.credentials().await?;
+        self.apca_client = Client::new(credentials.api_info()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::unique_temp_path;
+
+    #[tokio::test]
+    async fn file_provider_reads_toml() -> Result<()> {
+        let path = unique_temp_path("credentials.toml");
+        fs::write(&path, "key_id = \"key\"\nsecret = \"secret\"\n")?;
+
+        let credentials = FileSecretProvider(path.clone()).credentials().await;
+        fs::remove_file(&path)?;
+        let credentials = credentials?;
+
+        assert_eq!(credentials.key_id, "key");
+        assert_eq!(credentials.secret, "secret");
+        assert!(credentials.api_info()?.api_base_url.to_string().contains("paper"));
+
+        Ok(())
+    }
+}
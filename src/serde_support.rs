@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Serialization of the irontrade types embedded in the crate's own reports, which irontrade
+//! doesn't provide itself. Used through `#[serde(with = ...)]`.
+
+use irontrade::api::common::{
+    Amount, OpenPosition as IronTradeOpenPosition, Order as IronTradeOrder, OrderSide, OrderStatus,
+    OrderType,
+};
+use num_decimal::Num;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SideDef {
+    Buy,
+    Sell,
+}
+
+impl From<&OrderSide> for SideDef {
+    fn from(side: &OrderSide) -> Self {
+        match side {
+            OrderSide::Buy => SideDef::Buy,
+            OrderSide::Sell => SideDef::Sell,
+        }
+    }
+}
+
+impl From<SideDef> for OrderSide {
+    fn from(side: SideDef) -> Self {
+        match side {
+            SideDef::Buy => OrderSide::Buy,
+            SideDef::Sell => OrderSide::Sell,
+        }
+    }
+}
+
+pub(crate) mod order_side {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        side: &OrderSide,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        SideDef::from(side).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OrderSide, D::Error> {
+        Ok(SideDef::deserialize(deserializer)?.into())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenPositionDef {
+    asset_symbol: String,
+    average_entry_price: Option<Num>,
+    quantity: Num,
+    market_value: Option<Num>,
+}
+
+pub(crate) mod open_positions {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        positions: &[IronTradeOpenPosition],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(positions.iter().map(|position| OpenPositionDef {
+            asset_symbol: position.asset_symbol.clone(),
+            average_entry_price: position.average_entry_price.clone(),
+            quantity: position.quantity.clone(),
+            market_value: position.market_value.clone(),
+        }))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<IronTradeOpenPosition>, D::Error> {
+        let positions = Vec::<OpenPositionDef>::deserialize(deserializer)?;
+        Ok(positions
+            .into_iter()
+            .map(|position| IronTradeOpenPosition {
+                asset_symbol: position.asset_symbol,
+                average_entry_price: position.average_entry_price,
+                quantity: position.quantity,
+                market_value: position.market_value,
+            })
+            .collect())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AmountDef {
+    Quantity { quantity: Num },
+    Notional { notional: Num },
+}
+
+#[derive(Serialize, Deserialize)]
+struct OrderDef {
+    order_id: String,
+    asset_symbol: String,
+    filled_quantity: Num,
+    amount: AmountDef,
+    average_fill_price: Option<Num>,
+    /// Variant name, e.g. `PartiallyFilled`
+    status: String,
+    /// Variant name, e.g. `StopLimit`
+    type_: String,
+    limit_price: Option<Num>,
+    side: SideDef,
+}
+
+fn order_status(name: &str) -> Option<OrderStatus> {
+    Some(match name {
+        "New" => OrderStatus::New,
+        "PartiallyFilled" => OrderStatus::PartiallyFilled,
+        "Filled" => OrderStatus::Filled,
+        "Expired" => OrderStatus::Expired,
+        "Unimplemented" => OrderStatus::Unimplemented,
+        _ => return None,
+    })
+}
+
+fn order_type(name: &str) -> Option<OrderType> {
+    Some(match name {
+        "Market" => OrderType::Market,
+        "Limit" => OrderType::Limit,
+        "Stop" => OrderType::Stop,
+        "StopLimit" => OrderType::StopLimit,
+        _ => return None,
+    })
+}
+
+pub(crate) mod orders {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        orders: &[IronTradeOrder],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(orders.iter().map(|order| OrderDef {
+            order_id: order.order_id.clone(),
+            asset_symbol: order.asset_symbol.clone(),
+            filled_quantity: order.filled_quantity.clone(),
+            amount: match &order.amount {
+                Amount::Quantity { quantity } => AmountDef::Quantity {
+                    quantity: quantity.clone(),
+                },
+                Amount::Notional { notional } => AmountDef::Notional {
+                    notional: notional.clone(),
+                },
+            },
+            average_fill_price: order.average_fill_price.clone(),
+            status: format!("{:?}", order.status),
+            type_: format!("{:?}", order.type_),
+            limit_price: order.limit_price.clone(),
+            side: SideDef::from(&order.side),
+        }))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<IronTradeOrder>, D::Error> {
+        Vec::<OrderDef>::deserialize(deserializer)?
+            .into_iter()
+            .map(|order| {
+                Ok(IronTradeOrder {
+                    status: order_status(&order.status).ok_or_else(|| {
+                        D::Error::custom(format!("unknown order status {}", order.status))
+                    })?,
+                    type_: order_type(&order.type_).ok_or_else(|| {
+                        D::Error::custom(format!("unknown order type {}", order.type_))
+                    })?,
+                    order_id: order.order_id,
+                    asset_symbol: order.asset_symbol,
+                    filled_quantity: order.filled_quantity,
+                    amount: match order.amount {
+                        AmountDef::Quantity { quantity } => Amount::Quantity { quantity },
+                        AmountDef::Notional { notional } => Amount::Notional { notional },
+                    },
+                    average_fill_price: order.average_fill_price,
+                    limit_price: order.limit_price,
+                    side: order.side.into(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trade_log::Fill;
+    use chrono::{TimeZone, Utc};
+    use irontrade::api::common::OrderSide;
+    use num_decimal::Num;
+
+    #[test]
+    fn fills_round_trip() {
+        let fill = Fill {
+            order_id: "a".into(),
+            original_order_id: "a".into(),
+            asset_symbol: "AAPL".into(),
+            side: OrderSide::Sell,
+            quantity: Num::from(10),
+            price: Num::from(105),
+            fees: Num::from(1),
+            time: Utc.with_ymd_and_hms(2024, 1, 18, 15, 0, 0).unwrap(),
+            annotation: None,
+        };
+
+        let json = serde_json::to_value(&fill).unwrap();
+        assert_eq!(json["side"], "sell");
+        let parsed: Fill = serde_json::from_value(json).unwrap();
+        assert!(matches!(parsed.side, OrderSide::Sell));
+        assert_eq!(parsed.price, fill.price);
+    }
+}
@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::format::format_quantity;
+use crate::notify::Notifier;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use irontrade::api::common::{OpenPosition as IronTradeOpenPosition, Order as IronTradeOrder};
+use std::sync::{Mutex, PoisonError};
+
+/// Most stream gaps and unflushed entries kept for the report, the oldest are dropped first.
+const MAX_RECORDED: usize = 100;
+
+/// A record that failed to be written, e.g. the outcome of an audited action.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnflushedEntry {
+    /// Where the entry was meant to go, e.g. `audit log`
+    pub journal: String,
+    pub entry: String,
+    pub error: String,
+}
+
+/// A time a stream was disconnected until it reconnected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamGap {
+    pub stream: String,
+    pub disconnected_at: DateTime<Utc>,
+    pub reconnected_at: DateTime<Utc>,
+}
+
+/// What the client recorded going wrong in the background while it ran.
+#[derive(Debug, Default)]
+pub(crate) struct ShutdownLog {
+    unflushed: Mutex<Vec<UnflushedEntry>>,
+    stream_gaps: Mutex<Vec<StreamGap>>,
+}
+
+fn push_bounded<T>(records: &Mutex<Vec<T>>, record: T) {
+    let mut records = records.lock().unwrap_or_else(PoisonError::into_inner);
+    if records.len() >= MAX_RECORDED {
+        records.remove(0);
+    }
+    records.push(record);
+}
+
+/// What a shutdown left behind. Orders and positions that couldn't be listed are reported in
+/// `errors` instead, so the report always comes back.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownReport {
+    /// Open orders, none of them canceled by the shutdown
+    pub open_orders: Vec<IronTradeOrder>,
+    pub positions: Vec<IronTradeOpenPosition>,
+    pub unflushed_entries: Vec<UnflushedEntry>,
+    pub stream_gaps: Vec<StreamGap>,
+    pub errors: Vec<String>,
+}
+
+impl ShutdownReport {
+    /// Nothing was left behind.
+    pub fn is_clean(&self) -> bool {
+        self.open_orders.is_empty()
+            && self.positions.is_empty()
+            && self.unflushed_entries.is_empty()
+            && self.stream_gaps.is_empty()
+            && self.errors.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!("Open orders: {}", self.open_orders.len()),
+            format!("Positions: {}", self.positions.len()),
+            format!("Unflushed entries: {}", self.unflushed_entries.len()),
+            format!("Stream gaps: {}", self.stream_gaps.len()),
+        ];
+        for order in &self.open_orders {
+            lines.push(format!(
+                "- order {} {} filled {}",
+                order.order_id,
+                order.asset_symbol,
+                format_quantity(&order.filled_quantity)
+            ));
+        }
+        for position in &self.positions {
+            lines.push(format!(
+                "- position {} {}",
+                position.asset_symbol,
+                format_quantity(&position.quantity)
+            ));
+        }
+        for entry in &self.unflushed_entries {
+            lines.push(format!(
+                "- unflushed {} entry {}: {}",
+                entry.journal, entry.entry, entry.error
+            ));
+        }
+        for gap in &self.stream_gaps {
+            lines.push(format!(
+                "- {} gap from {} to {}",
+                gap.stream,
+                gap.disconnected_at.to_rfc3339(),
+                gap.reconnected_at.to_rfc3339()
+            ));
+        }
+        for error in &self.errors {
+            lines.push(format!("- error: {error}"));
+        }
+        lines.join("\n")
+    }
+}
+
+impl AlpacaClient {
+    pub(crate) fn record_unflushed(&self, journal: &str, entry: String, error: &anyhow::Error) {
+        push_bounded(
+            &self.shutdown_log.unflushed,
+            UnflushedEntry {
+                journal: journal.to_string(),
+                entry,
+                error: format!("{error:#}"),
+            },
+        );
+    }
+
+    pub(crate) fn record_stream_gap(
+        &self,
+        stream: &str,
+        disconnected_at: DateTime<Utc>,
+        reconnected_at: DateTime<Utc>,
+    ) {
+        push_bounded(
+            &self.shutdown_log.stream_gaps,
+            StreamGap {
+                stream: stream.to_string(),
+                disconnected_at,
+                reconnected_at,
+            },
+        );
+    }
+
+    /// Stops the background tasks of the supervisor and reports what's left behind: open orders,
+    /// positions, records that failed to be written and the times streams were down.
+    pub async fn shutdown(&self) -> ShutdownReport {
+        self.supervisor.shutdown().await;
+
+        let mut report = ShutdownReport::default();
+        let (open_orders, positions) =
+            tokio::join!(self.list_open_orders(), self.get_open_positions());
+        match open_orders {
+            Ok(open_orders) => report.open_orders = open_orders,
+            Err(error) => report
+                .errors
+                .push(format!("failed to list open orders: {error:#}")),
+        }
+        match positions {
+            Ok(positions) => report.positions = positions,
+            Err(error) => report
+                .errors
+                .push(format!("failed to list positions: {error:#}")),
+        }
+        report.unflushed_entries = self
+            .shutdown_log
+            .unflushed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        report.stream_gaps = self
+            .shutdown_log
+            .stream_gaps
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        report
+    }
+
+    /// [`AlpacaClient::shutdown`], sending the report through `notifier` unless it's clean.
+    pub async fn shutdown_and_notify(&self, notifier: &impl Notifier) -> Result<ShutdownReport> {
+        let report = self.shutdown().await;
+        if !report.is_clean() {
+            notifier
+                .notify("Shutdown report", &report.to_text())
+                .await?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_decimal::Num;
+
+    #[test]
+    fn keeps_the_latest_records() {
+        let records = Mutex::new(Vec::new());
+        for record in 0..MAX_RECORDED + 5 {
+            push_bounded(&records, record);
+        }
+        let records = records.into_inner().unwrap();
+        assert_eq!(records.len(), MAX_RECORDED);
+        assert_eq!(records[0], 5);
+
+        let report = ShutdownReport {
+            positions: vec![IronTradeOpenPosition {
+                asset_symbol: "AAPL".into(),
+                average_entry_price: None,
+                quantity: Num::from(3),
+                market_value: None,
+            }],
+            ..Default::default()
+        };
+        assert!(!report.is_clean());
+        assert!(report.to_text().contains("- position AAPL 3"));
+        assert!(ShutdownReport::default().is_clean());
+    }
+}
@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::data::{CryptoExchange, Quote};
+use anyhow::{Result, anyhow};
+use irontrade::api::common::OrderSide;
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+
+/// Crypto limit prices are accepted with up to this many decimal places.
+const CRYPTO_PRICE_PRECISION: u32 = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(rename_all = "snake_case")]
+pub enum WideSpreadAction {
+    /// Submit as a limit order at the far touch, shifted by `limit_offset_bps`
+    ConvertToLimit,
+    Reject,
+}
+
+/// Checks the live spread before crypto market orders are submitted.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpreadGuard {
+    pub max_spread_bps: Num,
+    pub action: WideSpreadAction,
+    /// How far through the touch a converted limit order is priced, to keep it marketable
+    pub limit_offset_bps: Num,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpreadDecision {
+    Proceed,
+    Limit(Num),
+    Reject(WideSpreadError),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WideSpreadError {
+    pub symbol: String,
+    pub spread_bps: Num,
+    pub max_spread_bps: Num,
+}
+
+impl Display for WideSpreadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} spread of {:.2}bps exceeds the {}bps limit",
+            self.symbol, self.spread_bps, self.max_spread_bps
+        )
+    }
+}
+
+impl std::error::Error for WideSpreadError {}
+
+pub fn spread_bps(quote: &Quote) -> Option<Num> {
+    let mid = (&quote.bid_price + &quote.ask_price) / Num::from(2);
+    if !quote.bid_price.is_positive() || !mid.is_positive() {
+        return None;
+    }
+    Some((&quote.ask_price - &quote.bid_price) / mid * Num::from(10_000))
+}
+
+impl SpreadGuard {
+    pub fn check(&self, quote: &Quote, side: &OrderSide) -> SpreadDecision {
+        let spread_bps = match spread_bps(quote) {
+            Some(spread_bps) => spread_bps,
+            // A one-sided book is as wide as it gets
+            None => {
+                return SpreadDecision::Reject(WideSpreadError {
+                    symbol: quote.symbol.clone(),
+                    spread_bps: Num::from(10_000),
+                    max_spread_bps: self.max_spread_bps.clone(),
+                });
+            }
+        };
+
+        if spread_bps <= self.max_spread_bps {
+            return SpreadDecision::Proceed;
+        }
+
+        match self.action {
+            WideSpreadAction::Reject => SpreadDecision::Reject(WideSpreadError {
+                symbol: quote.symbol.clone(),
+                spread_bps,
+                max_spread_bps: self.max_spread_bps.clone(),
+            }),
+            WideSpreadAction::ConvertToLimit => {
+                let offset = &self.limit_offset_bps / Num::from(10_000);
+                let limit_price = match side {
+                    OrderSide::Buy => &quote.ask_price * (Num::from(1) + offset),
+                    OrderSide::Sell => &quote.bid_price * (Num::from(1) - offset),
+                };
+                SpreadDecision::Limit(limit_price.round_with(CRYPTO_PRICE_PRECISION))
+            }
+        }
+    }
+}
+
+fn is_crypto(symbol: &str) -> bool {
+    symbol.contains('/')
+}
+
+impl AlpacaClient {
+    pub fn set_spread_guard(&mut self, spread_guard: Option<SpreadGuard>) {
+        self.spread_guard = spread_guard;
+    }
+
+    /// Runs crypto market orders through the spread guard, returning the request to submit.
+    pub(crate) async fn apply_spread_guard(&self, mut req: OrderRequest) -> Result<OrderRequest> {
+        let Some(spread_guard) = &self.spread_guard else {
+            return Ok(req);
+        };
+
+        let symbol = req.asset_pair.to_string();
+        if req.limit_price.is_some() || !is_crypto(&symbol) {
+            return Ok(req);
+        }
+
+        let quote = self
+            .get_latest_crypto_quotes(&[symbol.clone()], CryptoExchange::Alpaca)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no quote available for {symbol}"))?;
+
+        match spread_guard.check(&quote, &req.side) {
+            SpreadDecision::Proceed => {}
+            SpreadDecision::Limit(limit_price) => req.limit_price = Some(limit_price),
+            SpreadDecision::Reject(error) => return Err(error.into()),
+        }
+
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::str::FromStr;
+
+    #[test]
+    fn tight_spread_proceeds() -> Result<()> {
+        let guard = guard(WideSpreadAction::Reject);
+        assert_eq!(guard.check(&quote("100", "100.05")?, &OrderSide::Buy), SpreadDecision::Proceed);
+        Ok(())
+    }
+
+    #[test]
+    fn wide_spread_converts_to_marketable_limit() -> Result<()> {
+        let guard = guard(WideSpreadAction::ConvertToLimit);
+
+        assert_eq!(
+            guard.check(&quote("99", "101")?, &OrderSide::Buy),
+            SpreadDecision::Limit(Num::from_str("101.101")?)
+        );
+        assert_eq!(
+            guard.check(&quote("99", "101")?, &OrderSide::Sell),
+            SpreadDecision::Limit(Num::from_str("98.901")?)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn wide_spread_rejects() -> Result<()> {
+        let guard = guard(WideSpreadAction::Reject);
+        let decision = guard.check(&quote("99", "101")?, &OrderSide::Buy);
+
+        assert!(matches!(decision, SpreadDecision::Reject(error) if error.spread_bps == Num::from(200)));
+
+        Ok(())
+    }
+
+    fn guard(action: WideSpreadAction) -> SpreadGuard {
+        SpreadGuard {
+            max_spread_bps: Num::from(50),
+            action,
+            limit_offset_bps: Num::from(10),
+        }
+    }
+
+    fn quote(bid_price: &str, ask_price: &str) -> Result<Quote> {
+        Ok(Quote {
+            symbol: "BTC/USD".into(),
+            exchange: None,
+            time: Utc::now(),
+            bid_price: Num::from_str(bid_price)?,
+            bid_size: Num::from(1),
+            ask_price: Num::from_str(ask_price)?,
+            ask_size: Num::from(1),
+        })
+    }
+}
@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::data::{Bar, TimeFrame};
+use crate::order_defaults::OrderOptions;
+use crate::positions::AssetClass;
+use anyhow::{Result, bail};
+use chrono::{NaiveDate, TimeDelta, Utc};
+use chrono_tz::America::New_York;
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+use tokio_util::sync::CancellationToken;
+
+/// How often the daily bars are checked for the opening print after the bell.
+const OPENING_PRINT_POLL: Duration = Duration::from_secs(1);
+/// Symbols without an opening print by then stay held, e.g. when their open is delayed.
+const OPENING_PRINT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Enough daily bars to find the previous close across weekends and holidays.
+const PREVIOUS_CLOSE_LOOKBACK: TimeDelta = TimeDelta::days(10);
+
+/// How far the opening print may gap from the previous close for staged orders to go out.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OpeningBand {
+    pub max_gap_bps: Num,
+}
+
+impl OpeningBand {
+    pub fn admits(&self, gap: &OpeningGap) -> bool {
+        let magnitude = if gap.gap_bps.is_negative() { -&gap.gap_bps } else { gap.gap_bps.clone() };
+        magnitude <= self.max_gap_bps
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpeningGap {
+    pub previous_close: Num,
+    pub opening_price: Num,
+    /// Negative for a gap down
+    pub gap_bps: Num,
+}
+
+/// The gap between the opening print of `session` and the close before it, from daily bars of a
+/// single symbol. `None` until the session's bar exists.
+pub fn opening_gap(bars: &[Bar], session: NaiveDate) -> Option<OpeningGap> {
+    let session_date = |bar: &Bar| bar.time.with_timezone(&New_York).date_naive();
+    let opening = bars.iter().find(|bar| session_date(bar) == session)?;
+    let previous = bars
+        .iter()
+        .filter(|bar| session_date(bar) < session)
+        .max_by_key(|bar| bar.time)?;
+    if !previous.close.is_positive() {
+        return None;
+    }
+
+    Some(OpeningGap {
+        gap_bps: (&opening.open - &previous.close) / &previous.close * Num::from(10_000),
+        previous_close: previous.close.clone(),
+        opening_price: opening.open.clone(),
+    })
+}
+
+/// Stock orders held until the open of the current session.
+pub struct OrderStage {
+    band: OpeningBand,
+    orders: Vec<(OrderRequest, OrderOptions)>,
+}
+
+#[derive(Debug)]
+pub enum StagedRelease {
+    /// The open was within the band, with the outcome of placing the order
+    Placed(Result<String>),
+    /// The open gapped past the band, the order was dropped
+    OutsideBand(OpeningGap),
+    /// No opening print arrived in time, the order was dropped
+    NoOpeningPrint,
+}
+
+#[derive(Debug)]
+pub struct StagedOutcome {
+    pub asset_symbol: String,
+    pub release: StagedRelease,
+}
+
+impl OrderStage {
+    pub fn new(band: OpeningBand) -> Self {
+        Self {
+            band,
+            orders: Vec::new(),
+        }
+    }
+
+    pub fn stage(&mut self, req: OrderRequest, options: OrderOptions) -> Result<()> {
+        let asset_symbol = req.asset_pair.to_string();
+        if AssetClass::of_symbol(&asset_symbol) != AssetClass::Equity {
+            bail!("only stock orders can wait for the open, not {asset_symbol}");
+        }
+        self.orders.push((req, options));
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+}
+
+impl AlpacaClient {
+    /// Waits for today's open and places each staged order whose symbol opened within the band
+    /// of its previous close. Returns nothing placed if `cancellation` fires before the open.
+    pub async fn release_staged_orders(
+        &mut self,
+        stage: OrderStage,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<StagedOutcome>> {
+        let today = Utc::now().with_timezone(&New_York).date_naive();
+        let Some(session) = self.get_market_sessions(today, today).await?.into_iter().next() else {
+            bail!("the market doesn't open on {today}");
+        };
+        let wait = (session.open - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            biased;
+            _ = cancellation.cancelled() => return Ok(Vec::new()),
+            _ = sleep(wait) => {}
+        }
+
+        let mut symbols: Vec<String> = stage
+            .orders
+            .iter()
+            .map(|(req, _)| self.symbol_aliases.resolve(&req.asset_pair.to_string()).to_string())
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        let gaps = self.opening_gaps(&symbols, session.date).await?;
+
+        let mut outcomes = Vec::with_capacity(stage.len());
+        for (req, options) in stage.orders {
+            let asset_symbol = req.asset_pair.to_string();
+            let alpaca_symbol = self.symbol_aliases.resolve(&asset_symbol).to_string();
+            let gap = gaps
+                .iter()
+                .find(|(symbol, _)| *symbol == alpaca_symbol)
+                .and_then(|(_, gap)| gap.clone());
+            let release = match gap {
+                Some(gap) if stage.band.admits(&gap) => {
+                    StagedRelease::Placed(self.place_order_with_options(req, options).await)
+                }
+                Some(gap) => StagedRelease::OutsideBand(gap),
+                None => StagedRelease::NoOpeningPrint,
+            };
+            outcomes.push(StagedOutcome { asset_symbol, release });
+        }
+        Ok(outcomes)
+    }
+
+    /// Polls the daily bars until every symbol has its opening print or the timeout passes.
+    async fn opening_gaps(&self, symbols: &[String], session: NaiveDate) -> Result<Vec<(String, Option<OpeningGap>)>> {
+        let start = Utc::now() - PREVIOUS_CLOSE_LOOKBACK;
+        let deadline = Instant::now() + OPENING_PRINT_TIMEOUT;
+
+        loop {
+            let bars = self.get_bars_of(symbols, TimeFrame::OneDay, start, Utc::now()).await?;
+            let gaps: Vec<(String, Option<OpeningGap>)> = symbols
+                .iter()
+                .map(|symbol| {
+                    let symbol_bars: Vec<Bar> =
+                        bars.iter().filter(|bar| bar.symbol == *symbol).cloned().collect();
+                    (symbol.clone(), opening_gap(&symbol_bars, session))
+                })
+                .collect();
+
+            if gaps.iter().all(|(_, gap)| gap.is_some()) || Instant::now() >= deadline {
+                return Ok(gaps);
+            }
+            sleep(OPENING_PRINT_POLL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn daily_bar(day: u32, open: i64, close: i64) -> Bar {
+        Bar {
+            symbol: "AAPL".into(),
+            exchange: None,
+            // Daily bars start at midnight New York time
+            time: Utc.with_ymd_and_hms(2024, 1, day, 5, 0, 0).unwrap(),
+            open: Num::from(open),
+            high: Num::from(open.max(close)),
+            low: Num::from(open.min(close)),
+            close: Num::from(close),
+            volume: Num::from(1000),
+            vwap: None,
+        }
+    }
+
+    #[test]
+    fn measures_the_gap_from_the_previous_close() {
+        // Friday the 12th, then Tuesday the 16th after the holiday
+        let bars = [daily_bar(11, 100, 101), daily_bar(12, 101, 200), daily_bar(16, 196, 190)];
+        let session = NaiveDate::from_ymd_opt(2024, 1, 16).unwrap();
+
+        let gap = opening_gap(&bars, session).unwrap();
+        assert_eq!(gap.gap_bps, Num::from(-200));
+        assert!(OpeningBand { max_gap_bps: Num::from(200) }.admits(&gap));
+        assert!(!OpeningBand { max_gap_bps: Num::from(150) }.admits(&gap));
+
+        let next_session = NaiveDate::from_ymd_opt(2024, 1, 17).unwrap();
+        assert!(opening_gap(&bars, next_session).is_none());
+    }
+}
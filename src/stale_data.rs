@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(rename_all = "snake_case")]
+pub enum StaleDataAction {
+    Reject,
+    /// Submit anyway and publish a [`StaleDataError`] on the warning channel
+    Warn,
+}
+
+/// Refuses or flags orders on symbols whose latest quote or trade seen by the client is older
+/// than `max_age_ms`, e.g. because the feed froze. Symbols without any data count as stale.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StaleDataGuard {
+    pub max_age_ms: u64,
+    pub action: StaleDataAction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaleDataError {
+    pub symbol: String,
+    /// `None` when no data was ever seen for the symbol
+    pub age: Option<Duration>,
+    pub max_age: Duration,
+}
+
+impl Display for StaleDataError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.age {
+            Some(age) => write!(
+                f,
+                "latest {} data is {}ms old, older than the {}ms limit",
+                self.symbol,
+                age.as_millis(),
+                self.max_age.as_millis()
+            ),
+            None => write!(f, "no market data seen for {}", self.symbol),
+        }
+    }
+}
+
+impl std::error::Error for StaleDataError {}
+
+/// Time of the latest quote or trade the client fetched per symbol.
+pub struct MarketDataAges {
+    latest: Mutex<HashMap<String, DateTime<Utc>>>,
+    warnings: broadcast::Sender<StaleDataError>,
+}
+
+impl Default for MarketDataAges {
+    fn default() -> Self {
+        Self {
+            latest: Mutex::default(),
+            warnings: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl MarketDataAges {
+    pub(crate) fn record(&self, symbol: &str, time: DateTime<Utc>) {
+        let mut latest = self.latest.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = latest.entry(symbol.to_string()).or_insert(time);
+        if time > *entry {
+            *entry = time;
+        }
+    }
+
+    pub(crate) fn record_all<'a>(&self, data: impl IntoIterator<Item = (&'a str, DateTime<Utc>)>) {
+        for (symbol, time) in data {
+            self.record(symbol, time);
+        }
+    }
+
+    /// How old the latest data for `symbol` is as of `now`, `None` if there was none.
+    pub fn age(&self, symbol: &str, now: DateTime<Utc>) -> Option<Duration> {
+        let latest = self.latest.lock().unwrap_or_else(PoisonError::into_inner);
+        // A data timestamp slightly ahead of the local clock is as fresh as it gets
+        latest.get(symbol).map(|time| (now - *time).to_std().unwrap_or_default())
+    }
+}
+
+impl StaleDataGuard {
+    pub fn check(&self, ages: &MarketDataAges, symbol: &str, now: DateTime<Utc>) -> Result<(), StaleDataError> {
+        let max_age = Duration::from_millis(self.max_age_ms);
+        let age = ages.age(symbol, now);
+        match age {
+            Some(age) if age <= max_age => Ok(()),
+            _ => Err(StaleDataError {
+                symbol: symbol.to_string(),
+                age,
+                max_age,
+            }),
+        }
+    }
+}
+
+impl AlpacaClient {
+    pub fn set_stale_data_guard(&mut self, stale_data_guard: Option<StaleDataGuard>) {
+        self.stale_data_guard = stale_data_guard;
+    }
+
+    pub fn market_data_ages(&self) -> &MarketDataAges {
+        &self.market_data_ages
+    }
+
+    /// Orders submitted on stale data while the guard is set to warn.
+    pub fn subscribe_stale_data_warnings(&self) -> broadcast::Receiver<StaleDataError> {
+        self.market_data_ages.warnings.subscribe()
+    }
+
+    pub(crate) fn check_stale_data(&self, symbol: &str) -> Result<(), StaleDataError> {
+        let Some(stale_data_guard) = &self.stale_data_guard else {
+            return Ok(());
+        };
+
+        match stale_data_guard.check(&self.market_data_ages, symbol, Utc::now()) {
+            Err(error) if stale_data_guard.action == StaleDataAction::Warn => {
+                // Nobody listening isn't an error
+                let _ = self.market_data_ages.warnings.send(error);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn rejects_old_and_missing_data() {
+        let ages = MarketDataAges::default();
+        let now = Utc::now();
+        ages.record("BTC/USD", now - TimeDelta::seconds(2));
+        ages.record("BTC/USD", now - TimeDelta::seconds(10));
+        ages.record("ETH/USD", now - TimeDelta::seconds(30));
+
+        let guard = StaleDataGuard {
+            max_age_ms: 5_000,
+            action: StaleDataAction::Reject,
+        };
+
+        assert!(guard.check(&ages, "BTC/USD", now).is_ok());
+        assert_eq!(guard.check(&ages, "ETH/USD", now).unwrap_err().age, Some(Duration::from_secs(30)));
+        assert_eq!(guard.check(&ages, "SOL/USD", now).unwrap_err().age, None);
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::tracker::OrderTracker;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+const STATE_VERSION: u32 = 1;
+
+/// Everything the client keeps locally that a restarted bot needs to resume.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientState {
+    pub version: u32,
+    pub order_tracker: OrderTracker,
+}
+
+impl ClientState {
+    fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+
+        // Write next to the target and rename so a crash mid-write never leaves a torn file
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json).with_context(|| format!("failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path).with_context(|| format!("failed to replace {}", path.display()))?;
+
+        Ok(())
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        let json = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let state: ClientState = serde_json::from_slice(&json)?;
+
+        if state.version != STATE_VERSION {
+            bail!("unsupported client state version {}", state.version);
+        }
+
+        Ok(state)
+    }
+}
+
+impl AlpacaClient {
+    pub fn state(&self) -> ClientState {
+        ClientState {
+            version: STATE_VERSION,
+            order_tracker: self.order_tracker().clone(),
+        }
+    }
+
+    pub fn save_state(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.state().write(path.as_ref())
+    }
+
+    /// Replaces the local state with the one saved at `path`.
+    pub fn load_state(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let state = ClientState::read(path.as_ref())?;
+        *self.order_tracker_mut() = state.order_tracker;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::unique_temp_path;
+
+    #[test]
+    fn round_trips_through_file() -> Result<()> {
+        let mut order_tracker = OrderTracker::default();
+        order_tracker.track("order-1".into(), "BTC/USD".into());
+
+        let state = ClientState {
+            version: STATE_VERSION,
+            order_tracker,
+        };
+        let path = unique_temp_path("state_round_trip.json");
+
+        state.write(&path)?;
+        let restored = ClientState::read(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(restored, state);
+        assert!(restored.order_tracker.get("order-1").is_some());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::replace::OrderChange;
+use crate::retry::{Idempotency, RetryOverride};
+use crate::wait::WaitOutcome;
+use anyhow::{Result, anyhow, bail};
+use apca::api::v2::asset::Class;
+use apca::api::v2::order::{Amount, CreateReqInit, Order as ApcaOrder, Side, Type};
+use apca::api::v2::orders::{self, ListReq, Status};
+use irontrade::api::common::OpenPosition as IronTradeOpenPosition;
+use num_decimal::Num;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopAdjustment {
+    Moved { order_id: String, stop_price: Num },
+    /// The existing stop is already at or tighter than the requested one, stops are never loosened
+    Unchanged { order_id: String, stop_price: Num },
+}
+
+fn is_long(position: &IronTradeOpenPosition) -> bool {
+    !position.quantity.is_negative()
+}
+
+fn current_price(position: &IronTradeOpenPosition) -> Result<Num> {
+    let market_value = position
+        .market_value
+        .as_ref()
+        .ok_or_else(|| anyhow!("no market value for {}", position.asset_symbol))?;
+    if position.quantity.is_zero() {
+        bail!("no open quantity for {}", position.asset_symbol);
+    }
+    Ok(market_value / &position.quantity)
+}
+
+/// Whether moving a stop from `current` to `proposed` reduces the risk of the position.
+fn tightens(long: bool, current: &Num, proposed: &Num) -> bool {
+    if long { proposed > current } else { proposed < current }
+}
+
+/// Crypto orders can't be replaced, their stops are moved by canceling and resubmitting them.
+fn replaceable(stop: &ApcaOrder) -> bool {
+    !matches!(stop.asset_class, Class::Crypto)
+}
+
+/// The part of the stop that hasn't been filled yet.
+fn remaining_amount(stop: &ApcaOrder) -> Amount {
+    match &stop.amount {
+        Amount::Quantity { quantity } => Amount::Quantity {
+            quantity: quantity - &stop.filled_quantity,
+        },
+        Amount::Notional { notional } => {
+            let filled_notional = match &stop.average_fill_price {
+                Some(price) => price * &stop.filled_quantity,
+                None => Num::from(0),
+            };
+            Amount::Notional {
+                notional: notional - filled_notional,
+            }
+        }
+    }
+}
+
+impl AlpacaClient {
+    /// Open stop or stop limit order protecting the position, i.e. on the closing side.
+    async fn find_protective_stop(&self, position: &IronTradeOpenPosition) -> Result<ApcaOrder> {
+        let closing_side = if is_long(position) { Side::Sell } else { Side::Buy };
+        let request = ListReq {
+            symbols: vec![position.asset_symbol.clone()],
+            status: Status::Open,
+            ..Default::default()
+        };
+
+        self.retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+            self.apca_client.issue::<orders::List>(&request)
+        })
+        .await?
+        .into_iter()
+        .find(|order| matches!(order.type_, Type::Stop | Type::StopLimit) && order.side == closing_side)
+        .ok_or_else(|| anyhow!("no protective stop order found for {}", position.asset_symbol))
+    }
+
+    /// Cancels the stop and submits a copy at `stop_price`, for orders that can't be replaced in
+    /// place. The position is unprotected until the new order is accepted, or for good if
+    /// `cancellation` fires while waiting for the cancel to go through.
+    async fn resubmit_stop(
+        &self,
+        stop: &ApcaOrder,
+        stop_price: Num,
+        cancellation: &CancellationToken,
+    ) -> Result<String> {
+        let order_id = stop.id.to_string();
+        self.cancel_order(&order_id).await?;
+
+        let deadline = Instant::now() + CANCEL_TIMEOUT;
+        match self
+            .poll_until_terminal(&order_id, CANCEL_POLL_INTERVAL, cancellation, Some(deadline))
+            .await?
+        {
+            WaitOutcome::Ended(_) => {}
+            WaitOutcome::Filled(_) => bail!("stop order {order_id} filled before it could be moved"),
+            WaitOutcome::Cancelled(_) => {
+                bail!("stopped waiting for stop order {order_id} to be canceled, it wasn't resubmitted")
+            }
+            WaitOutcome::TimedOut(_) => {
+                bail!("stop order {order_id} wasn't canceled within {CANCEL_TIMEOUT:?}, it wasn't moved")
+            }
+        }
+
+        // It may have been partially filled until the cancel went through
+        let canceled = self.fetch_apca_order(&order_id).await?;
+        let amount = remaining_amount(&canceled);
+        // A stop limit keeps the same distance between its stop and limit prices
+        let limit_price = match (&stop.limit_price, &stop.stop_price) {
+            (Some(limit_price), Some(old_stop_price)) => Some(limit_price + (&stop_price - old_stop_price)),
+            _ => None,
+        };
+        let request = CreateReqInit {
+            type_: stop.type_,
+            time_in_force: stop.time_in_force,
+            stop_price: Some(stop_price),
+            limit_price,
+            ..Default::default()
+        }
+        .init(stop.symbol.clone(), stop.side, amount);
+
+        self.submit_order(&stop.symbol, &request, RetryOverride::Default).await
+    }
+
+    async fn tighten_stop(
+        &self,
+        position: &IronTradeOpenPosition,
+        stop_price: Num,
+        cancellation: &CancellationToken,
+    ) -> Result<StopAdjustment> {
+        let stop = self.find_protective_stop(position).await?;
+        let current_stop = stop
+            .stop_price
+            .clone()
+            .ok_or_else(|| anyhow!("stop order {} has no stop price", stop.id.0))?;
+
+        if !tightens(is_long(position), &current_stop, &stop_price) {
+            return Ok(StopAdjustment::Unchanged {
+                order_id: stop.id.to_string(),
+                stop_price: current_stop,
+            });
+        }
+
+        let order_id = if replaceable(&stop) {
+            let change = OrderChange {
+                stop_price: Some(stop_price.clone()),
+                ..Default::default()
+            };
+            self.change_order(&stop.id.to_string(), change).await?
+        } else {
+            self.resubmit_stop(&stop, stop_price.clone(), cancellation).await?
+        };
+
+        Ok(StopAdjustment::Moved { order_id, stop_price })
+    }
+
+    /// Moves the position's protective stop to its average entry price. `cancellation` stops
+    /// waiting for a stop that has to be resubmitted to be canceled first.
+    pub async fn move_stop_to_breakeven(
+        &self,
+        position: &IronTradeOpenPosition,
+        cancellation: &CancellationToken,
+    ) -> Result<StopAdjustment> {
+        let entry_price = position
+            .average_entry_price
+            .clone()
+            .ok_or_else(|| anyhow!("no average entry price for {}", position.asset_symbol))?;
+        self.tighten_stop(position, entry_price, cancellation).await
+    }
+
+    /// Moves the position's protective stop to `trail` away from the current price, if that is
+    /// tighter than where it is now. `cancellation` works as for
+    /// [`AlpacaClient::move_stop_to_breakeven`].
+    pub async fn ratchet_stop(
+        &self,
+        position: &IronTradeOpenPosition,
+        trail: &Num,
+        cancellation: &CancellationToken,
+    ) -> Result<StopAdjustment> {
+        let price = current_price(position)?;
+        let stop_price = if is_long(position) { price - trail } else { price + trail };
+        self.tighten_stop(position, stop_price, cancellation).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop_order(symbol: &str, asset_class: &str, amount: &str, filled_quantity: &str) -> ApcaOrder {
+        let json = format!(
+            r#"{{
+                "id": "61e69015-8549-4bfd-b9c3-01e75843f47d",
+                "client_order_id": "eb9e2aaa-f71a-4f51-b5b4-52a6c565dad4",
+                "created_at": "2024-01-18T15:00:00.000000Z",
+                "updated_at": "2024-01-18T15:00:01.000000Z",
+                "submitted_at": "2024-01-18T15:00:00.000000Z",
+                "filled_at": null,
+                "expired_at": null,
+                "canceled_at": "2024-01-18T15:00:01.000000Z",
+                "failed_at": null,
+                "replaced_at": null,
+                "replaced_by": null,
+                "replaces": null,
+                "asset_id": "276e2673-764b-4ab6-a611-caf665ca6340",
+                "symbol": "{symbol}",
+                "asset_class": "{asset_class}",
+                {amount},
+                "filled_qty": "{filled_quantity}",
+                "filled_avg_price": "40000",
+                "order_class": "",
+                "order_type": "stop",
+                "type": "stop",
+                "side": "sell",
+                "time_in_force": "gtc",
+                "limit_price": null,
+                "stop_price": "39000",
+                "status": "canceled",
+                "extended_hours": false,
+                "legs": null,
+                "trail_percent": null,
+                "trail_price": null,
+                "hwm": null
+            }}"#
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn crypto_stops_are_resubmitted() {
+        // The position of a crypto stop is reported as BTCUSD, the order itself tells the class
+        let crypto = stop_order("BTC/USD", "crypto", r#""notional": null, "qty": "0.5""#, "0");
+        assert!(!replaceable(&crypto));
+
+        let stock = stop_order("AAPL", "us_equity", r#""notional": null, "qty": "10""#, "0");
+        assert!(replaceable(&stock));
+    }
+
+    #[test]
+    fn resubmits_only_the_unfilled_part() {
+        let stop = stop_order("BTC/USD", "crypto", r#""notional": null, "qty": "0.5""#, "0.2");
+        assert_eq!(
+            remaining_amount(&stop),
+            Amount::Quantity {
+                quantity: Num::new(3, 10)
+            }
+        );
+
+        let stop = stop_order("BTC/USD", "crypto", r#""notional": "10000", "qty": null"#, "0.1");
+        assert_eq!(
+            remaining_amount(&stop),
+            Amount::Notional {
+                notional: Num::from(6000)
+            }
+        );
+    }
+
+    #[test]
+    fn stops_only_move_towards_the_position() {
+        assert!(tightens(true, &Num::from(95), &Num::from(100)));
+        assert!(!tightens(true, &Num::from(100), &Num::from(95)));
+        assert!(tightens(false, &Num::from(105), &Num::from(100)));
+        assert!(!tightens(false, &Num::from(100), &Num::from(100)));
+    }
+
+    #[test]
+    fn derives_current_price_of_shorts() -> Result<()> {
+        let position = IronTradeOpenPosition {
+            asset_symbol: "TSLA".into(),
+            average_entry_price: Some(Num::from(200)),
+            quantity: Num::from(-10),
+            market_value: Some(Num::from(-1900)),
+        };
+
+        assert!(!is_long(&position));
+        assert_eq!(current_price(&position)?, Num::from(190));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::budget::BudgetKind;
+use crate::client::AlpacaClient;
+use crate::convert::Order;
+use crate::data::{Bar, Quote, Trade};
+use anyhow::{Result, anyhow};
+use apca::api::v2::updates::{self, OrderStatus as UpdateEvent, OrderUpdates};
+use apca::data::v2::stream::{Data, IEX, MarketData, RealtimeData, SIP, Source, drive};
+use chrono::Utc;
+use futures::stream::BoxStream;
+use futures::{FutureExt, Stream, StreamExt};
+use irontrade::api::common::Order as IronTradeOrder;
+use num_decimal::Num;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Wait before the first attempt to reconnect the market data stream, doubled after every failed
+/// attempt up to [`MAX_RECONNECT_DELAY`].
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TradeUpdateKind {
+    New,
+    PartialFill,
+    Fill,
+    Canceled,
+    Expired,
+    Replaced,
+    Rejected,
+    /// Any other order event, e.g. pending cancel or done for day
+    Other,
+}
+
+impl From<&UpdateEvent> for TradeUpdateKind {
+    fn from(event: &UpdateEvent) -> Self {
+        match event {
+            UpdateEvent::New => TradeUpdateKind::New,
+            UpdateEvent::PartialFill => TradeUpdateKind::PartialFill,
+            UpdateEvent::Filled => TradeUpdateKind::Fill,
+            UpdateEvent::Canceled => TradeUpdateKind::Canceled,
+            UpdateEvent::Expired => TradeUpdateKind::Expired,
+            UpdateEvent::Replaced => TradeUpdateKind::Replaced,
+            UpdateEvent::Rejected => TradeUpdateKind::Rejected,
+            _ => TradeUpdateKind::Other,
+        }
+    }
+}
+
+/// An order event pushed by Alpaca, with the order as of that event.
+#[derive(Clone, Debug)]
+pub struct TradeUpdate {
+    pub kind: TradeUpdateKind,
+    pub order: IronTradeOrder,
+    pub client_order_id: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MarketDataFeed {
+    /// The free feed, trades and quotes of the IEX exchange only
+    #[default]
+    Iex,
+    /// All US exchanges, requires a market data subscription
+    Sip,
+}
+
+/// Stocks to stream live data of, by kind of data. Crypto data and its venue codes are only
+/// available over REST.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MarketDataSubscription {
+    pub quotes: Vec<String>,
+    pub trades: Vec<String>,
+    /// Minute bars
+    pub bars: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MarketDataEvent {
+    Quote(Quote),
+    Trade(Trade),
+    Bar(Bar),
+    /// The connection dropped and was reestablished with the same subscription, data in
+    /// between was missed
+    Reconnected,
+}
+
+/// Messages of one connection: `None` for messages that couldn't be decoded, an error once the
+/// connection is lost.
+type MarketDataMessages = BoxStream<'static, Result<Option<Data>>>;
+
+impl AlpacaClient {
+    /// Connects to Alpaca's trade updates websocket and yields an update for every event on an
+    /// order of the account, including orders placed elsewhere. The order tracker is kept up to
+    /// date along the way. The stream ends when the connection closes, reconnecting is up to the
+    /// caller. Updates are sequenced once published on an [`EventBus`](crate::event_bus::EventBus).
+    pub async fn stream_trade_updates(&self) -> Result<impl Stream<Item = Result<TradeUpdate>> + '_> {
+        self.acquire_budget(BudgetKind::Request).await?;
+        let (mut stream, mut subscription) = self.apca_client.subscribe::<OrderUpdates>().await?;
+        let subscribe = subscription.subscribe().boxed();
+        drive(subscribe, &mut stream)
+            .await
+            .map_err(|_| anyhow!("the trade updates stream ended before the subscription was confirmed"))???;
+
+        Ok(stream.map(move |message| {
+            // The subscription is the sending half of the connection, it lives as long as the stream
+            let _ = &subscription;
+            let update: updates::OrderUpdate = message??;
+            let client_order_id = update.order.client_order_id.clone();
+            let mut order = Order::from(update.order).0;
+            self.order_tracker().update(&order);
+            order.asset_symbol = self.symbol_aliases.alias(&order.asset_symbol).to_string();
+            Ok(TradeUpdate {
+                kind: TradeUpdateKind::from(&update.event),
+                order,
+                client_order_id,
+            })
+        }))
+    }
+
+    /// Connects to Alpaca's market data websocket and streams the live data of `subscription`.
+    /// When the connection drops, it's reestablished with the same subscription after a growing
+    /// delay and a [`MarketDataEvent::Reconnected`] is yielded, so the stream only ends when
+    /// dropped. Quotes and trades count as fresh data for the stale data guard. Events are
+    /// sequenced once published on an [`EventBus`](crate::event_bus::EventBus).
+    pub async fn stream_market_data(
+        &self,
+        feed: MarketDataFeed,
+        subscription: &MarketDataSubscription,
+    ) -> Result<impl Stream<Item = MarketDataEvent> + '_> {
+        let resolve = |symbols: &[String]| self.symbol_aliases.resolve_all(symbols).0;
+        let mut data = MarketData::default();
+        data.set_quotes(resolve(&subscription.quotes));
+        data.set_trades(resolve(&subscription.trades));
+        data.set_bars(resolve(&subscription.bars));
+
+        let messages = self.connect_market_data(feed, &data).await?;
+        Ok(futures::stream::unfold((messages, data), move |(mut messages, data)| async move {
+            loop {
+                match messages.next().await {
+                    Some(Ok(Some(message))) => {
+                        if let Some(event) = self.market_data_event(message) {
+                            return Some((event, (messages, data)));
+                        }
+                    }
+                    Some(Ok(None)) => {}
+                    Some(Err(_)) | None => {
+                        let disconnected_at = Utc::now();
+                        let mut delay = RECONNECT_DELAY;
+                        let messages = loop {
+                            sleep(delay).await;
+                            match self.connect_market_data(feed, &data).await {
+                                Ok(messages) => break messages,
+                                Err(_) => delay = (delay * 2).min(MAX_RECONNECT_DELAY),
+                            }
+                        };
+                        self.record_stream_gap("market data", disconnected_at, Utc::now());
+                        return Some((MarketDataEvent::Reconnected, (messages, data)));
+                    }
+                }
+            }
+        }))
+    }
+
+    async fn connect_market_data(&self, feed: MarketDataFeed, data: &MarketData) -> Result<MarketDataMessages> {
+        match feed {
+            MarketDataFeed::Iex => self.connect_market_data_source::<IEX>(data).await,
+            MarketDataFeed::Sip => self.connect_market_data_source::<SIP>(data).await,
+        }
+    }
+
+    async fn connect_market_data_source<S: Source + Send + 'static>(&self, data: &MarketData) -> Result<MarketDataMessages> {
+        self.acquire_budget(BudgetKind::Request).await?;
+        let (mut stream, mut subscription) = self.apca_client.subscribe::<RealtimeData<S>>().await?;
+        let subscribe = subscription.subscribe(data).boxed();
+        drive(subscribe, &mut stream)
+            .await
+            .map_err(|_| anyhow!("the market data stream ended before the subscription was confirmed"))???;
+
+        Ok(stream
+            .map(move |message| {
+                // The subscription is the sending half of the connection, it lives as long as the stream
+                let _ = &subscription;
+                Ok(message?.ok())
+            })
+            .boxed())
+    }
+
+    /// The stream only carries stock data, which has no venue code, so `exchange` is `None` just
+    /// as for stock data fetched over REST.
+    fn market_data_event(&self, message: Data) -> Option<MarketDataEvent> {
+        let symbol = |symbol: &str| self.symbol_aliases.alias(symbol).to_string();
+        let event = match message {
+            Data::Quote(quote) => {
+                self.market_data_ages.record(&quote.symbol, quote.timestamp);
+                MarketDataEvent::Quote(Quote {
+                    symbol: symbol(&quote.symbol),
+                    exchange: None,
+                    time: quote.timestamp,
+                    bid_price: quote.bid_price,
+                    bid_size: Num::from(quote.bid_size),
+                    ask_price: quote.ask_price,
+                    ask_size: Num::from(quote.ask_size),
+                })
+            }
+            Data::Trade(trade) => {
+                self.market_data_ages.record(&trade.symbol, trade.timestamp);
+                MarketDataEvent::Trade(Trade {
+                    symbol: symbol(&trade.symbol),
+                    exchange: None,
+                    time: trade.timestamp,
+                    price: trade.trade_price,
+                    size: Num::from(trade.trade_size),
+                })
+            }
+            Data::Bar(bar) => MarketDataEvent::Bar(Bar {
+                symbol: symbol(&bar.symbol),
+                exchange: None,
+                time: bar.timestamp,
+                open: bar.open_price,
+                high: bar.high_price,
+                low: bar.low_price,
+                close: bar.close_price,
+                volume: Num::from(bar.volume),
+                vwap: None,
+            }),
+            _ => return None,
+        };
+        Some(event)
+    }
+}
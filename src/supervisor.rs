@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use anyhow::Result;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestartPolicy {
+    Never,
+    /// Restart after errors and panics, but not after a clean exit
+    OnFailure { max_restarts: u32, backoff: Duration },
+    /// Restart whenever the task ends, until the supervisor shuts down
+    Always { backoff: Duration },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "event", rename_all = "snake_case"))]
+pub enum TaskEvent {
+    Started { name: String },
+    Exited { name: String },
+    Failed { name: String, error: String },
+    Panicked { name: String, message: String },
+    Restarting { name: String, restart: u32 },
+    /// The task ended for good, either by policy or because restarts were exhausted
+    Stopped { name: String },
+}
+
+/// Owns the background tasks of a client so they don't die silently: every start, exit, error
+/// and panic is published as a [`TaskEvent`] and tasks are restarted according to their policy.
+pub struct Supervisor {
+    events: broadcast::Sender<TaskEvent>,
+    shutdown: CancellationToken,
+    monitors: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            shutdown: CancellationToken::new(),
+            monitors: Mutex::default(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.events.subscribe()
+    }
+
+    /// Runs the future built by `task` in the background, building a fresh one on every restart.
+    /// The token passed to the task fires on [`Supervisor::shutdown`].
+    pub fn spawn<F, Fut>(&self, name: impl Into<String>, restart_policy: RestartPolicy, task: F)
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let name = name.into();
+        let events = self.events.clone();
+        let shutdown = self.shutdown.clone();
+
+        let monitor = tokio::spawn(async move {
+            let mut restarts = 0;
+
+            loop {
+                let _ = events.send(TaskEvent::Started { name: name.clone() });
+                let handle = tokio::spawn(task(shutdown.child_token()));
+
+                let failed = match handle.await {
+                    Ok(Ok(())) => {
+                        let _ = events.send(TaskEvent::Exited { name: name.clone() });
+                        false
+                    }
+                    Ok(Err(error)) => {
+                        let _ = events.send(TaskEvent::Failed {
+                            name: name.clone(),
+                            error: format!("{error:#}"),
+                        });
+                        true
+                    }
+                    Err(error) => {
+                        let message = match error.try_into_panic() {
+                            Ok(panic) => panic_message(panic),
+                            Err(_) => "task was aborted".into(),
+                        };
+                        let _ = events.send(TaskEvent::Panicked {
+                            name: name.clone(),
+                            message,
+                        });
+                        true
+                    }
+                };
+
+                let backoff = match &restart_policy {
+                    _ if shutdown.is_cancelled() => None,
+                    RestartPolicy::Never => None,
+                    RestartPolicy::OnFailure { max_restarts, backoff } => {
+                        (failed && restarts < *max_restarts).then_some(*backoff)
+                    }
+                    RestartPolicy::Always { backoff } => Some(*backoff),
+                };
+
+                let Some(backoff) = backoff else {
+                    let _ = events.send(TaskEvent::Stopped { name: name.clone() });
+                    return;
+                };
+
+                restarts += 1;
+                let _ = events.send(TaskEvent::Restarting {
+                    name: name.clone(),
+                    restart: restarts,
+                });
+
+                tokio::select! {
+                    _ = shutdown.cancelled() => {
+                        let _ = events.send(TaskEvent::Stopped { name: name.clone() });
+                        return;
+                    }
+                    _ = sleep(backoff) => {}
+                }
+            }
+        });
+
+        self.monitors.lock().unwrap_or_else(PoisonError::into_inner).push(monitor);
+    }
+
+    /// Signals every task to stop and waits until all of them have ended.
+    pub async fn shutdown(&self) {
+        self.shutdown.cancel();
+
+        let monitors: Vec<JoinHandle<()>> = self
+            .monitors
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain(..)
+            .collect();
+
+        for monitor in monitors {
+            let _ = monitor.await;
+        }
+    }
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".into(),
+        },
+    }
+}
+
+impl AlpacaClient {
+    /// Supervisor running the client's background tasks.
+    pub fn supervisor(&self) -> &Supervisor {
+        &self.supervisor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[tokio::test]
+    async fn restarts_failed_task_until_exhausted() {
+        let supervisor = Supervisor::new();
+        let mut events = supervisor.subscribe();
+
+        supervisor.spawn(
+            "flaky",
+            RestartPolicy::OnFailure {
+                max_restarts: 1,
+                backoff: Duration::ZERO,
+            },
+            |_| fail(),
+        );
+
+        let name = "flaky".to_string();
+        let expected = [
+            TaskEvent::Started { name: name.clone() },
+            TaskEvent::Failed {
+                name: name.clone(),
+                error: "boom".into(),
+            },
+            TaskEvent::Restarting {
+                name: name.clone(),
+                restart: 1,
+            },
+            TaskEvent::Started { name: name.clone() },
+            TaskEvent::Failed {
+                name: name.clone(),
+                error: "boom".into(),
+            },
+            TaskEvent::Stopped { name },
+        ];
+
+        for event in expected {
+            assert_eq!(events.recv().await.unwrap(), event);
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_panics() {
+        let supervisor = Supervisor::new();
+        let mut events = supervisor.subscribe();
+
+        supervisor.spawn("panicky", RestartPolicy::Never, |_| panics());
+
+        assert!(matches!(events.recv().await.unwrap(), TaskEvent::Started { .. }));
+        assert_eq!(
+            events.recv().await.unwrap(),
+            TaskEvent::Panicked {
+                name: "panicky".into(),
+                message: "oh no".into()
+            }
+        );
+    }
+
+    async fn fail() -> Result<()> {
+        Err(anyhow!("boom"))
+    }
+
+    async fn panics() -> Result<()> {
+        panic!("oh no")
+    }
+
+    #[tokio::test]
+    async fn shutdown_stops_tasks() {
+        let supervisor = Supervisor::new();
+        let mut events = supervisor.subscribe();
+
+        supervisor.spawn(
+            "forever",
+            RestartPolicy::Always {
+                backoff: Duration::ZERO,
+            },
+            |shutdown| async move {
+                shutdown.cancelled().await;
+                Ok(())
+            },
+        );
+
+        assert!(matches!(events.recv().await.unwrap(), TaskEvent::Started { .. }));
+        supervisor.shutdown().await;
+
+        assert!(matches!(events.recv().await.unwrap(), TaskEvent::Exited { .. }));
+        assert!(matches!(events.recv().await.unwrap(), TaskEvent::Stopped { .. }));
+    }
+}
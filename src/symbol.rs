@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use anyhow::Result;
+use irontrade::api::common::AssetPair;
+use irontrade::api::request::OrderRequest;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+/// Orders take crypto pairs as `BTC/USD` while positions and activities report them as `BTCUSD`,
+/// compare symbols without the slash so both spellings match.
+pub(crate) fn unslashed(symbol: &str) -> String {
+    symbol.replace('/', "")
+}
+
+pub(crate) fn same_symbol(a: &str, b: &str) -> bool {
+    unslashed(a).eq_ignore_ascii_case(&unslashed(b))
+}
+
+/// Names a strategy uses in place of Alpaca's symbols, e.g. `BTC` for `BTC/USD` or `BRK.B` for
+/// `BRK-B`, keyed by alias. Orders and data requests go out with the Alpaca symbol, and orders,
+/// positions and market data come back under the alias.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(transparent)]
+pub struct SymbolAliases(BTreeMap<String, String>);
+
+impl SymbolAliases {
+    pub fn insert(&mut self, alias: impl Into<String>, symbol: impl Into<String>) {
+        self.0.insert(alias.into(), symbol.into());
+    }
+
+    /// The Alpaca symbol `symbol` stands for, `symbol` itself when it isn't an alias.
+    pub fn resolve<'a>(&'a self, symbol: &'a str) -> &'a str {
+        self.0.get(symbol).map_or(symbol, String::as_str)
+    }
+
+    /// The alias of an Alpaca symbol in either crypto spelling, `symbol` itself when it has none.
+    /// The alphabetically first alias wins when several stand for the same symbol.
+    pub fn alias<'a>(&'a self, symbol: &'a str) -> &'a str {
+        self.0
+            .iter()
+            .find(|(_, target)| same_symbol(target, symbol))
+            .map_or(symbol, |(alias, _)| alias.as_str())
+    }
+
+    /// Alpaca symbols of `symbols`, along with the way back to the spelling the caller used.
+    pub(crate) fn resolve_all(&self, symbols: &[String]) -> (Vec<String>, SymbolRenames) {
+        let mut renames = HashMap::new();
+        let resolved = symbols
+            .iter()
+            .map(|symbol| {
+                let resolved = self.resolve(symbol);
+                if resolved != symbol {
+                    renames.insert(unslashed(resolved).to_ascii_uppercase(), symbol.clone());
+                }
+                resolved.to_string()
+            })
+            .collect();
+        (resolved, SymbolRenames(renames))
+    }
+}
+
+/// Maps the Alpaca symbols of a single request back to the aliases they were requested by.
+pub(crate) struct SymbolRenames(HashMap<String, String>);
+
+impl SymbolRenames {
+    pub(crate) fn restore(&self, symbol: &mut String) {
+        if let Some(requested) = self.0.get(&unslashed(symbol).to_ascii_uppercase()) {
+            symbol.clone_from(requested);
+        }
+    }
+}
+
+impl AlpacaClient {
+    pub fn set_symbol_aliases(&mut self, symbol_aliases: SymbolAliases) {
+        self.symbol_aliases = symbol_aliases;
+    }
+
+    pub fn symbol_aliases(&self) -> &SymbolAliases {
+        &self.symbol_aliases
+    }
+
+    /// `req` with its asset pair replaced by the Alpaca symbol it's an alias of.
+    pub(crate) fn resolve_order_request(&self, mut req: OrderRequest) -> Result<OrderRequest> {
+        let symbol = req.asset_pair.to_string();
+        let resolved = self.symbol_aliases.resolve(&symbol);
+        if resolved != symbol {
+            req.asset_pair = AssetPair::from_str(resolved)?;
+        }
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_both_crypto_spellings() {
+        assert!(same_symbol("BTC/USD", "BTCUSD"));
+        assert!(same_symbol("aapl", "AAPL"));
+        assert!(!same_symbol("BTC/USD", "ETHUSD"));
+    }
+
+    #[test]
+    fn maps_aliases_both_ways() {
+        let mut aliases = SymbolAliases::default();
+        aliases.insert("BTC", "BTC/USD");
+        aliases.insert("BRK.B", "BRK-B");
+
+        assert_eq!(aliases.resolve("BTC"), "BTC/USD");
+        assert_eq!(aliases.resolve("ETH/USD"), "ETH/USD");
+        assert_eq!(aliases.alias("BTCUSD"), "BTC");
+        assert_eq!(aliases.alias("BRK-B"), "BRK.B");
+
+        let (resolved, renames) = aliases.resolve_all(&["BTC".into(), "AAPL".into()]);
+        assert_eq!(resolved, ["BTC/USD", "AAPL"]);
+        let mut symbol = "BTC/USD".to_string();
+        renames.restore(&mut symbol);
+        assert_eq!(symbol, "BTC");
+    }
+}
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::data::Trade;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::America::New_York;
+use num_decimal::Num;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+/// Running figures of a symbol's trades during one session, a session being a New York
+/// calendar day.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SymbolStats {
+    pub session: NaiveDate,
+    pub vwap: Num,
+    pub high: Num,
+    pub low: Num,
+    pub volume: Num,
+    pub trade_count: u64,
+    pub last_trade_time: DateTime<Utc>,
+    notional: Num,
+}
+
+fn session_of(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&New_York).date_naive()
+}
+
+impl SymbolStats {
+    fn new(trade: &Trade) -> Self {
+        Self {
+            session: session_of(trade.time),
+            vwap: trade.price.clone(),
+            high: trade.price.clone(),
+            low: trade.price.clone(),
+            volume: trade.size.clone(),
+            trade_count: 1,
+            last_trade_time: trade.time,
+            notional: &trade.price * &trade.size,
+        }
+    }
+
+    fn add(&mut self, trade: &Trade) {
+        if session_of(trade.time) != self.session {
+            *self = Self::new(trade);
+            return;
+        }
+
+        if trade.price > self.high {
+            self.high = trade.price.clone();
+        }
+        if trade.price < self.low {
+            self.low = trade.price.clone();
+        }
+        self.volume += &trade.size;
+        self.notional += &trade.price * &trade.size;
+        if !self.volume.is_zero() {
+            self.vwap = &self.notional / &self.volume;
+        }
+        self.trade_count += 1;
+        self.last_trade_time = self.last_trade_time.max(trade.time);
+    }
+}
+
+/// Session statistics of every symbol the client has seen trades for.
+#[derive(Default)]
+pub struct SessionStats {
+    stats: Mutex<HashMap<String, SymbolStats>>,
+}
+
+impl SessionStats {
+    /// Adds a batch of trades, oldest first. Trades up to the latest one of an earlier batch
+    /// were counted already and are skipped, so overlapping fetches don't count twice.
+    pub(crate) fn record_all(&self, trades: &[Trade]) {
+        let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        let counted_until: HashMap<String, DateTime<Utc>> = stats
+            .iter()
+            .map(|(symbol, stats)| (symbol.clone(), stats.last_trade_time))
+            .collect();
+
+        for trade in trades {
+            if counted_until.get(&trade.symbol).is_some_and(|until| trade.time <= *until) {
+                continue;
+            }
+            match stats.get_mut(&trade.symbol) {
+                Some(symbol_stats) => symbol_stats.add(trade),
+                None => {
+                    stats.insert(trade.symbol.clone(), SymbolStats::new(trade));
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<SymbolStats> {
+        let stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        stats.get(symbol).cloned()
+    }
+}
+
+impl AlpacaClient {
+    /// VWAP, high, low and volume of the current session of `symbol`, from the trades fetched
+    /// through the client or fed to [`AlpacaClient::record_trades`]. Until the first trade of a
+    /// new session comes in these are the previous session's, check [`SymbolStats::session`].
+    pub fn symbol_stats(&self, symbol: &str) -> Option<SymbolStats> {
+        self.session_stats.get(symbol)
+    }
+
+    /// Feeds trades received elsewhere, e.g. from a stream, into [`AlpacaClient::symbol_stats`].
+    pub fn record_trades(&self, trades: &[Trade]) {
+        self.session_stats.record_all(trades);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn trade(day: u32, hour: u32, price: i64, size: i64) -> Trade {
+        Trade {
+            symbol: "BTC/USD".into(),
+            exchange: None,
+            time: Utc.with_ymd_and_hms(2024, 1, day, hour, 0, 0).unwrap(),
+            price: Num::from(price),
+            size: Num::from(size),
+        }
+    }
+
+    #[test]
+    fn accumulates_session_vwap() {
+        let stats = SessionStats::default();
+        stats.record_all(&[trade(18, 15, 100, 2), trade(18, 16, 110, 1)]);
+        // Overlaps the first batch, only the last trade is new
+        stats.record_all(&[trade(18, 16, 110, 1), trade(18, 17, 90, 1)]);
+
+        let btc = stats.get("BTC/USD").unwrap();
+        assert_eq!(btc.vwap, Num::from(100));
+        assert_eq!(btc.high, Num::from(110));
+        assert_eq!(btc.low, Num::from(90));
+        assert_eq!(btc.volume, Num::from(4));
+        assert_eq!(btc.trade_count, 3);
+    }
+
+    #[test]
+    fn resets_on_new_session() {
+        let stats = SessionStats::default();
+        stats.record_all(&[trade(18, 15, 100, 1), trade(19, 15, 120, 2)]);
+
+        let btc = stats.get("BTC/USD").unwrap();
+        assert_eq!(btc.session, NaiveDate::from_ymd_opt(2024, 1, 19).unwrap());
+        assert_eq!(btc.vwap, Num::from(120));
+        assert_eq!(btc.volume, Num::from(2));
+    }
+}
@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::deadline::DeadlineFill;
+use crate::order_defaults::OrderOptions;
+use crate::positions::AssetClass;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::{Result, bail};
+use apca::api::v2::order::{Amount, CreateReqInit, Order as ApcaOrder, Side, Type};
+use apca::api::v2::orders::{self, ListReq, Status};
+use futures::FutureExt;
+use futures::future::join_all;
+use irontrade::api::request::OrderRequest;
+use num_decimal::Num;
+use std::collections::BTreeMap;
+use std::panic::{AssertUnwindSafe, resume_unwind};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+const SETTLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Enough to cover every order a single test places
+const TAGGED_ORDERS_LIMIT: usize = 500;
+
+/// A path under the temp directory that no other test, in this run or a concurrent one, gets.
+pub fn unique_temp_path(name: &str) -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let count = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("irontrade_alpaca_{}_{count}_{name}", std::process::id()))
+}
+
+impl AlpacaClient {
+    /// Cancels every open order and closes every position, then waits up to `timeout` until the
+    /// account has neither left, so a test run starts from a clean slate. Refuses to touch
+    /// anything but a paper account.
+    pub async fn reset_paper_account(&self, timeout: Duration) -> Result<()> {
+        let api_base_url = self.apca_client.api_info().api_base_url.to_string();
+        if !api_base_url.contains("paper") {
+            bail!("refusing to reset {api_base_url}, only paper accounts can be reset");
+        }
+
+        let orders = self.list_open_orders().await?;
+        let cancels = orders.iter().map(|order| self.cancel_order(&order.order_id));
+        for result in join_all(cancels).await {
+            result?;
+        }
+
+        // Closing needs the cancels through first, open orders hold the quantity
+        let positions = self.list_open_positions().await?;
+        let closes = positions
+            .iter()
+            .map(|position| self.close_position_by_symbol(&position.asset_symbol));
+        for result in join_all(closes).await {
+            result?;
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let open_orders = self.list_open_orders().await?.len();
+            let open_positions = self.list_open_positions().await?.len();
+            if open_orders == 0 && open_positions == 0 {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                bail!("paper account still has {open_orders} open orders and {open_positions} positions");
+            }
+            sleep(SETTLE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Runs a paper account test with every order it places tagged by a unique `client_order_id`
+/// prefix, and cleans up only those orders afterwards: open ones are canceled and whatever they
+/// filled is traded back. Tests using it can share the account and run in parallel.
+pub struct TestHarness {
+    client: AlpacaClient,
+    tag: String,
+    placed: u64,
+}
+
+impl TestHarness {
+    pub fn new(client: AlpacaClient) -> Self {
+        Self {
+            client,
+            tag: format!("test-{}", Uuid::new_v4().simple()),
+            placed: 0,
+        }
+    }
+
+    pub fn client(&mut self) -> &mut AlpacaClient {
+        &mut self.client
+    }
+
+    /// Prefix of the `client_order_id` of every order placed through the harness.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    fn next_client_order_id(&mut self) -> String {
+        self.placed += 1;
+        format!("{}-{}", self.tag, self.placed)
+    }
+
+    pub async fn place_order(&mut self, req: OrderRequest) -> Result<String> {
+        let options = OrderOptions {
+            client_order_id: Some(self.next_client_order_id()),
+            ..Default::default()
+        };
+        self.client.place_order_with_options(req, options).await
+    }
+
+    /// Tagged counterpart of [`AlpacaClient::place_order_with_deadline`].
+    pub async fn place_order_with_deadline(
+        &mut self,
+        req: OrderRequest,
+        deadline: std::time::Instant,
+        cancellation: &CancellationToken,
+    ) -> Result<DeadlineFill> {
+        let order_id = self.place_order(req).await?;
+        self.client.settle_by_deadline(&order_id, deadline, cancellation).await
+    }
+
+    /// Runs `test` and cleans up after it whether it succeeds, fails or panics.
+    pub async fn run<T>(&mut self, test: impl AsyncFnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let outcome = AssertUnwindSafe(test(self)).catch_unwind().await;
+        let cleanup = self.cleanup().await;
+        match outcome {
+            Ok(result) => {
+                let value = result?;
+                cleanup?;
+                Ok(value)
+            }
+            Err(panic) => resume_unwind(panic),
+        }
+    }
+
+    async fn tagged_orders(&self, status: Status) -> Result<Vec<ApcaOrder>> {
+        let request = ListReq {
+            status,
+            limit: Some(TAGGED_ORDERS_LIMIT),
+            ..Default::default()
+        };
+        let orders = self
+            .client
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.client.apca_client.issue::<orders::List>(&request)
+            })
+            .await?;
+        Ok(orders
+            .into_iter()
+            .filter(|order| order.client_order_id.starts_with(&self.tag))
+            .collect())
+    }
+
+    /// Cancels the open tagged orders and trades back the net quantity the tagged orders filled
+    /// per symbol, leaving positions opened by anything else alone. No more than the position
+    /// holds is traded back, crypto fees are taken from the quantity bought.
+    pub async fn cleanup(&mut self) -> Result<()> {
+        let open_orders = self.tagged_orders(Status::Open).await?;
+        let cancels = open_orders
+            .iter()
+            .map(|order| self.client.cancel_order(&order.id.to_string()));
+        for result in join_all(cancels).await {
+            result?;
+        }
+
+        let mut net_quantities: BTreeMap<String, Num> = BTreeMap::new();
+        for order in self.tagged_orders(Status::All).await? {
+            let net_quantity = net_quantities.entry(order.symbol.clone()).or_insert(Num::from(0));
+            match order.side {
+                Side::Buy => *net_quantity += &order.filled_quantity,
+                Side::Sell => *net_quantity -= &order.filled_quantity,
+            }
+        }
+
+        for (symbol, net_quantity) in net_quantities {
+            if net_quantity.is_zero() {
+                continue;
+            }
+            let Some(position) = self.client.find_open_position(&symbol).await? else {
+                continue;
+            };
+            let (side, quantity) = match (net_quantity.is_positive(), position.quantity.is_positive()) {
+                (true, true) => (Side::Sell, net_quantity.min(position.quantity)),
+                (false, false) => (Side::Buy, (-net_quantity).min(-position.quantity)),
+                // Something else turned the position around, it isn't the test's to undo
+                _ => continue,
+            };
+            let time_in_force = self
+                .client
+                .order_defaults()
+                .for_class(AssetClass::of_symbol(&symbol))
+                .time_in_force;
+            let request = CreateReqInit {
+                type_: Type::Market,
+                time_in_force: time_in_force.into(),
+                // Tagged as well, so a second cleanup nets out to nothing
+                client_order_id: Some(self.next_client_order_id()),
+                ..Default::default()
+            }
+            .init(symbol.clone(), side, Amount::Quantity { quantity });
+            self.client.submit_order(&symbol, &request, RetryOverride::Default).await?;
+        }
+
+        Ok(())
+    }
+}
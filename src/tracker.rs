@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::intent::OrderAnnotation;
+use chrono::{DateTime, Utc};
+use irontrade::api::common::{Order as IronTradeOrder, OrderStatus};
+use num_decimal::Num;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// An order placed through this client, as last seen by it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackedOrder {
+    pub order_id: String,
+    pub asset_symbol: String,
+    pub placed_at: DateTime<Utc>,
+    pub filled_quantity: Num,
+    /// Filled or expired, the order won't change anymore
+    pub done: bool,
+    #[serde(default)]
+    pub annotation: Option<OrderAnnotation>,
+    /// Id of the order this one replaced
+    #[serde(default)]
+    pub replaces: Option<String>,
+    /// Id of the order that replaced this one
+    #[serde(default)]
+    pub replaced_by: Option<String>,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+}
+
+/// Local record of the orders placed through the client, keyed by order id.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderTracker {
+    orders: BTreeMap<String, TrackedOrder>,
+}
+
+impl OrderTracker {
+    pub(crate) fn track(&mut self, order_id: String, asset_symbol: String) {
+        self.orders.insert(
+            order_id.clone(),
+            TrackedOrder {
+                order_id,
+                asset_symbol,
+                placed_at: Utc::now(),
+                filled_quantity: Num::from(0),
+                done: false,
+                annotation: None,
+                replaces: None,
+                replaced_by: None,
+                client_order_id: None,
+            },
+        );
+    }
+
+    pub(crate) fn update(&mut self, order: &IronTradeOrder) {
+        if let Some(tracked) = self.orders.get_mut(&order.order_id) {
+            tracked.filled_quantity = order.filled_quantity.clone();
+            tracked.done = matches!(order.status, OrderStatus::Filled | OrderStatus::Expired);
+        }
+    }
+
+    /// Marks `order_id` as done and links it with its replacement, which is tracked for the same
+    /// symbol unless it already is.
+    pub(crate) fn replace(&mut self, order_id: &str, new_order_id: String) {
+        let Some(tracked) = self.orders.get_mut(order_id) else {
+            return;
+        };
+        tracked.done = true;
+        tracked.replaced_by = Some(new_order_id.clone());
+        let asset_symbol = tracked.asset_symbol.clone();
+        let annotation = tracked.annotation.clone();
+
+        if !self.orders.contains_key(&new_order_id) {
+            self.track(new_order_id.clone(), asset_symbol);
+        }
+        if let Some(replacement) = self.orders.get_mut(&new_order_id) {
+            replacement.replaces = Some(order_id.to_string());
+            if replacement.annotation.is_none() {
+                replacement.annotation = annotation;
+            }
+        }
+    }
+
+    pub(crate) fn identify(&mut self, order_id: &str, client_order_id: String) {
+        if let Some(tracked) = self.orders.get_mut(order_id) {
+            tracked.client_order_id = Some(client_order_id);
+        }
+    }
+
+    pub(crate) fn annotate(&mut self, order_id: &str, annotation: OrderAnnotation) {
+        if let Some(tracked) = self.orders.get_mut(order_id) {
+            tracked.annotation = Some(annotation);
+        }
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<&TrackedOrder> {
+        self.orders.get(order_id)
+    }
+
+    pub fn get_by_client_order_id(&self, client_order_id: &str) -> Option<&TrackedOrder> {
+        self.orders
+            .values()
+            .find(|order| order.client_order_id.as_deref() == Some(client_order_id))
+    }
+
+    /// Id of the first order of the replace chain `order_id` belongs to, `order_id` itself if it
+    /// didn't replace another order.
+    pub fn original_order_id<'a>(&'a self, order_id: &'a str) -> &'a str {
+        let mut original = order_id;
+        // Bounded in case a corrupted state file links orders in a cycle
+        for _ in 0..self.orders.len() {
+            match self.orders.get(original).and_then(|order| order.replaces.as_deref()) {
+                Some(replaces) => original = replaces,
+                None => break,
+            }
+        }
+        original
+    }
+
+    /// Ids of the replace chain `order_id` belongs to, from the original order to the latest
+    /// replacement.
+    pub fn replace_chain(&self, order_id: &str) -> Vec<String> {
+        let mut chain = vec![self.original_order_id(order_id).to_string()];
+        while chain.len() <= self.orders.len() {
+            let last = chain.last().and_then(|order_id| self.orders.get(order_id));
+            match last.and_then(|order| order.replaced_by.clone()) {
+                Some(replaced_by) => chain.push(replaced_by),
+                None => break,
+            }
+        }
+        chain
+    }
+
+    pub fn open_orders(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.orders.values().filter(|order| !order.done)
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.orders.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_replace_chains() {
+        let mut tracker = OrderTracker::default();
+        tracker.track("a".into(), "AAPL".into());
+        tracker.replace("a", "b".into());
+        tracker.replace("b", "c".into());
+
+        assert_eq!(tracker.original_order_id("c"), "a");
+        assert_eq!(tracker.original_order_id("a"), "a");
+        assert_eq!(tracker.replace_chain("b"), ["a", "b", "c"]);
+        assert!(tracker.get("b").is_some_and(|order| order.done));
+        assert_eq!(tracker.open_orders().count(), 1);
+    }
+}
@@ -0,0 +1,483 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::format::{format_money, format_price, format_quantity};
+use crate::intent::OrderAnnotation;
+use crate::positions::AssetClass;
+use crate::symbol::same_symbol;
+use anyhow::Result;
+use apca::api::v2::account_activities::{Activity, ActivityType, Side as ActivitySide};
+use chrono::{DateTime, TimeDelta, Utc};
+use irontrade::api::common::OrderSide;
+use num_decimal::Num;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+
+/// One execution of an order.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fill {
+    pub order_id: String,
+    /// First order of the replace chain the filled order belongs to, `order_id` unless the filled
+    /// order replaced another one
+    pub original_order_id: String,
+    pub asset_symbol: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::order_side"))]
+    pub side: OrderSide,
+    pub quantity: Num,
+    pub price: Num,
+    /// Fees charged for this execution, zero when unknown
+    pub fees: Num,
+    pub time: DateTime<Utc>,
+    /// Known for orders annotated through this client
+    pub annotation: Option<OrderAnnotation>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TradeDirection {
+    Long,
+    Short,
+}
+
+/// A position opened and closed again, matched first in first out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundTrip {
+    pub asset_symbol: String,
+    pub direction: TradeDirection,
+    pub quantity: Num,
+    pub entry_order_id: String,
+    pub exit_order_id: String,
+    pub entry_time: DateTime<Utc>,
+    pub exit_time: DateTime<Utc>,
+    pub entry_price: Num,
+    pub exit_price: Num,
+    /// Entry and exit fees attributable to the matched quantity
+    pub fees: Num,
+    /// Profit or loss net of fees
+    pub pnl: Num,
+    pub entry_annotation: Option<OrderAnnotation>,
+    pub exit_annotation: Option<OrderAnnotation>,
+}
+
+impl RoundTrip {
+    pub fn holding_period(&self) -> TimeDelta {
+        self.exit_time - self.entry_time
+    }
+
+    pub fn is_loss(&self) -> bool {
+        self.pnl.is_negative()
+    }
+}
+
+/// Buying back within this long after selling at a loss makes it a wash sale.
+pub const WASH_SALE_WINDOW: TimeDelta = TimeDelta::days(30);
+
+/// A round trip closed at a loss followed by a re-entry into the same symbol and direction
+/// within [`WASH_SALE_WINDOW`]. In a taxable account the loss on the re-entered quantity is
+/// disallowed and added to the cost basis of the new lot instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WashSale {
+    pub asset_symbol: String,
+    /// Exit order of the losing round trip
+    pub loss_order_id: String,
+    pub loss_time: DateTime<Utc>,
+    pub reentry_order_id: String,
+    pub reentry_time: DateTime<Utc>,
+    /// Part of the losing quantity that was re-entered
+    pub quantity: Num,
+    /// Loss attributable to `quantity`, positive
+    pub disallowed_loss: Num,
+}
+
+/// Quantity a fill opened, before anything closed it again.
+#[derive(Clone, Debug)]
+struct Opening {
+    order_id: String,
+    asset_symbol: String,
+    direction: TradeDirection,
+    quantity: Num,
+    time: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug)]
+struct Lot {
+    order_id: String,
+    direction: TradeDirection,
+    quantity: Num,
+    price: Num,
+    fees_per_unit: Num,
+    time: DateTime<Utc>,
+    annotation: Option<OrderAnnotation>,
+}
+
+/// Completed round trips built from a stream of fills, plus the lots still open.
+#[derive(Clone, Debug, Default)]
+pub struct TradeLog {
+    round_trips: Vec<RoundTrip>,
+    open_lots: HashMap<String, VecDeque<Lot>>,
+    openings: Vec<Opening>,
+}
+
+/// Quotes free-form text so commas and quotes in it don't break the row.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl TradeLog {
+    pub fn from_fills<'a>(fills: impl IntoIterator<Item = &'a Fill>) -> Self {
+        let mut trade_log = Self::default();
+        for fill in fills {
+            trade_log.add_fill(fill);
+        }
+        trade_log
+    }
+
+    /// Closes open lots of the opposite direction first in first out, and opens a new lot with
+    /// whatever quantity is left.
+    pub fn add_fill(&mut self, fill: &Fill) {
+        if !fill.quantity.is_positive() {
+            return;
+        }
+
+        let fill_direction = match fill.side {
+            OrderSide::Buy => TradeDirection::Long,
+            OrderSide::Sell => TradeDirection::Short,
+        };
+        let fees_per_unit = &fill.fees / &fill.quantity;
+        let lots = self.open_lots.entry(fill.asset_symbol.clone()).or_default();
+        let mut remaining = fill.quantity.clone();
+
+        while remaining.is_positive() {
+            let Some(lot) = lots.front_mut() else {
+                break;
+            };
+            if lot.direction == fill_direction {
+                break;
+            }
+
+            let quantity = if lot.quantity < remaining {
+                lot.quantity.clone()
+            } else {
+                remaining.clone()
+            };
+            let gross = match lot.direction {
+                TradeDirection::Long => (&fill.price - &lot.price) * &quantity,
+                TradeDirection::Short => (&lot.price - &fill.price) * &quantity,
+            };
+            let fees = (&lot.fees_per_unit + &fees_per_unit) * &quantity;
+
+            self.round_trips.push(RoundTrip {
+                asset_symbol: fill.asset_symbol.clone(),
+                direction: lot.direction,
+                quantity: quantity.clone(),
+                entry_order_id: lot.order_id.clone(),
+                exit_order_id: fill.order_id.clone(),
+                entry_time: lot.time,
+                exit_time: fill.time,
+                entry_price: lot.price.clone(),
+                exit_price: fill.price.clone(),
+                pnl: gross - &fees,
+                fees,
+                entry_annotation: lot.annotation.clone(),
+                exit_annotation: fill.annotation.clone(),
+            });
+
+            lot.quantity -= &quantity;
+            remaining -= &quantity;
+            if lot.quantity.is_zero() {
+                lots.pop_front();
+            }
+        }
+
+        if remaining.is_positive() {
+            self.openings.push(Opening {
+                order_id: fill.order_id.clone(),
+                asset_symbol: fill.asset_symbol.clone(),
+                direction: fill_direction,
+                quantity: remaining.clone(),
+                time: fill.time,
+            });
+            lots.push_back(Lot {
+                order_id: fill.order_id.clone(),
+                direction: fill_direction,
+                quantity: remaining,
+                price: fill.price.clone(),
+                fees_per_unit,
+                time: fill.time,
+                annotation: fill.annotation.clone(),
+            });
+        }
+    }
+
+    pub fn round_trips(&self) -> &[RoundTrip] {
+        &self.round_trips
+    }
+
+    pub fn for_symbol<'a>(&'a self, asset_symbol: &'a str) -> impl Iterator<Item = &'a RoundTrip> {
+        self.round_trips
+            .iter()
+            .filter(move |round_trip| same_symbol(&round_trip.asset_symbol, asset_symbol))
+    }
+
+    /// Round trips closed within `[from, to)`.
+    pub fn closed_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> impl Iterator<Item = &RoundTrip> {
+        self.round_trips
+            .iter()
+            .filter(move |round_trip| from <= round_trip.exit_time && round_trip.exit_time < to)
+    }
+
+    /// Potential wash sales, each losing round trip matched against the re-entries following it
+    /// in order until its quantity is used up. A re-entry's quantity only counts towards one loss.
+    pub fn wash_sales(&self) -> Vec<WashSale> {
+        self.match_wash_sales().into_iter().map(|(_, wash_sale)| wash_sale).collect()
+    }
+
+    /// Wash sales with the index of their losing round trip.
+    fn match_wash_sales(&self) -> Vec<(usize, WashSale)> {
+        let mut available: Vec<Num> = self.openings.iter().map(|opening| opening.quantity.clone()).collect();
+        let mut wash_sales = Vec::new();
+
+        for (index, round_trip) in self.round_trips.iter().enumerate() {
+            if !round_trip.is_loss() {
+                continue;
+            }
+            let mut unmatched = round_trip.quantity.clone();
+            for (opening, available) in self.openings.iter().zip(&mut available) {
+                if !unmatched.is_positive() {
+                    break;
+                }
+                let in_window = round_trip.exit_time < opening.time
+                    && opening.time <= round_trip.exit_time + WASH_SALE_WINDOW;
+                if !in_window
+                    || !available.is_positive()
+                    || opening.direction != round_trip.direction
+                    || !same_symbol(&opening.asset_symbol, &round_trip.asset_symbol)
+                {
+                    continue;
+                }
+
+                let quantity = if *available < unmatched {
+                    available.clone()
+                } else {
+                    unmatched.clone()
+                };
+                *available -= &quantity;
+                unmatched -= &quantity;
+                let wash_sale = WashSale {
+                    asset_symbol: round_trip.asset_symbol.clone(),
+                    loss_order_id: round_trip.exit_order_id.clone(),
+                    loss_time: round_trip.exit_time,
+                    reentry_order_id: opening.order_id.clone(),
+                    reentry_time: opening.time,
+                    disallowed_loss: -&round_trip.pnl * &quantity / &round_trip.quantity,
+                    quantity,
+                };
+                wash_sales.push((index, wash_sale));
+            }
+        }
+
+        wash_sales
+    }
+
+    /// Quantity still open per symbol, negative for shorts.
+    pub fn open_quantity(&self, asset_symbol: &str) -> Num {
+        self.open_lots
+            .iter()
+            .filter(|(symbol, _)| same_symbol(symbol, asset_symbol))
+            .flat_map(|(_, lots)| lots)
+            .fold(Num::from(0), |quantity, lot| match lot.direction {
+                TradeDirection::Long => quantity + &lot.quantity,
+                TradeDirection::Short => quantity - &lot.quantity,
+            })
+    }
+
+    pub fn write_csv(&self, mut writer: impl Write) -> Result<()> {
+        writeln!(
+            writer,
+            "symbol,direction,quantity,entry_time,exit_time,entry_price,exit_price,holding_seconds,fees,pnl,\
+             entry_intent,exit_intent,entry_note,exit_note,wash_sale_disallowed_loss"
+        )?;
+        let wash_sales = self.match_wash_sales();
+        for (index, round_trip) in self.round_trips.iter().enumerate() {
+            let asset_class = AssetClass::of_symbol(&round_trip.asset_symbol);
+            let direction = match round_trip.direction {
+                TradeDirection::Long => "long",
+                TradeDirection::Short => "short",
+            };
+            let intent = |annotation: &Option<OrderAnnotation>| match annotation {
+                Some(annotation) => annotation.intent.to_string(),
+                None => String::new(),
+            };
+            let note = |annotation: &Option<OrderAnnotation>| {
+                let note = annotation.as_ref().and_then(|annotation| annotation.note.as_deref());
+                csv_field(note.unwrap_or_default())
+            };
+            let disallowed_loss = wash_sales
+                .iter()
+                .filter(|(wash_sale_index, _)| *wash_sale_index == index)
+                .fold(None, |total: Option<Num>, (_, wash_sale)| {
+                    Some(total.unwrap_or_else(|| Num::from(0)) + &wash_sale.disallowed_loss)
+                });
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                round_trip.asset_symbol,
+                direction,
+                format_quantity(&round_trip.quantity),
+                round_trip.entry_time.to_rfc3339(),
+                round_trip.exit_time.to_rfc3339(),
+                format_price(&round_trip.entry_price, asset_class),
+                format_price(&round_trip.exit_price, asset_class),
+                round_trip.holding_period().num_seconds(),
+                format_money(&round_trip.fees),
+                format_money(&round_trip.pnl),
+                intent(&round_trip.entry_annotation),
+                intent(&round_trip.exit_annotation),
+                note(&round_trip.entry_annotation),
+                note(&round_trip.exit_annotation),
+                disallowed_loss.map(|loss| format_money(&loss)).unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl AlpacaClient {
+    /// Returns the order executions booked after the given time, oldest first.
+    pub async fn get_fills(&self, after: Option<DateTime<Utc>>) -> Result<Vec<Fill>> {
+        let activities = self.get_activities(vec![ActivityType::Fill], after).await?;
+
+        let order_tracker = self.order_tracker();
+        let fills = activities
+            .into_iter()
+            .filter_map(|activity| match activity {
+                Activity::Trade(activity) => {
+                    let order_id = activity.order_id.to_string();
+                    Some(Fill {
+                        original_order_id: order_tracker.original_order_id(&order_id).to_string(),
+                        annotation: order_tracker.get(&order_id).and_then(|order| order.annotation.clone()),
+                        order_id,
+                        asset_symbol: activity.symbol,
+                        side: match activity.side {
+                            ActivitySide::Buy => OrderSide::Buy,
+                            ActivitySide::Sell | ActivitySide::ShortSell => OrderSide::Sell,
+                        },
+                        quantity: activity.quantity,
+                        price: activity.price,
+                        fees: Num::from(0),
+                        time: activity.transaction_time,
+                    })
+                }
+                Activity::NonTrade(_) => None,
+            })
+            .collect();
+
+        Ok(fills)
+    }
+
+    /// Pairs the fills booked after the given time into round trips. Positions opened before
+    /// `after` show up as unmatched exits, so start from a time the account was flat.
+    pub async fn get_trade_log(&self, after: Option<DateTime<Utc>>) -> Result<TradeLog> {
+        let fills = self.get_fills(after).await?;
+        Ok(TradeLog::from_fills(&fills))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intent::OrderIntent;
+    use chrono::TimeZone;
+
+    fn fill(order_id: &str, side: OrderSide, quantity: i64, price: i64, minute: u32) -> Fill {
+        Fill {
+            order_id: order_id.into(),
+            original_order_id: order_id.into(),
+            asset_symbol: "AAPL".into(),
+            side,
+            quantity: Num::from(quantity),
+            price: Num::from(price),
+            fees: Num::from(0),
+            time: Utc.with_ymd_and_hms(2024, 1, 18, 15, minute, 0).unwrap(),
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn pairs_fills_first_in_first_out() {
+        let trade_log = TradeLog::from_fills(&[
+            fill("a", OrderSide::Buy, 10, 100, 0),
+            fill("b", OrderSide::Buy, 10, 110, 1),
+            fill("c", OrderSide::Sell, 15, 120, 2),
+        ]);
+
+        let round_trips = trade_log.round_trips();
+        assert_eq!(round_trips.len(), 2);
+        assert_eq!(round_trips[0].pnl, Num::from(200));
+        assert_eq!(round_trips[1].quantity, Num::from(5));
+        assert_eq!(round_trips[1].pnl, Num::from(50));
+        assert_eq!(round_trips[1].holding_period(), TimeDelta::minutes(1));
+        assert_eq!(trade_log.open_quantity("AAPL"), Num::from(5));
+    }
+
+    #[test]
+    fn flips_into_short_and_charges_fees() {
+        let mut exit = fill("b", OrderSide::Sell, 20, 90, 1);
+        exit.fees = Num::from(2);
+
+        let trade_log = TradeLog::from_fills(&[fill("a", OrderSide::Buy, 10, 100, 0), exit]);
+
+        let round_trip = &trade_log.round_trips()[0];
+        assert!(round_trip.is_loss());
+        assert_eq!(round_trip.fees, Num::from(1));
+        assert_eq!(round_trip.pnl, Num::from(-101));
+        assert_eq!(trade_log.open_quantity("AAPL"), Num::from(-10));
+    }
+
+    #[test]
+    fn flags_reentries_after_a_loss() {
+        let mut reentry = fill("d", OrderSide::Buy, 4, 85, 0);
+        reentry.time += TimeDelta::days(10);
+        let mut late_reentry = fill("e", OrderSide::Buy, 10, 80, 0);
+        late_reentry.time += TimeDelta::days(31);
+
+        let trade_log = TradeLog::from_fills(&[
+            fill("a", OrderSide::Buy, 10, 100, 0),
+            fill("b", OrderSide::Sell, 10, 90, 1),
+            reentry,
+            late_reentry,
+        ]);
+
+        let wash_sales = trade_log.wash_sales();
+        assert_eq!(wash_sales.len(), 1);
+        assert_eq!(wash_sales[0].reentry_order_id, "d");
+        assert_eq!(wash_sales[0].quantity, Num::from(4));
+        assert_eq!(wash_sales[0].disallowed_loss, Num::from(40));
+    }
+
+    #[test]
+    fn writes_csv() -> Result<()> {
+        let mut entry = fill("a", OrderSide::Buy, 1, 100, 0);
+        entry.annotation = Some(OrderAnnotation::with_note(OrderIntent::Entry, "breakout, retest"));
+        let trade_log = TradeLog::from_fills(&[entry, fill("b", OrderSide::Sell, 1, 101, 5)]);
+
+        let mut csv = Vec::new();
+        trade_log.write_csv(&mut csv)?;
+        let csv = String::from_utf8(csv)?;
+
+        assert_eq!(csv.lines().count(), 2);
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("AAPL,long,1,"));
+        assert!(row.ends_with(",entry,,\"breakout, retest\",,"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::auction::MarketSession;
+use crate::client::AlpacaClient;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc, Weekday};
+use chrono_tz::America::New_York;
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, PoisonError};
+
+/// Daily window in local time, from `start` inclusive to `end` exclusive. A window whose end is
+/// before its start wraps past midnight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TradingWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TradingWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+/// When the client is permitted to place orders, e.g. 09:45 to 15:45 New York time, not in the
+/// last 5 minutes of the session and not on Fridays:
+///
+/// ```toml
+/// [trading_schedule]
+/// windows = [{ start = "09:45", end = "15:45" }]
+/// skip_last_minutes = 5
+/// blocked_weekdays = ["Fri"]
+/// ```
+///
+/// The first and last minutes are counted from the open and close of the day's session in the
+/// market calendar, so they move with early closes. Skipping any of them also keeps orders
+/// within the session.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(deny_unknown_fields)]
+pub struct TradingSchedule {
+    pub windows: Vec<TradingWindow>,
+    #[serde(default)]
+    pub skip_first_minutes: u32,
+    #[serde(default)]
+    pub skip_last_minutes: u32,
+    #[serde(default)]
+    pub blocked_weekdays: Vec<Weekday>,
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+}
+
+fn default_timezone() -> Tz {
+    New_York
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutsideTradingWindow {
+    pub at: DateTime<Tz>,
+}
+
+impl Display for OutsideTradingWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is outside the permitted trading windows", self.at.format("%a %H:%M %Z"))
+    }
+}
+
+impl std::error::Error for OutsideTradingWindow {}
+
+impl TradingSchedule {
+    /// Whether the schedule depends on the day's market session.
+    pub fn uses_session(&self) -> bool {
+        self.skip_first_minutes > 0 || self.skip_last_minutes > 0
+    }
+
+    /// Checks `now` against the schedule, with `session` the market session of its day in New
+    /// York, `None` when the market doesn't open that day.
+    pub fn check(&self, now: DateTime<Utc>, session: Option<&MarketSession>) -> Result<(), OutsideTradingWindow> {
+        let local = self.timezone.from_utc_datetime(&now.naive_utc());
+
+        let blocked_day = self.blocked_weekdays.contains(&local.weekday());
+        let in_window = self.windows.iter().any(|window| window.contains(local.time()));
+        let in_session = !self.uses_session()
+            || session.is_some_and(|session| {
+                now >= session.open + TimeDelta::minutes(self.skip_first_minutes.into())
+                    && now < session.close - TimeDelta::minutes(self.skip_last_minutes.into())
+            });
+        if blocked_day || !in_window || !in_session {
+            return Err(OutsideTradingWindow { at: local });
+        }
+        Ok(())
+    }
+}
+
+/// The market session of the day last checked, so the calendar is asked once a day.
+#[derive(Debug, Default)]
+pub(crate) struct SessionCache(Mutex<Option<(NaiveDate, Option<MarketSession>)>>);
+
+impl AlpacaClient {
+    pub fn set_trading_schedule(&mut self, trading_schedule: Option<TradingSchedule>) {
+        self.trading_schedule = trading_schedule;
+    }
+
+    /// Fails with [`OutsideTradingWindow`] when the trading schedule doesn't permit orders now.
+    pub(crate) async fn check_trading_schedule(&self) -> Result<()> {
+        let Some(trading_schedule) = &self.trading_schedule else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        let session = if trading_schedule.uses_session() {
+            self.session_of(now).await?
+        } else {
+            None
+        };
+        trading_schedule.check(now, session.as_ref())?;
+        Ok(())
+    }
+
+    async fn session_of(&self, now: DateTime<Utc>) -> Result<Option<MarketSession>> {
+        let today = now.with_timezone(&New_York).date_naive();
+        if let Some((date, session)) = &*self.session_cache.0.lock().unwrap_or_else(PoisonError::into_inner)
+            && *date == today
+        {
+            return Ok(session.clone());
+        }
+
+        let session = self
+            .get_market_sessions(today, today)
+            .await?
+            .into_iter()
+            .find(|session| session.date == today);
+        *self.session_cache.0.lock().unwrap_or_else(PoisonError::into_inner) = Some((today, session.clone()));
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> TradingSchedule {
+        TradingSchedule {
+            windows: vec![TradingWindow {
+                start: NaiveTime::from_hms_opt(9, 45, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(15, 45, 0).unwrap(),
+            }],
+            skip_first_minutes: 0,
+            skip_last_minutes: 0,
+            blocked_weekdays: vec![Weekday::Fri],
+            timezone: New_York,
+        }
+    }
+
+    #[test]
+    fn checks_window_in_local_time() {
+        // Thursday 2024-01-18, 10:00 in New York is 15:00 UTC
+        let inside = Utc.with_ymd_and_hms(2024, 1, 18, 15, 0, 0).unwrap();
+        let too_early = Utc.with_ymd_and_hms(2024, 1, 18, 14, 30, 0).unwrap();
+
+        assert!(schedule().check(inside, None).is_ok());
+        assert!(schedule().check(too_early, None).is_err());
+    }
+
+    #[test]
+    fn rejects_blocked_weekdays() {
+        let friday = Utc.with_ymd_and_hms(2024, 1, 19, 15, 0, 0).unwrap();
+        assert!(schedule().check(friday, None).is_err());
+    }
+
+    #[test]
+    fn skips_the_last_minutes_before_an_early_close() {
+        let schedule = TradingSchedule {
+            skip_last_minutes: 5,
+            blocked_weekdays: Vec::new(),
+            ..schedule()
+        };
+        // Friday 2024-11-29 closed at 13:00 in New York, 18:00 UTC
+        let date = NaiveDate::from_ymd_opt(2024, 11, 29).unwrap();
+        let session = MarketSession {
+            date,
+            open: Utc.with_ymd_and_hms(2024, 11, 29, 14, 30, 0).unwrap(),
+            close: Utc.with_ymd_and_hms(2024, 11, 29, 18, 0, 0).unwrap(),
+        };
+
+        let before = Utc.with_ymd_and_hms(2024, 11, 29, 17, 50, 0).unwrap();
+        let last_minutes = Utc.with_ymd_and_hms(2024, 11, 29, 17, 57, 0).unwrap();
+        let after_close = Utc.with_ymd_and_hms(2024, 11, 29, 19, 0, 0).unwrap();
+        assert!(schedule.check(before, Some(&session)).is_ok());
+        assert!(schedule.check(last_minutes, Some(&session)).is_err());
+        assert!(schedule.check(after_close, Some(&session)).is_err());
+        assert!(schedule.check(before, None).is_err());
+    }
+
+    #[test]
+    fn windows_can_wrap_midnight() {
+        let window = TradingWindow {
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+        };
+
+        assert!(window.contains(NaiveTime::from_hms_opt(23, 0, 0).unwrap()));
+        assert!(window.contains(NaiveTime::from_hms_opt(1, 0, 0).unwrap()));
+        assert!(!window.contains(NaiveTime::from_hms_opt(12, 0, 0).unwrap()));
+    }
+}
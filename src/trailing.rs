@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::convert::Order;
+use crate::precision::precision_policy;
+use anyhow::{Result, bail};
+use apca::api::v2::order::{Order as ApcaOrder, Type};
+use irontrade::api::common::Order as IronTradeOrder;
+use num_decimal::Num;
+
+/// How far a trailing stop follows the best price since the order was placed, the high for a
+/// sell and the low for a buy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Trail {
+    /// Percent of the best price, e.g. 2 for 2%
+    Percent(Num),
+    /// Dollar amount below or above the best price
+    Price(Num),
+}
+
+impl Trail {
+    /// The `trail_price` and `trail_percent` fields of an Alpaca order, one of them set.
+    pub(crate) fn into_apca_fields(self) -> (Option<Num>, Option<Num>) {
+        match self {
+            Trail::Price(price) => (Some(price), None),
+            Trail::Percent(percent) => (None, Some(percent)),
+        }
+    }
+
+    pub(crate) fn from_apca_fields(trail_price: Option<Num>, trail_percent: Option<Num>) -> Option<Self> {
+        match (trail_price, trail_percent) {
+            (Some(price), _) => Some(Trail::Price(price)),
+            (None, Some(percent)) => Some(Trail::Percent(percent)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// A trailing stop order with its trail and the stop price Alpaca currently has it at.
+#[derive(Clone, Debug)]
+pub struct TrailingStop {
+    pub order: IronTradeOrder,
+    pub trail: Trail,
+    /// Moves with the best price, `None` until Alpaca computed it
+    pub stop_price: Option<Num>,
+}
+
+impl TryFrom<&ApcaOrder> for TrailingStop {
+    type Error = anyhow::Error;
+
+    fn try_from(order: &ApcaOrder) -> Result<Self> {
+        if !matches!(order.type_, Type::TrailingStop) {
+            bail!("order {} is not a trailing stop order", order.id.to_string());
+        }
+        let Some(trail) = Trail::from_apca_fields(order.trail_price.clone(), order.trail_percent.clone()) else {
+            bail!("trailing stop order {} has neither a trail price nor a trail percent", order.id.to_string());
+        };
+
+        let policy = precision_policy();
+        Ok(Self {
+            order: Order::from(order).0,
+            trail,
+            stop_price: order.stop_price.clone().map(|price| policy.round_price(price)),
+        })
+    }
+}
+
+impl AlpacaClient {
+    /// Current state of a trailing stop order placed with [`crate::order_defaults::OrderOptions::trail`].
+    pub async fn get_trailing_stop(&self, order_id: &str) -> Result<TrailingStop> {
+        let order = self.fetch_apca_order(order_id).await?;
+        TrailingStop::try_from(&order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trail_round_trips_through_apca_fields() {
+        for trail in [Trail::Percent(Num::from(2)), Trail::Price(Num::new(150, 100))] {
+            let (trail_price, trail_percent) = trail.clone().into_apca_fields();
+            assert_eq!(Trail::from_apca_fields(trail_price, trail_percent), Some(trail));
+        }
+        assert_eq!(Trail::from_apca_fields(None, None), None);
+    }
+}
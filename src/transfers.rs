@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::Result;
+use apca::api::v2::account_activities::{self, Activity, ActivityReq, ActivityType, Direction};
+use chrono::{DateTime, Utc};
+use num_decimal::Num;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransferDirection {
+    Deposit,
+    Withdrawal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub transfer_id: String,
+    pub direction: TransferDirection,
+    pub amount: Num,
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// No matching transfer has been booked to the account yet
+    Pending,
+    /// The transfer has been booked and its amount is part of the account cash
+    Settled(Transfer),
+}
+
+impl AlpacaClient {
+    /// Returns every account activity of the given types booked after the given time, oldest
+    /// first, following the pagination to the end.
+    pub(crate) async fn get_activities(
+        &self,
+        types: Vec<ActivityType>,
+        after: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Activity>> {
+        let mut activities = Vec::new();
+        let mut page_token = None;
+
+        loop {
+            let request = ActivityReq {
+                types: types.clone(),
+                direction: Direction::Ascending,
+                after,
+                page_token: page_token.take(),
+                ..Default::default()
+            };
+
+            let page = self
+                .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                    self.apca_client.issue::<account_activities::Get>(&request)
+                })
+                .await?;
+
+            let last_id = match page.last() {
+                Some(Activity::Trade(activity)) => activity.id.clone(),
+                Some(Activity::NonTrade(activity)) => activity.id.clone(),
+                None => break,
+            };
+
+            activities.extend(page);
+            page_token = Some(last_id);
+        }
+
+        Ok(activities)
+    }
+
+    /// Returns all deposits and withdrawals booked after the given time, oldest first.
+    pub async fn get_transfers(&self, after: Option<DateTime<Utc>>) -> Result<Vec<Transfer>> {
+        let activities = self
+            .get_activities(vec![ActivityType::CashDeposit, ActivityType::CashWithdrawal], after)
+            .await?;
+
+        let transfers = activities
+            .into_iter()
+            .filter_map(|activity| match activity {
+                Activity::NonTrade(activity) => {
+                    let direction = match activity.type_ {
+                        ActivityType::CashDeposit => TransferDirection::Deposit,
+                        ActivityType::CashWithdrawal => TransferDirection::Withdrawal,
+                        _ => return None,
+                    };
+
+                    Some(Transfer {
+                        transfer_id: activity.id,
+                        direction,
+                        amount: if activity.net_amount.is_negative() {
+                            -activity.net_amount
+                        } else {
+                            activity.net_amount
+                        },
+                        date: activity.date,
+                    })
+                }
+                Activity::Trade(_) => None,
+            })
+            .collect();
+
+        Ok(transfers)
+    }
+
+    /// Checks whether a deposit of at least `amount` has been booked since `since`, so capital
+    /// allocation can wait for funding to arrive before sizing up.
+    pub async fn get_deposit_status(&self, amount: &Num, since: DateTime<Utc>) -> Result<TransferStatus> {
+        let deposit = self
+            .get_transfers(Some(since))
+            .await?
+            .into_iter()
+            .find(|transfer| transfer.direction == TransferDirection::Deposit && &transfer.amount >= amount);
+
+        Ok(match deposit {
+            Some(deposit) => TransferStatus::Settled(deposit),
+            None => TransferStatus::Pending,
+        })
+    }
+}
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::client::AlpacaClient;
+use crate::convert::OrderId;
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::Result;
+use apca::RequestError;
+use apca::api::v2::order;
+use apca::api::v2::order::{GetByClientIdError, Order as ApcaOrder, Status as ApcaOrderStatus};
+use irontrade::api::common::Order as IronTradeOrder;
+use std::future::pending;
+use std::pin::pin;
+use std::time::Duration;
+use tokio::time::{Instant, sleep, sleep_until};
+use tokio_util::sync::CancellationToken;
+
+/// How waiting on an order ended. Every variant carries the last state seen so the caller knows
+/// what it is leaving behind, under the symbol's alias and recorded in the order tracker as by
+/// [`AlpacaClient::get_order`].
+#[derive(Clone, Debug)]
+pub enum WaitOutcome {
+    Filled(IronTradeOrder),
+    /// The order reached a terminal state without filling completely, e.g. canceled or expired
+    Ended(IronTradeOrder),
+    /// The cancellation token fired first, `None` if the order was never fetched
+    Cancelled(Option<IronTradeOrder>),
+    /// The timeout elapsed first, `None` if the order was never fetched
+    TimedOut(Option<IronTradeOrder>),
+}
+
+pub(crate) fn is_terminal(status: &ApcaOrderStatus) -> bool {
+    matches!(
+        status,
+        ApcaOrderStatus::Filled
+            | ApcaOrderStatus::Canceled
+            | ApcaOrderStatus::Expired
+            | ApcaOrderStatus::Rejected
+            | ApcaOrderStatus::Replaced
+            | ApcaOrderStatus::DoneForDay
+    )
+}
+
+impl AlpacaClient {
+    pub(crate) async fn fetch_apca_order(&self, order_id: &str) -> Result<ApcaOrder> {
+        let OrderId(id) = order_id.try_into()?;
+        self.retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+            self.apca_client.issue::<order::Get>(&id)
+        })
+        .await
+    }
+
+    /// The order with the given client order id, `None` when there's none.
+    pub(crate) async fn fetch_apca_order_by_client_id(&self, client_order_id: &str) -> Result<Option<ApcaOrder>> {
+        let request = client_order_id.to_string();
+        let fetched = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<order::GetByClientId>(&request)
+            })
+            .await;
+        match fetched {
+            Ok(order) => Ok(Some(order)),
+            Err(error) => match error.downcast_ref::<RequestError<GetByClientIdError>>() {
+                Some(RequestError::Endpoint(GetByClientIdError::NotFound(_))) => Ok(None),
+                _ => Err(error),
+            },
+        }
+    }
+
+    /// Polls the order until it reaches a terminal state or `cancellation` fires, whichever comes
+    /// first. Cancelling only stops the wait, the order itself stays as it is.
+    pub async fn wait_for_fill(
+        &self,
+        order_id: &str,
+        poll_interval: Duration,
+        cancellation: &CancellationToken,
+    ) -> Result<WaitOutcome> {
+        self.poll_until_terminal(order_id, poll_interval, cancellation, None)
+            .await
+    }
+
+    /// Polls the order until it reaches a terminal state or `timeout` elapses, for callers that
+    /// would otherwise hand-roll the loop. Timing out only stops the wait.
+    pub async fn wait_for_fill_with_timeout(
+        &self,
+        order_id: &str,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<WaitOutcome> {
+        let deadline = Instant::now() + timeout;
+        self.poll_until_terminal(
+            order_id,
+            poll_interval,
+            &CancellationToken::new(),
+            Some(deadline),
+        )
+        .await
+    }
+
+    /// Polls the order until it reaches a terminal state, `cancellation` fires or `deadline`
+    /// passes, whichever comes first.
+    pub(crate) async fn poll_until_terminal(
+        &self,
+        order_id: &str,
+        poll_interval: Duration,
+        cancellation: &CancellationToken,
+        deadline: Option<Instant>,
+    ) -> Result<WaitOutcome> {
+        let mut last_seen = None;
+        let mut timed_out = pin!(async {
+            match deadline {
+                Some(deadline) => sleep_until(deadline).await,
+                None => pending().await,
+            }
+        });
+
+        loop {
+            let order = tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => return Ok(WaitOutcome::Cancelled(last_seen)),
+                _ = &mut timed_out => return Ok(WaitOutcome::TimedOut(last_seen)),
+                order = self.fetch_apca_order(order_id) => order?,
+            };
+
+            let terminal = is_terminal(&order.status);
+            let filled = matches!(order.status, ApcaOrderStatus::Filled);
+            let order = self.record_order(order);
+
+            if filled {
+                return Ok(WaitOutcome::Filled(order));
+            }
+            if terminal {
+                return Ok(WaitOutcome::Ended(order));
+            }
+            last_seen = Some(order);
+
+            tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => return Ok(WaitOutcome::Cancelled(last_seen)),
+                _ = &mut timed_out => return Ok(WaitOutcome::TimedOut(last_seen)),
+                _ = sleep(poll_interval) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::balances::Balances;
+use crate::client::AlpacaClient;
+use crate::convert::Order;
+use crate::data::{Bar, TimeFrame};
+use crate::retry::{Idempotency, RetryOverride};
+use anyhow::Result;
+use apca::api::v2::orders::{self, ListReq, Status};
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::future::try_join_all;
+use irontrade::api::common::{OpenPosition as IronTradeOpenPosition, Order as IronTradeOrder};
+use std::collections::HashMap;
+
+/// Everything a strategy needs to pick up where the account is at startup.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WarmUpSnapshot {
+    pub taken_at: DateTime<Utc>,
+    /// Bars per requested symbol, oldest first
+    pub bars: HashMap<String, Vec<Bar>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::open_positions"))]
+    pub positions: Vec<IronTradeOpenPosition>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::orders"))]
+    pub open_orders: Vec<IronTradeOrder>,
+    pub balances: Balances,
+}
+
+impl AlpacaClient {
+    /// Fetches the bars of `symbols` over the last `lookback`, the open positions, the open
+    /// orders and the account balances concurrently. Stocks and crypto pairs can be mixed.
+    pub async fn warm_up(&self, symbols: &[String], lookback: TimeDelta, time_frame: TimeFrame) -> Result<WarmUpSnapshot> {
+        let taken_at = Utc::now();
+        let start = taken_at - lookback;
+
+        let (bars, positions, open_orders, balances) = tokio::try_join!(
+            self.get_bars_of(symbols, time_frame, start, taken_at),
+            self.list_open_positions(),
+            self.list_open_orders(),
+            self.get_balances(),
+        )?;
+
+        let mut bars_by_symbol: HashMap<String, Vec<Bar>> =
+            symbols.iter().map(|symbol| (symbol.clone(), Vec::new())).collect();
+        for bar in bars {
+            bars_by_symbol.entry(bar.symbol.clone()).or_default().push(bar);
+        }
+
+        Ok(WarmUpSnapshot {
+            taken_at,
+            bars: bars_by_symbol,
+            positions,
+            open_orders,
+            balances,
+        })
+    }
+
+    /// The bars of each of `symbols` from `start` until `end`, fetched concurrently.
+    pub(crate) async fn get_bars_of(
+        &self,
+        symbols: &[String],
+        time_frame: TimeFrame,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Bar>> {
+        let bars = symbols.iter().map(|symbol| self.get_bars(symbol, time_frame, start, end));
+        Ok(try_join_all(bars).await?.into_iter().flatten().collect())
+    }
+
+    pub(crate) async fn list_open_orders(&self) -> Result<Vec<IronTradeOrder>> {
+        let request = ListReq {
+            status: Status::Open,
+            ..Default::default()
+        };
+        let orders: Vec<IronTradeOrder> = self
+            .retrying(Idempotency::Idempotent, RetryOverride::Default, || {
+                self.apca_client.issue::<orders::List>(&request)
+            })
+            .await?
+            .into_iter()
+            .map(|order| {
+                let order: Order = order.into();
+                order.0
+            })
+            .collect();
+
+        let mut order_tracker = self.order_tracker();
+        for order in &orders {
+            order_tracker.update(order);
+        }
+
+        Ok(orders)
+    }
+}